@@ -1,9 +1,13 @@
 #!/bin/bash
 set -e
 
-# Application metadata
-APP_NAME="Click-To-Call"
-APP_IDENTIFIER="com.click-to-call.app"
+# Application metadata. Set CLICK_TO_CALL_NAME, CLICK_TO_CALL_BUNDLE_ID,
+# CLICK_TO_CALL_SLUG and CLICK_TO_CALL_URL_SCHEME to build a separately
+# branded copy that runs side by side with this one.
+APP_NAME="${CLICK_TO_CALL_NAME:-Click-To-Call}"
+APP_IDENTIFIER="${CLICK_TO_CALL_BUNDLE_ID:-com.click-to-call.app}"
+APP_SLUG="${CLICK_TO_CALL_SLUG:-}"
+APP_URL_SCHEME="${CLICK_TO_CALL_URL_SCHEME:-}"
 BINARY_NAME="click-to-call"
 ICON_SOURCE="assets/logo.png"
 
@@ -22,8 +26,9 @@ echo "Cleaning up existing app bundle..."
 rm -rf "$APP_DIR"
 
 # Step 1: Build the Rust application in release mode
+# Arguments are passed to cargo, e.g. ./build.sh --no-default-features
 echo "Building Rust application..."
-cargo build --release
+cargo build --release "$@"
 
 if [ ! -f "$TARGET_RELEASE/$BINARY_NAME" ]; then
     echo "Error: Rust build failed or binary not found at $TARGET_RELEASE/$BINARY_NAME"
@@ -46,6 +51,29 @@ chmod +x "$MACOS_DIR/$APP_NAME"
 echo "Copying Info.plist..."
 cp "Info.plist" "$CONTENTS_DIR/"
 
+# Step 4a: Give the bundle its identity. The binary reads the same settings
+# when it's built, the Info.plist keys cover copies that are only renamed.
+PLIST="$CONTENTS_DIR/Info.plist"
+PLISTBUDDY="/usr/libexec/PlistBuddy"
+"$PLISTBUDDY" -c "Set :CFBundleIdentifier $APP_IDENTIFIER" "$PLIST"
+"$PLISTBUDDY" -c "Set :CFBundleName $APP_NAME" "$PLIST"
+"$PLISTBUDDY" -c "Set :CFBundleDisplayName $APP_NAME" "$PLIST"
+"$PLISTBUDDY" -c "Set :CFBundleExecutable $APP_NAME" "$PLIST"
+"$PLISTBUDDY" -c "Set :CFBundleURLTypes:0:CFBundleURLName $APP_IDENTIFIER" "$PLIST"
+"$PLISTBUDDY" -c "Set :NSServices:0:NSPortName $APP_NAME" "$PLIST"
+"$PLISTBUDDY" -c "Set :NSServices:0:NSMenuItem:default Call with $APP_NAME" "$PLIST"
+if [ -n "$APP_SLUG" ]; then
+    "$PLISTBUDDY" -c "Add :ClickToCallSlug string $APP_SLUG" "$PLIST"
+fi
+if [ -n "$APP_URL_SCHEME" ]; then
+    "$PLISTBUDDY" -c "Add :ClickToCallURLScheme string $APP_URL_SCHEME" "$PLIST"
+    "$PLISTBUDDY" -c "Add :CFBundleURLTypes:0:CFBundleURLSchemes: string $APP_URL_SCHEME" "$PLIST"
+fi
+
+# Step 4b: The AppleScript dictionary Info.plist points at
+echo "Copying the scripting definition..."
+cp "ClickToCall.sdef" "$RESOURCES_DIR/"
+
 # Step 5: Process the icon
 echo "Converting icon to macOS format..."
 mkdir -p "$TEMP_ICONSET"
@@ -0,0 +1,82 @@
+// Calls the PBX gave an id for when it placed them, shown in the main window
+// with a Hang Up button. Unless calls are followed over the event socket, the
+// PBX doesn't say when a call ends, so each stays listed until it's hung up or
+// dismissed, or gets too old to still be going.
+
+use crate::backend::{self, Account};
+use crate::call::CALL_TIMEOUT;
+use crate::config::AppState;
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use druid::ExtEventSink;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+// Calls older than this are assumed to be over
+const MAX_AGE_HOURS: i64 = 4;
+
+#[derive(Clone)]
+pub struct ActiveCall {
+    // The PBX's id for the call
+    pub call_id: String,
+    // Who's being called, as the notification named them
+    pub callee: String,
+    pub started: DateTime<Local>,
+    // The PBX it went through, which is asked to hang it up
+    account: Account,
+}
+
+static CALLS: Mutex<Vec<ActiveCall>> = Mutex::new(Vec::new());
+
+// Lets the UI know the list changed so the Active Calls section is redone
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+fn changed() {
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(|data: &mut AppState| data.active_calls_revision += 1);
+    }
+}
+
+// Show a call the PBX has just placed
+pub fn add(call_id: String, callee: String, account: Account) {
+    CALLS.lock().unwrap().push(ActiveCall { call_id, callee, started: Local::now(), account });
+    changed();
+}
+
+// Calls that may still be going, oldest first
+pub fn list() -> Vec<ActiveCall> {
+    let cutoff = Local::now() - ChronoDuration::hours(MAX_AGE_HOURS);
+    let mut calls = CALLS.lock().unwrap();
+    calls.retain(|call| call.started > cutoff);
+    calls.clone()
+}
+
+// Stop showing a call, e.g. once it was hung up on the phone
+pub fn dismiss(call_id: &str) {
+    CALLS.lock().unwrap().retain(|call| call.call_id != call_id);
+    changed();
+}
+
+// Ask the PBX to end the call, and stop showing it once it has. Returns the
+// message for the status line.
+pub async fn hang_up(call_id: &str) -> Result<String, String> {
+    let call = CALLS.lock().unwrap().iter().find(|call| call.call_id == call_id).cloned();
+    let Some(call) = call else {
+        return Err("That call isn't listed anymore".to_string());
+    };
+
+    let result = backend::within(CALL_TIMEOUT, call.account.backend().hang_up(call_id)).await;
+    match result {
+        Ok(()) => {
+            dismiss(call_id);
+            Ok(format!("Hung up the call to {}", call.callee))
+        }
+        Err(e) => {
+            warn!("Couldn't hang up call {}: {}", call_id, e);
+            Err(format!("Couldn't hang up the call to {}: {}", call.callee, e))
+        }
+    }
+}
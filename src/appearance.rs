@@ -0,0 +1,165 @@
+// Larger text and high-contrast colors for low-vision users, applied to the
+// dialer, the menu bar dialer and the call lists while the settings are on
+
+use druid::widget::EnvScope;
+use druid::{
+    theme, BoxConstraints, Color, Data, Env, Event, EventCtx, Key, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Size,
+    UpdateCtx, Widget, WidgetExt, WidgetPod,
+};
+use crate::config::AppState;
+
+// How much bigger text and controls get with Larger Text on
+const LARGE_TEXT_SCALE: f64 = 1.4;
+
+// Size of secondary text, such as the dial hint and the notes under a call.
+// Use this instead of a fixed size so it grows with the rest.
+pub const SMALL_TEXT_SIZE: Key<f64> = Key::new("com.click-to-call.small-text-size");
+
+const DEFAULT_SMALL_TEXT_SIZE: f64 = 11.0;
+
+// Colors for High Contrast: white text on black, with yellow outlines and
+// highlights so controls stand out from the background
+const CONTRAST_BACKGROUND: Color = Color::BLACK;
+const CONTRAST_TEXT: Color = Color::WHITE;
+const CONTRAST_ACCENT: Color = Color::rgb8(0xff, 0xd6, 0x00);
+const CONTRAST_MUTED: Color = Color::rgb8(0xc8, 0xc8, 0xc8);
+
+// Values the app's own keys need in every window
+pub fn add_defaults(env: &mut Env) {
+    env.set(SMALL_TEXT_SIZE, DEFAULT_SMALL_TEXT_SIZE);
+}
+
+// Size for a window showing `state`'s display settings, so fixed-size
+// windows still fit their contents with Larger Text on
+pub fn window_size(width: f64, height: f64, state: &AppState) -> (f64, f64) {
+    if state.large_text {
+        (width * LARGE_TEXT_SCALE, height * LARGE_TEXT_SCALE)
+    } else {
+        (width, height)
+    }
+}
+
+// `widget` drawn with the display settings in `AppState`. It's given the
+// window background so high contrast covers the whole window.
+pub fn scoped(widget: impl Widget<AppState> + 'static) -> impl Widget<AppState> {
+    EnvScope::new(
+        |env, data: &AppState| {
+            if data.large_text {
+                enlarge(env);
+            }
+            if data.high_contrast {
+                use_high_contrast(env);
+            }
+        },
+        widget.background(theme::WINDOW_BACKGROUND_COLOR),
+    )
+}
+
+fn enlarge(env: &mut Env) {
+    for key in [theme::TEXT_SIZE_NORMAL, theme::TEXT_SIZE_LARGE, theme::BASIC_WIDGET_HEIGHT, theme::BORDERED_WIDGET_HEIGHT, SMALL_TEXT_SIZE] {
+        let value = env.get(&key);
+        env.set(key, value * LARGE_TEXT_SCALE);
+    }
+    for key in [theme::UI_FONT, theme::UI_FONT_BOLD, theme::UI_FONT_ITALIC] {
+        let font = env.get(&key);
+        let size = font.size * LARGE_TEXT_SCALE;
+        env.set(key, font.with_size(size));
+    }
+}
+
+fn use_high_contrast(env: &mut Env) {
+    for key in [
+        theme::WINDOW_BACKGROUND_COLOR,
+        theme::BACKGROUND_LIGHT,
+        theme::BACKGROUND_DARK,
+        theme::BUTTON_DARK,
+        theme::BUTTON_LIGHT,
+        theme::DISABLED_BUTTON_DARK,
+        theme::DISABLED_BUTTON_LIGHT,
+    ] {
+        env.set(key, CONTRAST_BACKGROUND);
+    }
+    for key in [theme::TEXT_COLOR, theme::FOREGROUND_LIGHT, theme::FOREGROUND_DARK, theme::CURSOR_COLOR] {
+        env.set(key, CONTRAST_TEXT);
+    }
+    for key in [
+        theme::BORDER_DARK,
+        theme::BORDER_LIGHT,
+        theme::PRIMARY_LIGHT,
+        theme::PRIMARY_DARK,
+        theme::SELECTED_TEXT_BACKGROUND_COLOR,
+    ] {
+        env.set(key, CONTRAST_ACCENT);
+    }
+    for key in [theme::PLACEHOLDER_COLOR, theme::DISABLED_TEXT_COLOR, theme::DISABLED_FOREGROUND_LIGHT, theme::DISABLED_FOREGROUND_DARK] {
+        env.set(key, CONTRAST_MUTED);
+    }
+    env.set(theme::SELECTION_TEXT_COLOR, CONTRAST_BACKGROUND);
+    env.set(theme::BUTTON_BORDER_WIDTH, 2.0);
+    env.set(theme::TEXTBOX_BORDER_WIDTH, 2.0);
+}
+
+// Lays its children out left to right like a Flex row, but starts a new line
+// when the next one doesn't fit, so rows of buttons reflow with larger text
+// instead of being cut off at the window's edge
+pub struct Wrap<T> {
+    children: Vec<WidgetPod<T, Box<dyn Widget<T>>>>,
+    spacing: f64,
+}
+
+impl<T: Data> Wrap<T> {
+    pub fn new(spacing: f64) -> Self {
+        Wrap { children: Vec::new(), spacing }
+    }
+
+    pub fn with_child(mut self, child: impl Widget<T> + 'static) -> Self {
+        self.children.push(WidgetPod::new(Box::new(child)));
+        self
+    }
+}
+
+impl<T: Data> Widget<T> for Wrap<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        for child in &mut self.children {
+            child.event(ctx, event, data, env);
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        for child in &mut self.children {
+            child.lifecycle(ctx, event, data, env);
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        for child in &mut self.children {
+            child.update(ctx, data, env);
+        }
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let max_width = bc.max().width;
+        let child_bc = BoxConstraints::new(Size::ZERO, Size::new(max_width, f64::INFINITY));
+        let (mut x, mut y, mut line_height, mut width) = (0.0, 0.0, 0.0_f64, 0.0_f64);
+
+        for child in &mut self.children {
+            let size = child.layout(ctx, &child_bc, data, env);
+            if x > 0.0 && x + size.width > max_width {
+                x = 0.0;
+                y += line_height + self.spacing;
+                line_height = 0.0;
+            }
+            child.set_origin(ctx, Point::new(x, y));
+            width = width.max(x + size.width);
+            line_height = line_height.max(size.height);
+            x += size.width + self.spacing;
+        }
+        bc.constrain(Size::new(width, y + line_height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        for child in &mut self.children {
+            child.paint(ctx, data, env);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{block_on, http_client, proxy};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::warn;
+
+// Header carrying the hex HMAC-SHA256 of the request body, keyed with the audit secret
+const SIGNATURE_HEADER: &str = "X-Click-To-Call-Signature";
+
+// What asked for a call to be placed. Pids are only known when macOS tells us
+// who sent the request.
+#[derive(Clone, Copy, Debug)]
+pub enum CallOrigin {
+    // The Place Call button in the main window
+    Dialer,
+    // The look-up hotkey's confirmation prompt
+    LookupHotkey,
+    // The hotkey that dials the number on the clipboard
+    ClipboardHotkey,
+    // Call on the notification for a copied number
+    ClipboardWatch,
+    // "Call with Click-To-Call" in the Services menu
+    Services,
+    // The menu bar icon's dial popover
+    MenuBar,
+    // Redial from the call history: Recent Calls or the frequent numbers
+    History,
+    // A favorite's button, in the main window or the menu bar popover
+    Favorite,
+    // The Call button next to a number in the shared directory
+    Directory,
+    // A tel: link opened in another app, or a Spotlight item
+    TelLink { sender_pid: Option<i32> },
+    // Another process writing to the instance socket
+    Socket { peer_pid: Option<i32> },
+    // `click-to-call call` in Terminal or a script
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    Cli,
+    // AppleScript's place call, from Script Editor, Automator or Shortcuts
+    AppleScript,
+    // POST /call on the local HTTP API
+    HttpApi,
+    // A campaign calling down a list imported from a CSV file
+    Campaign,
+    // Another try at a call that couldn't reach the PBX; `retry` counts from 1
+    Retry { retry: u32, first_failed: chrono::DateTime<chrono::Local> },
+}
+
+impl CallOrigin {
+    pub fn channel(&self) -> &'static str {
+        match self {
+            CallOrigin::Dialer => "dialer",
+            CallOrigin::LookupHotkey => "lookup-hotkey",
+            CallOrigin::ClipboardHotkey => "clipboard-hotkey",
+            CallOrigin::ClipboardWatch => "clipboard-watch",
+            CallOrigin::Services => "services",
+            CallOrigin::MenuBar => "menu-bar",
+            CallOrigin::History => "history",
+            CallOrigin::Favorite => "favorite",
+            CallOrigin::Directory => "directory",
+            CallOrigin::TelLink { .. } => "tel-link",
+            CallOrigin::Socket { .. } => "socket",
+            CallOrigin::Cli => "cli",
+            CallOrigin::AppleScript => "applescript",
+            CallOrigin::HttpApi => "http-api",
+            CallOrigin::Campaign => "campaign",
+            CallOrigin::Retry { .. } => "retry",
+        }
+    }
+
+    pub fn pid(&self) -> Option<i32> {
+        match self {
+            CallOrigin::TelLink { sender_pid } => *sender_pid,
+            CallOrigin::Socket { peer_pid } => *peer_pid,
+            CallOrigin::Dialer | CallOrigin::LookupHotkey | CallOrigin::ClipboardHotkey | CallOrigin::ClipboardWatch | CallOrigin::Services | CallOrigin::MenuBar | CallOrigin::History | CallOrigin::Favorite | CallOrigin::Directory | CallOrigin::Cli | CallOrigin::AppleScript | CallOrigin::HttpApi | CallOrigin::Campaign | CallOrigin::Retry { .. } => None,
+        }
+    }
+}
+
+// How an origination attempt ended
+#[derive(Clone, Copy)]
+pub enum Outcome {
+    Initiated,
+    Failed,
+    // Refused before reaching the PBX, e.g. an emergency number
+    Blocked,
+}
+
+impl Outcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Initiated => "initiated",
+            Outcome::Failed => "failed",
+            Outcome::Blocked => "blocked",
+        }
+    }
+}
+
+// One origination attempt, as sent to the audit webhook
+pub struct AuditEvent<'a> {
+    pub origin: CallOrigin,
+    pub domain: &'a str,
+    pub extension: &'a str,
+    pub number: &'a str,
+    pub outcome: Outcome,
+    pub http_status: Option<u16>,
+    pub detail: &'a str,
+    // Reason typed into the confirmation prompt, if any
+    pub note: Option<&'a str>,
+}
+
+// Post the event to the audit webhook, signed with `secret` when one is set.
+// Unlike the notification webhook this always carries the full number, since
+// it's meant for a security team's log store rather than a chat channel.
+// Blocks until the webhook answers; call it from a background thread.
+pub fn record(url: &str, secret: &str, event: &AuditEvent) {
+    if url.trim().is_empty() {
+        return;
+    }
+
+    let pid = event.origin.pid();
+    let body = serde_json::json!({
+        "event": "call.originate",
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "domain": event.domain,
+        "extension": event.extension,
+        "number": event.number,
+        "outcome": event.outcome.as_str(),
+        "http_status": event.http_status,
+        "detail": event.detail,
+        "note": event.note,
+        "requested_by": {
+            "channel": event.origin.channel(),
+            "pid": pid,
+            "process": pid.and_then(process_path),
+        },
+        "host": {
+            "name": host_name(),
+            "user": std::env::var("USER").ok(),
+            "app_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    let body = body.to_string();
+
+    let mut request = http_client()
+        .post(url.trim())
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    if !secret.is_empty() {
+        request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+    }
+
+    match block_on(async {
+        proxy::prepare(url.trim()).await;
+        request.body(body).send().await
+    }) {
+        Ok(response) if !response.status().is_success() => {
+            warn!("Audit webhook returned HTTP status {}", response.status());
+        }
+        Err(e) => warn!("Audit webhook failed: {}", e),
+        _ => {}
+    }
+}
+
+fn sign(secret: &str, body: &str) -> String {
+    // HMAC accepts keys of any length, so this can't fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn host_name() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return None;
+    }
+    let end = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    Some(String::from_utf8_lossy(&buffer[..end]).into_owned())
+}
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    // PROC_PIDPATHINFO_MAXSIZE
+    pub const PATH_MAX_SIZE: usize = 4096;
+    // getsockopt level and option for the pid of a Unix socket's peer
+    pub const SOL_LOCAL: libc::c_int = 0;
+    pub const LOCAL_PEERPID: libc::c_int = 0x002;
+
+    extern "C" {
+        // libproc, part of libSystem
+        pub fn proc_pidpath(pid: libc::c_int, buffer: *mut libc::c_void, size: u32) -> libc::c_int;
+    }
+}
+
+// Executable path of the requesting process, so an audit entry names the tool
+// that asked for the call and not just a pid
+#[cfg(target_os = "macos")]
+fn process_path(pid: i32) -> Option<String> {
+    let mut buffer = vec![0u8; ffi::PATH_MAX_SIZE];
+    let length = unsafe { ffi::proc_pidpath(pid, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len() as u32) };
+    if length <= 0 {
+        return None;
+    }
+    buffer.truncate(length as usize);
+    Some(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn process_path(pid: i32) -> Option<String> {
+    std::fs::read_link(format!("/proc/{}/exe", pid))
+        .ok()
+        .map(|path| path.display().to_string())
+}
+
+// Pid of the process on the other end of a Unix socket
+#[cfg(target_os = "macos")]
+pub fn peer_pid(stream: &std::os::unix::net::UnixStream) -> Option<i32> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut pid: libc::pid_t = 0;
+    let mut length = std::mem::size_of::<libc::pid_t>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            ffi::SOL_LOCAL,
+            ffi::LOCAL_PEERPID,
+            &mut pid as *mut libc::pid_t as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result == 0 { Some(pid) } else { None }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn peer_pid(stream: &std::os::unix::net::UnixStream) -> Option<i32> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result == 0 { Some(credentials.pid) } else { None }
+}
@@ -0,0 +1,269 @@
+use super::{connect, http, request_builder, tcp_address, CallBackend, CallError, CallRequest, Check, Connection, HangUp, MessageCount, Originate, Originated, Presence, PresenceLookup};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+const AMI_PORT: u16 = 5038;
+
+// Dialplan context used when none is configured, FreePBX's default
+const DEFAULT_CONTEXT: &str = "from-internal";
+
+// app_voicemail's context for mailboxes given without one, FreePBX's too
+const DEFAULT_VOICEMAIL_CONTEXT: &str = "default";
+
+fn context<'a>(connection: &Connection<'a>) -> &'a str {
+    if connection.context.is_empty() {
+        DEFAULT_CONTEXT
+    } else {
+        connection.context
+    }
+}
+
+// Post-dial digits as Dial()'s D option takes them, for the dialplan to use
+// as D(${POST_DIAL}): w there is half a second, and there's no waiting for
+// the caller, so a wait is a pause too
+fn dial_option_digits(post_dial: &str) -> String {
+    post_dial.replace(['p', 'w'], "ww")
+}
+
+// What the extension's phone shows while it rings, as "Name" <number>
+fn caller_id(request: &CallRequest<'_>) -> String {
+    match request.display_name {
+        Some(name) => format!("\"{}\" <{}>", name.replace('"', ""), request.shown_number()),
+        None => request.shown_number().to_string(),
+    }
+}
+
+// Originate through the Asterisk Manager Interface
+pub struct Ami<'a> {
+    pub connection: Connection<'a>,
+}
+
+impl Ami<'_> {
+    // Send one action and read its response block, returning the Response: value and Message:
+    async fn action(writer: &mut OwnedWriteHalf, reader: &mut BufReader<OwnedReadHalf>, lines: &[String]) -> Result<(String, String), CallError> {
+        let fields = Self::action_fields(writer, reader, lines).await?;
+        let field = |name: &str| fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.clone()).unwrap_or_default();
+        Ok((field("Response"), field("Message")))
+    }
+
+    // Send one action and read its response block, every `Name: value` of it
+    async fn action_fields(writer: &mut OwnedWriteHalf, reader: &mut BufReader<OwnedReadHalf>, lines: &[String]) -> Result<Vec<(String, String)>, CallError> {
+        let mut action = lines.join("\r\n");
+        action.push_str("\r\n\r\n");
+        writer
+            .write_all(action.as_bytes())
+            .await
+            .map_err(|e| CallError::Failed(format!("AMI write failed: {}", e)))?;
+
+        let mut fields = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| CallError::Failed(format!("AMI read failed: {}", e)))?;
+            let line = line.trim_end();
+            if read == 0 || (line.is_empty() && fields.iter().any(|(name, _)| name == "Response")) {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(": ") {
+                fields.push((name.to_string(), value.to_string()));
+            }
+        }
+        Ok(fields)
+    }
+
+    // Connect and log in, ready for the next action
+    async fn login(&self) -> Result<(OwnedWriteHalf, BufReader<OwnedReadHalf>), CallError> {
+        let address = tcp_address(self.connection.host, AMI_PORT);
+        let (reader, mut writer) = connect(&address, "AMI").await?;
+        let mut reader = BufReader::new(reader);
+
+        // Skip the "Asterisk Call Manager/x.y" banner
+        let mut banner = String::new();
+        reader.read_line(&mut banner).await.ok();
+
+        let (response, message) = Self::action(&mut writer, &mut reader, &[
+            "Action: Login".to_string(),
+            format!("Username: {}", self.connection.username),
+            format!("Secret: {}", self.connection.secret),
+        ]).await?;
+        if response != "Success" {
+            return Err(CallError::Unauthorized(format!("AMI login failed: {}", message)));
+        }
+        Ok((writer, reader))
+    }
+
+    async fn check_login(&self) -> Result<(), CallError> {
+        let (mut writer, mut reader) = self.login().await?;
+        Self::action(&mut writer, &mut reader, &["Action: Logoff".to_string()]).await.ok();
+        Ok(())
+    }
+
+    // MailboxCount for the box in app_voicemail's default context, unless
+    // `extension` names another one, as in 201@sales
+    async fn voicemail_count(&self, extension: &str) -> Result<u32, CallError> {
+        let mailbox = match extension.contains('@') {
+            true => extension.to_string(),
+            false => format!("{}@{}", extension, DEFAULT_VOICEMAIL_CONTEXT),
+        };
+        let (mut writer, mut reader) = self.login().await?;
+        let fields = Self::action_fields(&mut writer, &mut reader, &["Action: MailboxCount".to_string(), format!("Mailbox: {}", mailbox)]).await?;
+        Self::action(&mut writer, &mut reader, &["Action: Logoff".to_string()]).await.ok();
+
+        let field = |name: &str| fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str());
+        match (field("Response"), field("NewMessages").and_then(|count| count.parse().ok())) {
+            (Some("Success"), Some(count)) => Ok(count),
+            _ => Err(CallError::Failed(format!("AMI MailboxCount failed: {}", field("Message").unwrap_or("no count")))),
+        }
+    }
+
+    async fn call(&self, request: &CallRequest<'_>) -> Result<Originated, CallError> {
+        let (mut writer, mut reader) = self.login().await?;
+
+        // Async so we don't wait for the extension to pick up. AUTO_ANSWER is
+        // for a pre-dial handler in the dialplan to add the right header for the
+        // phone; RECORD and OUTBOUND_CALLERID are for the dialplan to act on too.
+        let mut lines = vec![
+            "Action: Originate".to_string(),
+            format!("Channel: PJSIP/{}", request.extension),
+            format!("Context: {}", context(&self.connection)),
+            format!("Exten: {}", request.number),
+            "Priority: 1".to_string(),
+            format!("CallerID: {}", caller_id(request)),
+            format!("Variable: AUTO_ANSWER={}", request.auto_answer),
+        ];
+        if request.record {
+            lines.push("Variable: RECORD=true".to_string());
+        }
+        if let Some(caller_id) = request.caller_id {
+            lines.push(format!("Variable: OUTBOUND_CALLERID={}", caller_id));
+        }
+        if let Some(post_dial) = request.post_dial {
+            lines.push(format!("Variable: POST_DIAL={}", dial_option_digits(post_dial)));
+        }
+        lines.push("Async: true".to_string());
+        let (response, message) = Self::action(&mut writer, &mut reader, &lines).await?;
+
+        Self::action(&mut writer, &mut reader, &["Action: Logoff".to_string()]).await.ok();
+
+        if response == "Success" {
+            Ok(Originated::default())
+        } else {
+            Err(CallError::Failed(format!("AMI originate failed: {}", message)))
+        }
+    }
+}
+
+impl CallBackend for Ami<'_> {
+    fn originate<'a>(&'a self, request: &'a CallRequest<'a>) -> Originate<'a> {
+        Box::pin(self.call(request))
+    }
+
+    fn check(&self) -> Check<'_> {
+        Box::pin(self.check_login())
+    }
+
+    fn new_voicemail<'a>(&'a self, extension: &'a str) -> MessageCount<'a> {
+        Box::pin(self.voicemail_count(extension))
+    }
+}
+
+// Originate through the Asterisk REST Interface
+pub struct Ari<'a> {
+    pub connection: Connection<'a>,
+}
+
+impl Ari<'_> {
+    async fn call(&self, request: &CallRequest<'_>) -> Result<Originated, CallError> {
+        let url = request_builder::pbx_url(self.connection.host, &["ari", "channels"], &[])?;
+        let mut body = serde_json::json!({
+            "endpoint": format!("PJSIP/{}", request.extension),
+            "extension": request.number,
+            "context": context(&self.connection),
+            "priority": 1,
+            "callerId": caller_id(request),
+            "variables": { "AUTO_ANSWER": request.auto_answer.to_string() },
+        });
+        if request.record {
+            body["variables"]["RECORD"] = "true".into();
+        }
+        if let Some(caller_id) = request.caller_id {
+            body["variables"]["OUTBOUND_CALLERID"] = caller_id.into();
+        }
+        if let Some(post_dial) = request.post_dial {
+            body["variables"]["POST_DIAL"] = dial_option_digits(post_dial).into();
+        }
+
+        let request = self.connection.client()?
+            .post(url)
+            .basic_auth(self.connection.username, Some(self.connection.secret))
+            .json(&body);
+        let response = http::send(request, self.connection.auth, &[self.connection.secret]).await?;
+        let http_status = Some(response.status().as_u16());
+        // The new channel, whose id hangs it up again
+        let channel: serde_json::Value = response.json().await.unwrap_or_default();
+        let call_id = channel["id"].as_str().map(str::to_string);
+        Ok(Originated { http_status, call_id })
+    }
+
+    // DELETE /ari/channels/{id}; a channel that's gone already answers 404
+    async fn end_call(&self, call_id: &str) -> Result<(), CallError> {
+        let url = request_builder::pbx_url(self.connection.host, &["ari", "channels", call_id], &[])?;
+        let request = self.connection.client()?
+            .delete(url)
+            .basic_auth(self.connection.username, Some(self.connection.secret));
+        match http::send(request, self.connection.auth, &[self.connection.secret]).await {
+            Err(CallError::HttpStatus(status, _)) if status == reqwest::StatusCode::NOT_FOUND => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    // GET /ari/endpoints/PJSIP/{extension}: offline while no phone is
+    // registered for it, with the channels it's on
+    async fn endpoint_presence(&self, extension: &str) -> Result<Presence, CallError> {
+        let url = request_builder::pbx_url(self.connection.host, &["ari", "endpoints", "PJSIP", extension], &[])?;
+        let request = self.connection.client()?
+            .get(url)
+            .basic_auth(self.connection.username, Some(self.connection.secret));
+        let response = http::send(request, self.connection.auth, &[self.connection.secret]).await?;
+        let endpoint: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| CallError::Failed(format!("ARI didn't describe the endpoint: {}", e)))?;
+        let on_call = endpoint["channel_ids"].as_array().is_some_and(|channels| !channels.is_empty());
+        Ok(match endpoint["state"].as_str() {
+            Some("offline") => Presence::Unregistered,
+            _ if on_call => Presence::OnCall,
+            _ => Presence::Idle,
+        })
+    }
+
+    // GET /ari/asterisk/info needs the same credentials and changes nothing
+    async fn check_login(&self) -> Result<(), CallError> {
+        let url = request_builder::pbx_url(self.connection.host, &["ari", "asterisk", "info"], &[])?;
+        let request = self.connection.client()?
+            .get(url)
+            .basic_auth(self.connection.username, Some(self.connection.secret));
+        http::send(request, self.connection.auth, &[self.connection.secret]).await?;
+        Ok(())
+    }
+}
+
+impl CallBackend for Ari<'_> {
+    fn originate<'a>(&'a self, request: &'a CallRequest<'a>) -> Originate<'a> {
+        Box::pin(self.call(request))
+    }
+
+    fn check(&self) -> Check<'_> {
+        Box::pin(self.check_login())
+    }
+
+    fn hang_up<'a>(&'a self, call_id: &'a str) -> HangUp<'a> {
+        Box::pin(self.end_call(call_id))
+    }
+
+    fn presence<'a>(&'a self, extension: &'a str) -> PresenceLookup<'a> {
+        Box::pin(self.endpoint_presence(extension))
+    }
+}
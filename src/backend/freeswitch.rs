@@ -0,0 +1,314 @@
+use super::{connect, tcp_address, CallBackend, CallError, CallRequest, CallState, Check, Connection, Follow, HangUp, MessageCount, Originate, Originated, Presence, PresenceLookup};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::mpsc::UnboundedSender;
+
+const ESL_PORT: u16 = 8021;
+
+// Dialplan context used when none is configured
+const DEFAULT_CONTEXT: &str = "default";
+
+// Post-dial digits as send_dtmf takes them: W there is a one-second pause,
+// and there's no waiting for the caller, so a wait is a pause too
+fn send_dtmf_digits(post_dial: &str) -> String {
+    post_dial.replace(['p', 'w'], "W")
+}
+
+// Originate through the FreeSWITCH event socket (mod_event_socket)
+pub struct Esl<'a> {
+    pub connection: Connection<'a>,
+}
+
+// Events that say how a call is going
+const CALL_EVENTS: &str = "CHANNEL_PROGRESS CHANNEL_PROGRESS_MEDIA CHANNEL_ANSWER CHANNEL_BRIDGE CHANNEL_HANGUP_COMPLETE";
+
+// Read one event socket message's headers, returning its Reply-Text
+async fn read_reply(reader: &mut BufReader<OwnedReadHalf>) -> Result<String, CallError> {
+    read_headers(reader).await.map(|headers| headers.reply)
+}
+
+// Send a command that's answered with +OK or -ERR
+async fn command(writer: &mut OwnedWriteHalf, reader: &mut BufReader<OwnedReadHalf>, command: &str) -> Result<(), CallError> {
+    writer
+        .write_all(format!("{}\n\n", command).as_bytes())
+        .await
+        .map_err(|e| CallError::Failed(format!("ESL write failed: {}", e)))?;
+    let reply = read_reply(reader).await?;
+    match reply.starts_with("+OK") {
+        true => Ok(()),
+        false => Err(CallError::Failed(format!("ESL {} failed: {}", command.split_whitespace().next().unwrap_or_default(), reply))),
+    }
+}
+
+// Run an api command and return what it printed, e.g. "+OK" or "-ERR No such channel!"
+async fn api(writer: &mut OwnedWriteHalf, reader: &mut BufReader<OwnedReadHalf>, command: &str) -> Result<String, CallError> {
+    writer
+        .write_all(format!("api {}\n\n", command).as_bytes())
+        .await
+        .map_err(|e| CallError::Failed(format!("ESL write failed: {}", e)))?;
+    let headers = read_headers(reader).await?;
+    let body = read_body(reader, headers.length).await?;
+    Ok(String::from_utf8_lossy(&body).trim().to_string())
+}
+
+async fn read_body(reader: &mut BufReader<OwnedReadHalf>, length: usize) -> Result<Vec<u8>, CallError> {
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .await
+        .map_err(|e| CallError::Failed(format!("ESL read failed: {}", e)))?;
+    Ok(body)
+}
+
+// What an event socket message's headers say about it
+#[derive(Default)]
+struct Headers {
+    reply: String,
+    content_type: String,
+    // Of the body that follows
+    length: usize,
+    // Nothing was read: the socket was closed
+    closed: bool,
+}
+
+// Read one event socket message's headers
+async fn read_headers(reader: &mut BufReader<OwnedReadHalf>) -> Result<Headers, CallError> {
+    let mut headers = Headers::default();
+    let mut seen_header = false;
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| CallError::Failed(format!("ESL read failed: {}", e)))?;
+        let line = line.trim_end();
+        if read == 0 {
+            headers.closed = !seen_header;
+            break;
+        }
+        if line.is_empty() && seen_header {
+            break;
+        }
+        seen_header |= !line.is_empty();
+        if let Some(value) = line.strip_prefix("Reply-Text: ") {
+            headers.reply = value.to_string();
+        }
+        if let Some(value) = line.strip_prefix("Content-Type: ") {
+            headers.content_type = value.to_string();
+        }
+        if let Some(value) = line.strip_prefix("Content-Length: ") {
+            headers.length = value.parse().unwrap_or(0);
+        }
+    }
+    Ok(headers)
+}
+
+// What a call event says about the call, from the Event-Name and
+// Hangup-Cause headers of its body. The values read are plain words, so the
+// URL encoding of event-plain doesn't matter.
+fn call_state(event: &str, connected: bool) -> Option<CallState> {
+    let header = |name: &str| event.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(": ")).map(str::trim);
+    match header("Event-Name")? {
+        "CHANNEL_PROGRESS" | "CHANNEL_PROGRESS_MEDIA" => Some(CallState::Ringing),
+        "CHANNEL_ANSWER" => Some(CallState::Answered),
+        "CHANNEL_BRIDGE" => Some(CallState::Connected),
+        "CHANNEL_HANGUP_COMPLETE" => Some(CallState::HungUp { connected, cause: header("Hangup-Cause").unwrap_or("NORMAL_CLEARING").to_string() }),
+        _ => None,
+    }
+}
+
+// Whether `show channels ... as json` lists a channel of `user`'s. `like`
+// matches anywhere in a channel's fields, so 1201@ would be listed for 201@
+// too; its presence_id is the user's own.
+fn on_call(channels: &str, user: &str) -> bool {
+    let channels: serde_json::Value = serde_json::from_str(channels).unwrap_or_default();
+    channels["rows"]
+        .as_array()
+        .is_some_and(|rows| rows.iter().any(|row| row["presence_id"].as_str().is_some_and(|id| id.eq_ignore_ascii_case(user))))
+}
+
+impl Esl<'_> {
+    // Connect and authenticate, ready for the next command
+    async fn login(&self) -> Result<(OwnedWriteHalf, BufReader<OwnedReadHalf>), CallError> {
+        let address = tcp_address(self.connection.host, ESL_PORT);
+        let (reader, mut writer) = connect(&address, "the event socket").await?;
+        let mut reader = BufReader::new(reader);
+
+        // The server greets with Content-Type: auth/request
+        read_reply(&mut reader).await?;
+        writer
+            .write_all(format!("auth {}\n\n", self.connection.secret).as_bytes())
+            .await
+            .map_err(|e| CallError::Failed(format!("ESL write failed: {}", e)))?;
+        let reply = read_reply(&mut reader).await?;
+        if !reply.starts_with("+OK") {
+            return Err(CallError::Unauthorized(format!("ESL login failed: {}", reply)));
+        }
+        Ok((writer, reader))
+    }
+
+    async fn check_login(&self) -> Result<(), CallError> {
+        let (mut writer, _reader) = self.login().await?;
+        writer.write_all(b"exit\n\n").await.ok();
+        Ok(())
+    }
+
+    async fn call(&self, request: &CallRequest<'_>) -> Result<Originated, CallError> {
+        let (mut writer, mut reader) = self.login().await?;
+
+        // Picking the call's UUID up front is the only way to know it with bgapi
+        let uuid = api(&mut writer, &mut reader, "create_uuid").await?;
+        if uuid.is_empty() || uuid.starts_with('-') {
+            return Err(CallError::Failed(format!("ESL create_uuid failed: {}", uuid)));
+        }
+
+        // bgapi returns as soon as the job is queued instead of waiting for an answer
+        let context = if self.connection.context.is_empty() { DEFAULT_CONTEXT } else { self.connection.context };
+        // post_dial is for the dialplan to send with send_dtmf once the number answers
+        let post_dial = match request.post_dial {
+            Some(post_dial) => format!(",post_dial={}", send_dtmf_digits(post_dial)),
+            None => String::new(),
+        };
+        // record_call is for the dialplan too; the caller ID is FreeSWITCH's own variable
+        let mut options = String::new();
+        if request.record {
+            options.push_str(",record_call=true");
+        }
+        if let Some(caller_id) = request.caller_id {
+            options.push_str(&format!(",outbound_caller_id_number={}", caller_id));
+        }
+        // Quoted for spaces, with commas escaped so they don't end the variable
+        if let Some(name) = request.display_name {
+            options.push_str(&format!(",origination_caller_id_name='{}'", name.replace('\'', "").replace(',', "\\,")));
+        }
+        let command = format!(
+            "bgapi originate {{origination_uuid={uuid},origination_caller_id_number={shown},sip_auto_answer={auto_answer}{options}{post_dial}}}user/{extension} {number} XML {context}\n\n",
+            uuid = uuid,
+            number = request.number,
+            shown = request.shown_number(),
+            auto_answer = request.auto_answer,
+            options = options,
+            post_dial = post_dial,
+            extension = request.extension,
+            context = context,
+        );
+        writer
+            .write_all(command.as_bytes())
+            .await
+            .map_err(|e| CallError::Failed(format!("ESL write failed: {}", e)))?;
+        let reply = read_reply(&mut reader).await?;
+        writer.write_all(b"exit\n\n").await.ok();
+
+        if reply.starts_with("+OK") {
+            Ok(Originated { http_status: None, call_id: Some(uuid) })
+        } else {
+            Err(CallError::Failed(format!("ESL originate failed: {}", reply)))
+        }
+    }
+
+    // Listen for the events of the call's own channel, the extension's leg,
+    // until it hangs up
+    async fn follow_call(&self, call_id: &str, states: UnboundedSender<CallState>) -> Result<(), CallError> {
+        let (mut writer, mut reader) = self.login().await?;
+        command(&mut writer, &mut reader, &format!("filter Unique-ID {}", call_id)).await?;
+        command(&mut writer, &mut reader, &format!("event plain {}", CALL_EVENTS)).await?;
+
+        let mut connected = false;
+        loop {
+            let headers = read_headers(&mut reader).await?;
+            if headers.closed {
+                return Err(CallError::Failed("The event socket closed before the call ended".to_string()));
+            }
+            let body = read_body(&mut reader, headers.length).await?;
+            if headers.content_type != "text/event-plain" {
+                continue;
+            }
+            let Some(state) = call_state(&String::from_utf8_lossy(&body), connected) else {
+                continue;
+            };
+            connected |= state == CallState::Connected;
+            let over = matches!(state, CallState::HungUp { .. });
+            // Nobody is listening anymore, e.g. the app is quitting
+            if states.send(state).is_err() || over {
+                writer.write_all(b"exit\n\n").await.ok();
+                return Ok(());
+            }
+        }
+    }
+
+    // `extension` as user@domain, where it has no domain of its own in
+    // FreeSWITCH's
+    async fn user(writer: &mut OwnedWriteHalf, reader: &mut BufReader<OwnedReadHalf>, extension: &str) -> Result<String, CallError> {
+        match extension.contains('@') {
+            true => Ok(extension.to_string()),
+            false => Ok(format!("{}@{}", extension, api(writer, reader, "global_getvar domain").await?)),
+        }
+    }
+
+    // sofia_contact finds the phone's registration, and its channels say
+    // whether it's on a call
+    async fn extension_presence(&self, extension: &str) -> Result<Presence, CallError> {
+        let (mut writer, mut reader) = self.login().await?;
+        let user = Self::user(&mut writer, &mut reader, extension).await?;
+        let contact = api(&mut writer, &mut reader, &format!("sofia_contact {}", user)).await?;
+        let presence = if contact.starts_with("error/") {
+            // error/user_not_registered
+            Ok(Presence::Unregistered)
+        } else if contact.is_empty() || contact.starts_with("-ERR") {
+            Err(CallError::Failed(format!("ESL sofia_contact failed: {}", contact)))
+        } else {
+            let channels = api(&mut writer, &mut reader, &format!("show channels like {} as json", user)).await?;
+            Ok(if on_call(&channels, &user) { Presence::OnCall } else { Presence::Idle })
+        };
+        writer.write_all(b"exit\n\n").await.ok();
+        presence
+    }
+
+    // vm_boxcount counts the new messages by default
+    async fn voicemail_count(&self, extension: &str) -> Result<u32, CallError> {
+        let (mut writer, mut reader) = self.login().await?;
+        let user = Self::user(&mut writer, &mut reader, extension).await?;
+        let count = api(&mut writer, &mut reader, &format!("vm_boxcount {}", user)).await?;
+        writer.write_all(b"exit\n\n").await.ok();
+        count.parse().map_err(|_| CallError::Failed(format!("ESL vm_boxcount failed: {}", count)))
+    }
+
+    async fn end_call(&self, call_id: &str) -> Result<(), CallError> {
+        let (mut writer, mut reader) = self.login().await?;
+        let reply = api(&mut writer, &mut reader, &format!("uuid_kill {}", call_id)).await?;
+        writer.write_all(b"exit\n\n").await.ok();
+
+        // A call that's over already has no channel left to kill
+        if reply.starts_with("+OK") || reply.contains("No such channel") {
+            Ok(())
+        } else {
+            Err(CallError::Failed(format!("ESL uuid_kill failed: {}", reply)))
+        }
+    }
+}
+
+impl CallBackend for Esl<'_> {
+    fn originate<'a>(&'a self, request: &'a CallRequest<'a>) -> Originate<'a> {
+        Box::pin(self.call(request))
+    }
+
+    fn check(&self) -> Check<'_> {
+        Box::pin(self.check_login())
+    }
+
+    fn hang_up<'a>(&'a self, call_id: &'a str) -> HangUp<'a> {
+        Box::pin(self.end_call(call_id))
+    }
+
+    fn follow<'a>(&'a self, call_id: &'a str, states: UnboundedSender<CallState>) -> Follow<'a> {
+        Box::pin(self.follow_call(call_id, states))
+    }
+
+    fn presence<'a>(&'a self, extension: &'a str) -> PresenceLookup<'a> {
+        Box::pin(self.extension_presence(extension))
+    }
+
+    fn new_voicemail<'a>(&'a self, extension: &'a str) -> MessageCount<'a> {
+        Box::pin(self.voicemail_count(extension))
+    }
+}
@@ -0,0 +1,115 @@
+use super::{http, request_builder, CallBackend, CallError, CallRequest, Check, Connection, HangUp, Originate, Originated};
+use regex::Regex;
+use std::sync::OnceLock;
+
+pub struct FusionPbx<'a> {
+    pub connection: Connection<'a>,
+}
+
+impl FusionPbx<'_> {
+    // click_to_call.php answers 200 whatever happened; the page says how the
+    // originate went
+    async fn call(&self, request: &CallRequest<'_>) -> Result<Originated, CallError> {
+        let url = request_builder::click_to_call_url(self.connection.host, request, self.connection.secret)?;
+
+        let reply = http::send_and_read(self.connection.client()?.get(url), self.connection.auth, &[self.connection.secret]).await?;
+        if !reply.url.path().ends_with("click_to_call.php") {
+            return Err(CallError::Unauthorized("FusionPBX didn't accept the API key".to_string()));
+        }
+        match parse_result(&reply.body) {
+            Outcome::Placed { call_id } => Ok(Originated { http_status: Some(reply.status.as_u16()), call_id }),
+            Outcome::Refused(cause) => Err(CallError::Refused(
+                format!("FusionPBX couldn't place the call: {}", describe_cause(&cause, request.extension)),
+                reply.transcript,
+            )),
+            Outcome::NoEventSocket => Err(CallError::Refused(
+                "FusionPBX couldn't reach FreeSWITCH's event socket".to_string(),
+                reply.transcript,
+            )),
+        }
+    }
+
+    // click_to_call.php without numbers only shows its form. FusionPBX sends a
+    // key it doesn't accept to the login page instead.
+    async fn check_login(&self) -> Result<(), CallError> {
+        let url = request_builder::pbx_url(self.connection.host, &["app", "click_to_call", "click_to_call.php"], &[("key", self.connection.secret)])?;
+        let response = http::send(self.connection.client()?.get(url), self.connection.auth, &[self.connection.secret]).await?;
+        if response.url().path().ends_with("click_to_call.php") {
+            Ok(())
+        } else {
+            Err(CallError::Unauthorized("FusionPBX didn't accept the API key".to_string()))
+        }
+    }
+
+    // The Active Calls page's hang-up, which takes the API key like
+    // click_to_call.php does; the key's user needs permission to hang up calls
+    async fn end_call(&self, call_id: &str) -> Result<(), CallError> {
+        let url = request_builder::pbx_url(
+            self.connection.host,
+            &["app", "calls_active", "calls_exec.php"],
+            &[("command", "hangup"), ("uuid", call_id), ("key", self.connection.secret)],
+        )?;
+        let response = http::send(self.connection.client()?.get(url), self.connection.auth, &[self.connection.secret]).await?;
+        if response.url().path().ends_with("calls_exec.php") {
+            Ok(())
+        } else {
+            Err(CallError::Unauthorized("FusionPBX didn't let the API key hang up calls".to_string()))
+        }
+    }
+}
+
+// What click_to_call.php's page says happened
+enum Outcome {
+    // FreeSWITCH answered +OK, with the new call's UUID if the page shows it.
+    // Older FusionPBX versions don't show the result at all; that's taken as
+    // placed, as before.
+    Placed { call_id: Option<String> },
+    // FreeSWITCH answered -ERR with this hangup cause, e.g. USER_NOT_REGISTERED
+    Refused(String),
+    // FusionPBX couldn't talk to FreeSWITCH
+    NoEventSocket,
+}
+
+fn parse_result(body: &str) -> Outcome {
+    static PLACED: OnceLock<Regex> = OnceLock::new();
+    static REFUSED: OnceLock<Regex> = OnceLock::new();
+    let placed = PLACED.get_or_init(|| Regex::new(r"\+OK\s+([0-9a-fA-F]{8}(?:-[0-9a-fA-F]{4}){3}-[0-9a-fA-F]{12})").unwrap());
+    let refused = REFUSED.get_or_init(|| Regex::new(r"-ERR\s+([A-Za-z_]+)").unwrap());
+
+    if let Some(captures) = refused.captures(body) {
+        return Outcome::Refused(captures[1].to_uppercase());
+    }
+    if body.to_lowercase().contains("connection to event socket failed") {
+        return Outcome::NoEventSocket;
+    }
+    Outcome::Placed { call_id: placed.captures(body).map(|captures| captures[1].to_lowercase()) }
+}
+
+// A FreeSWITCH hangup cause in words, keeping the cause for PBX admins
+fn describe_cause(cause: &str, extension: &str) -> String {
+    let reason = match cause {
+        "USER_NOT_REGISTERED" | "SUBSCRIBER_ABSENT" => format!("extension {} isn't registered", extension),
+        "USER_BUSY" => format!("extension {} is busy", extension),
+        "NO_ANSWER" | "NO_USER_RESPONSE" => format!("extension {} didn't answer", extension),
+        "CALL_REJECTED" => format!("extension {} declined the call", extension),
+        "UNALLOCATED_NUMBER" | "NO_ROUTE_DESTINATION" => format!("extension {} doesn't exist", extension),
+        "INVALID_NUMBER_FORMAT" => "the number isn't in a form the PBX accepts".to_string(),
+        "DESTINATION_OUT_OF_ORDER" | "NETWORK_OUT_OF_ORDER" | "NORMAL_TEMPORARY_FAILURE" => "the PBX couldn't get through".to_string(),
+        _ => return cause.to_string(),
+    };
+    format!("{} ({})", reason, cause)
+}
+
+impl CallBackend for FusionPbx<'_> {
+    fn originate<'a>(&'a self, request: &'a CallRequest<'a>) -> Originate<'a> {
+        Box::pin(self.call(request))
+    }
+
+    fn check(&self) -> Check<'_> {
+        Box::pin(self.check_login())
+    }
+
+    fn hang_up<'a>(&'a self, call_id: &'a str) -> HangUp<'a> {
+        Box::pin(self.end_call(call_id))
+    }
+}
@@ -0,0 +1,233 @@
+use super::{http, CallBackend, CallError, CallRequest, Check, Connection, Originate, Originated};
+use crate::{base_url, proxy};
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Method;
+use url::Url;
+
+// Any click-to-call gateway that takes an HTTP request, e.g. Grandstream UCM,
+// Yeastar or one of your own. The whole request is written out in the
+// settings the way HTTP files write them: the method and URL on the first
+// line, headers below it, then a blank line and the body. {number},
+// {extension}, {key}, {username}, {domain}, {auto_answer} and {caller_id} are
+// filled in, encoded for where they are, so a number can't add parameters or
+// break out of a JSON string.
+pub struct GenericHttp<'a> {
+    pub connection: Connection<'a>,
+}
+
+const VARIABLES: [&str; 7] = ["number", "extension", "key", "username", "domain", "auto_answer", "caller_id"];
+const METHODS: [&str; 5] = ["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+// The request as written in the settings, before it's filled in
+pub struct Template<'a> {
+    method: Method,
+    url: &'a str,
+    headers: Vec<(HeaderName, &'a str)>,
+    body: String,
+}
+
+// Read the request written in the settings, or say what's wrong with it
+pub fn parse_template(template: &str) -> Result<Template<'_>, String> {
+    let mut lines = template.lines().skip_while(|line| line.trim().is_empty());
+    let Some(first) = lines.next() else {
+        return Err("Write the request the gateway takes under HTTP Request".to_string());
+    };
+    let (method, url) = match first.trim().split_once(char::is_whitespace) {
+        Some((method, url)) => (method.to_uppercase(), url.trim()),
+        None => ("GET".to_string(), first.trim()),
+    };
+    if !METHODS.contains(&method.as_str()) {
+        return Err(format!("The request's method should be one of {}, not {}", METHODS.join(", "), method));
+    }
+    let method = Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+    check_variables(url)?;
+
+    let mut headers = Vec::new();
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(format!("Header \"{}\" isn't written as Name: value", line.trim()));
+        };
+        let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| format!("\"{}\" isn't a header name", name.trim()))?;
+        check_variables(value)?;
+        headers.push((name, value.trim()));
+    }
+    let body = lines.collect::<Vec<_>>().join("\n").trim_end().to_string();
+    check_variables(&body)?;
+    Ok(Template { method, url, headers, body })
+}
+
+// Fill in each {variable} in `text` with what `value` gives for it, already
+// encoded for where it goes
+fn fill(text: &str, value: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut filled = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name = after
+            .find('}')
+            .map(|end| &after[..end])
+            .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        let Some(name) = name else {
+            // Braces that aren't around a variable, e.g. a JSON object, are sent as written
+            filled.push('{');
+            rest = after;
+            continue;
+        };
+        let Some(value) = value(name) else {
+            return Err(format!(
+                "The request has {{{}}}; it can use {}",
+                name,
+                VARIABLES.map(|variable| format!("{{{}}}", variable)).join(", ")
+            ));
+        };
+        filled.push_str(&value);
+        rest = &after[name.len() + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+fn check_variables(text: &str) -> Result<(), String> {
+    fill(text, |name| VARIABLES.contains(&name).then(String::new)).map(|_| ())
+}
+
+// Percent-encode everything but the characters URLs never treat specially
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// Inside a JSON string, without the quotes around it
+fn json_encode(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+// The request for one call, ready to send
+struct Filled {
+    method: Method,
+    url: Url,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: String,
+}
+
+impl Template<'_> {
+    // The request for `request`, with the values filled in for where they go
+    fn build(&self, connection: &Connection<'_>, request: &CallRequest<'_>) -> Result<Filled, String> {
+        // The domain goes in as a host, whatever scheme it was typed with
+        let host = connection.host.trim().trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/');
+        let value = |name: &str| {
+            Some(match name {
+                "number" => request.number.to_string(),
+                "extension" => request.extension.to_string(),
+                "key" => connection.secret.to_string(),
+                "username" => connection.username.to_string(),
+                "domain" => host.to_string(),
+                "auto_answer" => request.auto_answer.to_string(),
+                "caller_id" => request.caller_id.unwrap_or_default().to_string(),
+                _ => return None,
+            })
+        };
+
+        // The host isn't encoded, so it can bring a port
+        let url = fill(self.url, |name| match name {
+            "domain" => Some(host.to_string()),
+            name => value(name).map(|value| percent_encode(&value)),
+        })?;
+        // A path alone is on the PBX set under Domain
+        let url = match url.starts_with('/') {
+            true => format!("{}{}", base_url(connection.host).trim_end_matches('/'), url),
+            false => url,
+        };
+        let url = Url::parse(&url).map_err(|e| format!("The request's URL {} isn't valid: {}", url, e))?;
+
+        let mut headers = Vec::new();
+        for (name, template) in &self.headers {
+            let filled = fill(template, |name| value(name).map(|value| value.chars().filter(|c| !c.is_control()).collect()))?;
+            let filled = HeaderValue::from_str(&filled).map_err(|_| format!("The {} header can't be sent as filled in", name))?;
+            headers.push((name.clone(), filled));
+        }
+
+        // Values in the body are encoded the way its content type reads them
+        let content_type = self
+            .headers
+            .iter()
+            .find(|(name, _)| name == reqwest::header::CONTENT_TYPE)
+            .map(|(_, value)| value.to_lowercase())
+            .unwrap_or_default();
+        let body = fill(&self.body, |name| {
+            let value = value(name)?;
+            Some(if content_type.contains("json") {
+                json_encode(&value)
+            } else if content_type.contains("x-www-form-urlencoded") {
+                percent_encode(&value)
+            } else {
+                value
+            })
+        })?;
+        Ok(Filled { method: self.method.clone(), url, headers, body })
+    }
+}
+
+impl GenericHttp<'_> {
+    async fn call(&self, request: &CallRequest<'_>) -> Result<Originated, CallError> {
+        let template = parse_template(self.connection.http_request).map_err(CallError::Failed)?;
+        let filled = template.build(&self.connection, request).map_err(CallError::Failed)?;
+
+        let mut sent = self.connection.client()?.request(filled.method, filled.url);
+        for (name, value) in filled.headers {
+            sent = sent.header(name, value);
+        }
+        if !filled.body.is_empty() {
+            sent = sent.body(filled.body);
+        }
+        // Any success status is taken to mean the gateway placed the call
+        let response = http::send(sent, self.connection.auth, &[self.connection.secret]).await?;
+        Ok(Originated { http_status: Some(response.status().as_u16()), call_id: None })
+    }
+
+    // There's no way to log in without placing a call, so this only checks
+    // that the gateway answers HTTP at all
+    async fn check_gateway(&self) -> Result<(), CallError> {
+        let template = parse_template(self.connection.http_request).map_err(CallError::Failed)?;
+        let sample = CallRequest {
+            extension: "",
+            number: "",
+            auto_answer: false,
+            ringback: "",
+            record: false,
+            caller_id: None,
+            display_name: None,
+            display_number: None,
+            parameters: "",
+            post_dial: None,
+        };
+        let mut url = template.build(&self.connection, &sample).map_err(CallError::Failed)?.url;
+        url.set_path("/");
+        url.set_query(None);
+        proxy::prepare(url.as_str()).await;
+        match self.connection.client()?.head(url.clone()).send().await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(CallError::Request(e, format!("> HEAD {}\n< no response\n", url))),
+        }
+    }
+}
+
+impl CallBackend for GenericHttp<'_> {
+    fn originate<'a>(&'a self, request: &'a CallRequest<'a>) -> Originate<'a> {
+        Box::pin(self.call(request))
+    }
+
+    fn check(&self) -> Check<'_> {
+        Box::pin(self.check_gateway())
+    }
+}
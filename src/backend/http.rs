@@ -0,0 +1,181 @@
+use super::{oauth, CallError, HttpAuth, HttpAuthKind};
+use crate::proxy;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use tracing::debug;
+use url::Url;
+
+// How much of an error response's body goes into a transcript
+const BODY_LIMIT: usize = 2048;
+
+// A successful response read to the end
+pub struct Reply {
+    pub status: StatusCode,
+    // Where the response came from, after any redirects
+    pub url: Url,
+    pub body: String,
+    // The whole exchange, with the secrets masked out
+    pub transcript: String,
+}
+
+// Send a request to the PBX, logging in to the server in front of it with
+// `auth`. Anything but a success status is an error carrying a transcript of
+// the exchange, with `secrets` masked out.
+pub async fn send(request: RequestBuilder, auth: &HttpAuth, secrets: &[&str]) -> Result<Response, CallError> {
+    exchange(request, auth, secrets).await.map(|(response, _)| response)
+}
+
+// Like send, but reads the body of a successful response too, for PBXs that
+// answer 200 and say in the body whether the call went through
+pub async fn send_and_read(request: RequestBuilder, auth: &HttpAuth, secrets: &[&str]) -> Result<Reply, CallError> {
+    let (response, mut transcript) = exchange(request, auth, secrets).await?;
+    let status = response.status();
+    let url = response.url().clone();
+    transcript.push_str(&format!("< HTTP {}\n", status));
+    transcript.push_str(&describe_headers("<", response.headers()));
+
+    let body = match response.bytes().await {
+        Ok(body) => body,
+        Err(e) => {
+            let e = e.without_url();
+            transcript.push_str(&format!("< body not received: {}\n", e));
+            return Err(CallError::Request(e, redact(&transcript, secrets)));
+        }
+    };
+    append_body(&mut transcript, &body);
+    Ok(Reply {
+        status,
+        url,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        transcript: redact(&transcript, secrets),
+    })
+}
+
+// The response, and a transcript of the request so far, unredacted
+async fn exchange(request: RequestBuilder, auth: &HttpAuth, secrets: &[&str]) -> Result<(Response, String), CallError> {
+    let secrets: Vec<&str> = secrets.iter().copied().chain([auth.password.as_str()]).collect();
+    let secrets = secrets.as_slice();
+    // The client the request was made with, which knows the PBX's certificate settings
+    let (client, request) = request.build_split();
+    let mut request = request.map_err(|e| CallError::Failed(e.to_string()))?;
+    proxy::prepare(request.url().as_str()).await;
+    // The PBX's own login wins where it uses the same header
+    let own_login = request.headers().contains_key(AUTHORIZATION);
+    if let Some(header) = auth.header().filter(|_| !own_login) {
+        request.headers_mut().insert(AUTHORIZATION, header);
+    }
+    let oauth = auth.kind == HttpAuthKind::OAuth2 && !own_login;
+    if oauth {
+        let token = oauth::access_token(auth).await.map_err(CallError::Unauthorized)?;
+        let mut header = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| CallError::Unauthorized("The identity provider's token can't be sent".to_string()))?;
+        header.set_sensitive(true);
+        request.headers_mut().insert(AUTHORIZATION, header);
+    }
+    // Digest is only answered once the server has sent its challenge
+    let mut digest_retry = (auth.kind == HttpAuthKind::Digest && !own_login).then(|| request.try_clone()).flatten();
+    let mut transcript = describe_request(&request);
+    let sent = redact(&format!("{} {}", request.method(), request.url()), secrets);
+
+    let mut response = match client.execute(request).await {
+        Ok(response) => response,
+        Err(e) => {
+            // Its URL carries the key and the numbers; `sent` is the redacted one
+            let e = e.without_url();
+            debug!("{}: no response: {}", sent, e);
+            transcript.push_str(&format!("< no response: {}\n", e));
+            return Err(CallError::Request(e, redact(&transcript, secrets)));
+        }
+    };
+    debug!("{}: HTTP {}", sent, response.status());
+    let challenge = response.headers().get(WWW_AUTHENTICATE).and_then(|challenge| challenge.to_str().ok()).map(str::to_string);
+    if let (StatusCode::UNAUTHORIZED, Some(mut retry), Some(challenge)) = (response.status(), digest_retry.take(), challenge) {
+        let uri = match retry.url().query() {
+            Some(query) => format!("{}?{}", retry.url().path(), query),
+            None => retry.url().path().to_string(),
+        };
+        if let Some(answer) = auth.digest_answer(&challenge, retry.method().as_str(), &uri) {
+            transcript.push_str(&format!("< HTTP {}\n", response.status()));
+            transcript.push_str(&describe_headers("<", response.headers()));
+            retry.headers_mut().insert(AUTHORIZATION, answer);
+            transcript.push_str(&describe_request(&retry));
+            response = match client.execute(retry).await {
+                Ok(response) => response,
+                Err(e) => {
+                    let e = e.without_url();
+                    debug!("{}: no response: {}", sent, e);
+                    transcript.push_str(&format!("< no response: {}\n", e));
+                    return Err(CallError::Request(e, redact(&transcript, secrets)));
+                }
+            };
+            debug!("{}: HTTP {} after the digest challenge", sent, response.status());
+        }
+    }
+    if response.status().is_success() {
+        return Ok((response, transcript));
+    }
+    // A token turned down, e.g. revoked early, isn't used for the next call
+    if oauth && response.status() == StatusCode::UNAUTHORIZED {
+        oauth::forget(auth);
+    }
+
+    transcript.push_str(&format!("< HTTP {}\n", response.status()));
+    transcript.push_str(&describe_headers("<", response.headers()));
+
+    let status = response.status();
+    let mut body = Vec::new();
+    while body.len() <= BODY_LIMIT {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    append_body(&mut transcript, &body);
+
+    Err(CallError::HttpStatus(status, redact(&transcript, secrets)))
+}
+
+// Add up to BODY_LIMIT bytes of a response body to the transcript
+fn append_body(transcript: &mut String, body: &[u8]) {
+    transcript.push_str("<\n");
+    transcript.push_str(&String::from_utf8_lossy(&body[..body.len().min(BODY_LIMIT)]));
+    if body.len() > BODY_LIMIT {
+        transcript.push_str(&format!("\n[body truncated after {} bytes]", BODY_LIMIT));
+    }
+    transcript.push('\n');
+}
+
+fn describe_request(request: &reqwest::Request) -> String {
+    let mut text = format!("> {} {}\n", request.method(), request.url());
+    text.push_str(&describe_headers(">", request.headers()));
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        text.push_str(">\n");
+        text.push_str(&String::from_utf8_lossy(body));
+        text.push('\n');
+    }
+    text
+}
+
+fn describe_headers(direction: &str, headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            // Basic and bearer credentials are secrets in any form
+            let value = if name == reqwest::header::AUTHORIZATION {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            format!("{} {}: {}\n", direction, name, value)
+        })
+        .collect()
+}
+
+// Mask each secret, as typed and as it appears percent-encoded in URLs and forms
+fn redact(transcript: &str, secrets: &[&str]) -> String {
+    let mut text = transcript.to_string();
+    for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+        let encoded: String = url::form_urlencoded::byte_serialize(secret.as_bytes()).collect();
+        text = text.replace(encoded.as_str(), "[redacted]").replace(secret, "[redacted]");
+    }
+    text
+}
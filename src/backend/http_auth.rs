@@ -0,0 +1,223 @@
+// Logging in to a web server in front of the PBX, e.g. nginx with basic
+// auth or an identity provider with OAuth2, on top of the PBX's own key or
+// password. These are profile settings and apply to every HTTP request to the
+// PBX, except where the PBX's own login already uses the Authorization header
+// (ARI and 3CX).
+
+use crate::config::AppState;
+use crate::profiles::DEFAULT_PROFILE;
+use druid::Data;
+use md5::Md5;
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+#[derive(Clone, Copy, Data, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpAuthKind {
+    #[default]
+    None,
+    Basic,
+    // The server's challenge is answered with a hash of the password (RFC 7616)
+    Digest,
+    // The password field holds the token
+    Bearer,
+    // Tokens from an identity provider; the username and password fields hold
+    // the client id and secret
+    OAuth2,
+}
+
+impl HttpAuthKind {
+    pub const ALL: [HttpAuthKind; 5] = [HttpAuthKind::None, HttpAuthKind::Basic, HttpAuthKind::Digest, HttpAuthKind::Bearer, HttpAuthKind::OAuth2];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            HttpAuthKind::None => "None",
+            HttpAuthKind::Basic => "Basic",
+            HttpAuthKind::Digest => "Digest",
+            HttpAuthKind::Bearer => "Bearer Token",
+            HttpAuthKind::OAuth2 => "OAuth2",
+        }
+    }
+}
+
+// How requests to the PBX log in to the server in front of it
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct HttpAuth {
+    pub kind: HttpAuthKind,
+    pub username: String,
+    // Password, the bearer token or the OAuth2 client secret; kept in the Keychain
+    pub password: String,
+    // Where OAuth2 tokens come from, and where Sign In sends the browser
+    pub token_url: String,
+    pub authorize_url: String,
+    pub scope: String,
+    // Whose OAuth2 refresh token to use
+    pub profile: String,
+}
+
+impl HttpAuth {
+    pub fn from_state(state: &AppState) -> Self {
+        HttpAuth {
+            kind: state.http_auth,
+            username: state.http_auth_username.clone(),
+            password: state.http_auth_password.clone(),
+            token_url: state.oauth_token_url.clone(),
+            authorize_url: state.oauth_authorize_url.clone(),
+            scope: state.oauth_scope.clone(),
+            profile: if state.profile.trim().is_empty() { DEFAULT_PROFILE.to_string() } else { state.profile.trim().to_string() },
+        }
+    }
+
+    // Whether the settings are complete, before they're saved
+    pub fn check(&self) -> Result<(), String> {
+        match self.kind {
+            HttpAuthKind::Basic | HttpAuthKind::Digest if self.username.trim().is_empty() => Err(format!("{} authentication needs a username", self.kind.label())),
+            HttpAuthKind::Bearer if self.password.trim().is_empty() => Err("Bearer token authentication needs the token".to_string()),
+            HttpAuthKind::OAuth2 if self.username.trim().is_empty() => Err("OAuth2 needs the client id as the username".to_string()),
+            HttpAuthKind::OAuth2 => {
+                check_oauth_url(&self.token_url, "token URL")?;
+                match self.authorize_url.trim().is_empty() {
+                    true => Ok(()),
+                    false => check_oauth_url(&self.authorize_url, "sign-in URL"),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The Authorization header sent up front; Digest waits for the challenge
+    // and OAuth2 for its token
+    pub fn header(&self) -> Option<HeaderValue> {
+        let value = match self.kind {
+            HttpAuthKind::None | HttpAuthKind::Digest | HttpAuthKind::OAuth2 => return None,
+            HttpAuthKind::Basic => format!("Basic {}", base64(format!("{}:{}", self.username, self.password).as_bytes())),
+            HttpAuthKind::Bearer => format!("Bearer {}", self.password.trim()),
+        };
+        HeaderValue::from_str(&value).ok().map(|mut value| {
+            value.set_sensitive(true);
+            value
+        })
+    }
+
+    // The answer to a Digest challenge from a WWW-Authenticate header, for a
+    // `method` request to `uri` (the path and query)
+    pub fn digest_answer(&self, challenge: &str, method: &str, uri: &str) -> Option<HeaderValue> {
+        let cnonce = format!("{:016x}", cnonce_seed());
+        let answer = digest_answer(&self.username, &self.password, challenge, method, uri, &cnonce)?;
+        HeaderValue::from_str(&answer).ok().map(|mut value| {
+            value.set_sensitive(true);
+            value
+        })
+    }
+}
+
+fn check_oauth_url(url: &str, name: &str) -> Result<(), String> {
+    match Url::parse(url.trim()) {
+        Ok(parsed) if matches!(parsed.scheme(), "https" | "http") => Ok(()),
+        _ if url.trim().is_empty() => Err(format!("OAuth2 needs the identity provider's {}", name)),
+        _ => Err(format!("The OAuth2 {} should be an http or https URL, not \"{}\"", name, url.trim())),
+    }
+}
+
+// Different for each answer, without pulling in a random number generator
+fn cnonce_seed() -> u64 {
+    static COUNT: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or_default();
+    let hash = Sha256::digest(format!("{}:{}:{}", nanos, std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed)));
+    u64::from_be_bytes(hash[..8].try_into().unwrap_or_default())
+}
+
+// The parameters of a Digest challenge, names lowercased and quotes removed.
+// None when it isn't one.
+fn challenge_parameters(challenge: &str) -> Option<Vec<(String, String)>> {
+    let (scheme, rest) = challenge.trim().split_once(char::is_whitespace)?;
+    if !scheme.eq_ignore_ascii_case("digest") {
+        return None;
+    }
+    let mut parameters = Vec::new();
+    let mut rest = rest.trim();
+    while !rest.is_empty() {
+        let (name, after) = rest.split_once('=')?;
+        let after = after.trim_start();
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (quoted[..end].to_string(), &quoted[end + 1..])
+            }
+            None => match after.find(',') {
+                Some(end) => (after[..end].trim().to_string(), &after[end..]),
+                None => (after.trim().to_string(), ""),
+            },
+        };
+        parameters.push((name.trim().to_lowercase(), value));
+        rest = after.trim_start().trim_start_matches(',').trim_start();
+    }
+    Some(parameters)
+}
+
+fn hex_digest(algorithm: &str, text: &str) -> String {
+    let bytes: Vec<u8> = if algorithm.starts_with("SHA-256") { Sha256::digest(text).to_vec() } else { Md5::digest(text).to_vec() };
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The Authorization header answering `challenge`, with `cnonce` as the
+// client's nonce. Only qop=auth is supported, as servers offering auth-int
+// offer it too. None when the challenge can't be answered.
+pub fn digest_answer(username: &str, password: &str, challenge: &str, method: &str, uri: &str, cnonce: &str) -> Option<String> {
+    let parameters = challenge_parameters(challenge)?;
+    let get = |name: &str| parameters.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    let realm = get("realm")?;
+    let nonce = get("nonce")?;
+    let algorithm = get("algorithm").unwrap_or("MD5").to_uppercase();
+    if !["MD5", "MD5-SESS", "SHA-256", "SHA-256-SESS"].contains(&algorithm.as_str()) {
+        return None;
+    }
+    let qop = get("qop").map(|qop| qop.split(',').any(|option| option.trim() == "auth"));
+    if qop == Some(false) {
+        return None;
+    }
+
+    let mut ha1 = hex_digest(&algorithm, &format!("{}:{}:{}", username, realm, password));
+    if algorithm.ends_with("-SESS") {
+        ha1 = hex_digest(&algorithm, &format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+    let ha2 = hex_digest(&algorithm, &format!("{}:{}", method, uri));
+    let nc = "00000001";
+    let response = match qop {
+        Some(_) => hex_digest(&algorithm, &format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2)),
+        None => hex_digest(&algorithm, &format!("{}:{}:{}", ha1, nonce, ha2)),
+    };
+
+    let mut answer = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+        username, realm, nonce, uri, algorithm.replace("-SESS", "-sess"), response
+    );
+    if qop.is_some() {
+        answer.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+    }
+    if let Some(opaque) = get("opaque") {
+        answer.push_str(&format!(", opaque=\"{}\"", opaque));
+    }
+    Some(answer)
+}
+
+// Standard base64 with padding, for Basic credentials
+pub(super) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| group | ((*byte as u32) << (16 - 8 * index)));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - 6 * index)) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,363 @@
+use druid::Data;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::UnboundedSender;
+
+mod asterisk;
+mod freeswitch;
+mod fusionpbx;
+mod generic_http;
+mod http;
+mod http_auth;
+mod oauth;
+mod request_builder;
+#[cfg(test)]
+mod request_builder_tests;
+mod threecx;
+
+pub use crate::tls::Tls;
+pub use http_auth::{HttpAuth, HttpAuthKind};
+pub use generic_http::parse_template as parse_http_request;
+pub use oauth::sign_in as oauth_sign_in;
+pub use request_builder::{click_to_call_parameters, parse_parameters};
+
+// How long the TCP backends wait for the PBX to accept the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Which kind of PBX calls are originated on
+#[derive(Clone, Copy, Data, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    // FusionPBX's click_to_call.php
+    #[default]
+    FusionPbx,
+    // Asterisk Manager Interface Originate action (TCP, port 5038)
+    AsteriskAmi,
+    // Asterisk REST Interface POST /ari/channels
+    AsteriskAri,
+    // FreeSWITCH event socket bgapi originate (TCP, port 8021)
+    FreeSwitchEsl,
+    // 3CX Call Control API makecall
+    ThreeCx,
+    // Any gateway taking an HTTP request written out in the settings
+    GenericHttp,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 6] = [
+        BackendKind::FusionPbx,
+        BackendKind::AsteriskAmi,
+        BackendKind::AsteriskAri,
+        BackendKind::FreeSwitchEsl,
+        BackendKind::ThreeCx,
+        BackendKind::GenericHttp,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BackendKind::FusionPbx => "FusionPBX",
+            BackendKind::AsteriskAmi => "Asterisk AMI",
+            BackendKind::AsteriskAri => "Asterisk ARI",
+            BackendKind::FreeSwitchEsl => "FreeSWITCH ESL",
+            BackendKind::ThreeCx => "3CX",
+            BackendKind::GenericHttp => "Generic HTTP",
+        }
+    }
+
+    // Backends reached over HTTPS, where keeping a pooled connection warm helps
+    pub fn uses_http(&self) -> bool {
+        matches!(self, BackendKind::FusionPbx | BackendKind::AsteriskAri | BackendKind::ThreeCx | BackendKind::GenericHttp)
+    }
+
+    // Backends that pass digits to dial after the call connects on to the
+    // dialplan. FusionPBX's click_to_call.php and 3CX have nowhere to put them.
+    pub fn sends_post_dial(&self) -> bool {
+        matches!(self, BackendKind::AsteriskAmi | BackendKind::AsteriskAri | BackendKind::FreeSwitchEsl)
+    }
+}
+
+// How to reach and log in to the PBX
+pub struct Connection<'a> {
+    // Domain or host, optionally with a scheme and/or port
+    pub host: &'a str,
+    // AMI/ARI user or 3CX client id; FusionPBX and FreeSWITCH don't use one
+    pub username: &'a str,
+    // API key, AMI/ARI/ESL password or 3CX client secret
+    pub secret: &'a str,
+    // Asterisk dialplan context, or the FreeSWITCH dialplan context
+    pub context: &'a str,
+    // How the HTTP backends check the PBX's certificate
+    pub tls: &'a Tls,
+    // The Generic HTTP backend's request, written out as in an HTTP file
+    pub http_request: &'a str,
+    // How the HTTP backends log in to a web server in front of the PBX
+    pub auth: &'a HttpAuth,
+}
+
+impl Connection<'_> {
+    // The HTTP client for this PBX's certificate settings
+    fn client(&self) -> Result<Client, CallError> {
+        crate::tls::client(self.tls).map_err(CallError::Failed)
+    }
+}
+
+// A Connection that owns its details, for calls that are hung up after the
+// settings they were placed with have gone
+#[derive(Clone)]
+pub struct Account {
+    pub kind: BackendKind,
+    pub host: String,
+    pub username: String,
+    pub secret: String,
+    pub context: String,
+    pub tls: Tls,
+    pub http_request: String,
+    pub auth: HttpAuth,
+}
+
+impl Account {
+    pub fn backend(&self) -> Box<dyn CallBackend + '_> {
+        backend_for(self.kind, Connection {
+            host: &self.host,
+            username: &self.username,
+            secret: &self.secret,
+            context: &self.context,
+            tls: &self.tls,
+            http_request: &self.http_request,
+            auth: &self.auth,
+        })
+    }
+}
+
+// One call to originate: ring `extension` first, then connect it to `number`
+pub struct CallRequest<'a> {
+    pub extension: &'a str,
+    pub number: &'a str,
+    pub auto_answer: bool,
+    // Tone the extension hears while the number rings, e.g. "uk-ring" (FusionPBX only)
+    pub ringback: &'a str,
+    // Whether the PBX should record the call: FusionPBX records it itself, the
+    // others pass it on to the dialplan
+    pub record: bool,
+    // Number the called party sees, None for the PBX's own choice (not 3CX)
+    pub caller_id: Option<&'a str>,
+    // Name and number the extension's own phone shows while it rings, from
+    // the profile's templates; the number being called when None (not 3CX)
+    pub display_name: Option<&'a str>,
+    pub display_number: Option<&'a str>,
+    // The profile's changes to click_to_call.php's parameters, one
+    // `name = value` or `-name` per line (FusionPBX only)
+    pub parameters: &'a str,
+    // Digits to send once the number answers, with p for a one-second pause
+    // and w to wait (see BackendKind::sends_post_dial)
+    pub post_dial: Option<&'a str>,
+}
+
+impl CallRequest<'_> {
+    // The number the extension's phone shows while it rings
+    pub fn shown_number(&self) -> &str {
+        self.display_number.unwrap_or(self.number)
+    }
+}
+
+// Why a call couldn't be placed. The HTTP variants carry a transcript of
+// the exchange with secrets masked, for PBX admins to look at.
+pub enum CallError {
+    // The PBX answered over HTTP with an error status
+    HttpStatus(reqwest::StatusCode, String),
+    // The HTTP request got no answer at all
+    Request(reqwest::Error, String),
+    // Couldn't connect to a TCP backend
+    Unreachable(String),
+    // The PBX turned down the login
+    Unauthorized(String),
+    // The PBX answered the request but reported that the call failed, with
+    // the reason and a transcript
+    Refused(String, String),
+    // The PBX refused the request, or it couldn't be made
+    Failed(String),
+    // Given up on before the PBX answered
+    Cancelled,
+}
+
+impl CallError {
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            CallError::HttpStatus(status, _) => Some(status.as_u16()),
+            CallError::Request(..) | CallError::Unreachable(_) | CallError::Unauthorized(_) | CallError::Refused(..) | CallError::Failed(_) | CallError::Cancelled => None,
+        }
+    }
+
+    // Whether the PBX turned down the key, user or password
+    pub fn unauthorized(&self) -> bool {
+        match self {
+            CallError::Unauthorized(_) => true,
+            CallError::HttpStatus(status, _) => status.as_u16() == 401 || status.as_u16() == 403,
+            CallError::Request(..) | CallError::Unreachable(_) | CallError::Refused(..) | CallError::Failed(_) | CallError::Cancelled => false,
+        }
+    }
+
+    // Whether the request never got to the PBX, so trying again can't place the call twice
+    pub fn unreachable(&self) -> bool {
+        match self {
+            CallError::Request(e, _) => e.is_connect(),
+            CallError::Unreachable(_) => true,
+            CallError::HttpStatus(..) | CallError::Unauthorized(_) | CallError::Refused(..) | CallError::Failed(_) | CallError::Cancelled => false,
+        }
+    }
+
+    pub fn transcript(&self) -> Option<&str> {
+        match self {
+            CallError::HttpStatus(_, transcript) | CallError::Request(_, transcript) | CallError::Refused(_, transcript) => Some(transcript),
+            CallError::Unreachable(_) | CallError::Unauthorized(_) | CallError::Failed(_) | CallError::Cancelled => None,
+        }
+    }
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::HttpStatus(status, _) => write!(f, "HTTP status {}", status),
+            CallError::Request(e, _) => write!(f, "{}", e),
+            CallError::Unreachable(message) | CallError::Unauthorized(message) | CallError::Refused(message, _) | CallError::Failed(message) => f.write_str(message),
+            CallError::Cancelled => f.write_str("Cancelled"),
+        }
+    }
+}
+
+// What the PBX said when it accepted a call
+#[derive(Default)]
+pub struct Originated {
+    // For backends that talk HTTP
+    pub http_status: Option<u16>,
+    // The PBX's id for the call, where it tells us, e.g. FreeSWITCH's UUID
+    pub call_id: Option<String>,
+}
+
+// The outcome of an originate request, once the PBX has accepted or refused
+// it. Dropping the future abandons the request.
+pub type Originate<'a> = Pin<Box<dyn Future<Output = Result<Originated, CallError>> + Send + 'a>>;
+
+// The outcome of a connection test: Ok once the PBX has accepted the login
+pub type Check<'a> = Pin<Box<dyn Future<Output = Result<(), CallError>> + Send + 'a>>;
+
+// The outcome of a hang-up: Ok once the call is over, including when it
+// already was
+pub type HangUp<'a> = Pin<Box<dyn Future<Output = Result<(), CallError>> + Send + 'a>>;
+
+// How a placed call is going, as the PBX reports it
+#[derive(Clone, Debug, PartialEq)]
+pub enum CallState {
+    // The extension's phone is ringing
+    Ringing,
+    // The extension picked up and the number is being called
+    Answered,
+    // The number answered; the two are talking
+    Connected,
+    // The call is over. `cause` is the PBX's reason, e.g. NORMAL_CLEARING or USER_BUSY.
+    HungUp { connected: bool, cause: String },
+}
+
+// Following a call: Ok once it's over, with each state sent along the way
+pub type Follow<'a> = Pin<Box<dyn Future<Output = Result<(), CallError>> + Send + 'a>>;
+
+// Whether an extension's phone can take a call, as the PBX sees it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Presence {
+    // No phone is registered for it, so calls can't ring it
+    Unregistered,
+    // Registered and free
+    Idle,
+    // Registered and on a call
+    OnCall,
+}
+
+// The outcome of asking for an extension's presence
+pub type PresenceLookup<'a> = Pin<Box<dyn Future<Output = Result<Presence, CallError>> + Send + 'a>>;
+
+// The outcome of asking how many new messages a voicemail box has
+pub type MessageCount<'a> = Pin<Box<dyn Future<Output = Result<u32, CallError>> + Send + 'a>>;
+
+// Something that can originate a call on a PBX
+pub trait CallBackend: Send + Sync {
+    fn originate<'a>(&'a self, request: &'a CallRequest<'a>) -> Originate<'a>;
+
+    // Reach the PBX and log in as a call would, without placing one
+    fn check(&self) -> Check<'_>;
+
+    // End a call this PBX placed, by the call_id it gave for it. Backends that
+    // don't give one never get asked.
+    fn hang_up<'a>(&'a self, _call_id: &'a str) -> HangUp<'a> {
+        Box::pin(async { Err(CallError::Failed("This PBX can't hang up calls from here".to_string())) })
+    }
+
+    // Report how the call `call_id` is going to `states` until it's over
+    fn follow<'a>(&'a self, _call_id: &'a str, _states: UnboundedSender<CallState>) -> Follow<'a> {
+        Box::pin(async { Err(CallError::Failed("This PBX doesn't say how its calls are going".to_string())) })
+    }
+
+    // Whether `extension`'s phone is registered and on a call. It may name
+    // its SIP domain, as in 201@pbx.example.com.
+    fn presence<'a>(&'a self, _extension: &'a str) -> PresenceLookup<'a> {
+        Box::pin(async { Err(CallError::Failed("This PBX doesn't say whether phones are registered".to_string())) })
+    }
+
+    // How many new messages `extension`'s voicemail box has. Like presence,
+    // it may name its domain, or its voicemail context on Asterisk.
+    fn new_voicemail<'a>(&'a self, _extension: &'a str) -> MessageCount<'a> {
+        Box::pin(async { Err(CallError::Failed("This PBX doesn't say how many voicemail messages there are".to_string())) })
+    }
+}
+
+pub fn backend_for<'a>(kind: BackendKind, connection: Connection<'a>) -> Box<dyn CallBackend + 'a> {
+    match kind {
+        BackendKind::FusionPbx => Box::new(fusionpbx::FusionPbx { connection }),
+        BackendKind::AsteriskAmi => Box::new(asterisk::Ami { connection }),
+        BackendKind::AsteriskAri => Box::new(asterisk::Ari { connection }),
+        BackendKind::FreeSwitchEsl => Box::new(freeswitch::Esl { connection }),
+        BackendKind::ThreeCx => Box::new(threecx::ThreeCx { connection }),
+        BackendKind::GenericHttp => Box::new(generic_http::GenericHttp { connection }),
+    }
+}
+
+// Wait for `request` (an originate, check or hang-up) for up to `timeout`,
+// failing the way a PBX that never answers does
+pub async fn within<T>(timeout: Duration, request: impl Future<Output = Result<T, CallError>>) -> Result<T, CallError> {
+    tokio::time::timeout(timeout, request)
+        .await
+        .unwrap_or_else(|_| Err(timed_out(timeout)))
+}
+
+// The error for a PBX that didn't answer within `timeout`
+pub fn timed_out(timeout: Duration) -> CallError {
+    CallError::Failed(format!("The PBX didn't answer within {} seconds", timeout.as_secs()))
+}
+
+// Open a connection for the TCP backends, split so replies can be read while
+// writing. `service` names what's at `address` in errors.
+async fn connect(address: &str, service: &str) -> Result<(OwnedReadHalf, OwnedWriteHalf), CallError> {
+    match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(Ok(stream)) => Ok(stream.into_split()),
+        Ok(Err(e)) => Err(CallError::Unreachable(format!("Couldn't connect to {} at {}: {}", service, address, e))),
+        Err(_) => Err(CallError::Unreachable(format!("Couldn't connect to {} at {}: timed out", service, address))),
+    }
+}
+
+// "host:port" for the TCP backends, dropping any scheme that was typed in
+fn tcp_address(host: &str, default_port: u16) -> String {
+    let host = host
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+    if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, default_port)
+    }
+}
@@ -0,0 +1,223 @@
+// OAuth2 for a PBX behind an identity provider, e.g. Keycloak or Azure AD in
+// front of the PBX's API. Requests carry an access token from the provider's
+// token URL, cached in memory and fetched again shortly before it runs out.
+// After Sign In, tokens come from the refresh token it left in the Keychain;
+// without one, the client secret gets them with the client credentials grant.
+
+use super::http_auth::base64;
+use super::HttpAuth;
+use crate::{http_client, keychain, proxy};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+use url::Url;
+
+// Tokens this close to running out are replaced before a call, not during it
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+// For providers that don't say how long their tokens last
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(3600);
+// How long Sign In waits for the browser to come back
+const SIGN_IN_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct CachedToken {
+    token: String,
+    expires: Instant,
+}
+
+static TOKENS: OnceLock<Mutex<HashMap<String, CachedToken>>> = OnceLock::new();
+
+fn tokens() -> &'static Mutex<HashMap<String, CachedToken>> {
+    TOKENS.get_or_init(Default::default)
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+// Tokens are for one profile's client at one provider
+fn cache_key(auth: &HttpAuth) -> String {
+    format!("{}\n{}\n{}", auth.profile, auth.token_url.trim(), auth.username.trim())
+}
+
+fn refresh_token_account(profile: &str) -> String {
+    format!("{}:{}", keychain::OAUTH_REFRESH_TOKEN, profile)
+}
+
+// Ask the token URL for a token with `grant`, as `auth`'s client
+async fn request_token(auth: &HttpAuth, grant: &[(&str, &str)]) -> Result<TokenResponse, String> {
+    let mut form = grant.to_vec();
+    form.push(("client_id", auth.username.trim()));
+    if !auth.password.is_empty() {
+        form.push(("client_secret", auth.password.as_str()));
+    }
+    proxy::prepare(auth.token_url.trim()).await;
+    let response = http_client()
+        .post(auth.token_url.trim())
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| format!("Couldn't reach the token URL: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        // Providers say what's wrong in `error`, e.g. invalid_grant for a revoked refresh token
+        let body = response.text().await.unwrap_or_default();
+        let error = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|body| body["error"].as_str().map(str::to_string))
+            .unwrap_or_else(|| format!("HTTP {}", status));
+        return Err(format!("The identity provider refused the token request: {}", error));
+    }
+    response.json().await.map_err(|e| format!("The token URL didn't answer with a token: {}", e))
+}
+
+// Cache the access token and keep any new refresh token; returns the access token
+fn remember(auth: &HttpAuth, token: TokenResponse) -> String {
+    if let Some(refresh_token) = token.refresh_token.filter(|token| !token.is_empty()) {
+        if let Err(e) = keychain::set_password(&refresh_token_account(&auth.profile), &refresh_token) {
+            warn!("Couldn't keep the OAuth2 refresh token in the Keychain: {}", e);
+        }
+    }
+    let lifetime = token.expires_in.map(Duration::from_secs).unwrap_or(DEFAULT_LIFETIME);
+    let cached = CachedToken { token: token.access_token.clone(), expires: Instant::now() + lifetime };
+    tokens().lock().unwrap().insert(cache_key(auth), cached);
+    token.access_token
+}
+
+// An access token good for the next request, fetching a new one when the
+// cached one is about to run out
+pub async fn access_token(auth: &HttpAuth) -> Result<String, String> {
+    if let Some(cached) = tokens().lock().unwrap().get(&cache_key(auth)) {
+        if cached.expires > Instant::now() + REFRESH_MARGIN {
+            return Ok(cached.token.clone());
+        }
+    }
+    let refresh_token = keychain::password(&refresh_token_account(&auth.profile)).filter(|token| !token.is_empty());
+    let token = match refresh_token {
+        Some(refresh_token) => request_token(auth, &[("grant_type", "refresh_token"), ("refresh_token", &refresh_token)])
+            .await
+            .map_err(|e| format!("{}; Sign In again under Web Server Login", e))?,
+        None if !auth.password.is_empty() => {
+            let mut grant = vec![("grant_type", "client_credentials")];
+            if !auth.scope.trim().is_empty() {
+                grant.push(("scope", auth.scope.trim()));
+            }
+            request_token(auth, &grant).await?
+        }
+        None => return Err("OAuth2 needs a client secret, or Sign In under Web Server Login".to_string()),
+    };
+    Ok(remember(auth, token))
+}
+
+// Drop the cached access token, e.g. once the PBX turned it down
+pub fn forget(auth: &HttpAuth) {
+    tokens().lock().unwrap().remove(&cache_key(auth));
+}
+
+// URL-safe base64 without padding, as PKCE uses
+fn base64_url(bytes: &[u8]) -> String {
+    base64(bytes).trim_end_matches('=').replace('+', "-").replace('/', "_")
+}
+
+// Something nobody can guess, for the PKCE verifier and the state
+fn random_string() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut bytes))
+        .map_err(|e| format!("Couldn't read random bytes for signing in: {}", e))?;
+    Ok(base64_url(&bytes))
+}
+
+// Sign In: the authorization code flow with PKCE in the browser, coming back
+// to a listener on this Mac. The refresh token it ends with goes in the
+// Keychain for `auth`'s profile, so calls get tokens without asking again.
+pub async fn sign_in(auth: HttpAuth) -> Result<(), String> {
+    let mut url = Url::parse(auth.authorize_url.trim()).map_err(|e| format!("The sign-in URL isn't valid: {}", e))?;
+    let listener = TcpListener::bind("127.0.0.1:0").await.map_err(|e| format!("Couldn't wait for the browser: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+    let verifier = random_string()?;
+    let state = random_string()?;
+
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", auth.username.trim())
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("state", &state)
+        .append_pair("code_challenge", &base64_url(&Sha256::digest(&verifier)))
+        .append_pair("code_challenge_method", "S256");
+    if !auth.scope.trim().is_empty() {
+        url.query_pairs_mut().append_pair("scope", auth.scope.trim());
+    }
+    info!("Signing in at {}", auth.authorize_url.trim());
+    match std::process::Command::new("open").arg(url.as_str()).status() {
+        Ok(status) if status.success() => {}
+        _ => return Err("Couldn't open the sign-in page in the browser".to_string()),
+    }
+
+    let code = tokio::time::timeout(SIGN_IN_TIMEOUT, callback(&listener, &state))
+        .await
+        .map_err(|_| "Sign In wasn't finished in the browser within 5 minutes".to_string())??;
+    let grant = [("grant_type", "authorization_code"), ("code", code.as_str()), ("redirect_uri", redirect_uri.as_str()), ("code_verifier", verifier.as_str())];
+    let token = request_token(&auth, &grant).await?;
+    if token.refresh_token.is_none() {
+        warn!("The identity provider gave no refresh token; Sign In will be needed again when the access token runs out");
+    }
+    remember(&auth, token);
+    Ok(())
+}
+
+// Wait for the browser to be sent back with the code, telling it how it went
+async fn callback(listener: &TcpListener, state: &str) -> Result<String, String> {
+    loop {
+        let (mut stream, _) = listener.accept().await.map_err(|e| format!("Couldn't wait for the browser: {}", e))?;
+        let mut buffer = vec![0u8; 8192];
+        let read = stream.read(&mut buffer).await.unwrap_or(0);
+        let request = String::from_utf8_lossy(&buffer[..read]);
+        let target = request.lines().next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or("/");
+        let url = Url::parse(&format!("http://127.0.0.1{}", target)).ok().filter(|url| url.path() == "/callback");
+        // Anything else, e.g. the favicon, isn't the sign-in coming back
+        let Some(url) = url else {
+            let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+            continue;
+        };
+
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        // Only the browser sent by this sign-in knows its state; anything else
+        // on the port mustn't end it
+        if query.get("state").map(String::as_str) != Some(state) {
+            warn!("Ignored a request to the sign-in callback from a different sign-in");
+            respond(&mut stream, "400 Bad Request", "This isn't the sign-in the app is waiting for.").await;
+            continue;
+        }
+        let result = if let Some(error) = query.get("error") {
+            Err(format!("Sign In failed: {}", query.get("error_description").unwrap_or(error)))
+        } else {
+            query.get("code").cloned().ok_or_else(|| "The browser came back without a sign-in code".to_string())
+        };
+        match &result {
+            Ok(_) => respond(&mut stream, "200 OK", "Signed in to the PBX. You can close this tab.").await,
+            Err(e) => respond(&mut stream, "200 OK", e).await,
+        }
+        return result;
+    }
+}
+
+// Send the browser a plain-text page
+async fn respond(stream: &mut TcpStream, status: &str, page: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        page.len(),
+        page
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
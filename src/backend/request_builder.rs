@@ -0,0 +1,99 @@
+// URLs for the HTTP backends. Numbers, extensions and keys go through url's
+// encoders rather than format!, so a + or # in a number reaches the PBX as
+// typed and an & can't add parameters of its own.
+
+use super::{CallError, CallRequest};
+use crate::base_url;
+use url::Url;
+
+// The PBX's base URL with `path` added one segment at a time and `query`
+// appended, each percent-encoded where needed
+pub fn pbx_url(host: &str, path: &[&str], query: &[(&str, &str)]) -> Result<Url, CallError> {
+    let invalid = |reason: String| CallError::Failed(format!("Invalid PBX address {}: {}", host, reason));
+    let mut url = Url::parse(&base_url(host)).map_err(|e| invalid(e.to_string()))?;
+    url.path_segments_mut()
+        .map_err(|_| invalid("it can't have a path".to_string()))?
+        .pop_if_empty()
+        .extend(path);
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+    Ok(url)
+}
+
+// What a profile changes about click_to_call.php's parameters, written one
+// per line: `name = value` sets or adds one, with an empty value sending it
+// empty, and `-name` leaves it out
+#[derive(Debug, PartialEq)]
+pub enum ParameterChange {
+    Set(String, String),
+    Remove(String),
+}
+
+// Read the parameter changes as typed in the settings
+pub fn parse_parameters(parameters: &str) -> Result<Vec<ParameterChange>, String> {
+    let mut changes = Vec::new();
+    for line in parameters.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (change, name) = match line.strip_prefix('-') {
+            Some(name) => (ParameterChange::Remove(name.trim().to_string()), name.trim()),
+            None => {
+                let Some((name, value)) = line.split_once('=') else {
+                    return Err(format!("Parameter \"{}\" isn't written as name = value or -name", line));
+                };
+                (ParameterChange::Set(name.trim().to_string(), value.trim().to_string()), name.trim())
+            }
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "_-[].".contains(c)) {
+            return Err(format!("Parameter \"{}\" needs a name of letters, digits and _", line));
+        }
+        // The key stays in the Keychain, not in a setting anyone can read
+        if name == "key" {
+            return Err("The API key is set under Key, not as a parameter".to_string());
+        }
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
+// click_to_call.php's parameters for `request`, with the profile's changes
+// made. The number doubles as the caller ID names and numbers shown on both
+// ends, unless the profile's templates say what the extension shows or a
+// caller ID was chosen for the called party.
+pub fn click_to_call_parameters(request: &CallRequest<'_>, key: &str, changes: &[ParameterChange]) -> Vec<(String, String)> {
+    let number = request.number;
+    let caller_id = request.caller_id.unwrap_or(number);
+    let mut parameters: Vec<(String, String)> = [
+        ("src_cid_name", request.display_name.unwrap_or(number)),
+        ("src_cid_number", request.shown_number()),
+        ("dest_cid_name", caller_id),
+        ("dest_cid_number", caller_id),
+        ("src", request.extension),
+        ("dest", number),
+        ("auto_answer", if request.auto_answer { "true" } else { "false" }),
+        ("rec", if request.record { "true" } else { "false" }),
+        ("ringback", request.ringback),
+    ]
+    .iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+    for change in changes {
+        match change {
+            ParameterChange::Set(name, value) => match parameters.iter_mut().find(|(existing, _)| existing == name) {
+                Some(parameter) => parameter.1 = value.clone(),
+                None => parameters.push((name.clone(), value.clone())),
+            },
+            ParameterChange::Remove(name) => parameters.retain(|(existing, _)| existing != name),
+        }
+    }
+    // Last, like FusionPBX's own examples
+    parameters.push(("key".to_string(), key.to_string()));
+    parameters
+}
+
+// FusionPBX's click_to_call.php request
+pub fn click_to_call_url(host: &str, request: &CallRequest<'_>, key: &str) -> Result<Url, CallError> {
+    let changes = parse_parameters(request.parameters).map_err(|e| CallError::Failed(format!("Invalid click_to_call parameters: {}", e)))?;
+    let parameters = click_to_call_parameters(request, key, &changes);
+    let query: Vec<(&str, &str)> = parameters.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+    pbx_url(host, &["app", "click_to_call", "click_to_call.php"], &query)
+}
@@ -0,0 +1,127 @@
+// Tests for the URLs sent to HTTP backends: the PBX address as typed in the
+// settings, and numbers, extensions and keys encoded so they arrive intact.
+// Also the logins for a web server in front of the PBX.
+
+use super::request_builder::{click_to_call_url, parse_parameters, pbx_url, ParameterChange};
+use super::http_auth::digest_answer;
+use super::{CallRequest, HttpAuth, HttpAuthKind};
+use crate::base_url;
+
+fn request<'a>(number: &'a str, auto_answer: bool) -> CallRequest<'a> {
+    CallRequest { extension: "201", number, auto_answer, ringback: "us-ring", record: false, caller_id: None, display_name: None, display_number: None, parameters: "", post_dial: None }
+}
+
+fn query(url: &url::Url, name: &str) -> Option<String> {
+    url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned())
+}
+
+#[test]
+fn base_url_defaults_to_https() {
+    assert_eq!(base_url("pbx.example.com"), "https://pbx.example.com");
+    assert_eq!(base_url("http://pbx.example.com:8080"), "http://pbx.example.com:8080");
+    assert_eq!(base_url("https://pbx.example.com/"), "https://pbx.example.com/");
+}
+
+#[test]
+fn pbx_url_adds_path_segments_and_query() {
+    let url = pbx_url("pbx.example.com", &["ari", "channels"], &[("endpoint", "PJSIP/201")]).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(url.as_str(), "https://pbx.example.com/ari/channels?endpoint=PJSIP%2F201");
+
+    // A trailing slash or a path of its own in the address is kept, without doubling the slash
+    let url = pbx_url("https://pbx.example.com/fusion/", &["app"], &[]).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(url.as_str(), "https://pbx.example.com/fusion/app");
+}
+
+#[test]
+fn pbx_url_encodes_what_would_change_the_request() {
+    let url = pbx_url("pbx.example.com", &["calls", "a/b"], &[("dest", "+1 555#22&key=x")]).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(url.path(), "/calls/a%2Fb");
+    assert_eq!(query(&url, "dest").as_deref(), Some("+1 555#22&key=x"));
+    assert_eq!(url.query_pairs().count(), 1);
+}
+
+#[test]
+fn pbx_url_rejects_addresses_that_arent_urls() {
+    assert!(pbx_url("pbx example com", &["app"], &[]).is_err());
+    assert!(pbx_url("", &["app"], &[]).is_err());
+}
+
+#[test]
+fn click_to_call_url_has_every_parameter() {
+    let url = click_to_call_url("pbx.example.com", &request("+15551234567", true), "k&y").unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(url.path(), "/app/click_to_call/click_to_call.php");
+    for name in ["src_cid_name", "src_cid_number", "dest_cid_name", "dest_cid_number", "dest"] {
+        assert_eq!(query(&url, name).as_deref(), Some("+15551234567"), "{}", name);
+    }
+    assert_eq!(query(&url, "src").as_deref(), Some("201"));
+    assert_eq!(query(&url, "auto_answer").as_deref(), Some("true"));
+    assert_eq!(query(&url, "rec").as_deref(), Some("false"));
+    assert_eq!(query(&url, "ringback").as_deref(), Some("us-ring"));
+    assert_eq!(query(&url, "key").as_deref(), Some("k&y"));
+}
+
+#[test]
+fn click_to_call_url_records_and_shows_the_chosen_caller_id() {
+    let request = CallRequest { record: true, caller_id: Some("+15550100"), ..request("+15551234567", true) };
+    let url = click_to_call_url("pbx.example.com", &request, "key").unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(query(&url, "rec").as_deref(), Some("true"));
+    assert_eq!(query(&url, "dest_cid_number").as_deref(), Some("+15550100"));
+    assert_eq!(query(&url, "src_cid_number").as_deref(), Some("+15551234567"));
+}
+
+#[test]
+fn click_to_call_url_shows_the_templated_caller_id_on_the_extension() {
+    let request = CallRequest { display_name: Some("Jane Doe (Acme)"), display_number: Some("5551234567"), ..request("+15551234567", true) };
+    let url = click_to_call_url("pbx.example.com", &request, "key").unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(query(&url, "src_cid_name").as_deref(), Some("Jane Doe (Acme)"));
+    assert_eq!(query(&url, "src_cid_number").as_deref(), Some("5551234567"));
+    assert_eq!(query(&url, "dest").as_deref(), Some("+15551234567"));
+}
+
+#[test]
+fn click_to_call_url_without_auto_answer() {
+    let url = click_to_call_url("pbx.example.com", &request("5551234", false), "key").unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(query(&url, "auto_answer").as_deref(), Some("false"));
+}
+
+#[test]
+fn click_to_call_url_takes_the_profiles_parameter_changes() {
+    let request = CallRequest { parameters: "timeout = 60\nringback = \n-rec\n\ncontext=default", ..request("5551234", true) };
+    let url = click_to_call_url("pbx.example.com", &request, "key").unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(query(&url, "timeout").as_deref(), Some("60"));
+    assert_eq!(query(&url, "context").as_deref(), Some("default"));
+    assert_eq!(query(&url, "ringback").as_deref(), Some(""));
+    assert_eq!(query(&url, "rec"), None);
+    assert_eq!(query(&url, "dest").as_deref(), Some("5551234"));
+    assert!(url.query().unwrap_or_default().ends_with("key=key"));
+}
+
+#[test]
+fn parameters_have_to_be_name_value_and_leave_the_key_alone() {
+    assert_eq!(parse_parameters(" -rec "), Ok(vec![ParameterChange::Remove("rec".to_string())]));
+    assert!(parse_parameters("timeout 60").is_err());
+    assert!(parse_parameters("= 60").is_err());
+    assert!(parse_parameters("key = abc").is_err());
+    assert!(parse_parameters("-key").is_err());
+}
+
+#[test]
+fn web_server_logins_are_answered_as_the_rfcs_do() {
+    let basic = HttpAuth { kind: HttpAuthKind::Basic, username: "Aladdin".to_string(), password: "open sesame".to_string(), ..HttpAuth::default() };
+    assert_eq!(basic.header().unwrap(), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+    let bearer = HttpAuth { kind: HttpAuthKind::Bearer, username: String::new(), password: "t0ken".to_string(), ..HttpAuth::default() };
+    assert_eq!(bearer.header().unwrap(), "Bearer t0ken");
+    assert!(HttpAuth { kind: HttpAuthKind::Bearer, ..HttpAuth::default() }.check().is_err());
+    let oauth = HttpAuth { kind: HttpAuthKind::OAuth2, username: "click-to-call".to_string(), token_url: "https://id.example.com/token".to_string(), ..HttpAuth::default() };
+    assert!(oauth.check().is_ok());
+    assert_eq!(oauth.header(), None);
+    assert!(HttpAuth { token_url: "id.example.com/token".to_string(), ..oauth.clone() }.check().is_err());
+    assert!(HttpAuth { username: String::new(), ..oauth }.check().is_err());
+
+    // RFC 2617's example
+    let challenge = r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#;
+    let answer = digest_answer("Mufasa", "Circle Of Life", challenge, "GET", "/dir/index.html", "0a4f113b").unwrap();
+    assert!(answer.contains(r#"response="6629fae49393a05397450978507c4ef1""#), "{}", answer);
+    assert!(answer.contains(r#"opaque="5ccc069c403ebaf9f0171e9517f40e41""#));
+    assert_eq!(digest_answer("Mufasa", "x", "Basic realm=\"x\"", "GET", "/", "0a4f113b"), None);
+}
@@ -0,0 +1,52 @@
+use super::{http, request_builder, CallBackend, CallError, CallRequest, Check, Connection, Originate, Originated};
+
+// Originate through the 3CX (v20) Call Control API, authenticating as an API
+// client with the client credentials grant
+pub struct ThreeCx<'a> {
+    pub connection: Connection<'a>,
+}
+
+impl ThreeCx<'_> {
+    async fn access_token(&self) -> Result<String, CallError> {
+        let request = self.connection.client()?
+            .post(request_builder::pbx_url(self.connection.host, &["connect", "token"], &[])?)
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", self.connection.username),
+                ("client_secret", self.connection.secret),
+            ]);
+        let response = http::send(request, self.connection.auth, &[self.connection.secret]).await?;
+
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| CallError::Failed(format!("Unexpected token response: {}", e)))?;
+        body["access_token"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| CallError::Failed("3CX didn't return an access token".to_string()))
+    }
+
+    async fn call(&self, request: &CallRequest<'_>) -> Result<Originated, CallError> {
+        // 3CX decides itself whether the extension's phone auto-answers
+        let token = self.access_token().await?;
+        let url = request_builder::pbx_url(self.connection.host, &["callcontrol", request.extension, "makecall"], &[])?;
+
+        let request = self.connection.client()?
+            .post(url)
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "destination": request.number }));
+        let response = http::send(request, self.connection.auth, &[self.connection.secret]).await?;
+        Ok(Originated { http_status: Some(response.status().as_u16()), call_id: None })
+    }
+}
+
+impl CallBackend for ThreeCx<'_> {
+    fn originate<'a>(&'a self, request: &'a CallRequest<'a>) -> Originate<'a> {
+        Box::pin(self.call(request))
+    }
+
+    fn check(&self) -> Check<'_> {
+        Box::pin(async { self.access_token().await.map(|_| ()) })
+    }
+}
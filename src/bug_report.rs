@@ -0,0 +1,96 @@
+use chrono::{DateTime, Local};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use crate::config::AppState;
+
+// How many call attempts are kept for the report
+const MAX_RECENT_CALLS: usize = 50;
+
+struct CallRecord {
+    time: DateTime<Local>,
+    number: String,
+    result: String,
+}
+
+// Call attempts made during this session, newest last
+static RECENT_CALLS: Mutex<VecDeque<CallRecord>> = Mutex::new(VecDeque::new());
+
+// Remember a call attempt for the next bug report
+pub fn record_call(number: &str, result: &str) {
+    let mut recent = RECENT_CALLS.lock().unwrap();
+    if recent.len() == MAX_RECENT_CALLS {
+        recent.pop_front();
+    }
+    recent.push_back(CallRecord {
+        time: Local::now(),
+        number: number.to_string(),
+        result: result.to_string(),
+    });
+}
+
+// Replace a number with a short salted hash, keeping only its shape.
+// The same number gets the same hash within one report, but the salt changes
+// every export so hashes can't be matched against a list of known numbers.
+fn redact_number(number: &str, salt: u64) -> String {
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    let mut hasher = Sha256::new();
+    hasher.update(salt.to_le_bytes());
+    hasher.update(digits.as_bytes());
+    let hash: String = hasher.finalize().iter().take(4).map(|b| format!("{:02x}", b)).collect();
+
+    let plus = if number.starts_with('+') { "+, " } else { "" };
+    format!("#{} ({}{} digits)", hash, plus, digits.len())
+}
+
+// Markdown report for pasting into (or attaching to) a GitHub issue.
+// Domains are kept since they matter for debugging; numbers and the key are not.
+pub fn build_report(state: &AppState) -> String {
+    let salt = RandomState::new().build_hasher().finish();
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+
+    let mut report = String::new();
+    report.push_str("<details>\n<summary>Click-To-Call diagnostics</summary>\n\n");
+    report.push_str(&format!("- **Version:** {}\n", env!("CARGO_PKG_VERSION")));
+    report.push_str(&format!("- **OS:** {} ({})\n", std::env::consts::OS, std::env::consts::ARCH));
+    report.push_str(&format!("- **Domain:** `{}`\n", state.domain));
+    report.push_str(&format!("- **Extension set:** {}\n", yes_no(!state.extension.is_empty())));
+    report.push_str(&format!("- **Key set:** {} (not included)\n", yes_no(!state.key.is_empty())));
+    report.push_str(&format!("- **Auto answer:** {}\n", yes_no(state.auto_answer())));
+    report.push_str(&format!("- **Country:** {}\n", if state.country.is_empty() { "-" } else { &state.country }));
+    report.push_str(&format!("- **Kiosk mode:** {}\n\n", yes_no(state.kiosk)));
+
+    let recent = RECENT_CALLS.lock().unwrap();
+    if recent.is_empty() {
+        report.push_str("No calls were placed in this session.\n\n");
+    } else {
+        report.push_str("| Time | Number | Result |\n| --- | --- | --- |\n");
+        for call in recent.iter() {
+            let number = redact_number(&call.number, salt);
+
+            // Results can quote the number, and request errors quote the whole URL
+            let mut result = call.result.replace(&call.number, &number);
+            if !state.key.is_empty() {
+                result = result.replace(&state.key, "[key]");
+            }
+            if !state.extension.is_empty() {
+                result = result.replace(&format!("src={}", state.extension), "src=[extension]");
+            }
+
+            report.push_str(&format!(
+                "| {} | {} | {} |\n",
+                call.time.format("%Y-%m-%d %H:%M:%S"),
+                number,
+                result.replace('|', "\\|")
+            ));
+        }
+        report.push('\n');
+    }
+
+    report.push_str("Numbers are replaced with salted hashes: the same number has the same hash within this report only.\n");
+    report.push_str("</details>\n");
+    report
+}
@@ -0,0 +1,574 @@
+// Placing a call: the checks every call goes through whichever way it was
+// asked for, the request to the PBX and recording how it went
+
+use crate::audit::{self, CallOrigin};
+use crate::call_links::Callback;
+use crate::config::AppState;
+use crate::ui::CONFIRM_CALL;
+use crate::{active_calls, backend, bug_report, call_events, call_options, caller_id_template, contacts, corporate_directory, dial_plan, directory, duplicates, emergency, failover, history, notify, number_format, number_lists, queue, retry, ringback, runtime, spotlight, srv, stats, tel_uri, tls};
+use druid::{Data, Target};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+// Shown instead of dialing when the number is an emergency number
+pub const EMERGENCY_MESSAGE: &str = "Emergency numbers can't be dialed with Click-To-Call. Dial them directly from your phone.";
+
+// How long a call request may take before it's given up on, unless the
+// profile says otherwise
+pub const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Woken to abandon every call request still waiting for the PBX
+static CANCEL_CALLS: OnceLock<tokio::sync::Notify> = OnceLock::new();
+
+// Whether tel: links wait for the user to confirm the call. Kept outside
+// AppState so the socket listener sees the setting change as soon as it's saved.
+pub static CONFIRM_TEL_LINKS: AtomicBool = AtomicBool::new(false);
+
+// Abandon every call request that's still waiting for the PBX; each is
+// reported as cancelled
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+pub fn cancel_calls() {
+    CANCEL_CALLS.get_or_init(tokio::sync::Notify::new).notify_waiters();
+}
+
+// One call from the dialer while its request is with the PBX, so Place Call
+// can cancel it. Clones share the same call.
+#[derive(Clone, Default)]
+pub struct InFlightCall(Arc<InFlight>);
+
+#[derive(Default)]
+struct InFlight {
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+    wake: tokio::sync::Notify,
+}
+
+impl InFlightCall {
+    // Abandon the request; if the PBX already placed the call, it's hung up
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.wake.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Note that place_call is done with the call, whichever way it went
+    pub fn finish(&self) {
+        self.0.finished.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.0.finished.load(Ordering::SeqCst)
+    }
+
+    // Finishes once cancel() is called, or right away if it already was
+    async fn cancelled(&self) {
+        let woken = self.0.wake.notified();
+        tokio::pin!(woken);
+        woken.as_mut().enable();
+        if !self.is_cancelled() {
+            woken.await;
+        }
+    }
+}
+
+impl Data for InFlightCall {
+    fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Function to make a direct call without involving the UI.
+// Returns the request task so launch paths that exit afterwards can wait for it.
+// `callback` is told how the call went.
+pub fn make_direct_call(state: &AppState, phone_number: &str, origin: CallOrigin, private: bool, callback: Option<Callback>) -> tokio::task::JoinHandle<()> {
+    info!("Making direct call to {} without showing UI", logged_number(phone_number, private));
+    
+    // Clone data we need for the HTTP request
+    let settings = state.clone();
+    let phone_number = phone_number.to_string();
+    
+    runtime().spawn(async move {
+        let result = place_call(&settings, &phone_number, origin, None, None, private, None).await;
+        if let Some(callback) = callback {
+            callback.report(&phone_number, &result);
+        }
+        info!("{}", result.unwrap_or_else(|failure| failure.message));
+    })
+}
+
+// Call a number from a tel: link with the settings in `state`, asking first
+// if Confirm Before Dialing is on or `ask` says to. With the app's UI (`event_sink`) the
+// question is a prompt window and the call is placed from there; without it,
+// or when the link's `callback` has to hear how it went, this waits for the
+// answer in a dialog. Returns the request task if the call was placed right away.
+pub fn dial_tel_link(state: &AppState, number: &str, origin: CallOrigin, private: bool, ask: bool, callback: Option<Callback>, event_sink: Option<&druid::ExtEventSink>) -> Option<tokio::task::JoinHandle<()>> {
+    if ask || CONFIRM_TEL_LINKS.load(Ordering::SeqCst) {
+        if let Some(event_sink) = event_sink.filter(|_| callback.is_none()) {
+            event_sink.submit_command(CONFIRM_CALL, (number.to_string(), origin, private), Target::Auto).ok();
+            return None;
+        }
+        if !confirm_in_dialog(number, contact_name(state, number).as_deref()) {
+            warn!("Call to {} from a tel: link not confirmed", logged_number(number, private));
+            if let Some(callback) = callback {
+                callback.cancelled();
+            }
+            return None;
+        }
+    }
+    Some(make_direct_call(state, number, origin, private, callback))
+}
+
+// Ask whether to call `number` where there's no window to ask in: headless
+// mode, or a launch that exits after the call. Gives up after a minute.
+#[cfg(target_os = "macos")]
+fn confirm_in_dialog(number: &str, name: Option<&str>) -> bool {
+    let prompt = match name {
+        Some(name) => format!("Call {} ({})?", name, number),
+        None => format!("Call {}?", number),
+    };
+    let quoted = format!("\"{}\"", prompt.replace('\\', "\\\\").replace('"', "\\\""));
+    let script = format!(
+        "display dialog {} with title \"Confirm Call\" buttons {{\"Cancel\", \"Call\"}} default button \"Call\" cancel button \"Cancel\" giving up after 60",
+        quoted
+    );
+    match std::process::Command::new("osascript").args(["-e", &script]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains("button returned:Call"),
+        Err(e) => {
+            warn!("Couldn't ask to confirm the call: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn confirm_in_dialog(_number: &str, _name: Option<&str>) -> bool {
+    // No dialog to ask in, so nothing gets dialed
+    false
+}
+
+// Name of the contact `number` belongs to, if Contacts are used, or else its
+// name in the shared directory, or in the corporate directory if it's been
+// looked up there already
+pub fn contact_name(state: &AppState, number: &str) -> Option<String> {
+    let contact = if state.use_contacts { contacts::name_for(number) } else { None };
+    // The shared directory names the company's own lines
+    contact
+        .or_else(|| directory::name_for(number))
+        .or_else(|| corporate_directory::name_for(number))
+}
+
+// What kind of problem kept place_call from placing a call, for the CLI's exit code
+#[derive(Clone, Copy, PartialEq)]
+pub enum FailureKind {
+    // An emergency number, or one the allow and block lists rule out
+    BadNumber,
+    // The PBX couldn't be reached
+    Unreachable,
+    // The PBX turned down the key or login
+    Unauthorized,
+    // Anything else, including a cancelled call
+    Other,
+}
+
+// A call that wasn't placed, and the message for the status line
+pub struct CallFailure {
+    // For the CLI's exit code and x-callback-url's errorCode
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl CallFailure {
+    fn new(kind: FailureKind, message: String) -> Self {
+        CallFailure { kind, message }
+    }
+}
+
+// Originate the call with `settings`, the ones in effect when it was asked
+// for, and report the outcome through the notification sinks. Finishes once
+// the PBX answers, the profile's timeout passes, cancel_calls() is called or
+// `in_flight` is cancelled.
+// `note` is the reason typed into the confirmation prompt, if any, and `route`
+// the trunk picked in the dialer. A `private` call is dialed the same, but
+// only counted: no history, audit, webhooks, bug report or numbers in the
+// log. Returns the message for the status line, as an error when the call
+// wasn't placed.
+pub async fn place_call(settings: &AppState, phone_number: &str, origin: CallOrigin, note: Option<&str>, route: Option<&dial_plan::Route>, private: bool, in_flight: Option<&InFlightCall>) -> Result<String, CallFailure> {
+    let (domain, extension, key) = (settings.domain.as_str(), settings.extension.as_str(), settings.key.as_str());
+    let policy = retry::policy(settings);
+    
+    // Digits after a pause or an extension are dialed once the call connects.
+    // Only the number goes in history, webhooks and notifications, as what
+    // follows it is often a PIN.
+    let as_typed = phone_number;
+    let (phone_number, post_dial) = tel_uri::split_post_dial(phone_number);
+    // PBXs that can't send them leave it to the caller
+    let dial_yourself = match &post_dial {
+        Some(post_dial) if !settings.backend.sends_post_dial() => {
+            let digits: String = post_dial.chars().filter(|c| !matches!(c, 'p' | 'w')).collect();
+            Some(format!("Dial {} once it connects.", digits))
+        }
+        _ => None,
+    };
+    
+    // Who's being called, for notifications and history. Status lines and the bug
+    // report stick to the bare number so names never end up in a GitHub issue.
+    let contact_name = match private {
+        true => None,
+        // The corporate directory may not have been asked about this number yet
+        false => match contact_name(settings, phone_number) {
+            Some(name) => Some(name),
+            None => corporate_directory::resolve(phone_number).await,
+        },
+    };
+    let callee = match &contact_name {
+        Some(name) => format!("{} ({})", name, phone_number),
+        None => logged_number(phone_number, private).to_string(),
+    };
+    let shown = logged_number(phone_number, private);
+    
+    let record_attempt = |outcome, http_status, detail: &str, transcript: Option<String>, call_id: Option<String>, server: Option<String>| {
+        if private {
+            return;
+        }
+        audit::record(&settings.audit_webhook_url, &settings.audit_webhook_secret, &audit::AuditEvent {
+            origin,
+            domain,
+            extension,
+            number: phone_number,
+            outcome,
+            http_status,
+            detail,
+            note,
+        });
+        
+        // Shared kiosk machines don't keep a record of who was called
+        if !settings.kiosk {
+            history::record(history::HistoryEntry {
+                time: chrono::Local::now(),
+                number: phone_number.to_string(),
+                name: contact_name.clone(),
+                succeeded: matches!(outcome, audit::Outcome::Initiated),
+                result: detail.to_string(),
+                http_status,
+                channel: origin.channel().to_string(),
+                color: settings.color.clone(),
+                note: note.map(str::to_string),
+                transcript,
+                follow_up: None,
+                call_id,
+                server,
+                state: None,
+            });
+        }
+    };
+    
+    // Last line of defence for calls that didn't come through the UI (tel: links, socket)
+    if emergency::is_emergency_number(phone_number, &settings.country) {
+        notify::show_notification("Emergency Number Not Dialed", EMERGENCY_MESSAGE);
+        record_attempt(audit::Outcome::Blocked, None, EMERGENCY_MESSAGE, None, None, None);
+        return Err(CallFailure::new(FailureKind::BadNumber, EMERGENCY_MESSAGE.to_string()));
+    }
+    
+    // Numbers ruled out by the allow and block lists, whichever way the call was asked for
+    if let Err(reason) = number_lists::check(phone_number, &settings.country, &settings.short_codes, &settings.allowed_numbers, &settings.blocked_numbers) {
+        let message = format!("Error: Call blocked: {}", reason);
+        notify::show_notification("Call Blocked", &format!("Didn't call {}: {}", callee, reason));
+        record_attempt(audit::Outcome::Blocked, None, &message, None, None, None);
+        return Err(CallFailure::new(FailureKind::BadNumber, message));
+    }
+    
+    // A tel: link clicked twice, or handed over twice by the browser, rings once.
+    // Retries are the same request again, not a new one.
+    if !matches!(origin, CallOrigin::Retry { .. }) {
+        let window = duplicates::window(settings);
+        let request = format!("{}{}", number_format::to_e164(phone_number, &settings.country, &settings.short_codes), post_dial.as_deref().unwrap_or_default());
+        if duplicates::is_repeat(&request, window) {
+            info!("Ignoring another request to call {} within {} seconds", shown, window.as_secs());
+            if settings.notify_duplicate_calls {
+                notify::show_notification("Call Not Repeated", &format!("{} was asked for again within {} seconds and is only called once.", callee, window.as_secs()));
+            }
+            return Err(CallFailure::new(FailureKind::Other, format!("Error: Already calling {}", shown)));
+        }
+    }
+    
+    let tls = tls::Tls::from_state(settings);
+    let auth = backend::HttpAuth::from_state(settings);
+    let fallbacks = failover::parse_domains(&settings.fallback_domains).unwrap_or_else(|message| {
+        warn!("{}; calling through {} only", message, domain);
+        Vec::new()
+    });
+    let primaries = match settings.srv_lookup {
+        true => srv::servers(domain).await,
+        false => vec![domain.to_string()],
+    };
+    // The primary domain, or the servers its SRV records list, then the fallbacks in order
+    let servers: Vec<(&str, Box<dyn backend::CallBackend + '_>)> = primaries
+        .iter()
+        .chain(fallbacks.iter())
+        .map(String::as_str)
+        .map(|host| {
+            let backend = backend::backend_for(settings.backend, backend::Connection {
+                host,
+                username: &settings.backend_username,
+                secret: key,
+                context: &settings.backend_context,
+                tls: &tls,
+                http_request: &settings.http_request,
+                auth: &auth,
+            });
+            (host, backend)
+        })
+        .collect();
+    // The PBX may want a different form; history and notifications keep the number as dialed
+    let dialed = match pbx_number(phone_number, settings) {
+        Ok(dialed) => dialed,
+        Err(e) => {
+            // Better no call than one to a number the rules were meant to fix
+            let message = format!("Error: Rewrite rules: {}", e);
+            if private {
+                notify::show_notification("Call Failed", &format!("Failed to call {}: rewrite rules: {}", callee, e));
+                stats::record_private_call();
+            } else {
+                notify::notify_call_failed(phone_number, &format!("Failed to call {}: rewrite rules: {}", callee, e));
+                record_attempt(audit::Outcome::Failed, None, &message, None, None, None);
+                stats::record_call(false);
+            }
+            return Err(CallFailure::new(FailureKind::Other, message));
+        }
+    };
+    // The route prefix goes in front of the number in the PBX's own format
+    let dialed = match route {
+        Some(route) => {
+            debug!("Routing {} via {}", shown, route.label);
+            format!("{}{}", route.prefix, dialed)
+        }
+        None => dialed,
+    };
+    if dialed != phone_number && !private {
+        debug!("Sending {} to the PBX as {}", phone_number, dialed);
+    }
+    // The tone follows the number as dialed, before any route prefix or rewriting
+    let ringback = ringback::tone_for(phone_number, &settings.country, &settings.short_codes, &settings.ringback);
+    let caller_id = call_options::parse_caller_id(&settings.caller_id).unwrap_or_else(|message| {
+        warn!("{}; leaving the caller ID to the PBX", message);
+        None
+    });
+    // What the extension's phone shows, from the profile's templates
+    let values = caller_id_template::Values { number: phone_number, contact: contact_name.as_deref(), extension, profile: &settings.profile };
+    let display_name = caller_id_template::render(&settings.caller_id_name_template, &values);
+    let display_number = caller_id_template::render(&settings.caller_id_number_template, &values);
+    let request = backend::CallRequest {
+        extension,
+        number: &dialed,
+        auto_answer: settings.auto_answer(),
+        ringback: &ringback,
+        record: settings.record_calls,
+        caller_id: caller_id.as_deref(),
+        display_name: display_name.as_deref(),
+        display_number: display_number.as_deref(),
+        parameters: &settings.click_to_call_parameters,
+        post_dial: post_dial.as_deref().filter(|_| dial_yourself.is_none()),
+    };
+    
+    let cancelled = async {
+        let every_call = CANCEL_CALLS.get_or_init(tokio::sync::Notify::new).notified();
+        match in_flight {
+            Some(call) => tokio::select! {
+                _ = every_call => {},
+                _ = call.cancelled() => {},
+            },
+            None => every_call.await,
+        }
+    };
+    tokio::pin!(cancelled);
+    
+    // Calls asked for earlier go first; the Call Queue window can cancel this one while it waits
+    let turn = tokio::select! {
+        turn = queue::wait_turn(phone_number, origin) => turn,
+        _ = &mut cancelled => None,
+    };
+    let mut used = 0;
+    let originated = if turn.is_some() {
+        tokio::select! {
+            (tried, result) = failover::originate(&servers, &request, policy.timeout) => {
+                used = tried;
+                result
+            }
+            _ = &mut cancelled => Err(backend::CallError::Cancelled),
+        }
+    } else {
+        Err(backend::CallError::Cancelled)
+    };
+    // Let the next call in line go while this one is recorded
+    drop(turn);
+    let (server, backend) = &servers[used];
+    // Which server it went to only means something when there's a choice
+    let handled_by = (servers.len() > 1 || settings.srv_lookup).then(|| server.to_string());
+    if used > 0 {
+        info!("Call to {} went to {} after the servers before it failed", shown, server);
+    }
+    
+    let mut transcript = None;
+    let mut retry_at = None;
+    let mut call_id = None;
+    // Where to follow the call once it's recorded
+    let mut follow = None;
+    let mut failure = FailureKind::Other;
+    let (succeeded, http_status, result) = match originated {
+        // Cancel was clicked just as the PBX answered: the call is already
+        // ringing the extension, so end it there
+        Ok(originated) if in_flight.is_some_and(InFlightCall::is_cancelled) => {
+            let result = match &originated.call_id {
+                Some(id) => {
+                    match backend::within(policy.timeout, backend.hang_up(id)).await {
+                        Ok(()) => {
+                            info!("Call to {} cancelled after the PBX placed it; hung up call {}", shown, id);
+                            format!("Call to {} cancelled", shown)
+                        }
+                        Err(e) => {
+                            warn!("Couldn't hang up the cancelled call to {}: {}", shown, e);
+                            format!("Error: Call to {} was already placed and couldn't be hung up: {}", shown, e)
+                        }
+                    }
+                }
+                None => format!("Error: Call to {} was already placed; hang up on the phone", shown),
+            };
+            (false, originated.http_status, result)
+        },
+        Ok(originated) => {
+            // The note rides along so the chat webhook captures why the call was made
+            let message = match note {
+                Some(note) => format!("Calling {}... ({})", callee, note),
+                None => format!("Calling {}...", callee),
+            };
+            // Say so when it only went through on a retry, as the first attempt was reported failing
+            let message = match origin {
+                CallOrigin::Retry { retry, .. } => format!("{} Got through to the PBX on attempt {}.", message, retry + 1),
+                _ => message,
+            };
+            // Private calls stay on this Mac: Notification Center only
+            if private {
+                notify::show_notification("Call Initiated", &message);
+            } else {
+                notify::notify("Call Initiated", &message);
+            }
+            // Only on this Mac, as the digits are often a PIN
+            if let Some(dial_yourself) = &dial_yourself {
+                notify::show_notification("Dial the Rest Yourself", &format!("{} doesn't send digits after a call connects. {}", settings.backend.label(), dial_yourself));
+            }
+            // The PBX's id for the call lets its admins find it in their logs,
+            // and the call be hung up from the Active Calls section
+            let result = match &originated.call_id {
+                Some(id) => {
+                    let account = backend::Account {
+                        kind: settings.backend,
+                        host: server.to_string(),
+                        username: settings.backend_username.clone(),
+                        secret: key.to_string(),
+                        context: settings.backend_context.clone(),
+                        tls: tls.clone(),
+                        http_request: settings.http_request.clone(),
+                        auth: auth.clone(),
+                    };
+                    if settings.track_calls && !private {
+                        follow = call_events::event_socket(&account, &settings.event_socket, &settings.event_socket_password).map(|socket| (id.clone(), socket));
+                    }
+                    active_calls::add(id.clone(), callee.clone(), account);
+                    format!("Call initialized to {} (call ID {})", shown, id)
+                }
+                None => format!("Call initialized to {}", shown),
+            };
+            let result = match used {
+                0 => result,
+                _ => format!("{} through {}", result, server),
+            };
+            call_id = originated.call_id;
+            (true, originated.http_status, result)
+        },
+        Err(backend::CallError::Cancelled) => {
+            info!("Call to {} cancelled", shown);
+            (false, None, format!("Call to {} cancelled", shown))
+        },
+        Err(e) => {
+            // Calls that never reached the PBX are tried again later; private ones aren't
+            // kept anywhere, so they can't be
+            let plan = if e.unreachable() && !private {
+                Some(retry::schedule(as_typed, origin, note, route.map(|route| route.prefix.as_str()), &policy))
+            } else {
+                None
+            };
+            match &plan {
+                Some(retry::Plan::RetryAt(due)) => {
+                    notify::show_notification("PBX Unreachable", &format!("Couldn't reach the PBX to call {}. Trying again at {}.", callee, due.format("%H:%M:%S")));
+                    retry_at = Some(*due);
+                }
+                Some(retry::Plan::GiveUp { attempts }) => {
+                    notify::notify_call_failed(phone_number, &format!("Gave up calling {} after {} attempts: {}", callee, attempts, e));
+                }
+                None if private => notify::show_notification("Call Failed", &format!("Failed to call {}: {}", callee, e)),
+                None => notify::notify_call_failed(phone_number, &format!("Failed to call {}: {}", callee, e)),
+            }
+            if settings.capture_transcripts && !private {
+                if let Some(text) = e.transcript() {
+                    warn!("Failed call to {}:\n{}", phone_number, text);
+                    transcript = Some(text.to_string());
+                }
+            }
+            let result = match retry_at {
+                Some(due) => format!("Error: {}; trying again at {}", e, due.format("%H:%M:%S")),
+                None => format!("Error: {}", e),
+            };
+            if e.unreachable() {
+                failure = FailureKind::Unreachable;
+            } else if e.unauthorized() {
+                failure = FailureKind::Unauthorized;
+            }
+            (false, e.http_status(), result)
+        },
+    };
+    
+    if private {
+        stats::record_private_call();
+        return if succeeded { Ok(result) } else { Err(CallFailure::new(failure, result)) };
+    }
+    let outcome = if succeeded { audit::Outcome::Initiated } else { audit::Outcome::Failed };
+    record_attempt(outcome, http_status, &result, transcript, call_id, handled_by);
+    if let Some((id, socket)) = follow {
+        call_events::follow(id, callee.clone(), socket);
+    }
+    // A call that's being retried is counted once it's placed or given up on
+    if retry_at.is_none() {
+        stats::record_call(succeeded);
+    }
+    bug_report::record_call(phone_number, &result);
+    if succeeded && settings.spotlight_recents {
+        spotlight::index_callee(phone_number, contact_name.as_deref());
+    }
+    if succeeded { Ok(result) } else { Err(CallFailure::new(failure, result)) }
+}
+
+// `number` as it goes in the log: private calls don't leave their number there
+pub fn logged_number(number: &str, private: bool) -> &str {
+    if private {
+        "a private number"
+    } else {
+        number
+    }
+}
+
+// `number` as it's sent to the PBX: in E.164 format if configured, then
+// through the rewrite rules
+pub fn pbx_number(number: &str, state: &AppState) -> Result<String, String> {
+    let number = if state.dial_e164 {
+        number_format::to_e164(number, &state.country, &state.short_codes)
+    } else {
+        number.to_string()
+    };
+    let rules = dial_plan::parse(&state.dial_plan)?;
+    Ok(dial_plan::rewrite(&number, &rules))
+}
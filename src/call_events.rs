@@ -0,0 +1,126 @@
+// Following placed calls over FreeSWITCH's event socket, for FreeSWITCH and
+// FusionPBX (which runs on it). The PBX taking the request says nothing about
+// whether the call connected, so with the setting on, each call it gives an
+// id for is followed until it hangs up: the status line says when the phone
+// rings and is answered, a notification says whether the number answered,
+// and the history entry and Active Calls are kept up to date.
+
+use crate::backend::{Account, BackendKind, CallState};
+use crate::config::AppState;
+use crate::{active_calls, base_url, history, notify, runtime};
+use druid::ExtEventSink;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use url::Url;
+
+// Calls longer than this aren't followed to the end
+const MAX_DURATION: Duration = Duration::from_secs(4 * 60 * 60);
+
+// Lets the UI show how the call is going in the status line
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+fn show_status(message: String) {
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(move |data: &mut AppState| data.status_message = message);
+    }
+}
+
+// Whether the settings for following calls are complete, before they're saved
+pub fn check(state: &AppState) -> Result<(), String> {
+    if !state.track_calls {
+        return Ok(());
+    }
+    match state.backend {
+        BackendKind::FreeSwitchEsl => Ok(()),
+        BackendKind::FusionPbx if state.event_socket_password.is_empty() => Err("Following calls needs the event socket's password".to_string()),
+        BackendKind::FusionPbx => Ok(()),
+        backend => Err(format!("Calls can only be followed on FusionPBX and FreeSWITCH, not {}", backend.label())),
+    }
+}
+
+// Where to follow calls placed through `account`: the same login for
+// FreeSWITCH, the event socket at `address` with `password` for FusionPBX.
+// None for other PBXs.
+pub fn event_socket(account: &Account, address: &str, password: &str) -> Option<Account> {
+    match account.kind {
+        BackendKind::FreeSwitchEsl => Some(account.clone()),
+        BackendKind::FusionPbx => {
+            let host = match address.trim() {
+                // The web server's port isn't the event socket's
+                "" => Url::parse(&base_url(&account.host)).ok()?.host_str()?.to_string(),
+                address => address.to_string(),
+            };
+            Some(Account { kind: BackendKind::FreeSwitchEsl, host, secret: password.to_string(), ..account.clone() })
+        }
+        _ => None,
+    }
+}
+
+// The PBX's reason a call ended, as it reads in a notification
+fn describe_cause(cause: &str) -> String {
+    match cause {
+        "NORMAL_CLEARING" => "hung up before the number answered".to_string(),
+        "USER_BUSY" => "busy".to_string(),
+        "NO_ANSWER" | "NO_USER_RESPONSE" => "no answer".to_string(),
+        "CALL_REJECTED" => "declined".to_string(),
+        "ORIGINATOR_CANCEL" => "cancelled".to_string(),
+        "UNALLOCATED_NUMBER" => "number not in service".to_string(),
+        cause => cause.to_lowercase().replace('_', " "),
+    }
+}
+
+fn minutes(duration: Duration) -> String {
+    format!("{}:{:02}", duration.as_secs() / 60, duration.as_secs() % 60)
+}
+
+// Follow the call `call_id` to `callee` on `account`'s event socket until it
+// hangs up, reporting how it goes
+pub fn follow(call_id: String, callee: String, account: Account) {
+    runtime().spawn(async move {
+        let (states, mut updates) = tokio::sync::mpsc::unbounded_channel();
+        let id = call_id.clone();
+        let following = runtime().spawn(async move {
+            let backend = account.backend();
+            tokio::time::timeout(MAX_DURATION, backend.follow(&id, states)).await
+        });
+
+        let mut connected_at = None;
+        while let Some(state) = updates.recv().await {
+            let (status, entry) = match state {
+                CallState::Ringing => (format!("Your phone is ringing for the call to {}", callee), "Ringing your phone".to_string()),
+                CallState::Answered => (format!("Calling {} from your phone...", callee), "Answered on your phone".to_string()),
+                CallState::Connected => {
+                    connected_at = Some(Instant::now());
+                    notify::show_notification("Call Connected", &format!("{} answered", callee));
+                    (format!("Connected to {}", callee), "Connected".to_string())
+                }
+                CallState::HungUp { connected: true, .. } => {
+                    let talked = minutes(connected_at.map(|at| at.elapsed()).unwrap_or_default());
+                    active_calls::dismiss(&call_id);
+                    (format!("Call to {} ended after {}", callee, talked), format!("Connected, talked {}", talked))
+                }
+                CallState::HungUp { connected: false, cause } => {
+                    let reason = describe_cause(&cause);
+                    active_calls::dismiss(&call_id);
+                    notify::show_notification("Call Not Connected", &format!("{}: {}", callee, reason));
+                    (format!("Call to {} not connected: {}", callee, reason), format!("Not connected: {}", reason))
+                }
+            };
+            info!("Call {}: {}", call_id, entry);
+            history::set_state(&call_id, &entry);
+            show_status(status);
+        }
+
+        match following.await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => warn!("Stopped following call {}: {}", call_id, e),
+            Ok(Err(_)) => info!("Stopped following call {} after {} hours", call_id, MAX_DURATION.as_secs() / 3600),
+            Err(e) => warn!("Following call {} failed: {}", call_id, e),
+        }
+    });
+}
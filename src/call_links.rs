@@ -0,0 +1,274 @@
+// Links that carry a number to call besides tel:: callto:, which many CRMs
+// and older pages use, and SIP addresses (sip: and sips:), whose user part is
+// the number, as in sip:+15551234567@pbx.example.com. Each is read as the
+// tel: link it stands for. What happens to each scheme is up to the settings:
+// dial it like a tel: link, always ask first, or leave it alone.
+//
+// The app's own scheme is for other apps, Shortcuts and launchers like
+// Raycast: clicktocall://call?number=+15551234567 dials the number, and the
+// x-callback-url parameters x-success, x-error and x-cancel are opened
+// afterwards to say how it went.
+
+use crate::call::{CallFailure, FailureKind};
+use crate::config::current_settings;
+use crate::identity::{self, identity};
+use crate::tel_uri;
+use tracing::{info, warn};
+
+// Schemes read here besides tel: and the app's own
+pub const SCHEMES: &[&str] = &["callto", "sip", "sips"];
+
+// The app's own scheme, which every copy answers to besides its own
+pub const APP_SCHEME: &str = "clicktocall";
+
+// errorCode for x-error when the link couldn't be read
+pub const BAD_LINK: &str = "bad-link";
+// errorCode for x-error when there are no settings to call with
+const NOT_CONFIGURED: &str = "not-configured";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchemeRule {
+    // Like a tel: link
+    Dial,
+    // Ask before dialing, even with Confirm before dialing off
+    Ask,
+    // Not dialed at all
+    Off,
+}
+
+// A link to dial, as the tel: link it stands for
+pub struct CallLink {
+    pub tel: String,
+    // The scheme's rule says to ask before dialing
+    pub ask: bool,
+    // Where to say how the call went, for links that asked
+    pub callback: Option<Callback>,
+}
+
+// The x-callback-url parameters of a link: URLs to open once the call is on
+// its way, if it couldn't be placed, or if it wasn't confirmed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Callback {
+    pub success: Option<String>,
+    pub error: Option<String>,
+    pub cancel: Option<String>,
+}
+
+impl Callback {
+    // x-success, told the number that was dialed
+    pub fn success_url(&self, number: &str) -> Option<String> {
+        with_parameters(self.success.as_deref()?, &[("number", number)])
+    }
+
+    // x-error, with the errorCode and errorMessage x-callback-url asks for
+    pub fn error_url(&self, code: &str, message: &str) -> Option<String> {
+        with_parameters(self.error.as_deref()?, &[("errorCode", code), ("errorMessage", message)])
+    }
+
+    pub fn placed(&self, number: &str) {
+        open(self.success_url(number));
+    }
+
+    pub fn failed(&self, code: &str, message: &str) {
+        open(self.error_url(code, message));
+    }
+
+    pub fn not_configured(&self) {
+        self.failed(NOT_CONFIGURED, "settings aren't configured, open the app to set them up");
+    }
+
+    pub fn cancelled(&self) {
+        open(self.cancel.as_deref().and_then(|cancel| with_parameters(cancel, &[])));
+    }
+
+    // How place_call went
+    pub fn report(&self, number: &str, result: &Result<String, CallFailure>) {
+        match result {
+            Ok(_) => self.placed(number),
+            Err(failure) => {
+                let code = match failure.kind {
+                    FailureKind::BadNumber => "bad-number",
+                    FailureKind::Unreachable => "unreachable",
+                    FailureKind::Unauthorized => "unauthorized",
+                    FailureKind::Other => "failed",
+                };
+                self.failed(code, &failure.message);
+            }
+        }
+    }
+}
+
+// `url` with `parameters` added to its query. None for something that isn't
+// a URL, or a file, which a link has no business opening.
+fn with_parameters(url: &str, parameters: &[(&str, &str)]) -> Option<String> {
+    let Ok(mut url) = url::Url::parse(url.trim()) else {
+        warn!("Not calling back {}: it isn't a URL", url);
+        return None;
+    };
+    if url.scheme() == "file" {
+        warn!("Not calling back {}: it's a file", url);
+        return None;
+    }
+    if !parameters.is_empty() {
+        url.query_pairs_mut().extend_pairs(parameters);
+    }
+    Some(url.to_string())
+}
+
+fn open(url: Option<String>) {
+    let Some(url) = url else {
+        return;
+    };
+    info!("Calling back {}", url);
+    match std::process::Command::new("open").arg(&url).status() {
+        Ok(status) if status.success() => {}
+        Ok(_) => warn!("Couldn't open {}", url),
+        Err(e) => warn!("Couldn't run open for {}: {}", url, e),
+    }
+}
+
+// Tell the callback of `url`, if it has one, that it couldn't be called
+pub fn report_failure(url: &str, code: &str, message: &str) {
+    if let Some(callback) = callback(url) {
+        callback.failed(code, message);
+    }
+}
+
+// Read rules written as comma-separated `scheme=rule` pairs, e.g.
+// "callto=ask, sip=off". Schemes without a rule are dialed.
+pub fn parse_rules(rules: &str) -> Result<Vec<(String, SchemeRule)>, String> {
+    let mut parsed = Vec::new();
+    for entry in rules.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (scheme, rule) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Link rule \"{}\" isn't written as scheme=rule", entry))?;
+        let scheme = scheme.trim().trim_end_matches(':').to_lowercase();
+        if !SCHEMES.contains(&scheme.as_str()) {
+            return Err(format!("Link rule for \"{}\": only {} links have rules", scheme, SCHEMES.join(", ")));
+        }
+        let rule = match rule.trim().to_lowercase().as_str() {
+            "dial" => SchemeRule::Dial,
+            "ask" => SchemeRule::Ask,
+            "off" => SchemeRule::Off,
+            other => return Err(format!("Link rule for {} should be dial, ask or off, not \"{}\"", scheme, other)),
+        };
+        parsed.push((scheme, rule));
+    }
+    Ok(parsed)
+}
+
+// The rule for `scheme`; rules that don't parse were refused when saving
+fn rule(scheme: &str, rules: &str) -> SchemeRule {
+    parse_rules(rules)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(rule_scheme, _)| rule_scheme.eq_ignore_ascii_case(scheme))
+        .map_or(SchemeRule::Dial, |(_, rule)| rule)
+}
+
+// The link `url` stands for, with the rules in effect. None for a URL that
+// isn't a link to call at all.
+pub fn read(url: &str) -> Option<Result<CallLink, String>> {
+    let scheme = url.trim().split_once(':')?.0;
+    if SCHEMES.iter().any(|known| known.eq_ignore_ascii_case(scheme)) {
+        read_with(url, &current_settings().link_rules)
+    } else {
+        read_with(url, "")
+    }
+}
+
+// Whether links with `scheme` are the app's own: clicktocall: or this copy's scheme
+fn is_app_scheme(scheme: &str) -> bool {
+    let own = &identity().url_scheme;
+    scheme.eq_ignore_ascii_case(APP_SCHEME) || (!own.is_empty() && scheme.eq_ignore_ascii_case(own))
+}
+
+// The action and parameters of one of the app's own links, e.g. "call" and
+// the number for clicktocall://call?number=… or, as x-callback-url writes it,
+// clicktocall://x-callback-url/call?number=…. None for other links, and for
+// ones like clicktocall://+15551234567 that are just a number.
+fn action(url: &str) -> Option<(String, Vec<(String, String)>)> {
+    let (scheme, rest) = url.trim().split_once(':')?;
+    if !is_app_scheme(scheme) {
+        return None;
+    }
+    let rest = rest.trim_start_matches('/');
+    let rest = match rest.get(..15) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("x-callback-url/") => &rest[15..],
+        _ => rest,
+    };
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    if !action.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    // A + in the number is a +, not a space as in a form
+    let parameters = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('=').or((!pair.is_empty()).then_some((pair, ""))))
+        .map(|(name, value)| (name.to_lowercase(), tel_uri::percent_decode(value).unwrap_or_else(|_| value.to_string())))
+        .collect();
+    Some((action.trim_end_matches('/').to_lowercase(), parameters))
+}
+
+fn parameter<'a>(parameters: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    parameters.iter().find(|(parameter, _)| parameter == name).map(|(_, value)| value.trim()).filter(|value| !value.is_empty())
+}
+
+// The x-callback-url parameters of one of the app's own links, if it has any
+pub fn callback(url: &str) -> Option<Callback> {
+    let (_, parameters) = action(url)?;
+    let callback = Callback {
+        success: parameter(&parameters, "x-success").map(str::to_string),
+        error: parameter(&parameters, "x-error").map(str::to_string),
+        cancel: parameter(&parameters, "x-cancel").map(str::to_string),
+    };
+    (callback != Callback::default()).then_some(callback)
+}
+
+// The link `url` stands for, with `rules`
+pub fn read_with(url: &str, rules: &str) -> Option<Result<CallLink, String>> {
+    if let Some((action, parameters)) = action(url) {
+        if action != "call" {
+            return Some(Err(format!("{} isn't something the app does; links to call are written {}://call?number=…", action, APP_SCHEME)));
+        }
+        let Some(number) = parameter(&parameters, "number") else {
+            return Some(Err(format!("{} has no number to call", url.trim())));
+        };
+        let private = parameter(&parameters, "private").is_some_and(|value| matches!(value.to_lowercase().as_str(), "1" | "yes" | "true"));
+        let tel = tel_uri::link(number, private);
+        return Some(Ok(CallLink { tel, ask: false, callback: callback(url) }));
+    }
+    if let Some(tel) = identity::tel_link(url) {
+        return Some(Ok(CallLink { tel, ask: false, callback: None }));
+    }
+    let (scheme, rest) = url.trim().split_once(':')?;
+    let scheme = scheme.to_lowercase();
+    // clicktocall:+15551234567, like a copy's own scheme
+    if scheme == APP_SCHEME {
+        return Some(Ok(CallLink { tel: format!("tel:{}", rest.trim_start_matches('/')), ask: false, callback: None }));
+    }
+    if !SCHEMES.contains(&scheme.as_str()) {
+        return None;
+    }
+    let ask = match rule(&scheme, rules) {
+        SchemeRule::Off => return Some(Err(format!("{}: links are turned off in the settings", scheme))),
+        SchemeRule::Ask => true,
+        SchemeRule::Dial => false,
+    };
+
+    // callto://+15551234567 as well as callto:+15551234567; headers and
+    // anything after the user part aren't part of the number
+    let address = rest.trim_start_matches('/').split('?').next().unwrap_or_default();
+    let user = match address.rsplit_once('@') {
+        Some((user, _host)) => user,
+        None if scheme == "callto" => address,
+        // sip:pbx.example.com names a server, not a number
+        None => return Some(Err(format!("{} has no number to call", url.trim()))),
+    };
+    // sip:user:password@host
+    let user = user.split(':').next().unwrap_or_default();
+    if user.is_empty() {
+        return Some(Err(format!("{} has no number to call", url.trim())));
+    }
+    Some(Ok(CallLink { tel: format!("tel:{}", user), ask, callback: None }))
+}
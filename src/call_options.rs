@@ -0,0 +1,30 @@
+// Per-call options: whether the PBX records the call, the ringback tone and
+// the caller ID the other end sees. They're kept with the profile, and when
+// the setting to ask is on, calls from the app's own windows first bring up a
+// small dialog to change them, with the last ones used filled in.
+
+use crate::audit::CallOrigin;
+
+// Longer than this isn't a phone number
+const MAX_CALLER_ID_DIGITS: usize = 15;
+
+// The caller ID as typed in the settings, with spaces and punctuation taken
+// out: None when it's empty and left to the PBX
+pub fn parse_caller_id(caller_id: &str) -> Result<Option<String>, String> {
+    let caller_id = caller_id.trim();
+    if caller_id.is_empty() {
+        return Ok(None);
+    }
+    let digits: String = caller_id.chars().filter(|c| c.is_ascii_digit()).collect();
+    let written_as_number = caller_id.chars().all(|c| c.is_ascii_digit() || " +-.()".contains(c)) && !caller_id[1..].contains('+');
+    if !written_as_number || digits.is_empty() || digits.len() > MAX_CALLER_ID_DIGITS {
+        return Err(format!("Caller ID should be a phone number, not \"{}\"", caller_id));
+    }
+    Ok(Some(if caller_id.starts_with('+') { format!("+{}", digits) } else { digits }))
+}
+
+// Whether a call from `origin` brings up the options dialog when asked for:
+// only calls someone just clicked in the app, not links, scripts or campaigns
+pub fn asks_first(origin: CallOrigin) -> bool {
+    matches!(origin, CallOrigin::Dialer | CallOrigin::MenuBar | CallOrigin::History | CallOrigin::Favorite | CallOrigin::Directory)
+}
@@ -0,0 +1,70 @@
+// Caller ID templates: what your own phone shows while it rings for a call
+// you asked for, e.g. "Jane Doe (Acme)" instead of the bare number. They're
+// kept with the profile and fill in {number}, {contact}, {extension} and
+// {profile}; empty ones leave the number being called.
+
+// Phones cut caller ID names off well before this
+const MAX_LENGTH: usize = 64;
+
+// What the variables stand for in one call
+pub struct Values<'a> {
+    // The number being called, without digits sent after it connects
+    pub number: &'a str,
+    // The contact it belongs to, when known; {contact} is the number otherwise
+    pub contact: Option<&'a str>,
+    pub extension: &'a str,
+    pub profile: &'a str,
+}
+
+impl Values<'_> {
+    fn get(&self, variable: &str) -> Option<&str> {
+        match variable {
+            "number" => Some(self.number),
+            "contact" => Some(self.contact.unwrap_or(self.number)),
+            "extension" => Some(self.extension),
+            "profile" => Some(self.profile),
+            _ => None,
+        }
+    }
+}
+
+// Fill in `template`, or say which variable it can't
+fn expand(template: &str, values: &Values<'_>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("Caller ID template \"{}\" has a {{ without a }}", template));
+        };
+        let variable = &rest[start + 1..start + end];
+        match values.get(variable) {
+            Some(value) => expanded.push_str(value),
+            None => {
+                return Err(format!(
+                    "Caller ID template \"{}\" has {{{}}}; it can use {{number}}, {{contact}}, {{extension}} and {{profile}}",
+                    template, variable
+                ))
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+// Whether `template` is one that can be filled in, before it's saved
+pub fn check(template: &str) -> Result<(), String> {
+    let values = Values { number: "", contact: None, extension: "", profile: "" };
+    expand(template, &values).map(|_| ())
+}
+
+// The caller ID from `template` for a call, None when it's empty or comes out
+// blank. Line breaks and other control characters are left out, since the
+// manager interfaces read them as the end of a line.
+pub fn render(template: &str, values: &Values<'_>) -> Option<String> {
+    let expanded = expand(template.trim(), values).ok()?;
+    let shown: String = expanded.chars().filter(|c| !c.is_control()).take(MAX_LENGTH).collect();
+    let shown = shown.trim();
+    (!shown.is_empty()).then(|| shown.to_string())
+}
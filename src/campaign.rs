@@ -0,0 +1,280 @@
+// Campaigns: calling down a list of numbers imported from a CSV file, e.g. a
+// CRM export, one call after another with a pause in between for the
+// conversation. Each call goes through place_call like any other, so it's
+// checked against the block list, kept in the history, notified and retried
+// when the PBX can't be reached. The Campaign window shows how far along it
+// is and pauses, resumes or cancels it.
+
+use crate::audit::CallOrigin;
+use crate::call::{place_call, InFlightCall};
+use crate::config::{current_settings, AppState};
+use crate::{notify, runtime};
+use druid::ExtEventSink;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::info;
+
+// Seconds between calls when the setting is empty
+const DEFAULT_DELAY_SECS: u64 = 30;
+const MAX_DELAY_SECS: u64 = 3600;
+// More rows than this is a list for a real dialer
+const MAX_ENTRIES: usize = 1000;
+// Header words of the column with the numbers, and of the one with names
+const NUMBER_HEADERS: [&str; 5] = ["phone", "number", "tel", "mobile", "cell"];
+const NAME_HEADERS: [&str; 3] = ["name", "contact", "company"];
+
+// One number to call
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub number: String,
+    pub name: Option<String>,
+    // How its call went, once it's been called
+    pub result: Option<Result<String, String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    // Imported, not started
+    Ready,
+    Running,
+    // Stops before the next call
+    Paused,
+    Cancelled,
+    Finished,
+}
+
+struct Campaign {
+    entries: Vec<Entry>,
+    // The next entry to call
+    next: usize,
+    status: Status,
+    // The call being placed, so Cancel can give up on it
+    in_flight: Option<InFlightCall>,
+}
+
+static CAMPAIGN: Mutex<Campaign> = Mutex::new(Campaign { entries: Vec::new(), next: 0, status: Status::Ready, in_flight: None });
+// Woken when the campaign is resumed or cancelled
+static WAKE: OnceLock<tokio::sync::Notify> = OnceLock::new();
+
+fn wake() -> &'static tokio::sync::Notify {
+    WAKE.get_or_init(tokio::sync::Notify::new)
+}
+
+// The pause between calls as typed in the settings: seconds, empty for 30
+pub fn parse_delay(delay: &str) -> Result<Duration, String> {
+    let delay = delay.trim();
+    if delay.is_empty() {
+        return Ok(Duration::from_secs(DEFAULT_DELAY_SECS));
+    }
+    match delay.parse::<u64>() {
+        Ok(secs) if secs <= MAX_DELAY_SECS => Ok(Duration::from_secs(secs)),
+        _ => Err(format!("Time between campaign calls should be a whole number of seconds from 0 to {}, not \"{}\"", MAX_DELAY_SECS, delay)),
+    }
+}
+
+// Read the numbers to call from CSV `text`. With a header row the numbers
+// come from the column named like "Phone" or "Number", otherwise from the
+// first column with digits in it. Rows without a number are skipped.
+pub fn parse_csv(text: &str) -> Result<Vec<Entry>, String> {
+    let mut rows = text.lines().filter(|line| !line.trim().is_empty()).map(fields).peekable();
+    let Some(first) = rows.peek() else {
+        return Err("The file is empty".to_string());
+    };
+    let column = |headers: &[&str], row: &[String]| {
+        row.iter().position(|field| {
+            let field = field.to_lowercase();
+            headers.iter().any(|header| field.contains(header))
+        })
+    };
+    let header = !first.iter().any(|field| field.chars().any(|c| c.is_ascii_digit()));
+    let (number_column, name_column) = if header {
+        let number = column(&NUMBER_HEADERS, first).ok_or("The header row has no column named Phone or Number")?;
+        let name = column(&NAME_HEADERS, first).filter(|name| *name != number);
+        rows.next();
+        (number, name)
+    } else {
+        let number = first.iter().position(|field| field.chars().filter(|c| c.is_ascii_digit()).count() >= 3).unwrap_or(0);
+        let name = first.iter().position(|field| field.chars().any(char::is_alphabetic) && !field.chars().any(|c| c.is_ascii_digit()));
+        (number, name)
+    };
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let Some(number) = row.get(number_column).map(|number| number.trim()) else {
+            continue;
+        };
+        if !number.chars().any(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let name = name_column.and_then(|column| row.get(column)).map(|name| name.trim()).filter(|name| !name.is_empty());
+        entries.push(Entry { number: number.to_string(), name: name.map(str::to_string), result: None });
+    }
+    if entries.is_empty() {
+        return Err("The file has no phone numbers".to_string());
+    }
+    if entries.len() > MAX_ENTRIES {
+        return Err(format!("A campaign can call {} numbers, not {}", MAX_ENTRIES, entries.len()));
+    }
+    Ok(entries)
+}
+
+// The fields of one CSV line, with quotes taken off
+fn fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' | ';' | '\t' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// Have the Campaign window show the campaign as it is now
+fn changed(event_sink: &ExtEventSink) {
+    event_sink.add_idle_callback(|data: &mut AppState| data.campaign_revision += 1);
+}
+
+// Replace the list to call, unless a campaign is under way
+pub fn import(entries: Vec<Entry>) -> Result<(), String> {
+    let mut campaign = CAMPAIGN.lock().unwrap();
+    if matches!(campaign.status, Status::Running | Status::Paused) {
+        return Err("Cancel the campaign under way first".to_string());
+    }
+    *campaign = Campaign { entries, next: 0, status: Status::Ready, in_flight: None };
+    Ok(())
+}
+
+// The numbers, how far along the campaign is and what it's doing
+pub fn snapshot() -> (Vec<Entry>, usize, Status) {
+    let campaign = CAMPAIGN.lock().unwrap();
+    (campaign.entries.clone(), campaign.next, campaign.status)
+}
+
+// Start calling down the list, or resume a paused campaign
+pub fn start(delay: Duration, event_sink: ExtEventSink) {
+    let mut campaign = CAMPAIGN.lock().unwrap();
+    match campaign.status {
+        Status::Paused => {
+            campaign.status = Status::Running;
+            drop(campaign);
+            wake().notify_waiters();
+            changed(&event_sink);
+        }
+        Status::Ready if !campaign.entries.is_empty() => {
+            campaign.status = Status::Running;
+            info!("Starting a campaign of {} calls", campaign.entries.len());
+            drop(campaign);
+            changed(&event_sink);
+            runtime().spawn(run(delay, event_sink));
+        }
+        _ => {}
+    }
+}
+
+// Stop before the next call; the one being placed carries on
+pub fn pause() {
+    let mut campaign = CAMPAIGN.lock().unwrap();
+    if campaign.status == Status::Running {
+        campaign.status = Status::Paused;
+    }
+}
+
+// Stop calling, giving up on the call being placed
+pub fn cancel() {
+    let mut campaign = CAMPAIGN.lock().unwrap();
+    if matches!(campaign.status, Status::Running | Status::Paused) {
+        campaign.status = Status::Cancelled;
+        if let Some(call) = campaign.in_flight.take() {
+            call.cancel();
+        }
+    }
+    drop(campaign);
+    wake().notify_waiters();
+}
+
+// What the campaign does next
+enum Step {
+    Call(usize, Entry, InFlightCall),
+    // Paused until resumed or cancelled
+    Wait,
+    Stop,
+}
+
+async fn run(delay: Duration, event_sink: ExtEventSink) {
+    loop {
+        // Made before looking, so a resume in between isn't missed
+        let woken = wake().notified();
+        let step = {
+            let mut campaign = CAMPAIGN.lock().unwrap();
+            match campaign.status {
+                Status::Paused => Step::Wait,
+                Status::Running if campaign.next < campaign.entries.len() => {
+                    let call = InFlightCall::default();
+                    campaign.in_flight = Some(call.clone());
+                    Step::Call(campaign.next, campaign.entries[campaign.next].clone(), call)
+                }
+                Status::Running => {
+                    campaign.status = Status::Finished;
+                    Step::Stop
+                }
+                Status::Ready | Status::Cancelled | Status::Finished => Step::Stop,
+            }
+        };
+        let (index, entry, call) = match step {
+            Step::Call(index, entry, call) => (index, entry, call),
+            Step::Wait => {
+                woken.await;
+                continue;
+            }
+            Step::Stop => break,
+        };
+
+        // The settings as they are now, in case they were changed mid-campaign
+        let settings = current_settings();
+        let result = place_call(&settings, &entry.number, CallOrigin::Campaign, None, None, false, Some(&call))
+            .await
+            .map_err(|failure| failure.message);
+        let more = {
+            let mut campaign = CAMPAIGN.lock().unwrap();
+            campaign.in_flight = None;
+            if let Some(called) = campaign.entries.get_mut(index) {
+                called.result = Some(result);
+            }
+            campaign.next = index + 1;
+            campaign.next < campaign.entries.len()
+        };
+        changed(&event_sink);
+
+        // Time for the conversation before the next call; Cancel cuts it short
+        if more {
+            let woken = wake().notified();
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {},
+                _ = woken => {},
+            }
+        }
+    }
+
+    let (entries, _, status) = snapshot();
+    let placed = entries.iter().filter(|entry| matches!(entry.result, Some(Ok(_)))).count();
+    let failed = entries.iter().filter(|entry| matches!(entry.result, Some(Err(_)))).count();
+    let summary = format!("{} of {} calls placed, {} failed", placed, entries.len(), failed);
+    if status == Status::Cancelled {
+        info!("Campaign cancelled: {}", summary);
+        notify::show_notification("Campaign Cancelled", &summary);
+    } else {
+        info!("Campaign finished: {}", summary);
+        notify::show_notification("Campaign Finished", &summary);
+    }
+    changed(&event_sink);
+}
@@ -0,0 +1,293 @@
+// Command-line interface for Terminal and scripts: `click-to-call call`,
+// `config`, `history` and `completions`. Uses the same preferences, history
+// and PBX backends as the app, and runs without any window.
+
+use crate::audit::CallOrigin;
+use crate::call::{cancel_calls, place_call, FailureKind};
+use crate::config::{self, load_preferences, save_preferences, AppState};
+use crate::{configure_notification_sinks, dial_plan, history, ipc, logging, managed, profiles, runtime, tel_uri};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::io::Write;
+
+// First arguments that mean a subcommand. Anything else (tel: links, Finder's
+// -psn_ argument, --daemon) is left to the app.
+const COMMANDS: &[&str] = &["call", "config", "history", "completions", "help", "--help", "-h", "--version", "-V"];
+
+// Exit codes, so scripts can tell what went wrong. Anything not listed,
+// including a mistyped command line, is 1.
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+// The number can't be dialed: not a number, an emergency number or ruled out
+// by the allow and block lists
+const EXIT_BAD_NUMBER: i32 = 2;
+// The PBX couldn't be reached
+const EXIT_UNREACHABLE: i32 = 3;
+// The PBX turned down the key or login
+const EXIT_UNAUTHORIZED: i32 = 4;
+// The domain or extension isn't set
+const EXIT_NOT_CONFIGURED: i32 = 5;
+
+// Why a subcommand failed, with the exit code that says so
+struct Failure {
+    code: i32,
+    message: String,
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure { code: EXIT_FAILED, message }
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "click-to-call", version, about = "Place calls through your PBX from Terminal and scripts")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Call a number (or tel: URL) from the configured extension")]
+    Call {
+        number: String,
+        #[arg(long, help = "Reason for the call, kept in the history and sent to the audit webhook")]
+        note: Option<String>,
+        #[arg(long, help = "Prefix of one of the configured routes to dial through")]
+        route: Option<String>,
+        #[arg(long, help = "Dial it but keep it out of the history, stats and webhooks; tel: URLs can say ;private instead")]
+        private: bool,
+    },
+    #[command(about = "Show or change settings")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    #[command(about = "List past calls, newest first")]
+    History {
+        #[arg(long, help = "Print the entries as JSON")]
+        json: bool,
+        #[arg(long, default_value_t = 20, help = "How many calls to list, 0 for all")]
+        limit: usize,
+    },
+    #[command(about = "Print a completion script for bash, zsh or fish")]
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    #[command(about = "Print every setting")]
+    Show,
+    #[command(about = "Print one setting")]
+    Get { name: String },
+    #[command(about = "Change a setting and save it to the active profile, e.g. `config set domain pbx.example.com`")]
+    Set { name: String, value: String },
+}
+
+// Run the subcommand in `args`, if there is one, and return the exit code
+pub fn run(args: &[String]) -> Option<i32> {
+    if !args.get(1).is_some_and(|arg| COMMANDS.contains(&arg.as_str())) {
+        return None;
+    }
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        // Usage errors, but also --help and --version
+        Err(e) => {
+            e.print().ok();
+            return Some(if e.use_stderr() { EXIT_FAILED } else { EXIT_OK });
+        }
+    };
+    let mut out = std::io::stdout();
+
+    let result = match cli.command {
+        Command::Call { number, note, route, private } => call(&mut out, &number, note.as_deref(), route.as_deref(), private),
+        Command::Config { action: ConfigAction::Show } => show_config(&mut out).map_err(Failure::from),
+        Command::Config { action: ConfigAction::Get { name } } => get_config(&mut out, &name).map_err(Failure::from),
+        Command::Config { action: ConfigAction::Set { name, value } } => set_config(&mut out, &name, &value).map_err(Failure::from),
+        Command::History { json, limit } => list_history(&mut out, json, limit).map_err(Failure::from),
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "click-to-call", &mut out);
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => Some(EXIT_OK),
+        Err(failure) => {
+            eprintln!("click-to-call: {}", failure.message);
+            Some(failure.code)
+        }
+    }
+}
+
+fn call(out: &mut dyn Write, number: &str, note: Option<&str>, route: Option<&str>, private: bool) -> Result<(), Failure> {
+    let state = load_preferences();
+    logging::set_debug(state.debug_logging);
+    if state.domain.is_empty() || state.extension.is_empty() {
+        return Err(Failure {
+            code: EXIT_NOT_CONFIGURED,
+            message: "domain and extension aren't set, e.g. `click-to-call config set domain pbx.example.com`".to_string(),
+        });
+    }
+    let bad_number = |message: String| Failure { code: EXIT_BAD_NUMBER, message };
+
+    // Links copied from a web page work as they are
+    let (number, private) = if number.to_lowercase().starts_with("tel:") {
+        let uri = tel_uri::parse(number).map_err(bad_number)?;
+        (uri.dial_string(), private || uri.private)
+    } else {
+        (number.to_string(), private)
+    };
+    if !number.chars().any(|c| c.is_ascii_digit()) {
+        return Err(bad_number(format!("{:?} isn't a phone number", number)));
+    }
+
+    let route = match route {
+        Some(prefix) => Some(
+            dial_plan::parse_routes(&state.routes)?
+                .into_iter()
+                .find(|route| route.prefix == prefix)
+                .ok_or_else(|| format!("no route with prefix {} is configured", prefix))?,
+        ),
+        None => None,
+    };
+
+    configure_notification_sinks(&state, None);
+
+    // Ctrl-C gives up on the request rather than killing it, so the attempt is still recorded
+    runtime().spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            cancel_calls();
+        }
+    });
+    let call = place_call(&state, &number, CallOrigin::Cli, note, route.as_ref(), private, None);
+    let message = runtime().block_on(call).map_err(|failure| Failure {
+        code: match failure.kind {
+            FailureKind::BadNumber => EXIT_BAD_NUMBER,
+            FailureKind::Unreachable => EXIT_UNREACHABLE,
+            FailureKind::Unauthorized => EXIT_UNAUTHORIZED,
+            FailureKind::Other => EXIT_FAILED,
+        },
+        message: failure.message,
+    })?;
+    writeln!(out, "{}", message).map_err(|e| Failure::from(e.to_string()))
+}
+
+// Settings as the preferences file names them, with the key and password hidden
+fn visible_settings(state: &AppState) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let mut settings = match serde_json::to_value(state) {
+        Ok(serde_json::Value::Object(settings)) => settings,
+        Ok(_) => return Err("settings aren't an object".to_string()),
+        Err(e) => return Err(format!("couldn't read settings: {}", e)),
+    };
+    let hidden = if state.key.is_empty() { "" } else { "(hidden)" };
+    settings.insert("key".to_string(), serde_json::Value::String(hidden.to_string()));
+    let hidden = if state.corporate_directory_password.is_empty() { "" } else { "(hidden)" };
+    settings.insert("corporate_directory_password".to_string(), serde_json::Value::String(hidden.to_string()));
+    let hidden = if state.client_certificate_passphrase.is_empty() { "" } else { "(hidden)" };
+    settings.insert("client_certificate_passphrase".to_string(), serde_json::Value::String(hidden.to_string()));
+    let hidden = if state.http_auth_password.is_empty() { "" } else { "(hidden)" };
+    settings.insert("http_auth_password".to_string(), serde_json::Value::String(hidden.to_string()));
+    let hidden = if state.event_socket_password.is_empty() { "" } else { "(hidden)" };
+    settings.insert("event_socket_password".to_string(), serde_json::Value::String(hidden.to_string()));
+    Ok(settings)
+}
+
+fn display(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn show_config(out: &mut dyn Write) -> Result<(), String> {
+    for (name, value) in visible_settings(&load_preferences())? {
+        writeln!(out, "{} = {}", name, display(&value)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn get_config(out: &mut dyn Write, name: &str) -> Result<(), String> {
+    let name = name.replace('-', "_");
+    let settings = visible_settings(&load_preferences())?;
+    let value = settings.get(&name).ok_or_else(|| format!("unknown setting {}", name))?;
+    writeln!(out, "{}", display(value)).map_err(|e| e.to_string())
+}
+
+fn set_config(out: &mut dyn Write, name: &str, value: &str) -> Result<(), String> {
+    let name = name.replace('-', "_");
+    let state = load_preferences();
+    if state.kiosk {
+        return Err("settings are managed by your administrator".to_string());
+    }
+    if managed::load_managed_settings().forced_fields().contains(&name.as_str()) {
+        return Err(format!("{} is set by your administrator", name));
+    }
+
+    let mut settings = visible_settings(&state)?;
+    settings.insert("key".to_string(), serde_json::Value::String(state.key.clone()));
+    settings.insert("corporate_directory_password".to_string(), serde_json::Value::String(state.corporate_directory_password.clone()));
+    settings.insert("client_certificate_passphrase".to_string(), serde_json::Value::String(state.client_certificate_passphrase.clone()));
+    settings.insert("http_auth_password".to_string(), serde_json::Value::String(state.http_auth_password.clone()));
+    settings.insert("event_socket_password".to_string(), serde_json::Value::String(state.event_socket_password.clone()));
+    let new_value = match settings.get(&name) {
+        None => return Err(format!("unknown setting {}, see `click-to-call config show`", name)),
+        Some(serde_json::Value::Bool(_)) => match value.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => serde_json::Value::Bool(true),
+            "false" | "no" | "off" | "0" => serde_json::Value::Bool(false),
+            _ => return Err(format!("{} is on or off, not {:?}", name, value)),
+        },
+        Some(_) => serde_json::Value::String(value.to_string()),
+    };
+    settings.insert(name.clone(), new_value);
+
+    let mut updated: AppState = serde_json::from_value(serde_json::Value::Object(settings))
+        .map_err(|e| format!("invalid value for {}: {}", name, e))?;
+    config::check(&updated)?;
+
+    // Same as Save Settings in the app
+    if updated.profile.trim().is_empty() {
+        updated.profile = profiles::DEFAULT_PROFILE.to_string();
+    }
+    save_preferences(&updated)?;
+    profiles::store(profiles::Profile::from_state(&updated))?;
+    // The running app keeps what it read until it's told to read them again
+    if let Err(message) = ipc::reload_primary() {
+        eprintln!("click-to-call: {}", message);
+    }
+
+    let shown = visible_settings(&updated)?;
+    writeln!(out, "{} = {}", name, shown.get(&name).map(display).unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+fn list_history(out: &mut dyn Write, json: bool, limit: usize) -> Result<(), String> {
+    let mut entries = history::load();
+    if limit > 0 {
+        entries.truncate(limit);
+    }
+
+    if json {
+        let json = serde_json::to_string_pretty(&entries).map_err(|e| format!("couldn't serialize history: {}", e))?;
+        return writeln!(out, "{}", json).map_err(|e| e.to_string());
+    }
+
+    for entry in &entries {
+        let callee = match &entry.name {
+            Some(name) => format!("{} ({})", name, entry.number),
+            None => entry.number.clone(),
+        };
+        writeln!(
+            out,
+            "{}  {}  {}  {}",
+            entry.time.format("%Y-%m-%d %H:%M"),
+            if entry.succeeded { "ok    " } else { "failed" },
+            callee,
+            entry.result,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+// Watching the clipboard, when turned on in the settings or the menu bar
+// popover: copying a phone number brings up a notification with a Call
+// button. Only text that's a phone number by itself is offered, not every
+// paragraph or order number with enough digits in it, and each copy is looked
+// at once it has settled. Password managers mark what they copy as concealed,
+// and that's never read.
+
+use crate::config::current_settings;
+use crate::{notify, number_format, phone_text};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// How often the clipboard's change count is checked
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// A copy is read once the clipboard has stayed the same this long, so apps
+// that put it there in steps, or a burst of copies, bring up one notification
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+// The same number isn't offered again this soon, e.g. when it's copied twice
+const REPEAT_WINDOW: Duration = Duration::from_secs(60);
+// Copied text longer than this is a passage with a number in it
+const MAX_TEXT_LENGTH: usize = 60;
+// Letters allowed around the number, for labels like "Mobile:" or "tel:"
+const MAX_LABEL_LENGTH: usize = 12;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+static STARTED: AtomicBool = AtomicBool::new(false);
+// The app's own Copy buttons put numbers on the clipboard; those aren't offered
+static COPIED_HERE: AtomicBool = AtomicBool::new(false);
+
+// Turn the watcher on or off. Safe to call whenever the settings change.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+    #[cfg(target_os = "macos")]
+    if enabled && !STARTED.swap(true, Ordering::SeqCst) {
+        std::thread::spawn(watch);
+    }
+}
+
+// Called before the app copies a number itself
+pub fn copied_here() {
+    COPIED_HERE.store(true, Ordering::SeqCst);
+}
+
+// The number to offer for copied `text`: a phone number, written the way
+// phone numbers are, with at most a short label
+pub fn copied_number(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.chars().count() > MAX_TEXT_LENGTH {
+        return None;
+    }
+    let number = phone_text::find_phone_number(text)?;
+    let digits = number.chars().filter(|c| c.is_ascii_digit()).count();
+    // One number, not a list or a number next to a date
+    if text.chars().filter(|c| c.is_ascii_digit()).count() != digits {
+        return None;
+    }
+    let label = text.chars().filter(|c| c.is_alphabetic()).count();
+    if label > MAX_LABEL_LENGTH {
+        return None;
+    }
+    // A bare run of seven to nine digits is more often a code or an order number
+    let start = text.find(|c: char| c == '+' || c == '(' || c.is_ascii_digit())?;
+    let end = text.rfind(|c: char| c.is_ascii_digit())?;
+    let grouped = text[start..end].contains(|c: char| " -.()/".contains(c));
+    (number.starts_with('+') || digits >= 10 || grouped).then_some(number)
+}
+
+// When to look at the clipboard, and which numbers to offer
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+#[derive(Default)]
+pub struct Debounce {
+    // The clipboard's change count when last checked
+    change: Option<i64>,
+    // When it last changed, while that change hasn't been read
+    changed_at: Option<Instant>,
+    offered: Option<(String, Instant)>,
+}
+
+impl Debounce {
+    // Note the clipboard's change count. True once a change has settled and
+    // the clipboard should be read. What's there when watching starts isn't new.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn settled(&mut self, change: i64, now: Instant) -> bool {
+        if self.change != Some(change) {
+            if self.change.is_some() {
+                self.changed_at = Some(now);
+            }
+            self.change = Some(change);
+            return false;
+        }
+        match self.changed_at {
+            Some(changed_at) if now.duration_since(changed_at) >= SETTLE_TIME => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Whether to offer `number`, which isn't if it just was
+    pub fn offer(&mut self, number: &str, now: Instant) -> bool {
+        if let Some((offered, at)) = &self.offered {
+            if offered == number && now.duration_since(*at) < REPEAT_WINDOW {
+                return false;
+            }
+        }
+        self.offered = Some((number.to_string(), now));
+        true
+    }
+}
+
+// Offer to call what was copied, if it's a number
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn consider(text: &str, debounce: &mut Debounce) {
+    let Some(number) = copied_number(text) else {
+        return;
+    };
+    if debounce.offer(&number, Instant::now()) {
+        let shown = number_format::format_number(&number, &current_settings().country);
+        notify::notify_number_copied(&number, &shown);
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn watch() {
+    use crate::macos::{ns_string, rust_string};
+    use objc::rc::autoreleasepool;
+    use objc::runtime::{Class, Object, BOOL, NO};
+    use objc::{msg_send, sel, sel_impl};
+    use tracing::info;
+
+    // Set by password managers, which don't want what they copy read
+    const PRIVATE_TYPES: [&str; 2] = ["org.nspasteboard.ConcealedType", "org.nspasteboard.TransientType"];
+
+    info!("Watching the clipboard for phone numbers");
+    let mut debounce = Debounce::default();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if !ENABLED.load(Ordering::SeqCst) {
+            // Copies made while it was off aren't offered once it's back on
+            debounce = Debounce::default();
+            COPIED_HERE.store(false, Ordering::SeqCst);
+            continue;
+        }
+        autoreleasepool(|| unsafe {
+            let pasteboard: *mut Object = msg_send![Class::get("NSPasteboard").unwrap(), generalPasteboard];
+            let change: i64 = msg_send![pasteboard, changeCount];
+            if !debounce.settled(change, Instant::now()) || COPIED_HERE.swap(false, Ordering::SeqCst) {
+                return;
+            }
+            let types: *mut Object = msg_send![pasteboard, types];
+            for private in PRIVATE_TYPES {
+                let marked: BOOL = msg_send![types, containsObject: ns_string(private)];
+                if marked != NO {
+                    return;
+                }
+            }
+            let text: *mut Object = msg_send![pasteboard, stringForType: ns_string("public.utf8-plain-text")];
+            if let Some(text) = rust_string(text) {
+                consider(&text, &mut debounce);
+            }
+        });
+    }
+}
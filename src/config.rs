@@ -0,0 +1,448 @@
+// The settings: what's kept in the preferences file, and how it's read and
+// written together with the Keychain and managed preferences
+
+use crate::backend::{self, BackendKind, HttpAuth, HttpAuthKind};
+use crate::call::InFlightCall;
+use crate::ui::SetupStep;
+use crate::{call_events, call_links, call_options, caller_id_template, campaign, config_watch, corporate_directory, dial_plan, directory, duplicates, failover, favorites, http_api, keychain, managed, number_format, number_lists, paths, presence, queue, retry, ringback, storage, tls, voicemail};
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+// The settings in effect, for what acts on them away from the window, like
+// the socket. Replaced whenever settings are saved, switched or reloaded.
+static CURRENT: RwLock<Option<AppState>> = RwLock::new(None);
+
+// This Mac's own auto-answer choice, e.g. on for a desk with a desk phone and
+// off on a laptop using a softphone
+#[derive(Clone, Copy, Data, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoAnswerOverride {
+    // Follow the configuration's Auto Answer setting
+    #[default]
+    Default,
+    Always,
+    Never,
+}
+
+// Application data model
+#[derive(Clone, Data, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppState {
+    // Name of the active profile; profiles::Profile lists the settings that belong to it
+    pub profile: String,
+    pub domain: String,
+    // Domains tried in order when the PBX at `domain` times out or fails with a 5xx
+    pub fallback_domains: String,
+    // Find the servers from the SRV records under `domain` instead of calling it directly
+    pub srv_lookup: bool,
+    pub extension: String,
+    // Kept in the Keychain; only written to the preferences file where there isn't one
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub key: String,
+    // Default for the configuration; see auto_answer() for what's actually sent
+    pub auto_answer: bool,
+    // Per-device override of auto_answer, kept separate so it survives configuration changes
+    pub auto_answer_override: AutoAnswerOverride,
+    // Live in the menu bar: dial from the icon's popover and keep the main window hidden
+    pub menu_bar: bool,
+    // Bigger text and controls in the dialer and call lists
+    pub large_text: bool,
+    // White on black with yellow outlines in the dialer and call lists
+    pub high_contrast: bool,
+    // The dial pad under the phone number field, for dialing without the keyboard
+    pub show_keypad: bool,
+    // Look up names in Contacts for notifications and history, and suggest contacts while typing
+    pub use_contacts: bool,
+    // Add numbers we've called to Spotlight so they can be redialed from there
+    pub spotlight_recents: bool,
+    // Color tag shown next to the dialer so it's obvious which PBX a call goes through
+    pub color: String,
+    // ISO 3166 country code (e.g. "US") used to recognise local emergency numbers
+    pub country: String,
+    // Send numbers to the PBX in E.164 format, reading them as dialed from `country`
+    pub dial_e164: bool,
+    // Rewrite rules for numbers sent to the PBX, one `pattern => replacement` per line
+    pub dial_plan: String,
+    // Trunk selection prefixes offered in the dialer, as "8=Backup carrier, 9=International"
+    pub routes: String,
+    // Numbers dialed exactly as typed on top of the country's own, as "13xxxx, 1831*"
+    pub short_codes: String,
+    // Ringback tone for every call, e.g. "uk-ring"; empty picks one by the destination's country
+    pub ringback: String,
+    // Ask the PBX to record calls (FusionPBX only)
+    pub record_calls: bool,
+    // Number the called party sees, e.g. a main line; empty leaves it to the PBX
+    pub caller_id: String,
+    // What your phone shows while it rings, e.g. "{contact} ({profile})"; empty for the number
+    pub caller_id_name_template: String,
+    pub caller_id_number_template: String,
+    // Changes to FusionPBX's click_to_call.php parameters, one `name = value` or `-name` per line
+    pub click_to_call_parameters: String,
+    // The Generic HTTP backend's request: method and URL, headers, a blank line and the body
+    pub http_request: String,
+    // Seconds a request to the PBX may take, empty for 30
+    pub request_timeout: String,
+    // Times a call that couldn't reach the PBX is tried again, empty for 7 and "0" for none
+    pub retry_attempts: String,
+    // Seconds before the first retry, doubling after each, empty for 30
+    pub retry_delay: String,
+    // Calls originated at once, empty for 1; the rest wait in the call queue
+    pub max_concurrent_calls: String,
+    // PEM file of CA certificates to trust for the PBX, e.g. a lab's internal CA; empty for the system's
+    pub ca_certificate: String,
+    // Accept the PBX's certificate even if it doesn't check out; never on unless asked for
+    pub accept_invalid_certificates: bool,
+    // Client certificate for a PBX behind a proxy that asks for one: a PKCS#12 or PEM file, empty for none
+    pub client_certificate: String,
+    // PEM private key for the client certificate, empty when it's in the certificate file
+    pub client_key: String,
+    // Passphrase of a PKCS#12 client certificate; kept in the Keychain, like the key
+    pub client_certificate_passphrase: String,
+    // How requests to the PBX log in to a web server in front of it, e.g. nginx with basic auth
+    pub http_auth: HttpAuthKind,
+    pub http_auth_username: String,
+    // Password, the bearer token or the OAuth2 client secret; kept in the Keychain, like the key
+    pub http_auth_password: String,
+    // The identity provider's token URL and sign-in URL for OAuth2, and the scope asked for
+    pub oauth_token_url: String,
+    pub oauth_authorize_url: String,
+    pub oauth_scope: String,
+    // Follow placed calls over FreeSWITCH's event socket, so they're known to have connected
+    pub track_calls: bool,
+    // host:port of the event socket for FusionPBX, empty for the domain on 8021
+    pub event_socket: String,
+    // Its password; kept in the Keychain, like the key. FreeSWITCH profiles use the key.
+    pub event_socket_password: String,
+    // Ask the PBX every few seconds whether the extension's phone is registered and on a call
+    pub show_presence: bool,
+    // Count the extension's new voicemail messages every minute, for the Voicemail button
+    pub show_voicemail: bool,
+    // What the Voicemail button calls, empty for *97
+    pub voicemail_number: String,
+    // Which kind of PBX calls are originated on
+    pub backend: BackendKind,
+    // AMI/ARI user or 3CX client id, for backends that log in with one
+    pub backend_username: String,
+    // Dialplan context for Asterisk and FreeSWITCH, empty for the backend's default
+    pub backend_context: String,
+    // Keep the HTTP request and response of failed calls (secrets masked) for PBX admins
+    pub capture_transcripts: bool,
+    // Log what the app decides at each step and the requests it sends, for working out why a call wasn't placed
+    pub debug_logging: bool,
+    // Most the logs and the configuration folder may take, in MB; empty for the default
+    pub storage_limit_mb: String,
+    // Days log files and request details of failed calls are kept, empty for the default
+    pub log_retention_days: String,
+    // Keep a connection to the PBX open so the first call doesn't wait for DNS and TLS
+    pub keep_alive: bool,
+    // Log in to the PBX every few minutes and show a warning on the menu bar icon if that fails
+    pub health_check: bool,
+    // Ask before calling a number from a tel: link, in case a page's link isn't what it shows
+    pub confirm_tel_links: bool,
+    // Choose recording, ringback and caller ID before each call from the dialer
+    pub ask_call_options: bool,
+    // Seconds in which a second request to call the same number is ignored, empty for 5 and "0" for never
+    pub duplicate_window: String,
+    // Say so in a notification when a repeat request is ignored
+    pub notify_duplicate_calls: bool,
+    // Seconds between a campaign's calls, empty for 30
+    pub campaign_delay: String,
+    // Asked once whether to become the app for tel: links, so it isn't asked again
+    pub tel_handler_prompted: bool,
+    // What to do with callto: and SIP links, e.g. "callto=ask, sip=off"; see call_links
+    pub link_rules: String,
+    // Port for the local HTTP API on 127.0.0.1, empty to leave it off
+    pub http_api_port: String,
+    // Token local API requests have to send; kept in the Keychain, like the key
+    pub http_api_token: String,
+    // Numbers with a button of their own, one `label = number` per line
+    pub favorites: String,
+    // Numbers calls may go to, one exact number, prefix* or /regex/ per line; empty allows all
+    pub allowed_numbers: String,
+    // Numbers calls must never go to, written like `allowed_numbers`
+    pub blocked_numbers: String,
+    // Shared company directory: an http(s) URL or a file with a JSON or CSV list of numbers
+    pub directory_url: String,
+    // LDAP server (ldap:// or ldaps://) or CardDAV address book (https://) that
+    // names customer numbers, empty for none
+    pub corporate_directory_url: String,
+    // Bind DN for LDAP, or the CardDAV user; empty to connect anonymously
+    pub corporate_directory_user: String,
+    // Kept in the Keychain, like the key
+    pub corporate_directory_password: String,
+    // Where LDAP searches start, e.g. ou=Customers,dc=example,dc=com
+    pub corporate_directory_base: String,
+    // Time of day ("HH:MM") for the daily call summary notification, empty to disable
+    pub summary_time: String,
+    // Minutes scheduled calls wait after the Mac wakes, empty for the default and "0" for none
+    pub wake_grace_minutes: String,
+    // Slack-compatible webhook that also receives call events, e.g. for supervisors
+    pub webhook_url: String,
+    // Audit webhook that receives every origination attempt, for security teams
+    pub audit_webhook_url: String,
+    // Shared secret used to sign audit webhook requests (HMAC-SHA256)
+    pub audit_webhook_secret: String,
+    // Global hotkey that dials the number under the pointer or in the selection, e.g. "ctrl+alt+cmd+L"
+    pub lookup_hotkey: String,
+    // Global hotkey that dials the number on the clipboard, e.g. "ctrl+alt+cmd+V"
+    pub clipboard_hotkey: String,
+    // Offer to call phone numbers as they're copied
+    pub watch_clipboard: bool,
+    #[serde(skip)]
+    pub phone_number: String,
+    // Bumped whenever a call is added to the history, so views built from it refresh
+    #[serde(skip)]
+    pub history_revision: u64,
+    // Bumped whenever the shared directory is reloaded or the corporate
+    // directory has answered a lookup
+    #[serde(skip)]
+    pub directory_revision: u64,
+    // Bumped whenever profiles are saved or deleted, for the same reason
+    #[serde(skip)]
+    pub profiles_revision: u64,
+    // Bumped whenever calls join or leave the call queue
+    #[serde(skip)]
+    pub queue_revision: u64,
+    // Bumped whenever a call is added to or leaves the Active Calls section
+    #[serde(skip)]
+    pub active_calls_revision: u64,
+    // Bumped whenever the campaign moves on, is imported, paused or cancelled
+    #[serde(skip)]
+    pub campaign_revision: u64,
+    #[serde(skip)]
+    pub status_message: String,
+    // The extension's presence as last asked, e.g. "registered"; empty while it isn't asked
+    #[serde(skip)]
+    pub presence: String,
+    // New voicemail messages as last counted; 0 while they aren't counted
+    #[serde(skip)]
+    pub new_voicemail: u32,
+    // Reason for the call typed into the confirmation prompt, sent with the next call
+    #[serde(skip)]
+    pub call_note: String,
+    // Prefix of the route picked in the dialer, empty for the PBX's default route
+    #[serde(skip)]
+    pub route: String,
+    // Whether the next call is private: dialed, but only counted in the stats.
+    // Like the note, it only applies to the call it was set for.
+    #[serde(skip)]
+    pub private_call: bool,
+    // The dialer's call while it's being placed; Place Call turns into Cancel meanwhile
+    #[serde(skip)]
+    pub in_flight: Option<InFlightCall>,
+    // The number the dialer last called, in international form for copying
+    // or sharing from the status line; empty after a private call
+    #[serde(skip)]
+    pub last_dialed: String,
+    // What the app's files take on disk, for the settings
+    #[serde(skip)]
+    pub storage_used: String,
+    // Restricted dialer-only mode for shared workstations, only ever set by managed preferences
+    #[serde(skip)]
+    pub kiosk: bool,
+    // Where the first-launch setup is
+    #[serde(skip)]
+    pub setup_step: SetupStep,
+    // Whether tel: links open in this app, as macOS said when last asked
+    #[serde(skip)]
+    pub handles_tel_links: bool,
+}
+
+impl AppState {
+    // Take the settings from `saved`, keeping what belongs to this session:
+    // the dialer's number, the status line, the call being placed and so on
+    pub fn reload(&mut self, saved: AppState) {
+        let session = std::mem::replace(self, saved);
+        self.phone_number = session.phone_number;
+        self.history_revision = session.history_revision;
+        self.directory_revision = session.directory_revision;
+        self.profiles_revision = session.profiles_revision + 1;
+        self.queue_revision = session.queue_revision;
+        self.active_calls_revision = session.active_calls_revision;
+        self.campaign_revision = session.campaign_revision;
+        self.status_message = session.status_message;
+        self.presence = session.presence;
+        self.new_voicemail = session.new_voicemail;
+        self.call_note = session.call_note;
+        self.private_call = session.private_call;
+        self.in_flight = session.in_flight;
+        self.last_dialed = session.last_dialed;
+        self.storage_used = session.storage_used;
+        self.setup_step = session.setup_step;
+        self.handles_tel_links = session.handles_tel_links;
+        // A route of the same profile is still a route
+        if self.profile == session.profile {
+            self.route = session.route;
+        }
+    }
+
+    // Whether the PBX should auto-answer on our side: this device's override
+    // if it has one, otherwise the configuration's default
+    pub fn auto_answer(&self) -> bool {
+        match self.auto_answer_override {
+            AutoAnswerOverride::Default => self.auto_answer,
+            AutoAnswerOverride::Always => true,
+            AutoAnswerOverride::Never => false,
+        }
+    }
+}
+
+// Whether the settings make sense, before they're saved or put into effect.
+// Returns a message for the user about the first one that doesn't.
+pub fn check(state: &AppState) -> Result<(), String> {
+    failover::parse_domains(&state.fallback_domains)?;
+    dial_plan::parse(&state.dial_plan)?;
+    dial_plan::parse_routes(&state.routes)?;
+    number_format::parse_short_codes(&state.short_codes)?;
+    ringback::check(&state.ringback)?;
+    call_options::parse_caller_id(&state.caller_id)?;
+    caller_id_template::check(&state.caller_id_name_template)?;
+    caller_id_template::check(&state.caller_id_number_template)?;
+    backend::parse_parameters(&state.click_to_call_parameters)?;
+    if state.backend == BackendKind::GenericHttp || !state.http_request.trim().is_empty() {
+        backend::parse_http_request(&state.http_request)?;
+    }
+    retry::parse_policy(&state.request_timeout, &state.retry_attempts, &state.retry_delay)?;
+    queue::parse_limit(&state.max_concurrent_calls)?;
+    tls::check(&state.ca_certificate)?;
+    tls::check_client_certificate(&state.client_certificate, &state.client_key, &state.client_certificate_passphrase)?;
+    HttpAuth::from_state(state).check()?;
+    call_events::check(state)?;
+    presence::check(state)?;
+    voicemail::check(state)?;
+    storage::parse_limits(&state.storage_limit_mb, &state.log_retention_days)?;
+    call_links::parse_rules(&state.link_rules)?;
+    duplicates::parse_window(&state.duplicate_window)?;
+    campaign::parse_delay(&state.campaign_delay)?;
+    http_api::parse_port(&state.http_api_port, &state.http_api_token)?;
+    favorites::parse(&state.favorites)?;
+    number_lists::parse(&state.allowed_numbers).map_err(|e| format!("Allow list: {}", e))?;
+    number_lists::parse(&state.blocked_numbers).map_err(|e| format!("Block list: {}", e))?;
+    directory::check_source(&state.directory_url)?;
+    corporate_directory::check_settings(&state.corporate_directory_url, &state.corporate_directory_base)
+}
+
+// Function to save preferences
+// Returns a message for the user if the settings couldn't be written
+pub fn save_preferences(state: &AppState) -> Result<(), String> {
+    // Kiosk configuration is owned by the administrator, never the local user
+    if state.kiosk {
+        return Ok(());
+    }
+    
+    let config_path = paths::config_dir();
+    std::fs::create_dir_all(config_path)
+        .map_err(|e| format!("Couldn't create {}: {}", config_path.display(), e))?;
+    
+    // The key goes to the Keychain rather than sitting in the file in cleartext
+    let mut stored = state.clone();
+    if keychain::set_password(keychain::API_KEY, &state.key)? {
+        stored.key.clear();
+    }
+    if keychain::set_password(keychain::CORPORATE_DIRECTORY_PASSWORD, &state.corporate_directory_password)? {
+        stored.corporate_directory_password.clear();
+    }
+    if keychain::set_password(keychain::CLIENT_CERTIFICATE_PASSPHRASE, &state.client_certificate_passphrase)? {
+        stored.client_certificate_passphrase.clear();
+    }
+    if keychain::set_password(keychain::HTTP_AUTH_PASSWORD, &state.http_auth_password)? {
+        stored.http_auth_password.clear();
+    }
+    if keychain::set_password(keychain::EVENT_SOCKET_PASSWORD, &state.event_socket_password)? {
+        stored.event_socket_password.clear();
+    }
+    if keychain::set_password(keychain::HTTP_API_TOKEN, &state.http_api_token)? {
+        stored.http_api_token.clear();
+    }
+    
+    let prefs_path = paths::preferences_file();
+    let json = serde_json::to_string(&stored)
+        .map_err(|e| format!("Couldn't serialize settings: {}", e))?;
+    
+    std::fs::write(&prefs_path, &json)
+        .map_err(|e| format!("Couldn't write {}: {}", prefs_path.display(), e))?;
+    // Our own change, not one to reload
+    config_watch::written(&json);
+    Ok(())
+}
+
+// The settings in effect: the last ones put into effect, or else the saved ones
+pub fn current_settings() -> AppState {
+    let current = CURRENT.read().unwrap().clone();
+    current.unwrap_or_else(load_preferences)
+}
+
+pub fn set_current_settings(state: &AppState) {
+    *CURRENT.write().unwrap() = Some(state.clone());
+}
+
+// Function to load preferences
+pub fn load_preferences() -> AppState {
+    match read_preferences() {
+        Ok(state) => state,
+        Err(e) => {
+            warn!("Ignoring unreadable preferences file: {}", e);
+            with_secrets(AppState::default(), &managed::load_managed_settings())
+        }
+    }
+}
+
+// The saved settings like load_preferences, but a preferences file that
+// can't be read is an error instead of no settings, e.g. for a reload while
+// someone is halfway through editing it
+pub fn read_preferences() -> Result<AppState, String> {
+    let mut state = AppState::default();
+    let managed = managed::load_managed_settings();
+    
+    // Kiosk machines take all configuration from managed preferences, so
+    // anything left behind in the local preferences file is ignored
+    if managed.kiosk {
+        managed.apply(&mut state);
+        return Ok(state);
+    }
+    
+    if let Ok(content) = std::fs::read_to_string(paths::preferences_file()) {
+        state = serde_json::from_str::<AppState>(&content)
+            .map_err(|e| format!("{} isn't valid: {}", paths::preferences_file().display(), e))?;
+    }
+    Ok(with_secrets(state, &managed))
+}
+
+// `state` with what the Keychain keeps for it, and what's managed forced on it
+fn with_secrets(mut state: AppState, managed: &managed::ManagedSettings) -> AppState {
+    if state.key.is_empty() {
+        state.key = keychain::password(keychain::API_KEY).unwrap_or_default();
+    } else if keychain::set_password(keychain::API_KEY, &state.key) == Ok(true) {
+        // Written by a version that kept the key in cleartext; now that it's in
+        // the Keychain, rewrite the file without it
+        match save_preferences(&state) {
+            Ok(()) => info!("Moved the API key to the Keychain"),
+            Err(e) => warn!("Couldn't remove the API key from the preferences file: {}", e),
+        }
+    }
+    
+    if state.corporate_directory_password.is_empty() {
+        state.corporate_directory_password = keychain::password(keychain::CORPORATE_DIRECTORY_PASSWORD).unwrap_or_default();
+    }
+    
+    if state.client_certificate_passphrase.is_empty() {
+        state.client_certificate_passphrase = keychain::password(keychain::CLIENT_CERTIFICATE_PASSPHRASE).unwrap_or_default();
+    }
+    
+    if state.http_auth_password.is_empty() {
+        state.http_auth_password = keychain::password(keychain::HTTP_AUTH_PASSWORD).unwrap_or_default();
+    }
+    
+    if state.event_socket_password.is_empty() {
+        state.event_socket_password = keychain::password(keychain::EVENT_SOCKET_PASSWORD).unwrap_or_default();
+    }
+    
+    if state.http_api_token.is_empty() {
+        state.http_api_token = keychain::password(keychain::HTTP_API_TOKEN).unwrap_or_default();
+    }
+    
+    managed.apply(&mut state);
+    state
+}
@@ -0,0 +1,280 @@
+// Tests for the preferences file format: what's written, what's left out,
+// and reading files from older versions. Only serde is involved; nothing is
+// read from or written to disk or the Keychain.
+
+use crate::backend::BackendKind;
+use crate::config::{self, AppState, AutoAnswerOverride};
+use crate::favorites::{self, Favorite};
+use crate::srv::{self, Record};
+use crate::{failover, profiles, provisioning, queue, retry, storage, voicemail};
+use serde_json::Value;
+use std::time::Duration;
+
+fn configured() -> AppState {
+    AppState {
+        profile: "Office".to_string(),
+        domain: "pbx.example.com".to_string(),
+        extension: "201".to_string(),
+        key: "secret".to_string(),
+        auto_answer: true,
+        auto_answer_override: AutoAnswerOverride::Never,
+        country: "GB".to_string(),
+        dial_e164: true,
+        dial_plan: "^\\+44 => 0".to_string(),
+        backend: BackendKind::FreeSwitchEsl,
+        backend_username: "admin".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn settings_survive_a_round_trip() {
+    let json = serde_json::to_string(&configured()).unwrap();
+    let state: AppState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state.profile, "Office");
+    assert_eq!(state.domain, "pbx.example.com");
+    assert_eq!(state.extension, "201");
+    assert_eq!(state.key, "secret");
+    assert!(state.auto_answer);
+    assert!(state.auto_answer_override == AutoAnswerOverride::Never);
+    assert_eq!(state.country, "GB");
+    assert!(state.dial_e164);
+    assert_eq!(state.dial_plan, "^\\+44 => 0");
+    assert!(state.backend == BackendKind::FreeSwitchEsl);
+    assert_eq!(state.backend_username, "admin");
+}
+
+#[test]
+fn enums_are_written_by_name() {
+    let json: Value = serde_json::to_value(configured()).unwrap();
+    assert_eq!(json["auto_answer_override"], "never");
+    assert_eq!(json["backend"], "free_switch_esl");
+}
+
+#[test]
+fn what_belongs_to_the_session_isnt_written() {
+    let state = AppState {
+        phone_number: "5551234567".to_string(),
+        status_message: "Call to 5551234567 initiated".to_string(),
+        call_note: "Callback".to_string(),
+        route: "8".to_string(),
+        private_call: true,
+        kiosk: true,
+        history_revision: 3,
+        ..configured()
+    };
+    let json: Value = serde_json::to_value(&state).unwrap();
+    for field in ["phone_number", "status_message", "call_note", "route", "private_call", "kiosk", "history_revision"] {
+        assert!(json.get(field).is_none(), "{} was written", field);
+    }
+
+    // Not even when a hand-edited file has them
+    let state: AppState = serde_json::from_str(r#"{"kiosk": true, "phone_number": "911"}"#).unwrap();
+    assert!(!state.kiosk);
+    assert!(state.phone_number.is_empty());
+}
+
+#[test]
+fn an_empty_key_is_left_out() {
+    let state = AppState { key: String::new(), ..configured() };
+    let json: Value = serde_json::to_value(&state).unwrap();
+    assert!(json.get("key").is_none());
+    assert_eq!(serde_json::to_value(configured()).unwrap()["key"], "secret");
+}
+
+#[test]
+fn older_files_get_defaults_for_newer_settings() {
+    let state: AppState = serde_json::from_str(r#"{"domain": "pbx.example.com", "extension": "201", "auto_answer": true}"#).unwrap();
+    assert_eq!(state.domain, "pbx.example.com");
+    assert!(state.auto_answer);
+    assert!(state.auto_answer_override == AutoAnswerOverride::Default);
+    assert!(state.backend == BackendKind::FusionPbx);
+    assert!(!state.dial_e164);
+    assert!(state.dial_plan.is_empty());
+
+    let state: AppState = serde_json::from_str("{}").unwrap();
+    assert!(state.domain.is_empty());
+}
+
+#[test]
+fn unknown_settings_are_ignored() {
+    let state: AppState = serde_json::from_str(r#"{"domain": "pbx.example.com", "removed_setting": 1}"#).unwrap();
+    assert_eq!(state.domain, "pbx.example.com");
+}
+
+#[test]
+fn auto_answer_follows_the_override() {
+    let mut state = AppState { auto_answer: true, ..Default::default() };
+    assert!(state.auto_answer());
+    state.auto_answer_override = AutoAnswerOverride::Never;
+    assert!(!state.auto_answer());
+    state.auto_answer = false;
+    state.auto_answer_override = AutoAnswerOverride::Always;
+    assert!(state.auto_answer());
+}
+
+#[test]
+fn timeout_and_retries_default_when_empty() {
+    let policy = retry::policy(&AppState::default());
+    assert_eq!(policy.timeout, Duration::from_secs(30));
+    assert_eq!(policy.retries, 7);
+    assert_eq!(policy.first_delay, Duration::from_secs(30));
+}
+
+#[test]
+fn timeout_and_retries_come_from_the_profile() {
+    let state = AppState {
+        request_timeout: " 8 ".to_string(),
+        retry_attempts: "0".to_string(),
+        retry_delay: "120".to_string(),
+        ..Default::default()
+    };
+    let policy = retry::policy(&state);
+    assert_eq!(policy.timeout, Duration::from_secs(8));
+    assert_eq!(policy.retries, 0);
+    assert_eq!(policy.first_delay, Duration::from_secs(120));
+}
+
+#[test]
+fn calls_at_once_default_to_one() {
+    assert_eq!(queue::limit(&AppState::default()), 1);
+    assert_eq!(queue::parse_limit(" 4 "), Ok(4));
+    for limit in ["0", "21", "many"] {
+        assert!(queue::parse_limit(limit).is_err(), "{}", limit);
+    }
+}
+
+#[test]
+fn timeout_and_retries_out_of_range_are_refused() {
+    for (timeout, retries, delay) in [("0", "", ""), ("301", "", ""), ("2.5", "", ""), ("", "21", ""), ("", "-1", ""), ("", "", "0"), ("", "", "601"), ("", "", "soon")] {
+        assert!(retry::parse_policy(timeout, retries, delay).is_err(), "{:?}", (timeout, retries, delay));
+    }
+    assert!(retry::parse_policy("300", "20", "600").is_ok());
+}
+
+#[test]
+fn storage_limits_default_when_empty() {
+    let limits = storage::limits(&AppState::default());
+    assert_eq!(limits.bytes, 100 * 1024 * 1024);
+    assert_eq!(limits.keep_days, 7);
+
+    let limits = storage::parse_limits(" 250 ", "30").unwrap();
+    assert_eq!(limits.bytes, 250 * 1024 * 1024);
+    assert_eq!(limits.keep_days, 30);
+}
+
+#[test]
+fn storage_limits_out_of_range_are_refused() {
+    for (limit, days) in [("9", ""), ("10001", ""), ("1.5", ""), ("", "0"), ("", "366"), ("", "a week")] {
+        assert!(storage::parse_limits(limit, days).is_err(), "{:?}", (limit, days));
+    }
+}
+
+#[test]
+fn settings_that_dont_make_sense_are_refused() {
+    assert!(config::check(&configured()).is_ok());
+    let state = AppState { blocked_numbers: "not a number".to_string(), ..configured() };
+    assert!(config::check(&state).unwrap_err().starts_with("Block list: "));
+    let state = AppState { retry_attempts: "many".to_string(), ..configured() };
+    assert!(config::check(&state).is_err());
+}
+
+#[test]
+fn fallback_domains_are_read_in_order() {
+    assert_eq!(failover::parse_domains("").unwrap(), Vec::<String>::new());
+    assert_eq!(failover::parse_domains("pbx2.example.com, https://pbx3.example.com:8443\npbx4.example.com").unwrap(), ["pbx2.example.com", "https://pbx3.example.com:8443", "pbx4.example.com"]);
+    assert!(failover::parse_domains("pbx2.example.com/?key=x").is_err());
+    assert!(failover::parse_domains("a b c d e f").is_err());
+}
+
+#[test]
+fn srv_records_are_tried_by_priority_then_weight() {
+    let record = |priority, weight, target: &str, port| Record { priority, weight, target: target.to_string(), port };
+    let records = vec![record(20, 0, "standby.example.com.", 8443), record(10, 10, "b.example.com.", 443), record(10, 60, "a.example.com.", 443)];
+    assert_eq!(srv::addresses("example.com", "_clicktocall._tcp", records), ["a.example.com:443", "b.example.com:443", "standby.example.com:8443"]);
+    // SIP records give the host, not the web port
+    assert_eq!(srv::addresses("http://example.com", "_sip._tcp", vec![record(0, 0, "pbx.example.com.", 5060)]), ["http://pbx.example.com"]);
+    assert!(srv::addresses("example.com", "_clicktocall._tcp", vec![record(0, 0, ".", 0)]).is_empty());
+}
+
+#[test]
+fn voicemail_is_counted_where_the_pbx_can_say() {
+    let state = AppState { show_voicemail: true, ..configured() };
+    assert!(config::check(&state).is_ok());
+    assert_eq!(voicemail::number(&state), "*97");
+    let state = AppState { voicemail_number: " *98 ".to_string(), ..state };
+    assert_eq!(voicemail::number(&state), "*98");
+    assert!(config::check(&AppState { voicemail_number: "voicemail".to_string(), ..state.clone() }).is_err());
+    assert!(config::check(&AppState { backend: BackendKind::ThreeCx, ..state.clone() }).is_err());
+    // FusionPBX is asked over its event socket
+    assert!(config::check(&AppState { backend: BackendKind::FusionPbx, ..state.clone() }).is_err());
+    assert!(config::check(&AppState { backend: BackendKind::FusionPbx, event_socket_password: "ClueCon".to_string(), ..state }).is_ok());
+}
+
+#[test]
+fn favorites_are_read_one_per_line() {
+    let parsed = favorites::parse("Front desk = 201\n\n  +44 20 7946 0958 \nA = B = (555) 123-4567").unwrap();
+    assert_eq!(
+        parsed,
+        vec![
+            Favorite { label: "Front desk".to_string(), number: "201".to_string() },
+            Favorite { label: "+44 20 7946 0958".to_string(), number: "+44 20 7946 0958".to_string() },
+            Favorite { label: "A = B".to_string(), number: "(555) 123-4567".to_string() },
+        ]
+    );
+    assert!(favorites::parse("").unwrap().is_empty());
+
+    for wrong in ["Front desk", "Front desk = ", "Sales = call me"] {
+        assert!(favorites::parse(wrong).is_err(), "{}", wrong);
+    }
+    let too_many = (0..=favorites::MAX_FAVORITES).map(|n| format!("{}", 200 + n)).collect::<Vec<_>>().join("\n");
+    assert!(favorites::parse(&too_many).is_err());
+    let state = AppState { favorites: "Front desk".to_string(), ..configured() };
+    assert!(config::check(&state).is_err());
+}
+
+#[test]
+fn saving_favorites_indexes_the_new_ones_and_removes_the_deleted_ones() {
+    let before = favorites::parse("Front desk = 201
+Sales = 202
+Support = 203").unwrap();
+    let saved = favorites::parse("Front desk = 201
+Sales team = 202
+Billing = 204").unwrap();
+    let (added, deleted) = favorites::changes(&before, &saved);
+    // A relabelled favorite keeps its item, with the new label
+    assert_eq!(added.iter().map(|favorite| favorite.label.as_str()).collect::<Vec<_>>(), ["Sales team", "Billing"]);
+    assert_eq!(deleted.iter().map(|favorite| favorite.label.as_str()).collect::<Vec<_>>(), ["Support"]);
+
+    // At launch every favorite is indexed
+    let (added, deleted) = favorites::changes(&[], &saved);
+    assert_eq!(added.len(), 3);
+    assert!(deleted.is_empty());
+}
+
+#[test]
+fn settings_files_read_back_as_json_and_toml() {
+    let file = provisioning::SettingsFile {
+        version: provisioning::FORMAT_VERSION,
+        settings: configured(),
+        profiles: vec![profiles::Profile::from_state(&configured()), profiles::Profile { name: "Lab".to_string(), ..Default::default() }],
+    };
+    for toml in [false, true] {
+        let text = provisioning::to_string(&file, toml).unwrap();
+        let read = provisioning::parse(&text, toml).unwrap();
+        assert_eq!(read.settings.domain, "pbx.example.com");
+        assert!(read.settings.backend == BackendKind::FreeSwitchEsl);
+        assert_eq!(read.profiles.len(), 2);
+        assert_eq!(read.profiles[1].name, "Lab");
+    }
+    assert!(provisioning::is_toml("/Users/me/Desktop/Settings.TOML"));
+    assert!(!provisioning::is_toml("settings.json"));
+}
+
+#[test]
+fn settings_files_from_a_newer_version_are_refused() {
+    assert!(provisioning::parse(r#"{"version": 99, "settings": {}}"#, false).is_err());
+    let read = provisioning::parse("[settings]\ndomain = \"pbx.example.com\"\n\n[[profiles]]\nname = \"Office\"\n", true).unwrap();
+    assert_eq!(read.settings.domain, "pbx.example.com");
+    assert_eq!(read.profiles[0].name, "Office");
+}
@@ -0,0 +1,103 @@
+// Watching the preferences file, so changes made outside the app, by a
+// management tool or by hand, take effect without restarting it. The system
+// says when the folder it's in changes (FSEvents on macOS), and the file is
+// then compared with what was last seen, which leaves out the app's own writes.
+
+use crate::daemon;
+use crate::paths;
+use crate::ui::RELOAD_SETTINGS;
+use ::notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use druid::{ExtEventSink, Target};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+// How long after the last event the file is read, so an editor that writes
+// in several steps is done
+const SETTLE: Duration = Duration::from_millis(250);
+
+// Kept for as long as the app runs; dropping it stops the watching
+static WATCHER: OnceLock<Mutex<RecommendedWatcher>> = OnceLock::new();
+
+// The file's contents as last seen or written by this process
+static SEEN: Mutex<Option<String>> = Mutex::new(None);
+static WATCHER_STARTED: AtomicBool = AtomicBool::new(false);
+
+fn read() -> Option<String> {
+    std::fs::read_to_string(paths::preferences_file()).ok()
+}
+
+// The app wrote `contents` to the file itself, so seeing them isn't a change
+pub fn written(contents: &str) {
+    *SEEN.lock().unwrap() = Some(contents.to_string());
+}
+
+// Start watching the preferences file. With a window, the reload goes through
+// it so the form shows the new settings; headless, they're put into effect
+// directly. Safe to call more than once.
+pub fn start(event_sink: Option<ExtEventSink>) {
+    if WATCHER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    {
+        let mut seen = SEEN.lock().unwrap();
+        if seen.is_none() {
+            *seen = read();
+        }
+    }
+
+    // The folder is watched rather than the file, which editors and management
+    // tools often replace with a new one
+    let path = paths::preferences_file();
+    let Some(folder) = path.parent().map(|folder| folder.to_path_buf()) else {
+        return;
+    };
+    let (sender, events) = mpsc::channel();
+    let watcher = ::notify::recommended_watcher(move |event: ::notify::Result<::notify::Event>| {
+        if event.is_ok_and(|event| event.paths.iter().any(|changed| changed.file_name() == path.file_name())) {
+            sender.send(()).ok();
+        }
+    });
+    let watcher = match watcher.and_then(|mut watcher| watcher.watch(&folder, RecursiveMode::NonRecursive).map(|_| watcher)) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Couldn't watch {} for changes: {}", folder.display(), e);
+            return;
+        }
+    };
+    WATCHER.set(Mutex::new(watcher)).ok();
+
+    thread::spawn(move || while events.recv().is_ok() {
+        while events.recv_timeout(SETTLE).is_ok() {}
+        if !changed() {
+            continue;
+        }
+        info!("The preferences file changed, reloading the settings");
+        match &event_sink {
+            Some(event_sink) => {
+                event_sink.submit_command(RELOAD_SETTINGS, (), Target::Auto).ok();
+            }
+            None => {
+                if let Err(message) = daemon::reload() {
+                    warn!("Kept the settings in effect: {}", message);
+                }
+            }
+        }
+    });
+}
+
+// Whether the file is different from when it was last seen. A file that was
+// removed isn't a change; the settings in effect stay until it's back.
+fn changed() -> bool {
+    let Some(contents) = read() else {
+        return false;
+    };
+    let mut seen = SEEN.lock().unwrap();
+    if seen.as_deref() == Some(contents.as_str()) {
+        return false;
+    }
+    *seen = Some(contents);
+    true
+}
@@ -0,0 +1,225 @@
+use std::sync::RwLock;
+use tracing::info;
+
+// How many autocomplete suggestions are offered at once
+pub const MAX_SUGGESTIONS: usize = 5;
+// Numbers are compared on their trailing digits so "+1 555 123 4567" matches
+// "(555) 123-4567"; shorter numbers have to match exactly
+const MIN_SUFFIX_DIGITS: usize = 7;
+
+// A contact's name with one of its numbers
+#[derive(Clone)]
+pub struct ContactNumber {
+    pub name: String,
+    pub number: String,
+    digits: String,
+}
+
+// Address book snapshot, None until it has been read
+static CACHE: RwLock<Option<Vec<ContactNumber>>> = RwLock::new(None);
+
+pub fn digits(number: &str) -> String {
+    number.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+pub fn same_number(a: &str, b: &str) -> bool {
+    if a.len() < MIN_SUFFIX_DIGITS || b.len() < MIN_SUFFIX_DIGITS {
+        return a == b;
+    }
+    a.ends_with(b) || b.ends_with(a)
+}
+
+// Read the address book into the cache. Asks for Contacts access the first
+// time; until it's granted the cache stays empty and this can be retried.
+pub fn refresh() {
+    match read_address_book() {
+        Some(contacts) => {
+            info!("Loaded {} contact numbers", contacts.len());
+            *CACHE.write().unwrap() = Some(contacts);
+        }
+        None => *CACHE.write().unwrap() = None,
+    }
+}
+
+// Drop the cache, e.g. when the feature is switched off
+pub fn clear() {
+    *CACHE.write().unwrap() = None;
+}
+
+fn with_contacts<T>(f: impl FnOnce(&[ContactNumber]) -> T) -> T {
+    // Access may have been granted since the last attempt
+    if CACHE.read().unwrap().is_none() && is_authorized() {
+        refresh();
+    }
+    let cache = CACHE.read().unwrap();
+    f(cache.as_deref().unwrap_or(&[]))
+}
+
+// Name of the contact a number belongs to
+pub fn name_for(number: &str) -> Option<String> {
+    let wanted = digits(number);
+    if wanted.is_empty() {
+        return None;
+    }
+    with_contacts(|contacts| {
+        contacts
+            .iter()
+            .find(|contact| same_number(&contact.digits, &wanted))
+            .map(|contact| contact.name.clone())
+    })
+}
+
+// Contacts whose name contains the query, or whose number contains its digits
+pub fn suggestions(query: &str) -> Vec<ContactNumber> {
+    let query = query.trim();
+    let query_lower = query.to_lowercase();
+    let query_digits = digits(query);
+    // Searching on letters or on at least a few digits, not on "+" or "("
+    let by_digits = query_digits.len() >= 3 && query_lower.chars().all(|c| !c.is_alphabetic());
+    if !by_digits && query_lower.chars().filter(|c| c.is_alphabetic()).count() < 2 {
+        return Vec::new();
+    }
+
+    with_contacts(|contacts| {
+        contacts
+            .iter()
+            .filter(|contact| {
+                if by_digits {
+                    // The number is already complete, nothing left to suggest
+                    contact.digits.contains(&query_digits) && contact.digits != query_digits
+                } else {
+                    contact.name.to_lowercase().contains(&query_lower)
+                }
+            })
+            .take(MAX_SUGGESTIONS)
+            .cloned()
+            .collect()
+    })
+}
+
+// Number to put in the dialer for a suggestion: digits, keeping a leading plus
+pub fn dialable(number: &str) -> String {
+    let plus = if number.trim_start().starts_with('+') { "+" } else { "" };
+    format!("{}{}", plus, digits(number))
+}
+
+// CNAuthorizationStatusAuthorized
+#[cfg(all(target_os = "macos", feature = "contacts"))]
+const AUTHORIZED: isize = 3;
+
+#[cfg(all(target_os = "macos", feature = "contacts"))]
+fn is_authorized() -> bool {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::Class;
+
+    let store_class = match Class::get("CNContactStore") {
+        Some(class) => class,
+        None => return false,
+    };
+    // CNEntityTypeContacts
+    let status: isize = unsafe { msg_send![store_class, authorizationStatusForEntityType: 0isize] };
+    status == AUTHORIZED
+}
+
+#[cfg(not(all(target_os = "macos", feature = "contacts")))]
+fn is_authorized() -> bool {
+    false
+}
+
+#[cfg(all(target_os = "macos", feature = "contacts"))]
+mod ffi {
+    // Contacts is only used through the Objective-C runtime, but has to be loaded
+    #[link(name = "Contacts", kind = "framework")]
+    extern "C" {}
+}
+
+// Every phone number in every account's contacts. The first access shows the
+// system permission prompt and comes back empty; None means no access (yet).
+#[cfg(all(target_os = "macos", feature = "contacts"))]
+fn read_address_book() -> Option<Vec<ContactNumber>> {
+    use crate::macos::{ns_string, rust_string};
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+    use tracing::warn;
+
+    let store_class = Class::get("CNContactStore")?;
+    let contact_class = Class::get("CNContact")?;
+
+    unsafe {
+        let store: *mut Object = msg_send![store_class, alloc];
+        let store: *mut Object = msg_send![store, init];
+
+        // CNContactGivenNameKey etc.
+        let array_class = Class::get("NSMutableArray").unwrap();
+        let keys: *mut Object = msg_send![array_class, array];
+        for key in ["givenName", "familyName", "organizationName", "phoneNumbers"] {
+            let _: () = msg_send![keys, addObject: ns_string(key)];
+        }
+
+        let nil: *mut Object = std::ptr::null_mut();
+        let mut error: *mut Object = std::ptr::null_mut();
+        let containers: *mut Object = msg_send![store, containersMatchingPredicate:nil error:&mut error];
+        if containers.is_null() {
+            // Access denied, or the permission prompt is still showing
+            warn!("Contacts not available (access not granted yet?)");
+            let _: () = msg_send![store, release];
+            return None;
+        }
+
+        let mut contacts = Vec::new();
+        let container_count: usize = msg_send![containers, count];
+        for i in 0..container_count {
+            let container: *mut Object = msg_send![containers, objectAtIndex: i];
+            let identifier: *mut Object = msg_send![container, identifier];
+            let predicate: *mut Object = msg_send![contact_class, predicateForContactsInContainerWithIdentifier: identifier];
+            let found: *mut Object = msg_send![store, unifiedContactsMatchingPredicate:predicate keysToFetch:keys error:&mut error];
+            if found.is_null() {
+                continue;
+            }
+
+            let found_count: usize = msg_send![found, count];
+            for j in 0..found_count {
+                let contact: *mut Object = msg_send![found, objectAtIndex: j];
+                let given: *mut Object = msg_send![contact, givenName];
+                let family: *mut Object = msg_send![contact, familyName];
+                let organization: *mut Object = msg_send![contact, organizationName];
+
+                let full_name = [rust_string(given), rust_string(family)]
+                    .into_iter()
+                    .flatten()
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let name = if full_name.is_empty() {
+                    rust_string(organization).unwrap_or_default()
+                } else {
+                    full_name
+                };
+                if name.is_empty() {
+                    continue;
+                }
+
+                // CNLabeledValue<CNPhoneNumber>
+                let numbers: *mut Object = msg_send![contact, phoneNumbers];
+                let number_count: usize = msg_send![numbers, count];
+                for k in 0..number_count {
+                    let labeled: *mut Object = msg_send![numbers, objectAtIndex: k];
+                    let phone: *mut Object = msg_send![labeled, value];
+                    let text: *mut Object = msg_send![phone, stringValue];
+                    if let Some(number) = rust_string(text) {
+                        contacts.push(ContactNumber { name: name.clone(), digits: digits(&number), number });
+                    }
+                }
+            }
+        }
+
+        let _: () = msg_send![store, release];
+        Some(contacts)
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "contacts")))]
+fn read_address_book() -> Option<Vec<ContactNumber>> {
+    // No Contacts framework outside macOS
+    None
+}
@@ -0,0 +1,409 @@
+// Names and suggestions from the company's LDAP server or a CardDAV address
+// book, for customer numbers that live there rather than in everyone's
+// Contacts. LDAP is searched as numbers and names come up; a CardDAV address
+// book is read whole. Either way, answers are reused for a while so the
+// server isn't asked on every keystroke, and asking never holds up the UI.
+
+use crate::config::AppState;
+use crate::directory::DirectoryEntry;
+use crate::{contacts, runtime};
+use druid::ExtEventSink;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// How long an answer from the server is reused
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+// How long a failed lookup keeps the server from being asked again
+const FAILURE_TTL: Duration = Duration::from_secs(60);
+
+// Longest a lookup may take, so a call isn't held up by a slow server
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Most entries one LDAP search brings back
+#[cfg(feature = "corporate-directory")]
+const MAX_RESULTS: i32 = 25;
+
+// LDAP attributes that hold phone numbers
+#[cfg(feature = "corporate-directory")]
+const PHONE_ATTRIBUTES: [&str; 4] = ["telephoneNumber", "mobile", "homePhone", "ipPhone"];
+
+// Answers by lookup ("number:<digits>", "name:<query>", ...), and when they came in
+type Cache = HashMap<String, (Instant, Result<Vec<DirectoryEntry>, String>)>;
+
+static SERVER: RwLock<Option<Server>> = RwLock::new(None);
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+// Lookups running in the background, so each is only started once
+static PENDING: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+// Where to look names up and how to log in, taken from the settings
+#[derive(Clone, PartialEq)]
+pub struct Server {
+    // ldap:// or ldaps:// for LDAP, an https:// address book URL for CardDAV
+    url: String,
+    // Bind DN for LDAP, user name for CardDAV; empty to connect anonymously
+    user: String,
+    password: String,
+    // Where LDAP searches start, e.g. ou=Customers,dc=example,dc=com
+    base: String,
+}
+
+impl Server {
+    // None when no server is set
+    pub fn from_state(state: &AppState) -> Option<Self> {
+        let url = state.corporate_directory_url.trim();
+        if url.is_empty() {
+            return None;
+        }
+        Some(Server {
+            url: url.to_string(),
+            user: state.corporate_directory_user.trim().to_string(),
+            password: state.corporate_directory_password.clone(),
+            base: state.corporate_directory_base.trim().to_string(),
+        })
+    }
+
+    #[cfg(feature = "corporate-directory")]
+    fn is_ldap(&self) -> bool {
+        let lower = self.url.to_lowercase();
+        lower.starts_with("ldap://") || lower.starts_with("ldaps://")
+    }
+}
+
+// What's being looked up
+#[derive(Clone)]
+enum Query {
+    // Whoever has this number, as digits
+    Number(String),
+    // Numbers with these digits in them, as typed so far
+    Digits(String),
+    // Names containing this, lowercased
+    Name(String),
+}
+
+impl Query {
+    fn key(&self) -> String {
+        match self {
+            Query::Number(digits) => format!("number:{}", digits),
+            Query::Digits(digits) => format!("digits:{}", digits),
+            Query::Name(name) => format!("name:{}", name),
+        }
+    }
+
+    fn matches(&self, entry: &DirectoryEntry) -> bool {
+        match self {
+            Query::Number(digits) => contacts::same_number(&contacts::digits(&entry.number), digits),
+            Query::Digits(digits) => contacts::digits(&entry.number).contains(digits.as_str()),
+            Query::Name(name) => entry.name.to_lowercase().contains(name.as_str()),
+        }
+    }
+}
+
+// Whether the settings describe a server that can be asked
+pub fn check_settings(url: &str, base: &str) -> Result<(), String> {
+    let url = url.trim();
+    let lower = url.to_lowercase();
+    if url.is_empty() || lower.starts_with("https://") || lower.starts_with("http://") {
+        Ok(())
+    } else if lower.starts_with("ldap://") || lower.starts_with("ldaps://") {
+        if base.trim().is_empty() {
+            Err("the corporate directory needs a search base for LDAP, e.g. ou=People,dc=example,dc=com".to_string())
+        } else {
+            Ok(())
+        }
+    } else {
+        Err("the corporate directory should be an ldap://, ldaps:// or https:// (CardDAV) address".to_string())
+    }
+}
+
+// Look names up on `server` from now on, or nowhere with None. What was
+// cached from another server is dropped.
+pub fn set_server(server: Option<Server>) {
+    let mut current = SERVER.write().unwrap();
+    if *current != server {
+        *current = server;
+        *CACHE.lock().unwrap() = None;
+    }
+}
+
+// Start telling the UI when a lookup has finished
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+// The cached answer for `key`, if it's still fresh
+fn cached(key: &str) -> Option<Result<Vec<DirectoryEntry>, String>> {
+    let mut cache = CACHE.lock().unwrap();
+    let (found_at, result) = cache.get_or_insert_with(HashMap::new).get(key)?;
+    let ttl = if result.is_ok() { CACHE_TTL } else { FAILURE_TTL };
+    (found_at.elapsed() < ttl).then(|| result.clone())
+}
+
+// Ask the server, and keep its answer
+async fn lookup(server: &Server, query: &Query) -> Result<Vec<DirectoryEntry>, String> {
+    let result = match tokio::time::timeout(LOOKUP_TIMEOUT, search(server, query)).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("no answer within {} seconds", LOOKUP_TIMEOUT.as_secs())),
+    };
+    let result = result.map(|entries| entries.into_iter().filter(|entry| query.matches(entry)).collect());
+    if let Err(e) = &result {
+        warn!("Corporate directory lookup failed: {}", e);
+    }
+
+    // The server may have been changed while it was asked
+    if SERVER.read().unwrap().as_ref() == Some(server) {
+        CACHE.lock().unwrap().get_or_insert_with(HashMap::new).insert(query.key(), (Instant::now(), result.clone()));
+    }
+    result
+}
+
+// What's known about `query` now. If it's not known yet it's looked up in
+// the background, and views are rebuilt when the answer is in.
+fn known(query: Query) -> Vec<DirectoryEntry> {
+    let Some(server) = SERVER.read().unwrap().clone() else {
+        return Vec::new();
+    };
+    if let Some(result) = cached(&query.key()) {
+        return result.unwrap_or_default();
+    }
+
+    if PENDING.lock().unwrap().get_or_insert_with(HashSet::new).insert(query.key()) {
+        runtime().spawn(async move {
+            lookup(&server, &query).await.ok();
+            PENDING.lock().unwrap().get_or_insert_with(HashSet::new).remove(&query.key());
+            if let Some(sink) = EVENT_SINK.get() {
+                sink.add_idle_callback(|data: &mut AppState| data.directory_revision += 1);
+            }
+        });
+    }
+    Vec::new()
+}
+
+// Name the server gives `number`, if it's already been looked up
+pub fn name_for(number: &str) -> Option<String> {
+    let wanted = contacts::digits(number);
+    if wanted.is_empty() {
+        return None;
+    }
+    known(Query::Number(wanted)).into_iter().next().map(|entry| entry.name)
+}
+
+// Name the server gives `number`, asking it now if need be, for calls
+// being placed
+pub async fn resolve(number: &str) -> Option<String> {
+    let wanted = contacts::digits(number);
+    let server = SERVER.read().unwrap().clone()?;
+    if wanted.is_empty() {
+        return None;
+    }
+    let query = Query::Number(wanted);
+    let entries = match cached(&query.key()) {
+        Some(result) => result.ok()?,
+        None => lookup(&server, &query).await.ok()?,
+    };
+    entries.into_iter().next().map(|entry| entry.name)
+}
+
+// Entries whose name contains the query, or whose number contains its
+// digits, for the suggestions under the phone number field
+pub fn suggestions(query: &str) -> Vec<DirectoryEntry> {
+    let query = query.trim();
+    let query_lower = query.to_lowercase();
+    let query_digits = contacts::digits(query);
+    let by_digits = query_digits.len() >= 3 && query_lower.chars().all(|c| !c.is_alphabetic());
+    if by_digits {
+        known(Query::Digits(query_digits))
+    } else if query_lower.chars().filter(|c| c.is_alphabetic()).count() >= 2 {
+        known(Query::Name(query_lower))
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "corporate-directory")]
+async fn search(server: &Server, query: &Query) -> Result<Vec<DirectoryEntry>, String> {
+    if server.is_ldap() {
+        ldap_search(server, query).await
+    } else {
+        carddav_address_book(server).await
+    }
+}
+
+#[cfg(not(feature = "corporate-directory"))]
+async fn search(_server: &Server, _query: &Query) -> Result<Vec<DirectoryEntry>, String> {
+    Err("this build has no corporate directory support".to_string())
+}
+
+// An LDAP filter value for these digits in this order, whatever is written
+// between them: "*5*5*5*1*2*3*4"
+#[cfg(feature = "corporate-directory")]
+fn digits_pattern(digits: &str) -> String {
+    digits.chars().map(|digit| format!("*{}", digit)).collect()
+}
+
+#[cfg(feature = "corporate-directory")]
+async fn ldap_search(server: &Server, query: &Query) -> Result<Vec<DirectoryEntry>, String> {
+    use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry, SearchOptions, SearchResult};
+
+    let has_number = format!("(|{})", PHONE_ATTRIBUTES.iter().map(|attribute| format!("({}=*)", attribute)).collect::<String>());
+    let filter = match query {
+        Query::Number(_) | Query::Digits(_) => {
+            let pattern = match query {
+                // The last few digits find the number however its country or area code is written
+                Query::Number(digits) => digits_pattern(&digits[digits.len().saturating_sub(7)..]),
+                Query::Digits(digits) => format!("{}*", digits_pattern(digits)),
+                Query::Name(_) => unreachable!(),
+            };
+            format!("(|{})", PHONE_ATTRIBUTES.iter().map(|attribute| format!("({}={})", attribute, pattern)).collect::<String>())
+        }
+        Query::Name(name) => {
+            let name = ldap_escape(name.as_str());
+            format!("(&(|(cn=*{0}*)(displayName=*{0}*)){1})", name, has_number)
+        }
+    };
+
+    let settings = LdapConnSettings::new().set_conn_timeout(LOOKUP_TIMEOUT);
+    let (connection, mut ldap) = LdapConnAsync::with_settings(settings, &server.url)
+        .await
+        .map_err(|e| format!("couldn't connect to {}: {}", server.url, e))?;
+    ldap3::drive!(connection);
+
+    if !server.user.is_empty() {
+        ldap.simple_bind(&server.user, &server.password)
+            .await
+            .and_then(|result| result.success())
+            .map_err(|e| format!("the LDAP server refused the login: {}", e))?;
+    }
+
+    let mut attributes = vec!["cn", "displayName"];
+    attributes.extend(PHONE_ATTRIBUTES);
+    let SearchResult(results, outcome) = ldap
+        .with_search_options(SearchOptions::new().sizelimit(MAX_RESULTS))
+        .search(&server.base, Scope::Subtree, &filter, attributes)
+        .await
+        .map_err(|e| format!("LDAP search failed: {}", e))?;
+    ldap.unbind().await.ok();
+    // 4 is "size limit exceeded": the first MAX_RESULTS entries still came back
+    if outcome.rc != 0 && outcome.rc != 4 {
+        return Err(format!("LDAP search failed: {} (result code {})", outcome.text, outcome.rc));
+    }
+
+    let mut entries = Vec::new();
+    for result in results {
+        let mut entry = SearchEntry::construct(result);
+        let name = ["displayName", "cn"]
+            .iter()
+            .find_map(|attribute| entry.attrs.get(*attribute).and_then(|values| values.first()).cloned());
+        let Some(name) = name else {
+            continue;
+        };
+        for attribute in PHONE_ATTRIBUTES {
+            for number in entry.attrs.remove(attribute).unwrap_or_default() {
+                entries.push(DirectoryEntry { name: name.clone(), number, group: None });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+// Every card with a phone number in the address book, from the cache while
+// it's fresh. Address books are small enough to read whole, and CardDAV
+// servers differ too much in how they match numbers to search them.
+#[cfg(feature = "corporate-directory")]
+async fn carddav_address_book(server: &Server) -> Result<Vec<DirectoryEntry>, String> {
+    use tracing::info;
+
+    const BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<C:addressbook-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:carddav">
+  <D:prop><C:address-data><C:prop name="FN"/><C:prop name="TEL"/></C:address-data></D:prop>
+  <C:filter><C:prop-filter name="TEL"/></C:filter>
+</C:addressbook-query>"#;
+
+    const KEY: &str = "address book";
+    if let Some(Ok(entries)) = cached(KEY) {
+        return Ok(entries);
+    }
+
+    let method = reqwest::Method::from_bytes(b"REPORT").map_err(|e| e.to_string())?;
+    crate::proxy::prepare(&server.url).await;
+    let mut request = crate::http_client()
+        .request(method, &server.url)
+        .header("Depth", "1")
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(BODY);
+    if !server.user.is_empty() {
+        request = request.basic_auth(&server.user, Some(&server.password));
+    }
+    let response = request.send().await.map_err(|e| format!("couldn't reach {}: {}", server.url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("the CardDAV server answered with HTTP status {}", response.status()));
+    }
+    let text = response.text().await.map_err(|e| e.to_string())?;
+
+    let document = roxmltree::Document::parse(&text).map_err(|e| format!("the CardDAV server's answer isn't valid XML: {}", e))?;
+    let entries: Vec<DirectoryEntry> = document
+        .descendants()
+        .filter(|node| node.has_tag_name(("urn:ietf:params:xml:ns:carddav", "address-data")))
+        .filter_map(|node| node.text())
+        .flat_map(parse_vcard)
+        .collect();
+    info!("Loaded {} numbers from the CardDAV address book", entries.len());
+
+    if SERVER.read().unwrap().as_ref() == Some(server) {
+        CACHE.lock().unwrap().get_or_insert_with(HashMap::new).insert(KEY.to_string(), (Instant::now(), Ok(entries.clone())));
+    }
+    Ok(entries)
+}
+
+// The name and phone numbers on a vCard
+#[cfg(feature = "corporate-directory")]
+fn parse_vcard(card: &str) -> Vec<DirectoryEntry> {
+    // Long lines are folded onto following lines that start with a space or tab
+    let unfolded = card.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    let mut name = None;
+    let mut numbers = Vec::new();
+    for line in unfolded.lines() {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Properties can have parameters ("TEL;TYPE=work") and a group ("item1.TEL")
+        let property = property.split(';').next().unwrap_or_default();
+        let property = property.rsplit('.').next().unwrap_or_default().to_uppercase();
+        match property.as_str() {
+            "FN" => name = Some(unescape(value.trim())),
+            "TEL" => numbers.push(value.trim().trim_start_matches("tel:").to_string()),
+            _ => {}
+        }
+    }
+
+    match name {
+        Some(name) if !name.is_empty() => numbers
+            .into_iter()
+            .filter(|number| !number.is_empty())
+            .map(|number| DirectoryEntry { name: name.clone(), number, group: None })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// vCard text values escape commas, semicolons, backslashes and newlines
+#[cfg(feature = "corporate-directory")]
+fn unescape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push(' '),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    text
+}
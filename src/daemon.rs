@@ -0,0 +1,162 @@
+// Headless mode (--daemon): only the instance socket and the tel: link handler,
+// no windows, so links work without the app being open. Meant to be started
+// by launchd at login; --install-daemon sets that up.
+
+use crate::config::{self, AppState};
+use crate::identity::identity;
+use crate::{config_watch, configure_notification_sinks, corporate_directory, directory, http_api, ipc, paths, retry, storage};
+use std::path::PathBuf;
+use tracing::info;
+
+// launchd label of the agent, also its plist's file name
+fn launch_agent_label() -> String {
+    format!("{}.daemon", identity().bundle_id)
+}
+
+// Serve tel: links until the process is stopped
+pub fn run(state: AppState) {
+    info!("Running headless; tel: links and the socket are served without a window");
+    apply_settings(&state);
+    ipc::listen(None);
+    config_watch::start(None);
+    retry::start();
+    storage::start();
+    run_event_loop();
+}
+
+// Put settings into effect, at start and when they're reloaded
+pub fn apply_settings(state: &AppState) {
+    config::set_current_settings(state);
+    configure_notification_sinks(state, None);
+    directory::set_source(&state.directory_url);
+    corporate_directory::set_server(corporate_directory::Server::from_state(state));
+    storage::set_limits(storage::limits(state));
+    http_api::configure(state, None);
+}
+
+// Read the saved settings again and put them into effect, unless they don't
+// make sense, e.g. a hand-edited file with a typo in the dial plan
+pub fn reload() -> Result<(), String> {
+    let state = config::read_preferences()?;
+    config::check(&state)?;
+    apply_settings(&state);
+    info!("Settings reloaded");
+    Ok(())
+}
+
+// Apple Events are only delivered while NSApplication's run loop is running
+#[cfg(target_os = "macos")]
+fn run_event_loop() {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+
+    crate::configure_apple_event_handler();
+    unsafe {
+        let cls = Class::get("NSApplication").unwrap();
+        let app: *mut Object = msg_send![cls, sharedApplication];
+        let _: () = msg_send![app, setActivationPolicy:2]; // NSApplicationActivationPolicyProhibited = 2
+        let _: () = msg_send![app, run];
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn run_event_loop() {
+    // Only the socket listener thread has work to do
+    loop {
+        std::thread::park();
+    }
+}
+
+fn launch_agent_file() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Couldn't find the home directory")?;
+    Ok(home.join("Library").join("LaunchAgents").join(format!("{}.plist", launch_agent_label())))
+}
+
+// launchd job that starts `executable` headless at login and restarts it if it crashes
+fn launch_agent_plist(executable: &str) -> String {
+    let log = paths::daemon_log_file();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{executable}</string>
+        <string>--daemon</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>ProcessType</key>
+    <string>Interactive</string>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = xml_escape(&launch_agent_label()),
+        executable = xml_escape(executable),
+        log = xml_escape(&log.display().to_string()),
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Write the launch agent for this executable and load it, replacing any
+// earlier one. Returns the plist's path.
+pub fn install_launch_agent() -> Result<PathBuf, String> {
+    let executable = std::env::current_exe()
+        .map_err(|e| format!("Couldn't find this executable: {}", e))?;
+    let path = launch_agent_file()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+    }
+
+    // An agent loaded from an older plist keeps running the old one until it's unloaded
+    launchctl(&["bootout", &launchd_domain(), &path.display().to_string()]).ok();
+    std::fs::write(&path, launch_agent_plist(&executable.display().to_string()))
+        .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+    launchctl(&["bootstrap", &launchd_domain(), &path.display().to_string()])?;
+    Ok(path)
+}
+
+// Stop the launch agent and remove its plist
+pub fn uninstall_launch_agent() -> Result<PathBuf, String> {
+    let path = launch_agent_file()?;
+    if !path.exists() {
+        return Err(format!("No launch agent at {}", path.display()));
+    }
+    launchctl(&["bootout", &launchd_domain(), &path.display().to_string()]).ok();
+    std::fs::remove_file(&path)
+        .map_err(|e| format!("Couldn't remove {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// The logged-in user's GUI session, where launch agents run
+fn launchd_domain() -> String {
+    format!("gui/{}", unsafe { libc::getuid() })
+}
+
+fn launchctl(args: &[&str]) -> Result<(), String> {
+    let output = std::process::Command::new("launchctl")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Couldn't run launchctl: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("launchctl {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
@@ -0,0 +1,308 @@
+// Tests for what the delegate decides on MAKE_CALL, PROCESS_TEL_URL and
+// APP_INITIALIZED: the plain functions its command handler acts on, with a
+// dialer that records calls instead of placing them. Also what the keypad's
+// keys do to the number.
+// Nothing here sends a request or touches the preferences on disk.
+
+use crate::audit::CallOrigin;
+use crate::backend::BackendKind;
+use crate::call::EMERGENCY_MESSAGE;
+use crate::config::AppState;
+use crate::ui::keypad::press_key;
+use crate::ui::{accept_tel_uri, offers_tel_handler, open_tel_link, start_call, CallStart, Dialer, PendingCall, TelLinkCall};
+use crate::{keep_alive_target, tel_uri};
+use druid::Data;
+use std::sync::{Arc, Mutex};
+
+// Records the calls it's given and reports each one as placed
+#[derive(Default)]
+struct MockDialer {
+    calls: Mutex<Vec<PendingCall>>,
+}
+
+impl Dialer for MockDialer {
+    fn dial(&self, call: PendingCall, on_result: Box<dyn FnOnce(String) + Send>) {
+        let number = call.phone_number.clone();
+        self.calls.lock().unwrap().push(call);
+        on_result(format!("Call to {} initiated", number));
+    }
+}
+
+impl MockDialer {
+    fn calls(&self) -> std::sync::MutexGuard<'_, Vec<PendingCall>> {
+        self.calls.lock().unwrap()
+    }
+}
+
+fn configured() -> AppState {
+    AppState {
+        domain: "pbx.example.com".to_string(),
+        extension: "201".to_string(),
+        key: "secret".to_string(),
+        phone_number: "5551234567".to_string(),
+        ..Default::default()
+    }
+}
+
+fn ignore_result() -> Box<dyn FnOnce(String) + Send> {
+    Box::new(|_| {})
+}
+
+fn make_call(data: &mut AppState, dialer: &MockDialer) -> CallStart {
+    start_call(data, CallOrigin::Dialer, dialer, ignore_result())
+}
+
+#[test]
+fn unconfigured_state_never_dials() {
+    let dialer = MockDialer::default();
+    for missing in ["domain", "extension", "phone number"] {
+        let mut data = configured();
+        match missing {
+            "domain" => data.domain.clear(),
+            "extension" => data.extension.clear(),
+            _ => data.phone_number.clear(),
+        }
+
+        assert!(matches!(make_call(&mut data, &dialer), CallStart::Rejected), "missing {}", missing);
+        assert_eq!(data.status_message, "Error: Missing domain, extension or phone number");
+    }
+    assert!(dialer.calls().is_empty());
+}
+
+#[test]
+fn configured_state_dials_with_its_settings() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    data.auto_answer = true;
+
+    assert!(matches!(make_call(&mut data, &dialer), CallStart::Dialing));
+    assert_eq!(data.status_message, "Initiating call to 5551234567...");
+
+    let calls = dialer.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].settings.domain, "pbx.example.com");
+    assert_eq!(calls[0].settings.extension, "201");
+    assert_eq!(calls[0].settings.key, "secret");
+    assert_eq!(calls[0].phone_number, "5551234567");
+    assert!(calls[0].settings.auto_answer());
+    assert!(matches!(calls[0].origin, CallOrigin::Dialer));
+    assert!(calls[0].note.is_none());
+    assert!(calls[0].route.is_none());
+}
+
+#[test]
+fn result_reaches_the_status_line_callback() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    let reported = Arc::new(Mutex::new(None));
+    let sink = reported.clone();
+
+    start_call(&mut data, CallOrigin::MenuBar, &dialer, Box::new(move |message| *sink.lock().unwrap() = Some(message)));
+    assert_eq!(reported.lock().unwrap().as_deref(), Some("Call to 5551234567 initiated"));
+}
+
+#[test]
+fn call_being_placed_can_be_cancelled_from_the_state() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    assert!(data.in_flight.is_none());
+
+    make_call(&mut data, &dialer);
+    let in_flight = data.in_flight.clone().expect("no call in flight");
+    let calls = dialer.calls();
+    assert!(in_flight.same(&calls[0].in_flight));
+    assert!(!calls[0].in_flight.is_cancelled());
+
+    in_flight.cancel();
+    assert!(calls[0].in_flight.is_cancelled());
+}
+
+#[test]
+fn rejected_call_has_nothing_to_cancel() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    data.extension.clear();
+
+    make_call(&mut data, &dialer);
+    assert!(data.in_flight.is_none());
+}
+
+#[test]
+fn dialed_number_is_offered_in_international_form() {
+    let dialer = MockDialer::default();
+    let mut data = AppState { country: "US".to_string(), phone_number: "(555) 123-4567".to_string(), ..configured() };
+
+    make_call(&mut data, &dialer);
+    assert_eq!(data.last_dialed, "+15551234567");
+
+    // Not after a private call, nor once a call is refused
+    data.private_call = true;
+    make_call(&mut data, &dialer);
+    assert!(data.last_dialed.is_empty());
+
+    make_call(&mut data, &dialer);
+    data.extension.clear();
+    make_call(&mut data, &dialer);
+    assert!(data.last_dialed.is_empty());
+}
+
+#[test]
+fn note_goes_with_one_call_only() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    data.call_note = "  Contract renewal ".to_string();
+
+    make_call(&mut data, &dialer);
+    make_call(&mut data, &dialer);
+
+    let calls = dialer.calls();
+    assert_eq!(calls[0].note.as_deref(), Some("Contract renewal"));
+    assert!(calls[1].note.is_none());
+    assert!(data.call_note.is_empty());
+}
+
+#[test]
+fn rejected_call_still_clears_the_note() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    data.domain.clear();
+    data.call_note = "Follow-up".to_string();
+
+    make_call(&mut data, &dialer);
+    assert!(data.call_note.is_empty());
+}
+
+#[test]
+fn emergency_numbers_are_refused() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    data.phone_number = "112".to_string();
+    data.call_note = "Accident".to_string();
+
+    match make_call(&mut data, &dialer) {
+        CallStart::Emergency { note } => assert_eq!(note.as_deref(), Some("Accident")),
+        _ => panic!("emergency number wasn't refused"),
+    }
+    assert_eq!(data.status_message, EMERGENCY_MESSAGE);
+    assert!(dialer.calls().is_empty());
+}
+
+#[test]
+fn picked_route_is_used_while_configured() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    data.routes = "8=Backup carrier, 9=International".to_string();
+    data.route = "8".to_string();
+    make_call(&mut data, &dialer);
+
+    // A prefix removed from the routes since it was picked is ignored
+    data.route = "7".to_string();
+    make_call(&mut data, &dialer);
+
+    let calls = dialer.calls();
+    let route = calls[0].route.as_ref().expect("route wasn't passed on");
+    assert_eq!(route.prefix, "8");
+    assert_eq!(route.label, "Backup carrier");
+    assert!(calls[1].route.is_none());
+}
+
+#[test]
+fn tel_link_is_ignored_without_settings() {
+    let dialer = MockDialer::default();
+    let mut data = AppState::default();
+
+    assert!(!accept_tel_uri(&mut data, tel_uri::parse("tel:+1-555-123-4567").unwrap()));
+    assert!(data.phone_number.is_empty());
+    assert!(data.status_message.is_empty());
+
+    // Even a MAKE_CALL arriving anyway doesn't reach the dialer
+    start_call(&mut data, CallOrigin::TelLink { sender_pid: None }, &dialer, ignore_result());
+    assert!(dialer.calls().is_empty());
+}
+
+#[test]
+fn tel_link_sets_the_number_and_dials_it() {
+    let dialer = MockDialer::default();
+    let mut data = configured();
+    data.phone_number.clear();
+
+    assert!(accept_tel_uri(&mut data, tel_uri::parse("tel:+1-555-123-4567;ext=22").unwrap()));
+    // The extension is dialed once the call connects
+    assert_eq!(data.phone_number, "+15551234567,22");
+    assert_eq!(data.status_message, "Processing tel: URL: +15551234567");
+
+    start_call(&mut data, CallOrigin::TelLink { sender_pid: None }, &dialer, ignore_result());
+    let calls = dialer.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].phone_number, "+15551234567,22");
+    assert!(matches!(calls[0].origin, CallOrigin::TelLink { .. }));
+}
+
+#[test]
+fn keep_alive_needs_settings_and_an_http_backend() {
+    // Unconfigured, nothing is warmed at startup
+    let mut data = AppState { keep_alive: true, ..Default::default() };
+    assert_eq!(keep_alive_target(&data), None);
+
+    data.domain = "pbx.example.com".to_string();
+    assert_eq!(keep_alive_target(&data).as_deref(), Some("pbx.example.com"));
+
+    data.backend = BackendKind::AsteriskAmi;
+    assert_eq!(keep_alive_target(&data), None);
+
+    data.backend = BackendKind::FusionPbx;
+    data.keep_alive = false;
+    assert_eq!(keep_alive_target(&data), None);
+}
+
+#[test]
+fn opened_tel_link_is_dialed_asked_about_or_refused() {
+    let mut data = configured();
+    assert_eq!(open_tel_link(&mut data, "tel:+1-555-123-4567"), TelLinkCall::Dial);
+    assert_eq!(data.phone_number, "+15551234567");
+
+    data.confirm_tel_links = true;
+    assert_eq!(open_tel_link(&mut data, "tel:+1-555-123-4567"), TelLinkCall::Confirm);
+
+    let mut data = AppState::default();
+    assert_eq!(open_tel_link(&mut data, "tel:+1-555-123-4567"), TelLinkCall::Ignored);
+    assert!(data.phone_number.is_empty());
+}
+
+#[test]
+fn unreadable_tel_link_says_why_in_the_status_line() {
+    let mut data = configured();
+    assert_eq!(open_tel_link(&mut data, "mailto:someone@example.com"), TelLinkCall::Unreadable);
+    assert!(data.status_message.starts_with("Error: "), "{}", data.status_message);
+    assert_eq!(data.phone_number, "5551234567");
+}
+
+#[test]
+fn launch_offers_the_tel_handler_once_from_the_primary_instance() {
+    let data = configured();
+    assert!(offers_tel_handler(&data, true, false));
+    assert!(!offers_tel_handler(&data, false, false));
+    // The setup asks itself
+    assert!(!offers_tel_handler(&data, true, true));
+    assert!(!offers_tel_handler(&AppState { handles_tel_links: true, ..configured() }, true, false));
+    assert!(!offers_tel_handler(&AppState { tel_handler_prompted: true, ..configured() }, true, false));
+    assert!(!offers_tel_handler(&AppState { kiosk: true, ..configured() }, true, false));
+}
+
+#[test]
+fn the_keypad_puts_a_number_together_with_pauses() {
+    let mut number = String::new();
+    // A pause needs something before it, and + only starts a number
+    for key in [',', '+', '4', '4', '+', '2', '0', ',', ',', '1', '0', '1', '#'] {
+        press_key(&mut number, key);
+    }
+    assert_eq!(number, "+4420,,101#");
+
+    press_key(&mut number, '⌫');
+    press_key(&mut number, '*');
+    assert_eq!(number, "+4420,,101*");
+    for _ in 0..20 {
+        press_key(&mut number, '⌫');
+    }
+    assert_eq!(number, "");
+}
@@ -0,0 +1,84 @@
+// User-editable rewrite rules applied to numbers just before they're sent to
+// the PBX, e.g. to strip a leading +61 and dial the trunk prefix instead
+
+use regex::Regex;
+
+pub struct Rule {
+    pub pattern: Regex,
+    // May refer to groups of the pattern as $1, $2, ...
+    pub replacement: String,
+    // Skip the rules below once this one matched
+    pub stop: bool,
+}
+
+// Read rules written one per line as `pattern => replacement`, with `stop`
+// after the replacement to end rewriting there, e.g. `^\+61 => 0 stop`.
+// Blank lines are skipped; the error names the first line that's wrong.
+pub fn parse(rules: &str) -> Result<Vec<Rule>, String> {
+    let mut parsed = Vec::new();
+    for (index, line) in rules.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (pattern, rest) = line
+            .split_once("=>")
+            .ok_or_else(|| format!("Rule on line {} has no =>", index + 1))?;
+        let rest = rest.trim();
+        // Numbers don't contain letters, so a trailing word "stop" is always the flag
+        let (replacement, stop) = match rest.strip_suffix("stop") {
+            Some(replacement) if replacement.is_empty() || replacement.ends_with(char::is_whitespace) => (replacement.trim_end(), true),
+            _ => (rest, false),
+        };
+        let pattern = Regex::new(pattern.trim())
+            .map_err(|e| format!("Rule on line {} has an invalid pattern: {}", index + 1, e))?;
+
+        parsed.push(Rule { pattern, replacement: replacement.to_string(), stop });
+    }
+    Ok(parsed)
+}
+
+// Run `number` through the rules in order; each matching rule replaces the
+// first match of its pattern
+pub fn rewrite(number: &str, rules: &[Rule]) -> String {
+    let mut number = number.to_string();
+    for rule in rules {
+        if !rule.pattern.is_match(&number) {
+            continue;
+        }
+        number = rule.pattern.replace(&number, rule.replacement.as_str()).into_owned();
+        if rule.stop {
+            break;
+        }
+    }
+    number
+}
+
+// Technical prefix the PBX picks a trunk by, e.g. 8 for the backup carrier
+#[derive(Clone)]
+pub struct Route {
+    pub prefix: String,
+    pub label: String,
+}
+
+// Read routes written as comma-separated `prefix=label` pairs, e.g.
+// "8=Backup carrier, 9=International"
+pub fn parse_routes(routes: &str) -> Result<Vec<Route>, String> {
+    let mut parsed = Vec::new();
+    for entry in routes.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (prefix, label) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Route \"{}\" isn't written as prefix=label", entry))?;
+        let prefix = prefix.trim();
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_digit() || c == '*' || c == '#') {
+            return Err(format!("Route prefix \"{}\" can only have digits, * and #", prefix));
+        }
+        let label = label.trim();
+        parsed.push(Route {
+            prefix: prefix.to_string(),
+            label: if label.is_empty() { prefix.to_string() } else { label.to_string() },
+        });
+    }
+    Ok(parsed)
+}
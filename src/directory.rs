@@ -0,0 +1,217 @@
+// The company's shared phone book (departments, branches, partner lines),
+// read-only and loaded from a URL or a file an administrator points every Mac
+// at. It's fetched again every hour and the last copy is kept on disk, so it
+// still works offline.
+
+use crate::config::AppState;
+use crate::{block_on, contacts, http_client, paths, proxy};
+use druid::ExtEventSink;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+// How often the directory is fetched again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Where the directory comes from: an http(s) URL, a file:// URL or a path
+static SOURCE: RwLock<String> = RwLock::new(String::new());
+// Entries as last loaded, None until the cache file has been read
+static ENTRIES: RwLock<Option<Vec<DirectoryEntry>>> = RwLock::new(None);
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+static REFRESHER_STARTED: AtomicBool = AtomicBool::new(false);
+
+// One number in the directory. `group` is a heading such as "Branches".
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub number: String,
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+// Read a directory: a JSON array of {"name", "number", "group"} objects, or
+// CSV lines of name,number and an optional group. A CSV header is skipped.
+pub fn parse(text: &str) -> Result<Vec<DirectoryEntry>, String> {
+    let text = text.trim_start_matches('\u{feff}').trim();
+    if text.starts_with('[') {
+        let entries: Vec<DirectoryEntry> = serde_json::from_str(text).map_err(|e| format!("not a valid JSON directory: {}", e))?;
+        return Ok(entries.into_iter().filter(|entry| !entry.number.trim().is_empty()).collect());
+    }
+
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim().trim_matches('"').trim()).collect();
+        if fields.len() < 2 || fields[0].is_empty() {
+            return Err(format!("line {} should be name,number[,group]", index + 1));
+        }
+        if !fields[1].chars().any(|c| c.is_ascii_digit()) {
+            if index == 0 {
+                continue;
+            }
+            return Err(format!("line {} has no number", index + 1));
+        }
+        entries.push(DirectoryEntry {
+            name: fields[0].to_string(),
+            number: fields[1].to_string(),
+            group: fields.get(2).filter(|group| !group.is_empty()).map(|group| group.to_string()),
+        });
+    }
+    Ok(entries)
+}
+
+// Whether `source` is somewhere a directory can be loaded from
+pub fn check_source(source: &str) -> Result<(), String> {
+    let source = source.trim();
+    let lower = source.to_lowercase();
+    if source.is_empty() || lower.starts_with("https://") || lower.starts_with("http://") || lower.starts_with("file://") || source.starts_with('/') {
+        Ok(())
+    } else {
+        Err("the shared directory should be an http(s) URL or a file path starting with /".to_string())
+    }
+}
+
+fn fetch(source: &str) -> Result<String, String> {
+    let lower = source.to_lowercase();
+    if lower.starts_with("https://") || lower.starts_with("http://") {
+        block_on(async {
+            proxy::prepare(source).await;
+            let response = http_client().get(source).send().await.map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("HTTP status {}", response.status()));
+            }
+            response.text().await.map_err(|e| e.to_string())
+        })
+    } else {
+        let path = source.strip_prefix("file://").unwrap_or(source);
+        std::fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path, e))
+    }
+}
+
+fn load_cache() -> Vec<DirectoryEntry> {
+    std::fs::read_to_string(paths::directory_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn store(entries: Vec<DirectoryEntry>) {
+    let path = paths::directory_file();
+    let json = serde_json::to_string(&entries).unwrap_or_default();
+    if let Err(e) = std::fs::create_dir_all(paths::config_dir()).and_then(|_| std::fs::write(&path, json)) {
+        warn!("Failed to save the shared directory to {}: {}", path.display(), e);
+    }
+
+    *ENTRIES.write().unwrap() = Some(entries);
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(|data: &mut AppState| data.directory_revision += 1);
+    }
+}
+
+// Fetch the directory again. A failed fetch keeps the last copy.
+pub fn refresh() {
+    let source = SOURCE.read().unwrap().trim().to_string();
+    if source.is_empty() {
+        return;
+    }
+    match fetch(&source).and_then(|text| parse(&text)) {
+        Ok(entries) => {
+            info!("Loaded {} numbers from the shared directory", entries.len());
+            store(entries);
+        }
+        Err(e) => warn!("Couldn't load the shared directory from {}: {}", source, e),
+    }
+}
+
+// Load the directory from `source`, e.g. after settings were saved; an empty
+// one removes it. Starts refreshing it hourly the first time.
+pub fn set_source(source: &str) {
+    let source = source.trim().to_string();
+    let changed = *SOURCE.read().unwrap() != source;
+    *SOURCE.write().unwrap() = source.clone();
+    if source.is_empty() {
+        // Don't leave a directory that was switched off in the cache
+        if !entries().is_empty() {
+            store(Vec::new());
+        }
+        return;
+    }
+
+    if !REFRESHER_STARTED.swap(true, Ordering::SeqCst) {
+        thread::spawn(|| loop {
+            refresh();
+            thread::sleep(REFRESH_INTERVAL);
+        });
+    } else if changed {
+        thread::spawn(refresh);
+    }
+}
+
+// Start telling the UI when the directory was reloaded
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+fn with_entries<T>(f: impl FnOnce(&[DirectoryEntry]) -> T) -> T {
+    // The copy from the last fetch, which may have been by another process
+    if ENTRIES.read().unwrap().is_none() {
+        let cached = load_cache();
+        ENTRIES.write().unwrap().get_or_insert(cached);
+    }
+    let entries = ENTRIES.read().unwrap();
+    f(entries.as_deref().unwrap_or(&[]))
+}
+
+// Every entry, in the directory's order
+pub fn entries() -> Vec<DirectoryEntry> {
+    with_entries(|entries| entries.to_vec())
+}
+
+// Name the directory gives `number`
+pub fn name_for(number: &str) -> Option<String> {
+    let wanted = contacts::digits(number);
+    if wanted.is_empty() {
+        return None;
+    }
+    with_entries(|entries| {
+        entries
+            .iter()
+            .find(|entry| contacts::same_number(&contacts::digits(&entry.number), &wanted))
+            .map(|entry| entry.name.clone())
+    })
+}
+
+// Entries whose name or group contains the query, or whose number contains
+// its digits, for the suggestions under the phone number field
+pub fn suggestions(query: &str) -> Vec<DirectoryEntry> {
+    let query = query.trim();
+    let query_lower = query.to_lowercase();
+    let query_digits = contacts::digits(query);
+    let by_digits = query_digits.len() >= 3 && query_lower.chars().all(|c| !c.is_alphabetic());
+    if !by_digits && query_lower.chars().filter(|c| c.is_alphabetic()).count() < 2 {
+        return Vec::new();
+    }
+
+    with_entries(|entries| {
+        entries
+            .iter()
+            .filter(|entry| {
+                if by_digits {
+                    let digits = contacts::digits(&entry.number);
+                    digits.contains(&query_digits) && digits != query_digits
+                } else {
+                    entry.name.to_lowercase().contains(&query_lower)
+                        || entry.group.as_ref().is_some_and(|group| group.to_lowercase().contains(&query_lower))
+                }
+            })
+            .take(contacts::MAX_SUGGESTIONS)
+            .cloned()
+            .collect()
+    })
+}
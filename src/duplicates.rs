@@ -0,0 +1,61 @@
+// Duplicate call suppression: a second request to call the same number soon
+// after the first is dropped, e.g. a double-clicked tel: link or a browser
+// that hands the link over twice. Retries aren't new requests and always go
+// through.
+
+use crate::config::AppState;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// Seconds a repeat is ignored for when the setting is empty
+const DEFAULT_WINDOW_SECS: u64 = 5;
+// Longer than this and it's no longer an accidental double request
+const MAX_WINDOW_SECS: u64 = 300;
+
+static RECENT: Mutex<RecentCalls> = Mutex::new(RecentCalls { calls: Vec::new() });
+
+// The window as typed in the settings: seconds, empty for 5 and 0 to let
+// every request through
+pub fn parse_window(window: &str) -> Result<Duration, String> {
+    let window = window.trim();
+    if window.is_empty() {
+        return Ok(Duration::from_secs(DEFAULT_WINDOW_SECS));
+    }
+    match window.parse::<u64>() {
+        Ok(secs) if secs <= MAX_WINDOW_SECS => Ok(Duration::from_secs(secs)),
+        _ => Err(format!("Repeat calls should be ignored for a whole number of seconds from 0 to {}, not \"{}\"", MAX_WINDOW_SECS, window)),
+    }
+}
+
+// The window from the settings, the default one if the setting can't be read
+pub fn window(state: &AppState) -> Duration {
+    parse_window(&state.duplicate_window).unwrap_or_else(|e| {
+        warn!("Ignoring repeat calls for {} seconds: {}", DEFAULT_WINDOW_SECS, e);
+        Duration::from_secs(DEFAULT_WINDOW_SECS)
+    })
+}
+
+// Numbers asked for lately, with when
+#[derive(Default)]
+pub struct RecentCalls {
+    calls: Vec<(String, Instant)>,
+}
+
+impl RecentCalls {
+    // Note a request to call `number`. True if it was asked for within
+    // `window` already, and so is a repeat.
+    pub fn repeat(&mut self, number: &str, window: Duration, now: Instant) -> bool {
+        self.calls.retain(|(_, at)| now.duration_since(*at) < window);
+        if self.calls.iter().any(|(recent, _)| recent == number) {
+            return true;
+        }
+        self.calls.push((number.to_string(), now));
+        false
+    }
+}
+
+// Whether a request to call `number` repeats one within `window`
+pub fn is_repeat(number: &str, window: Duration) -> bool {
+    !window.is_zero() && RECENT.lock().unwrap().repeat(number, window, Instant::now())
+}
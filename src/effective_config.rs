@@ -0,0 +1,80 @@
+use crate::config::{load_preferences, AutoAnswerOverride};
+use crate::{managed, paths};
+
+// Settings that are never shown in full
+const SECRET_FIELDS: &[&str] = &["key", "audit_webhook_secret", "corporate_directory_password", "client_certificate_passphrase", "http_auth_password", "event_socket_password", "http_api_token"];
+
+// One setting as the app actually uses it
+pub struct EffectiveValue {
+    pub name: String,
+    pub value: String,
+    // Where the value came from, e.g. "managed preferences"
+    pub source: &'static str,
+}
+
+// Every setting after managed preferences have been laid over the local
+// preferences file, with the layer that decided each value
+pub fn effective_config() -> Vec<EffectiveValue> {
+    let state = load_preferences();
+    let managed = managed::load_managed_settings();
+    let forced = managed.forced_fields();
+
+    // The raw file tells us which values the user actually set
+    let local: serde_json::Map<String, serde_json::Value> = std::fs::read_to_string(paths::preferences_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    let effective = match serde_json::to_value(&state) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return Vec::new(),
+    };
+
+    let mut values: Vec<EffectiveValue> = effective
+        .into_iter()
+        .map(|(name, value)| {
+            let source = if forced.contains(&name.as_str()) {
+                "managed preferences"
+            } else if managed.kiosk {
+                // Kiosk machines ignore the local file entirely
+                "default"
+            } else if local.contains_key(&name) {
+                "local settings"
+            } else if name == "key" && !state.key.is_empty() {
+                "Keychain"
+            } else {
+                "default"
+            };
+
+            EffectiveValue { value: display_value(&name, &value), name, source }
+        })
+        .collect();
+
+    // What's sent with each call can differ from the configured default
+    values.push(EffectiveValue {
+        name: "auto_answer (sent)".to_string(),
+        value: state.auto_answer().to_string(),
+        source: if state.auto_answer_override == AutoAnswerOverride::Default {
+            "auto_answer"
+        } else {
+            "this Mac's override"
+        },
+    });
+
+    values.push(EffectiveValue {
+        name: "kiosk".to_string(),
+        value: state.kiosk.to_string(),
+        source: if managed.kiosk { "managed preferences" } else { "default" },
+    });
+
+    values
+}
+
+fn display_value(name: &str, value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) if text.is_empty() => "(empty)".to_string(),
+        _ if SECRET_FIELDS.contains(&name) => "(set, hidden)".to_string(),
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
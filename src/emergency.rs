@@ -0,0 +1,42 @@
+// Numbers that reach emergency services on mobile networks almost everywhere,
+// and that many PBXs route as emergency calls too
+const UNIVERSAL: &[&str] = &["112", "911"];
+
+// National emergency numbers by ISO 3166 country code
+fn national_numbers(country: &str) -> &'static [&'static str] {
+    match country {
+        "US" | "CA" | "MX" => &["911"],
+        "GB" | "IE" => &["999", "112"],
+        "AU" => &["000", "106", "112"],
+        "NZ" => &["111"],
+        "DE" | "AT" => &["110", "112", "19222"],
+        "FR" => &["15", "17", "18", "114", "112"],
+        "IT" => &["113", "115", "118", "112"],
+        "ES" => &["091", "061", "080", "112"],
+        "NL" | "BE" | "SE" | "DK" | "FI" | "NO" | "PL" | "PT" => &["112"],
+        "CH" => &["117", "118", "144", "112"],
+        "LB" => &["112", "140", "175"],
+        "AE" => &["999", "998", "997"],
+        "IN" => &["100", "101", "102", "108", "112"],
+        "JP" => &["110", "119"],
+        "CN" => &["110", "119", "120"],
+        "BR" => &["190", "192", "193"],
+        "ZA" => &["10111", "10177", "112"],
+        _ => &[],
+    }
+}
+
+// Whether a dialed number is an emergency number in the given country.
+// Separators and a leading plus are ignored, so "9-1-1" and "+112" still match.
+pub fn is_emergency_number(number: &str, country: &str) -> bool {
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() {
+        return false;
+    }
+
+    let country = country.trim().to_uppercase();
+    UNIVERSAL
+        .iter()
+        .chain(national_numbers(&country))
+        .any(|emergency| *emergency == digits)
+}
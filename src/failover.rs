@@ -0,0 +1,58 @@
+// Failover: fallback domains kept with the profile, e.g. the second server of
+// an HA FusionPBX pair. A call the primary domain doesn't answer in time,
+// can't be reached on or fails with a 5xx goes to the next domain in order,
+// with the same backend and login, and the history says which server placed
+// it. Errors that would come back the same from every server, like a refused
+// key or a bad number, don't move on.
+
+use crate::backend::{self, CallBackend, CallError, CallRequest, Originated};
+use std::time::Duration;
+use tracing::warn;
+
+// More than this is a load balancer's job
+const MAX_FALLBACKS: usize = 5;
+
+// The fallback domains as typed in the settings, separated by commas or one a line
+pub fn parse_domains(text: &str) -> Result<Vec<String>, String> {
+    let domains: Vec<String> = text.split(|c: char| c == ',' || c.is_whitespace()).filter(|domain| !domain.is_empty()).map(str::to_string).collect();
+    if let Some(domain) = domains.iter().find(|domain| domain.contains(['?', '#', '@', '\\'])) {
+        return Err(format!("Fallback domain \"{}\" should be a domain like the primary one", domain));
+    }
+    if domains.len() > MAX_FALLBACKS {
+        return Err(format!("A profile can have {} fallback domains, not {}", MAX_FALLBACKS, domains.len()));
+    }
+    Ok(domains)
+}
+
+// Whether the server failed rather than the call, so another one may place it
+fn server_failed(error: &CallError) -> bool {
+    match error {
+        CallError::HttpStatus(status, _) => status.is_server_error(),
+        CallError::Request(e, _) => e.is_connect() || e.is_timeout(),
+        CallError::Unreachable(_) => true,
+        CallError::Unauthorized(_) | CallError::Refused(..) | CallError::Failed(_) | CallError::Cancelled => false,
+    }
+}
+
+// Place the call on the first of `servers` that takes it, each one given
+// `timeout` to answer. Returns which server was tried last and how it went.
+pub async fn originate(servers: &[(&str, Box<dyn CallBackend + '_>)], request: &CallRequest<'_>, timeout: Duration) -> (usize, Result<Originated, CallError>) {
+    let mut tried = 0;
+    loop {
+        let (domain, backend) = &servers[tried];
+        let result = tokio::time::timeout(timeout, backend.originate(request)).await;
+        let failed = match &result {
+            Ok(Ok(_)) => false,
+            Ok(Err(e)) => server_failed(e),
+            Err(_) => true,
+        };
+        let result = result.unwrap_or_else(|_| Err(backend::timed_out(timeout)));
+        match (&result, servers.get(tried + 1)) {
+            (Err(e), Some((next, _))) if failed => {
+                warn!("{} failed ({}); trying {}", domain, e, next);
+                tried += 1;
+            }
+            _ => return (tried, result),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Favorites: numbers dialed often enough to get a button of their own, in the
+// main window and the menu bar popover. They're kept with the settings, one
+// per line as `label = number`, e.g. "Front desk = 201".
+
+use crate::spotlight;
+use std::sync::Mutex;
+
+// Buttons beyond this many wouldn't fit the window
+pub const MAX_FAVORITES: usize = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Favorite {
+    pub label: String,
+    pub number: String,
+}
+
+// The favorites in Spotlight, so saving can tell which ones were deleted
+static INDEXED: Mutex<Vec<Favorite>> = Mutex::new(Vec::new());
+
+// Read favorites written one per line as `label = number`. A line with just a
+// number is labelled with it.
+pub fn parse(favorites: &str) -> Result<Vec<Favorite>, String> {
+    let mut parsed = Vec::new();
+    for line in favorites.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        // Labels may have = in them, numbers never do
+        let (label, number) = line.rsplit_once('=').unwrap_or(("", line));
+        let (label, number) = (label.trim(), number.trim());
+        if !number.chars().any(|c| c.is_ascii_digit())
+            || !number.chars().all(|c| c.is_ascii_digit() || " +-.()/*#".contains(c))
+        {
+            return Err(format!("Favorite \"{}\" isn't written as label = number", line));
+        }
+        parsed.push(Favorite {
+            label: if label.is_empty() { number.to_string() } else { label.to_string() },
+            number: number.to_string(),
+        });
+    }
+    if parsed.len() > MAX_FAVORITES {
+        return Err(format!("There's room for {} favorites, not {}", MAX_FAVORITES, parsed.len()));
+    }
+    Ok(parsed)
+}
+
+// Which of `saved` are new or relabelled, and which of `before` were deleted
+pub fn changes<'a>(before: &'a [Favorite], saved: &'a [Favorite]) -> (Vec<&'a Favorite>, Vec<&'a Favorite>) {
+    let added = saved.iter().filter(|favorite| !before.contains(favorite)).collect();
+    let deleted = before.iter().filter(|favorite| !saved.iter().any(|kept| kept.number == favorite.number)).collect();
+    (added, deleted)
+}
+
+// Put the favorites in effect into Spotlight: the saved ones are indexed, and
+// the deleted ones removed
+pub fn index(favorites: &str) {
+    let saved = parse(favorites).unwrap_or_default();
+    let mut indexed = INDEXED.lock().unwrap();
+    let (added, deleted) = changes(&indexed, &saved);
+    for favorite in deleted {
+        spotlight::remove_favorite(favorite);
+    }
+    for favorite in added {
+        spotlight::index_favorite(favorite);
+    }
+    *indexed = saved;
+}
@@ -0,0 +1,183 @@
+// Whether the PBX can be reached and accepts our login: checked on demand by
+// Test Connection, and every few minutes in the background if that's on,
+// with the result shown on the menu bar icon.
+
+use crate::backend::{self, BackendKind, CallError, HttpAuth};
+use crate::config::AppState;
+use crate::tls::Tls;
+use crate::{base_url, menu_bar, runtime, srv};
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::info;
+
+// How often the background check runs
+const INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// Longest a check may take before the PBX counts as unreachable
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+// PBX to check in the background, None while that's switched off
+static TARGET: Mutex<Option<Target>> = Mutex::new(None);
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+// What's needed to log in to the PBX, taken from the settings
+#[derive(Clone)]
+pub struct Target {
+    backend: BackendKind,
+    domain: String,
+    srv_lookup: bool,
+    username: String,
+    secret: String,
+    context: String,
+    tls: Tls,
+    http_request: String,
+    auth: HttpAuth,
+}
+
+impl Target {
+    pub fn from_state(state: &AppState) -> Self {
+        Target {
+            backend: state.backend,
+            domain: state.domain.clone(),
+            srv_lookup: state.srv_lookup,
+            username: state.backend_username.clone(),
+            secret: state.key.clone(),
+            context: state.backend_context.clone(),
+            tls: Tls::from_state(state),
+            http_request: state.http_request.clone(),
+            auth: HttpAuth::from_state(state),
+        }
+    }
+}
+
+// What a check found
+pub enum Health {
+    // Reached and logged in. `tls` is whether that was over HTTPS, with a valid certificate.
+    Healthy { tls: bool },
+    // No connection could be made: DNS, routing, firewall or the PBX is down
+    Unreachable(String),
+    // Reached, but the certificate isn't valid for the address or isn't trusted
+    BadCertificate(String),
+    // Reached, but the key, user or password was turned down
+    Rejected(String),
+    // Reached, but something else went wrong
+    Failed(String),
+}
+
+impl Health {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Health::Healthy { .. })
+    }
+
+    // One line for the status label, e.g. "pbx.example.com: reachable, certificate valid, login accepted"
+    pub fn summary(&self, domain: &str) -> String {
+        match self {
+            Health::Healthy { tls: true } => format!("{}: reachable, certificate valid, login accepted", domain),
+            Health::Healthy { tls: false } => format!("{}: reachable, login accepted (not encrypted)", domain),
+            Health::Unreachable(reason) => format!("{}: not reachable: {}", domain, reason),
+            Health::BadCertificate(reason) => format!("{}: reachable, but its certificate isn't valid: {}", domain, reason),
+            Health::Rejected(reason) => format!("{}: reachable, but the login was refused: {}", domain, reason),
+            Health::Failed(reason) => format!("{}: reachable, but the check failed: {}", domain, reason),
+        }
+    }
+}
+
+// Log in to `target`'s PBX without placing a call
+pub async fn check(target: &Target) -> Health {
+    if target.domain.trim().is_empty() {
+        return Health::Failed("no domain is set".to_string());
+    }
+    // The server calls go to first
+    let host = match target.srv_lookup {
+        true => srv::servers(&target.domain).await.swap_remove(0),
+        false => target.domain.clone(),
+    };
+    let backend = backend::backend_for(target.backend, backend::Connection {
+        host: &host,
+        username: &target.username,
+        secret: &target.secret,
+        context: &target.context,
+        tls: &target.tls,
+        http_request: &target.http_request,
+        auth: &target.auth,
+    });
+    let result = tokio::time::timeout(CHECK_TIMEOUT, backend.check())
+        .await
+        .unwrap_or_else(|_| Err(CallError::Unreachable(format!("no answer within {} seconds", CHECK_TIMEOUT.as_secs()))));
+
+    match result {
+        Ok(()) => Health::Healthy { tls: target.backend.uses_http() && base_url(&target.domain).starts_with("https://") },
+        Err(CallError::Unreachable(reason)) => Health::Unreachable(reason),
+        Err(CallError::Request(e, _)) => {
+            let reason = error_chain(&e);
+            if reason.to_lowercase().contains("certificate") {
+                Health::BadCertificate(reason)
+            } else if e.is_connect() || e.is_timeout() {
+                Health::Unreachable(reason)
+            } else {
+                Health::Failed(reason)
+            }
+        }
+        Err(CallError::HttpStatus(status, _)) if status.as_u16() == 401 || status.as_u16() == 403 => {
+            Health::Rejected(format!("HTTP status {}", status))
+        }
+        Err(CallError::Unauthorized(reason)) => Health::Rejected(reason),
+        Err(e) => Health::Failed(e.to_string()),
+    }
+}
+
+// reqwest's own message rarely says what went wrong; its causes do
+fn error_chain(error: &dyn Error) -> String {
+    let mut text = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        text.push_str(": ");
+        text.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    text
+}
+
+// Check this PBX in the background, or stop with None. A new target is
+// checked straight away.
+pub fn set_target(target: Option<Target>) {
+    *TARGET.lock().unwrap() = target.clone();
+
+    match target {
+        Some(target) => {
+            if !STARTED.swap(true, Ordering::SeqCst) {
+                runtime().spawn(check_loop());
+            }
+            runtime().spawn(async move { report(&target, check(&target).await) });
+        }
+        None => menu_bar::set_warning(None),
+    }
+}
+
+async fn check_loop() {
+    loop {
+        tokio::time::sleep(INTERVAL).await;
+
+        let target = TARGET.lock().unwrap().clone();
+        if let Some(target) = target {
+            let health = check(&target).await;
+            report(&target, health);
+        }
+    }
+}
+
+fn report(target: &Target, health: Health) {
+    // The settings may have changed while the check ran
+    if TARGET.lock().unwrap().as_ref().map(|current| &current.domain) != Some(&target.domain) {
+        return;
+    }
+    let summary = health.summary(&target.domain);
+    if health.is_healthy() {
+        menu_bar::set_warning(None);
+    } else {
+        info!("PBX health check: {}", summary);
+        menu_bar::set_warning(Some(summary));
+    }
+}
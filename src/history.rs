@@ -0,0 +1,336 @@
+// Calls made, kept in an SQLite database in the configuration folder. The
+// history used to be a JSON file; it's moved into the database the first
+// time it's opened, and JSON is still what the history is exported to and
+// imported from.
+
+use crate::config::AppState;
+use crate::paths;
+use chrono::{DateTime, Local, SecondsFormat};
+use druid::ExtEventSink;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+// Oldest entries are dropped beyond this
+const MAX_ENTRIES: usize = 500;
+
+// How long a write waits for another process (the CLI, a second instance)
+// that has the database locked
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Schema changes, applied in order. The database's user_version is how many
+// have been applied; new ones go at the end and existing ones never change.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE calls (
+        id INTEGER PRIMARY KEY,
+        time TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        number TEXT NOT NULL,
+        name TEXT,
+        succeeded INTEGER NOT NULL,
+        result TEXT NOT NULL,
+        http_status INTEGER,
+        channel TEXT NOT NULL,
+        color TEXT NOT NULL,
+        note TEXT,
+        transcript TEXT,
+        follow_up TEXT,
+        call_id TEXT
+    );
+    CREATE INDEX calls_by_timestamp ON calls (timestamp);
+    CREATE INDEX calls_by_number ON calls (number);",
+    "ALTER TABLE calls ADD COLUMN server TEXT;",
+    "ALTER TABLE calls ADD COLUMN state TEXT;",
+];
+
+// The open database, shared by request threads and the UI. Opened on first use.
+static DATABASE: Mutex<Option<Connection>> = Mutex::new(None);
+
+// Lets the UI know the history changed so views computed from it are redone
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+// One call attempt
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time: DateTime<Local>,
+    pub number: String,
+    // Contact name at the time of the call, when Contacts is enabled
+    #[serde(default)]
+    pub name: Option<String>,
+    pub succeeded: bool,
+    // Status line text, e.g. "Error: HTTP status 403 Forbidden"
+    pub result: String,
+    pub http_status: Option<u16>,
+    // How the call was requested, see CallOrigin::channel
+    pub channel: String,
+    // Color of the configuration the call went through
+    pub color: String,
+    // Reason typed into the confirmation prompt, if any
+    #[serde(default)]
+    pub note: Option<String>,
+    // Request and response of a failed call, when capturing them is switched on
+    #[serde(default)]
+    pub transcript: Option<String>,
+    // When a follow-up reminder for an unanswered call is due
+    #[serde(default)]
+    pub follow_up: Option<DateTime<Local>>,
+    // The PBX's id for a placed call, where it reports one
+    #[serde(default)]
+    pub call_id: Option<String>,
+    // The domain the call went to, when the profile has fallback domains
+    #[serde(default)]
+    pub server: Option<String>,
+    // How the call went after the PBX placed it, when calls are followed
+    #[serde(default)]
+    pub state: Option<String>,
+}
+
+impl HistoryEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(HistoryEntry {
+            time: parse_time(row.get("time")?)?,
+            number: row.get("number")?,
+            name: row.get("name")?,
+            succeeded: row.get("succeeded")?,
+            result: row.get("result")?,
+            http_status: row.get("http_status")?,
+            channel: row.get("channel")?,
+            color: row.get("color")?,
+            note: row.get("note")?,
+            transcript: row.get("transcript")?,
+            follow_up: row.get::<_, Option<String>>("follow_up")?.map(parse_time).transpose()?,
+            call_id: row.get("call_id")?,
+            server: row.get("server")?,
+            state: row.get("state")?,
+        })
+    }
+}
+
+// Times are kept as RFC 3339 text, to the nanosecond so they read back unchanged
+fn format_time(time: &DateTime<Local>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, false)
+}
+
+fn parse_time(text: String) -> rusqlite::Result<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(&text)
+        .map(|time| time.with_timezone(&Local))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+// Bring the database's tables up to date
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let applied: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", version + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn open() -> rusqlite::Result<Connection> {
+    std::fs::create_dir_all(paths::config_dir()).ok();
+    let mut connection = Connection::open(paths::history_database())?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    migrate(&mut connection)?;
+
+    // Calls from before the history moved to SQLite
+    let legacy = paths::history_file();
+    if legacy.exists() {
+        match read_json(&legacy) {
+            Ok(entries) => {
+                let imported = insert_new(&mut connection, &entries)?;
+                info!("Moved {} calls from {} into the history database", imported, legacy.display());
+                let mut moved = legacy.clone().into_os_string();
+                moved.push(".imported");
+                std::fs::rename(&legacy, moved).ok();
+            }
+            Err(e) => warn!("Couldn't move the old call history into the database: {}", e),
+        }
+    }
+    Ok(connection)
+}
+
+// Run `f` on the database, opening it first if need be. Errors are logged
+// and come back as None, so a broken database never stops a call.
+fn with_database<T>(f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> Option<T> {
+    let mut database = DATABASE.lock().unwrap();
+    if database.is_none() {
+        match open() {
+            Ok(connection) => *database = Some(connection),
+            Err(e) => {
+                warn!("Couldn't open the call history at {}: {}", paths::history_database().display(), e);
+                return None;
+            }
+        }
+    }
+    match f(database.as_mut()?) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("Call history query failed: {}", e);
+            None
+        }
+    }
+}
+
+fn changed() {
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(|data: &mut AppState| data.history_revision += 1);
+    }
+}
+
+// Add the entries that aren't in the history yet, a call being the same one
+// when it has the same time and number. Returns how many were added.
+fn insert_new(connection: &mut Connection, entries: &[HistoryEntry]) -> rusqlite::Result<usize> {
+    let transaction = connection.transaction()?;
+    let mut added = 0;
+    for entry in entries {
+        let time = format_time(&entry.time);
+        let known = transaction
+            .query_row("SELECT 1 FROM calls WHERE time = ?1 AND number = ?2", params![time, entry.number], |_| Ok(()))
+            .optional()?
+            .is_some();
+        if known {
+            continue;
+        }
+        transaction.execute(
+            "INSERT INTO calls (time, timestamp, number, name, succeeded, result, http_status, channel, color, note, transcript, follow_up, call_id, server, state)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![
+                time,
+                entry.time.timestamp_micros(),
+                entry.number,
+                entry.name,
+                entry.succeeded,
+                entry.result,
+                entry.http_status,
+                entry.channel,
+                entry.color,
+                entry.note,
+                entry.transcript,
+                entry.follow_up.as_ref().map(format_time),
+                entry.call_id,
+                entry.server,
+                entry.state,
+            ],
+        )?;
+        added += 1;
+    }
+    transaction.execute(
+        "DELETE FROM calls WHERE id NOT IN (SELECT id FROM calls ORDER BY timestamp DESC, id DESC LIMIT ?1)",
+        params![MAX_ENTRIES],
+    )?;
+    transaction.commit()?;
+    Ok(added)
+}
+
+// Every recorded call, newest first
+pub fn load() -> Vec<HistoryEntry> {
+    with_database(|connection| {
+        let mut statement = connection.prepare("SELECT * FROM calls ORDER BY timestamp DESC, id DESC")?;
+        let entries = statement.query_map([], HistoryEntry::from_row)?.collect();
+        entries
+    })
+    .unwrap_or_default()
+}
+
+// Add a call attempt to the top of the history
+pub fn record(entry: HistoryEntry) {
+    with_database(|connection| insert_new(connection, &[entry]));
+    changed();
+}
+
+// Note a follow-up reminder on the entry for the call made at `time`
+pub fn set_follow_up(time: DateTime<Local>, follow_up: DateTime<Local>) {
+    with_database(|connection| {
+        connection.execute(
+            "UPDATE calls SET follow_up = ?1 WHERE time = ?2",
+            params![format_time(&follow_up), format_time(&time)],
+        )
+    });
+    changed();
+}
+
+// Note how the call the PBX knows as `call_id` is going
+pub fn set_state(call_id: &str, state: &str) {
+    with_database(|connection| connection.execute("UPDATE calls SET state = ?1 WHERE call_id = ?2", params![state, call_id]));
+    changed();
+}
+
+// Forget the request details of calls made before `before` and give the space
+// back. Returns how many calls had them.
+pub fn clear_transcripts(before: DateTime<Local>) -> usize {
+    let cleared = with_database(|connection| {
+        let cleared = connection.execute(
+            "UPDATE calls SET transcript = NULL WHERE transcript IS NOT NULL AND timestamp < ?1",
+            params![before.timestamp_micros()],
+        )?;
+        if cleared > 0 {
+            connection.execute_batch("VACUUM")?;
+        }
+        Ok(cleared)
+    })
+    .unwrap_or_default();
+    if cleared > 0 {
+        changed();
+    }
+    cleared
+}
+
+// Start telling the UI about new entries
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+// Numbers called most recently, without repeats
+pub fn recent_numbers(limit: usize) -> Vec<String> {
+    with_database(|connection| {
+        let mut statement = connection.prepare("SELECT number FROM calls GROUP BY number ORDER BY MAX(timestamp) DESC LIMIT ?1")?;
+        let numbers = statement.query_map(params![limit], |row| row.get(0))?.collect();
+        numbers
+    })
+    .unwrap_or_default()
+}
+
+// The numbers dialed most often, with the latest contact name seen for each.
+// Ties go to the number called most recently.
+pub fn frequent_numbers(limit: usize) -> Vec<(String, Option<String>)> {
+    with_database(|connection| {
+        // With MAX(), SQLite takes the other bare columns from the row that
+        // has the maximum, so `name` is the newest call's
+        let mut statement = connection.prepare(
+            "SELECT number, name, MAX(timestamp) AS newest, COUNT(*) AS calls FROM calls GROUP BY number ORDER BY calls DESC, newest DESC LIMIT ?1",
+        )?;
+        let numbers = statement.query_map(params![limit], |row| Ok((row.get(0)?, row.get(1)?)))?.collect();
+        numbers
+    })
+    .unwrap_or_default()
+}
+
+fn read_json(path: &Path) -> Result<Vec<HistoryEntry>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| format!("{} isn't a call history: {}", path.display(), e))
+}
+
+// Write the whole history to `path` as JSON, newest first. Returns how many
+// calls were written.
+pub fn export_json(path: &Path) -> Result<usize, String> {
+    let entries = load();
+    let json = serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())?;
+    Ok(entries.len())
+}
+
+// Add the calls in an exported history that aren't in this one yet. Returns
+// how many were added.
+pub fn import_json(path: &Path) -> Result<usize, String> {
+    let entries = read_json(path)?;
+    let added = with_database(|connection| insert_new(connection, &entries))
+        .ok_or_else(|| "the call history database couldn't be written, see the log".to_string())?;
+    changed();
+    Ok(added)
+}
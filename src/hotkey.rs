@@ -0,0 +1,245 @@
+use druid::{ExtEventSink, Selector};
+use std::sync::{Mutex, OnceLock};
+
+// Sent to the delegate with the id of the hotkey that was pressed
+pub const HOTKEY_PRESSED: Selector<u32> = Selector::new("app.hotkey-pressed");
+
+// Carbon modifier flags (cmdKey, shiftKey, optionKey, controlKey)
+const CMD_KEY: u32 = 1 << 8;
+const SHIFT_KEY: u32 = 1 << 9;
+const OPTION_KEY: u32 = 1 << 11;
+const CONTROL_KEY: u32 = 1 << 12;
+
+// Where hotkey presses are forwarded, set once the app is running
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+// Registered hotkeys as (id, EventHotKeyRef) so they can be replaced later
+static REGISTERED: Mutex<Vec<(u32, usize)>> = Mutex::new(Vec::new());
+
+// A parsed hotkey: macOS virtual key code plus Carbon modifier flags
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub struct HotKey {
+    pub key_code: u32,
+    pub modifiers: u32,
+}
+
+// Parse a hotkey such as "ctrl+alt+cmd+L" or "cmd+shift+F5".
+// Returns None for an empty string or anything we don't recognise.
+pub fn parse_hotkey(spec: &str) -> Option<HotKey> {
+    let mut modifiers = 0;
+    let mut key_code = None;
+
+    for part in spec.split('+').map(|p| p.trim().to_lowercase()) {
+        match part.as_str() {
+            "cmd" | "command" => modifiers |= CMD_KEY,
+            "shift" => modifiers |= SHIFT_KEY,
+            "alt" | "opt" | "option" => modifiers |= OPTION_KEY,
+            "ctrl" | "control" => modifiers |= CONTROL_KEY,
+            key => {
+                // Only one non-modifier key is allowed
+                if key_code.is_some() {
+                    return None;
+                }
+                key_code = Some(virtual_key_code(key)?);
+            }
+        }
+    }
+
+    // A global hotkey without modifiers would swallow normal typing
+    if modifiers == 0 {
+        return None;
+    }
+
+    key_code.map(|key_code| HotKey { key_code, modifiers })
+}
+
+// macOS virtual key codes (kVK_ANSI_*, kVK_F*) for the keys we accept
+fn virtual_key_code(key: &str) -> Option<u32> {
+    let code = match key {
+        "a" => 0x00, "s" => 0x01, "d" => 0x02, "f" => 0x03, "h" => 0x04,
+        "g" => 0x05, "z" => 0x06, "x" => 0x07, "c" => 0x08, "v" => 0x09,
+        "b" => 0x0B, "q" => 0x0C, "w" => 0x0D, "e" => 0x0E, "r" => 0x0F,
+        "y" => 0x10, "t" => 0x11, "1" => 0x12, "2" => 0x13, "3" => 0x14,
+        "4" => 0x15, "6" => 0x16, "5" => 0x17, "9" => 0x19, "7" => 0x1A,
+        "8" => 0x1C, "0" => 0x1D, "o" => 0x1F, "u" => 0x20, "i" => 0x22,
+        "p" => 0x23, "l" => 0x25, "j" => 0x26, "k" => 0x28, "n" => 0x2D,
+        "m" => 0x2E, "space" => 0x31,
+        "f1" => 0x7A, "f2" => 0x78, "f3" => 0x63, "f4" => 0x76,
+        "f5" => 0x60, "f6" => 0x61, "f7" => 0x62, "f8" => 0x64,
+        "f9" => 0x65, "f10" => 0x6D, "f11" => 0x67, "f12" => 0x6F,
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[cfg(target_os = "macos")]
+mod carbon {
+    use std::ffi::c_void;
+
+    pub const EVENT_CLASS_KEYBOARD: u32 = 0x6B657962; // 'keyb'
+    pub const EVENT_HOT_KEY_PRESSED: u32 = 5;
+    pub const PARAM_DIRECT_OBJECT: u32 = 0x2D2D2D2D; // '----'
+    pub const TYPE_EVENT_HOT_KEY_ID: u32 = 0x686B6964; // 'hkid'
+    pub const SIGNATURE: u32 = 0x4354434C; // 'CTCL'
+
+    #[repr(C)]
+    pub struct EventTypeSpec {
+        pub event_class: u32,
+        pub event_kind: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct EventHotKeyID {
+        pub signature: u32,
+        pub id: u32,
+    }
+
+    pub type EventHandlerUPP = extern "C" fn(*mut c_void, *mut c_void, *mut c_void) -> i32;
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        pub fn GetApplicationEventTarget() -> *mut c_void;
+        pub fn InstallEventHandler(
+            target: *mut c_void,
+            handler: EventHandlerUPP,
+            num_types: libc::c_ulong,
+            list: *const EventTypeSpec,
+            user_data: *mut c_void,
+            out_ref: *mut *mut c_void,
+        ) -> i32;
+        pub fn RegisterEventHotKey(
+            key_code: u32,
+            modifiers: u32,
+            id: EventHotKeyID,
+            target: *mut c_void,
+            options: u32,
+            out_ref: *mut *mut c_void,
+        ) -> i32;
+        pub fn UnregisterEventHotKey(hot_key: *mut c_void) -> i32;
+        pub fn GetEventParameter(
+            event: *mut c_void,
+            name: u32,
+            desired_type: u32,
+            actual_type: *mut u32,
+            buffer_size: libc::c_ulong,
+            actual_size: *mut libc::c_ulong,
+            data: *mut c_void,
+        ) -> i32;
+    }
+}
+
+// Install the Carbon hotkey handler; presses are forwarded to the delegate
+// as HOTKEY_PRESSED commands. Safe to call more than once.
+#[cfg(target_os = "macos")]
+pub fn install(sink: ExtEventSink) {
+    use druid::Target;
+    use std::ffi::c_void;
+
+    extern "C" fn handle_hotkey(_call: *mut c_void, event: *mut c_void, _user_data: *mut c_void) -> i32 {
+        let mut hotkey_id = carbon::EventHotKeyID::default();
+        let status = unsafe {
+            carbon::GetEventParameter(
+                event,
+                carbon::PARAM_DIRECT_OBJECT,
+                carbon::TYPE_EVENT_HOT_KEY_ID,
+                std::ptr::null_mut(),
+                std::mem::size_of::<carbon::EventHotKeyID>() as libc::c_ulong,
+                std::ptr::null_mut(),
+                &mut hotkey_id as *mut _ as *mut c_void,
+            )
+        };
+
+        if status == 0 && hotkey_id.signature == carbon::SIGNATURE {
+            if let Some(sink) = EVENT_SINK.get() {
+                sink.submit_command(HOTKEY_PRESSED, hotkey_id.id, Target::Auto).ok();
+            }
+        }
+        0
+    }
+
+    if EVENT_SINK.set(sink).is_err() {
+        // Handler is already installed
+        return;
+    }
+
+    let event_type = carbon::EventTypeSpec {
+        event_class: carbon::EVENT_CLASS_KEYBOARD,
+        event_kind: carbon::EVENT_HOT_KEY_PRESSED,
+    };
+
+    unsafe {
+        carbon::InstallEventHandler(
+            carbon::GetApplicationEventTarget(),
+            handle_hotkey,
+            1,
+            &event_type,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        );
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn install(sink: ExtEventSink) {
+    // Global hotkeys are only implemented on macOS
+    EVENT_SINK.set(sink).ok();
+}
+
+// Register (or replace) the hotkey with the given id.
+// An empty spec just removes any existing registration.
+// Returns false if the spec couldn't be parsed or registered.
+#[cfg(target_os = "macos")]
+pub fn register(id: u32, spec: &str) -> bool {
+    use std::ffi::c_void;
+    use tracing::warn;
+
+    let mut registered = REGISTERED.lock().unwrap();
+
+    // Drop the previous registration for this id
+    if let Some(pos) = registered.iter().position(|(existing, _)| *existing == id) {
+        let (_, hotkey_ref) = registered.remove(pos);
+        unsafe {
+            carbon::UnregisterEventHotKey(hotkey_ref as *mut c_void);
+        }
+    }
+
+    if spec.trim().is_empty() {
+        return true;
+    }
+
+    let hotkey = match parse_hotkey(spec) {
+        Some(hotkey) => hotkey,
+        None => {
+            warn!("Invalid hotkey: {}", spec);
+            return false;
+        }
+    };
+
+    let mut hotkey_ref: *mut c_void = std::ptr::null_mut();
+    let status = unsafe {
+        carbon::RegisterEventHotKey(
+            hotkey.key_code,
+            hotkey.modifiers,
+            carbon::EventHotKeyID { signature: carbon::SIGNATURE, id },
+            carbon::GetApplicationEventTarget(),
+            0,
+            &mut hotkey_ref,
+        )
+    };
+
+    if status != 0 {
+        warn!("Failed to register hotkey {}: OSStatus {}", spec, status);
+        return false;
+    }
+
+    registered.push((id, hotkey_ref as usize));
+    true
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn register(id: u32, spec: &str) -> bool {
+    // Keep the bookkeeping so behaviour matches macOS, but nothing is grabbed
+    let mut registered = REGISTERED.lock().unwrap();
+    registered.retain(|(existing, _)| *existing != id);
+    spec.trim().is_empty() || parse_hotkey(spec).is_some()
+}
@@ -0,0 +1,288 @@
+// The local HTTP API: calls and the call history over HTTP on 127.0.0.1, for
+// browser extensions and web CRMs that can't open the instance socket and
+// shouldn't depend on tel: links. Calls take the same way in as the socket's.
+// It's off until a port is set, and every request needs the token from the
+// settings as `Authorization: Bearer <token>`, so a web page that guesses the
+// port still can't place calls.
+//
+//   POST /call     {"number": "+15551234567", "private": false}
+//   GET  /history  ?limit=20
+//   GET  /ping
+
+use crate::audit::CallOrigin;
+use crate::config::AppState;
+use crate::history;
+use crate::ipc::{self, Command, Reply};
+use druid::ExtEventSink;
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+// Limits for one request; the API only ever takes a number
+const MAX_HEADER_SIZE: u64 = 16 * 1024;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+// A client that stops sending mid-request only ties up its own thread for this long
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// Calls returned by GET /history unless ?limit= says otherwise
+const HISTORY_LIMIT: usize = 50;
+
+// The token requests have to carry, as last put into effect
+static TOKEN: Mutex<String> = Mutex::new(String::new());
+// The port being listened on, if any
+static LISTENING: Mutex<Option<u16>> = Mutex::new(None);
+// Bumped to tell the listener to stop
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// The port to listen on for the settings, None when the API is off. A port
+// without a token is refused.
+pub fn parse_port(port: &str, token: &str) -> Result<Option<u16>, String> {
+    let port = port.trim();
+    if port.is_empty() {
+        return Ok(None);
+    }
+    let port = match port.parse::<u16>() {
+        Ok(port) if port >= 1024 => port,
+        _ => return Err(format!("Local API port should be a number from 1024 to 65535, not \"{}\"", port)),
+    };
+    if token.trim().is_empty() {
+        return Err("Local API needs a token, so other programs and web pages can't place calls".to_string());
+    }
+    Ok(Some(port))
+}
+
+// A token that's hard to guess, for New Token
+pub fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; 24];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut bytes))
+        .map_err(|e| format!("couldn't make a token: {}", e))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Listen on the port the settings ask for, or stop listening. Only the
+// primary instance serves the API. Safe to call whenever the settings change.
+pub fn configure(state: &AppState, event_sink: Option<ExtEventSink>) {
+    *TOKEN.lock().unwrap() = state.http_api_token.trim().to_string();
+    let port = match parse_port(&state.http_api_port, &state.http_api_token) {
+        Ok(port) if ipc::is_primary() => port,
+        Ok(_) => None,
+        Err(message) => {
+            warn!("Not serving the local API: {}", message);
+            None
+        }
+    };
+
+    let mut listening = LISTENING.lock().unwrap();
+    if *listening == port {
+        return;
+    }
+    if let Some(old) = listening.take() {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        // Wake the listener so it sees it's been stopped
+        TcpStream::connect((Ipv4Addr::LOCALHOST, old)).ok();
+        info!("Stopped the local API on port {}", old);
+    }
+    let Some(port) = port else {
+        return;
+    };
+    let listener = match TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port))) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Couldn't serve the local API on port {}: {}", port, e);
+            return;
+        }
+    };
+    info!("Serving the local API on http://127.0.0.1:{}", port);
+    *listening = Some(port);
+    let generation = GENERATION.load(Ordering::SeqCst);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if GENERATION.load(Ordering::SeqCst) != generation {
+                break;
+            }
+            match stream {
+                Ok(stream) => {
+                    let event_sink = event_sink.clone();
+                    thread::spawn(move || handle_connection(stream, &event_sink));
+                }
+                Err(e) => warn!("Failed to accept a local API connection: {}", e),
+            }
+        }
+    });
+}
+
+// One request, as far as the API cares
+pub struct HttpRequest {
+    pub method: String,
+    // Without the query
+    pub path: String,
+    pub query: String,
+    pub authorization: Option<String>,
+    pub body: Vec<u8>,
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    // JSON, or empty
+    pub body: String,
+}
+
+impl HttpResponse {
+    fn json(status: u16, body: &impl serde::Serialize) -> Self {
+        HttpResponse { status, body: serde_json::to_string(body).unwrap_or_default() }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        HttpResponse::json(status, &Reply::Error { message: message.to_string() })
+    }
+}
+
+fn handle_connection(stream: TcpStream, event_sink: &Option<ExtEventSink>) {
+    stream.set_read_timeout(Some(READ_TIMEOUT)).ok();
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            warn!("Failed to set up a local API connection: {}", e);
+            return;
+        }
+    };
+    let response = match read_request(&mut BufReader::new(stream)) {
+        Ok(request) => respond(&request, &TOKEN.lock().unwrap().clone(), event_sink),
+        Err(message) => HttpResponse::error(400, &message),
+    };
+    // Clients that hung up don't need an answer
+    writer.write_all(&encode(&response)).ok();
+}
+
+// Read the request line, headers and body of one request
+pub fn read_request(reader: &mut impl BufRead) -> Result<HttpRequest, String> {
+    let mut head = reader.by_ref().take(MAX_HEADER_SIZE);
+    let mut line = String::new();
+    head.read_line(&mut line).map_err(|e| format!("couldn't read the request: {}", e))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("that isn't an HTTP request".to_string());
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = HttpRequest {
+        method: method.to_uppercase(),
+        path: path.trim_end_matches('/').to_string(),
+        query: query.to_string(),
+        authorization: None,
+        body: Vec::new(),
+    };
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header).map_err(|e| format!("couldn't read the request: {}", e))? == 0 {
+            return Err("the request's headers don't end".to_string());
+        }
+        let header = header.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_lowercase().as_str() {
+            "authorization" => request.authorization = Some(value.trim().to_string()),
+            "content-length" => length = value.trim().parse().map_err(|_| "Content-Length isn't a number".to_string())?,
+            _ => {}
+        }
+    }
+    if length > MAX_BODY_SIZE {
+        return Err(format!("the body is longer than {} bytes", MAX_BODY_SIZE));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).map_err(|e| format!("couldn't read the body: {}", e))?;
+    Ok(request)
+}
+
+#[derive(Deserialize)]
+struct CallBody {
+    number: String,
+    #[serde(default)]
+    private: bool,
+}
+
+// Carry out a request sent with `token` in effect
+pub fn respond(request: &HttpRequest, token: &str, event_sink: &Option<ExtEventSink>) -> HttpResponse {
+    // A browser asks first whether it may send the Authorization header
+    if request.method == "OPTIONS" {
+        return HttpResponse { status: 204, body: String::new() };
+    }
+    let presented = request.authorization.as_deref().and_then(|value| value.strip_prefix("Bearer ")).unwrap_or_default();
+    if token.is_empty() || !same_token(presented.trim(), token) {
+        return HttpResponse::error(401, "send the token from the settings as Authorization: Bearer <token>");
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/call") => {
+            let body: CallBody = match serde_json::from_slice(&request.body) {
+                Ok(body) => body,
+                Err(e) => return HttpResponse::error(400, &format!("the body should be {{\"number\": \"...\"}}: {}", e)),
+            };
+            let command = Command::Call { number: body.number, private: body.private };
+            match ipc::dispatch(command, CallOrigin::HttpApi, event_sink) {
+                Ok(reply) => HttpResponse::json(200, &reply),
+                Err(message) => HttpResponse::error(422, &message),
+            }
+        }
+        ("GET", "/history") => {
+            let limit = query_value(&request.query, "limit").and_then(|limit| limit.parse().ok()).unwrap_or(HISTORY_LIMIT);
+            let entries: Vec<_> = history::load()
+                .into_iter()
+                .take(limit)
+                // Transcripts are for bug reports, not for other programs
+                .map(|mut entry| {
+                    entry.transcript = None;
+                    entry
+                })
+                .collect();
+            HttpResponse::json(200, &entries)
+        }
+        ("GET", "/ping") => match ipc::dispatch(Command::Ping, CallOrigin::HttpApi, event_sink) {
+            Ok(reply) => HttpResponse::json(200, &reply),
+            Err(message) => HttpResponse::error(500, &message),
+        },
+        (_, "/call" | "/history" | "/ping") => HttpResponse::error(405, &format!("{} doesn't take {}", request.path, request.method)),
+        _ => HttpResponse::error(404, &format!("there's no {}; try POST /call, GET /history or GET /ping", request.path)),
+    }
+}
+
+fn query_value<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').filter_map(|pair| pair.split_once('=')).find(|(key, _)| *key == name).map(|(_, value)| value)
+}
+
+// Compared in full, so how long it takes says nothing about the token
+fn same_token(presented: &str, token: &str) -> bool {
+    presented.len() == token.len() && presented.bytes().zip(token.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+fn encode(response: &HttpResponse) -> Vec<u8> {
+    let reason = match response.status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    };
+    // Extensions and web CRMs call from their own origin; the token is what keeps others out
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Headers: Authorization, Content-Type\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.body.len(),
+        response.body
+    )
+    .into_bytes()
+}
@@ -0,0 +1,119 @@
+// Who this copy of the app is: its name, its bundle identifier, the name its
+// configuration, logs and socket go under, and a URL scheme of its own. Two
+// copies with different identities, e.g. one for the MSP's PBX and one for a
+// client's, run side by side without sharing settings, keys, sockets or
+// logs. The identity is set when building, with the CLICK_TO_CALL_*
+// environment variables below, and the bundle's Info.plist can change it
+// without a rebuild.
+
+use std::sync::OnceLock;
+
+const DEFAULT_NAME: &str = "Click-To-Call";
+const DEFAULT_BUNDLE_ID: &str = "com.click-to-call.app";
+const DEFAULT_SLUG: &str = "click-to-call";
+
+// Info.plist keys for what the standard bundle keys don't cover
+#[cfg(target_os = "macos")]
+const SLUG_KEY: &str = "ClickToCallSlug";
+#[cfg(target_os = "macos")]
+const URL_SCHEME_KEY: &str = "ClickToCallURLScheme";
+
+pub struct Identity {
+    // Shown in window titles, e.g. "Click-To-Call"
+    pub name: String,
+    // Keychain items, the launch agent and managed preferences go by it
+    pub bundle_id: String,
+    // Folder name for the configuration and logs, and the socket's file name
+    pub slug: String,
+    // Links with this scheme dial like tel: links but only ever reach this
+    // copy, since every copy wants to handle tel:. Empty for none.
+    pub url_scheme: String,
+    // Where it came from, for Diagnostics
+    pub source: &'static str,
+}
+
+static IDENTITY: OnceLock<Identity> = OnceLock::new();
+
+// A build-time setting, or `default` when it isn't set
+fn built_in(value: Option<&'static str>, default: &str) -> String {
+    value.filter(|value| !value.trim().is_empty()).unwrap_or(default).trim().to_string()
+}
+
+fn from_build() -> Identity {
+    Identity {
+        name: built_in(option_env!("CLICK_TO_CALL_NAME"), DEFAULT_NAME),
+        bundle_id: built_in(option_env!("CLICK_TO_CALL_BUNDLE_ID"), DEFAULT_BUNDLE_ID),
+        slug: built_in(option_env!("CLICK_TO_CALL_SLUG"), DEFAULT_SLUG),
+        url_scheme: built_in(option_env!("CLICK_TO_CALL_URL_SCHEME"), "").to_lowercase(),
+        source: "build",
+    }
+}
+
+// A string from the running bundle's Info.plist; none for a bare binary
+#[cfg(target_os = "macos")]
+fn info_plist_string(key: &str) -> Option<String> {
+    use crate::macos::{ns_string, rust_string};
+    use objc::runtime::{Class, Object, BOOL, NO};
+    use objc::{msg_send, sel, sel_impl};
+
+    unsafe {
+        let bundle: *mut Object = msg_send![Class::get("NSBundle").unwrap(), mainBundle];
+        let value: *mut Object = msg_send![bundle, objectForInfoDictionaryKey: ns_string(key)];
+        if value.is_null() {
+            return None;
+        }
+        let is_string: BOOL = msg_send![value, isKindOfClass: Class::get("NSString").unwrap()];
+        if is_string == NO { None } else { rust_string(value) }
+    }
+    .filter(|value| !value.trim().is_empty())
+    .map(|value| value.trim().to_string())
+}
+
+// The build's identity, with what the bundle says instead. A bundle with an
+// identifier of its own and no name for its files gets its identifier as that
+// name, so renaming a copy is enough to keep it apart.
+#[cfg(target_os = "macos")]
+fn detect() -> Identity {
+    let mut identity = from_build();
+    let Some(bundle_id) = info_plist_string("CFBundleIdentifier") else {
+        return identity;
+    };
+    let slug = info_plist_string(SLUG_KEY);
+    if bundle_id != identity.bundle_id || slug.is_some() {
+        identity.slug = slug.unwrap_or_else(|| bundle_id.clone());
+        identity.bundle_id = bundle_id;
+        identity.source = "Info.plist";
+    }
+    if let Some(name) = info_plist_string("CFBundleDisplayName").or_else(|| info_plist_string("CFBundleName")) {
+        identity.name = name;
+    }
+    if let Some(scheme) = info_plist_string(URL_SCHEME_KEY) {
+        identity.url_scheme = scheme.to_lowercase();
+    }
+    identity
+}
+
+#[cfg(not(target_os = "macos"))]
+fn detect() -> Identity {
+    from_build()
+}
+
+pub fn identity() -> &'static Identity {
+    IDENTITY.get_or_init(detect)
+}
+
+// The tel: link for a link the app was opened with: tel: links as they are,
+// and links with this copy's own scheme as the tel: link they stand for.
+// None for anything else.
+pub fn tel_link(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once(':')?;
+    if scheme.eq_ignore_ascii_case("tel") {
+        return Some(url.to_string());
+    }
+    let own = &identity().url_scheme;
+    if !own.is_empty() && scheme.eq_ignore_ascii_case(own) {
+        // e.g. acme-call://+15551234567 as well as acme-call:+15551234567
+        return Some(format!("tel:{}", rest.trim_start_matches('/')));
+    }
+    None
+}
@@ -0,0 +1,479 @@
+// The instance socket: how a second copy of the app hands a tel: link to the
+// one already running, and how other tools on this Mac drive it. Messages are
+// JSON objects, one per line, with a type, the protocol version and a payload,
+// and every message is answered the same way. Lines that aren't JSON are read
+// as the plain "tel:..." lines earlier versions took, so scripts written for
+// those keep working.
+//
+// Only the user running the app may use the socket: it's kept in a folder
+// only they can open, is only readable and writable by them, and connections
+// from other users are turned away. The primary instance holds a lock file
+// while it runs, so two instances can never serve the socket at once.
+
+use crate::audit::{self, CallOrigin};
+use crate::call::{dial_tel_link, logged_number};
+use crate::config::current_settings;
+use crate::daemon;
+use crate::identity::identity;
+use crate::ui::{PROCESS_TEL_URL, RELOAD_SETTINGS};
+use crate::call_links::{self, CallLink};
+use crate::{get_socket_path, paths, tel_uri};
+use druid::{ExtEventSink, Target};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, DirBuilder, File, OpenOptions, Permissions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+// Version of the protocol, sent with every message. Requests for a newer
+// version are refused, so a client can tell it needs a newer app.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Upper bound for one message, generous enough for provisioning payloads
+const MAX_MESSAGE_SIZE: u64 = 1024 * 1024;
+// A client that stops sending mid-message only ties up its own thread for this long
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// How long a client waits for the running instance to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The primary instance's lock on the lock file. The system lets go of it when
+// the process ends, however it ends, so there's never a stale lock to clean up.
+static INSTANCE_LOCK: OnceLock<File> = OnceLock::new();
+
+// What a client asks the running instance to do
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum Command {
+    // Dial a number, or a tel: link, the way a clicked link is dialed
+    Call {
+        number: String,
+        #[serde(default)]
+        private: bool,
+    },
+    // Check the instance is alive
+    Ping,
+    // Read the saved settings again
+    ReloadConfig,
+    // Quit the instance
+    Shutdown,
+}
+
+// The running instance's answer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum Reply {
+    // The command was taken. For a call, whether it went through shows in
+    // notifications and the call history.
+    Ok,
+    // The answer to a ping
+    Pong { pid: u32, app: String, version: String },
+    Error { message: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+    // Anything the client likes, sent back with the reply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub reply: Reply,
+}
+
+// Why a request got no answer
+#[derive(Debug)]
+pub enum RequestError {
+    // Nothing is listening on the socket
+    NotRunning,
+    // The request may have been delivered, but no answer came back
+    NoReply(String),
+}
+
+// Send `command` to the running instance and wait for its answer
+pub fn request(socket_path: &Path, command: Command) -> Result<Reply, RequestError> {
+    let mut stream = UnixStream::connect(socket_path).map_err(|_| RequestError::NotRunning)?;
+    let request = Request { version: PROTOCOL_VERSION, id: None, command };
+    let mut line = serde_json::to_string(&request).map_err(|e| RequestError::NoReply(e.to_string()))?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).map_err(|_| RequestError::NotRunning)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT)).ok();
+
+    let mut answer = String::new();
+    BufReader::new(stream.take(MAX_MESSAGE_SIZE))
+        .read_line(&mut answer)
+        .map_err(|e| RequestError::NoReply(e.to_string()))?;
+    match serde_json::from_str::<Response>(&answer) {
+        Ok(response) => Ok(response.reply),
+        // An instance from before the protocol answers "ok" or "error: ..."
+        Err(_) if answer.trim_end() == "ok" => Ok(Reply::Ok),
+        Err(e) => Err(RequestError::NoReply(format!("unexpected answer {:?}: {}", answer.trim_end(), e))),
+    }
+}
+
+// Hand a tel: link's number to the running instance. True when it took the
+// call, or may have: once the request is out, dialing here as well could
+// call the number twice.
+pub fn hand_off(socket_path: &Path, number: &str, private: bool) -> bool {
+    match request(socket_path, Command::Call { number: number.to_string(), private }) {
+        Ok(Reply::Error { message }) => {
+            warn!("The running instance didn't take the call: {}", message);
+            false
+        }
+        Ok(_) => true,
+        Err(RequestError::NotRunning) => false,
+        Err(RequestError::NoReply(reason)) => {
+            warn!("No answer from the running instance, leaving the call to it: {}", reason);
+            true
+        }
+    }
+}
+
+// The folder for the socket and the lock file, made if need be. One that
+// another user owns is refused, and one others could open is closed to them.
+fn prepare_socket_dir() -> Result<(), String> {
+    let dir = paths::socket_dir();
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(format!("couldn't create {}: {}", dir.display(), e)),
+    }
+    let metadata = fs::symlink_metadata(&dir).map_err(|e| format!("couldn't check {}: {}", dir.display(), e))?;
+    if !metadata.is_dir() {
+        return Err(format!("{} isn't a folder", dir.display()));
+    }
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(format!("{} belongs to another user", dir.display()));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(&dir, Permissions::from_mode(0o700)).map_err(|e| format!("couldn't make {} private: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+// Become the primary instance, the one serving the socket, unless another
+// process already is. Only the first call in a process can succeed.
+pub fn claim_primary() -> bool {
+    let lock_path = paths::lock_file();
+    let file = match prepare_socket_dir().and_then(|_| {
+        OpenOptions::new().write(true).create(true).truncate(false).mode(0o600).open(&lock_path).map_err(|e| format!("couldn't open {}: {}", lock_path.display(), e))
+    }) {
+        Ok(file) => file,
+        Err(reason) => {
+            warn!("Not serving the instance socket: {}", reason);
+            return false;
+        }
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return false;
+    }
+    // Whoever looks can tell which process it is
+    file.set_len(0).ok();
+    writeln!(&file, "{}", std::process::id()).ok();
+    INSTANCE_LOCK.set(file).is_ok()
+}
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    extern "C" {
+        // Part of libSystem
+        pub fn getpeereid(socket: libc::c_int, uid: *mut libc::uid_t, gid: *mut libc::gid_t) -> libc::c_int;
+    }
+}
+
+// The user id of the process on the other end of a Unix socket
+#[cfg(target_os = "macos")]
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    let result = unsafe { ffi::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if result == 0 { Some(uid) } else { None }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result == 0 { Some(credentials.uid) } else { None }
+}
+
+// Whether this process is the primary instance
+pub fn is_primary() -> bool {
+    INSTANCE_LOCK.get().is_some()
+}
+
+// Have the primary instance read the saved settings again, after they were
+// changed somewhere else. Nothing to do when no instance is running.
+pub fn reload_primary() -> Result<(), String> {
+    match request(&get_socket_path(), Command::ReloadConfig) {
+        Ok(Reply::Error { message }) => Err(format!("the running app didn't reload its settings: {}", message)),
+        Ok(_) | Err(RequestError::NotRunning) => Ok(()),
+        Err(RequestError::NoReply(reason)) => Err(format!("the running app didn't answer when asked to reload its settings: {}", reason)),
+    }
+}
+
+// Accept connections on the instance socket, each on its own thread so a slow
+// client can't hold up the others. Headless instances have no event sink.
+// Only the primary instance listens.
+pub fn listen(event_sink: Option<ExtEventSink>) {
+    if INSTANCE_LOCK.get().is_none() {
+        warn!("Not listening on the instance socket without holding {}", paths::lock_file().display());
+        return;
+    }
+    thread::spawn(move || {
+        let socket_path = get_socket_path();
+
+        // Holding the lock, a socket file left behind can only be from an
+        // instance that has gone
+        match fs::remove_file(&socket_path) {
+            Ok(()) => info!("Removed the socket left behind by an earlier instance"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Couldn't remove the old socket {:?}: {}", socket_path, e),
+        }
+        let listener = match UnixListener::bind(&socket_path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("Failed to listen on {:?}: {}", socket_path, e);
+                return;
+            }
+        };
+        if let Err(e) = fs::set_permissions(&socket_path, Permissions::from_mode(0o600)) {
+            warn!("Couldn't make {:?} private: {}", socket_path, e);
+        }
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let event_sink = event_sink.clone();
+                    thread::spawn(move || handle_connection(stream, &event_sink));
+                }
+                Err(e) => warn!("Failed to accept socket connection: {}", e),
+            }
+        }
+    });
+}
+
+// Read newline-terminated messages until the client closes the connection.
+// The last message doesn't need a newline. Every message gets a one-line reply,
+// so clients can tell when a request was rejected.
+fn handle_connection(stream: UnixStream, event_sink: &Option<ExtEventSink>) {
+    stream.set_read_timeout(Some(READ_TIMEOUT)).ok();
+    let origin = CallOrigin::Socket { peer_pid: audit::peer_pid(&stream) };
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            warn!("Failed to set up socket connection: {}", e);
+            return;
+        }
+    };
+    // The folder already keeps others out; this is in case it's been opened up
+    let uid = peer_uid(&stream);
+    if uid != Some(unsafe { libc::geteuid() }) {
+        warn!("Refusing a socket connection from user {:?}, process {:?}", uid, origin.pid());
+        writer.write_all(error_line("only the user running the app may use its socket").as_bytes()).ok();
+        return;
+    }
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut line = Vec::new();
+        let answer = match reader.by_ref().take(MAX_MESSAGE_SIZE).read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.last() != Some(&b'\n') && line.len() as u64 == MAX_MESSAGE_SIZE => {
+                // The rest of the oversized message can't be told apart from the next one
+                warn!("Socket message larger than {} bytes, closing connection", MAX_MESSAGE_SIZE);
+                writer.write_all(error_line(&format!("message longer than {} bytes", MAX_MESSAGE_SIZE)).as_bytes()).ok();
+                break;
+            }
+            Ok(_) => match String::from_utf8(line) {
+                Ok(message) => handle_message(message.trim_end_matches(['\r', '\n']), origin, event_sink),
+                Err(e) => {
+                    // Log what we can make of it, but don't guess at a number from mangled text
+                    let lossy = String::from_utf8_lossy(e.as_bytes());
+                    warn!("Socket message is not valid UTF-8: {:?}", lossy.trim_end());
+                    Answer { line: error_line("message is not valid UTF-8"), shutdown: false }
+                }
+            },
+            Err(e) => {
+                warn!("Socket read failed: {}", e);
+                break;
+            }
+        };
+
+        // Clients that don't wait for the reply have usually hung up already,
+        // so failing to send it is expected and not worth logging
+        writer.write_all(answer.line.as_bytes()).ok();
+        if answer.shutdown {
+            shut_down(event_sink);
+            break;
+        }
+    }
+}
+
+// The reply to one message, and whether to quit once it's sent
+pub struct Answer {
+    pub line: String,
+    shutdown: bool,
+}
+
+fn error_line(message: &str) -> String {
+    response_line(None, Reply::Error { message: message.to_string() })
+}
+
+fn response_line(id: Option<serde_json::Value>, reply: Reply) -> String {
+    let response = Response { version: PROTOCOL_VERSION, id, reply };
+    // Only strings and numbers go in, which always serialize
+    format!("{}\n", serde_json::to_string(&response).unwrap_or_default())
+}
+
+// A request from the text of one message. Text that doesn't start like a
+// JSON object is an older client's plain line.
+pub fn parse_request(message: &str) -> Result<Request, String> {
+    serde_json::from_str::<Request>(message).map_err(|e| format!("couldn't read the request: {}", e)).and_then(|request| {
+        if request.version > PROTOCOL_VERSION {
+            Err(format!("protocol version {} isn't supported, this app speaks version {}", request.version, PROTOCOL_VERSION))
+        } else {
+            Ok(request)
+        }
+    })
+}
+
+pub fn handle_message(message: &str, origin: CallOrigin, event_sink: &Option<ExtEventSink>) -> Answer {
+    if !message.trim_start().starts_with('{') {
+        return handle_plain_message(message, origin, event_sink);
+    }
+    let request = match parse_request(message) {
+        Ok(request) => request,
+        Err(reason) => {
+            warn!("Ignoring socket message: {}", reason);
+            return Answer { line: error_line(&reason), shutdown: false };
+        }
+    };
+    let shutdown = request.command == Command::Shutdown;
+    let reply = dispatch(request.command, origin, event_sink).unwrap_or_else(|message| Reply::Error { message });
+    Answer { shutdown: shutdown && reply == Reply::Ok, line: response_line(request.id, reply) }
+}
+
+// What earlier versions took: a tel:, callto:, SIP or clicktocall: link, or
+// "ping-..." from another instance, answered with "ok" or "error: <reason>"
+fn handle_plain_message(message: &str, origin: CallOrigin, event_sink: &Option<ExtEventSink>) -> Answer {
+    let result = if message.starts_with("ping-") {
+        Ok(())
+    } else if tel_uri::parse(message).is_ok() || message.to_lowercase().starts_with("tel:") || call_links::read(message).is_some() {
+        call(message, false, origin, event_sink)
+    } else {
+        warn!("Unknown socket message: {:?}", message);
+        Err("unknown message, expected a tel:, callto:, SIP or clicktocall: link".to_string())
+    };
+    let line = match result {
+        Ok(()) => "ok\n".to_string(),
+        Err(reason) => format!("error: {}\n", reason),
+    };
+    Answer { line, shutdown: false }
+}
+
+// Carry out a command, from the socket or another way in
+pub fn dispatch(command: Command, origin: CallOrigin, event_sink: &Option<ExtEventSink>) -> Result<Reply, String> {
+    match command {
+        Command::Call { number, private } => call(&number, private, origin, event_sink).map(|_| Reply::Ok),
+        Command::Ping => Ok(Reply::Pong { pid: std::process::id(), app: identity().name.clone(), version: env!("CARGO_PKG_VERSION").to_string() }),
+        Command::ReloadConfig => {
+            info!("Reloading the settings, as asked on the socket");
+            match event_sink {
+                // The window puts them into effect, the socket's calls included
+                Some(event_sink) => {
+                    event_sink.submit_command(RELOAD_SETTINGS, (), Target::Auto).ok();
+                }
+                None => daemon::reload()?,
+            }
+            Ok(Reply::Ok)
+        }
+        Command::Shutdown => {
+            info!("Quitting, as asked on the socket");
+            Ok(Reply::Ok)
+        }
+    }
+}
+
+fn shut_down(event_sink: &Option<ExtEventSink>) {
+    match event_sink {
+        Some(event_sink) => {
+            event_sink.submit_command(druid::commands::QUIT_APP, (), Target::Global).ok();
+        }
+        None => {
+            // Headless: nothing else to wind down, and the next instance can bind at once
+            std::fs::remove_file(get_socket_path()).ok();
+            std::process::exit(0);
+        }
+    }
+}
+
+// Dial `number`, a number or a tel:, callto:, SIP or clicktocall: link, like
+// a clicked link. Returns once the call is on its way; asking first, where
+// that's on, happens after.
+fn call(number: &str, private: bool, origin: CallOrigin, event_sink: &Option<ExtEventSink>) -> Result<(), String> {
+    let link = call_links::read(number).unwrap_or_else(|| Ok(CallLink { tel: tel_uri::link(number.trim(), false), ask: false, callback: None }));
+    let (uri, link) = link.and_then(|link| Ok((tel_uri::parse(&link.tel)?, link))).inspect_err(|reason| call_links::report_failure(number, call_links::BAD_LINK, reason))?;
+    let private = private || uri.private;
+
+    // Hide app from dock when processing tel URLs in socket
+    #[cfg(target_os = "macos")]
+    {
+        use objc::{msg_send, sel, sel_impl};
+        use objc::runtime::{Class, Object};
+
+        unsafe {
+            // Don't activate the app when processing tel URLs
+            let cls = Class::get("NSApplication").unwrap();
+            let app: *mut Object = msg_send![cls, sharedApplication];
+            let _: () = msg_send![app, setActivationPolicy:1]; // NSApplicationActivationPolicyAccessory = 1
+        }
+    }
+
+    info!("Socket received tel: URL with number: {}", logged_number(&uri.number, private));
+
+    // If we have valid settings, make call directly without UI. They're the
+    // ones in effect now, not the ones the socket started with.
+    let app_state = current_settings();
+    if !app_state.domain.is_empty() && !app_state.extension.is_empty() {
+        // A headless instance asks in a dialog, which mustn't hold up the reply
+        let event_sink = event_sink.clone();
+        thread::spawn(move || dial_tel_link(&app_state, &uri.dial_string(), origin, private, link.ask, link.callback, event_sink.as_ref()));
+        return Ok(());
+    }
+    if let Some(callback) = &link.callback {
+        callback.not_configured();
+    }
+    if let Some(event_sink) = event_sink {
+        // Only if settings not configured, send to UI
+        let tel = if private && !uri.private { tel_uri::link(&uri.dial_string(), true) } else { link.tel };
+        event_sink.submit_command(PROCESS_TEL_URL, tel, Target::Auto).ok();
+    } else {
+        return Err("settings aren't configured, open the app to set them up".to_string());
+    }
+
+    Ok(())
+}
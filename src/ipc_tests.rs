@@ -0,0 +1,168 @@
+// Tests for the socket protocol and the local HTTP API and native messaging
+// that share its commands: how requests and replies look on the wire, and
+// what's refused.
+// Nothing is sent over a socket.
+
+use crate::audit::CallOrigin;
+use crate::config::{self, AppState};
+use crate::http_api::{self, HttpRequest};
+use crate::ipc::{self, Command, Reply, Request, Response, PROTOCOL_VERSION};
+use crate::native_messaging;
+use serde_json::{json, Value};
+
+#[test]
+fn requests_carry_their_type_version_and_payload() {
+    let request = Request { version: 1, id: Some(json!(7)), command: Command::Call { number: "+15551234567".to_string(), private: true } };
+    let wire: Value = serde_json::to_value(&request).unwrap();
+    assert_eq!(wire, json!({"version": 1, "id": 7, "type": "call", "payload": {"number": "+15551234567", "private": true}}));
+
+    let wire: Value = serde_json::to_value(Request { version: 1, id: None, command: Command::ReloadConfig }).unwrap();
+    assert_eq!(wire, json!({"version": 1, "type": "reload_config"}));
+}
+
+#[test]
+fn requests_are_read_with_what_can_be_left_out() {
+    let request = ipc::parse_request(r#"{"type": "call", "version": 1, "payload": {"number": "tel:5551234567"}}"#).unwrap();
+    assert_eq!(request.command, Command::Call { number: "tel:5551234567".to_string(), private: false });
+    assert_eq!(request.id, None);
+
+    for (message, command) in [("ping", Command::Ping), ("reload_config", Command::ReloadConfig), ("shutdown", Command::Shutdown)] {
+        let request = ipc::parse_request(&format!(r#"{{"type": "{}", "version": 1, "id": "a"}}"#, message)).unwrap();
+        assert_eq!(request.command, command);
+        assert_eq!(request.id, Some(json!("a")));
+    }
+}
+
+#[test]
+fn unknown_or_newer_requests_are_refused() {
+    assert!(ipc::parse_request(r#"{"type": "dance", "version": 1}"#).is_err());
+    assert!(ipc::parse_request(r#"{"type": "ping"}"#).is_err());
+    assert!(ipc::parse_request(r#"{"type": "call", "version": 1}"#).is_err());
+    let newer = ipc::parse_request(&format!(r#"{{"type": "ping", "version": {}}}"#, PROTOCOL_VERSION + 1)).unwrap_err();
+    assert!(newer.contains("isn't supported"), "{}", newer);
+}
+
+#[test]
+fn replies_look_like_requests() {
+    let wire: Value = serde_json::to_value(Response { version: 1, id: Some(json!("a")), reply: Reply::Ok }).unwrap();
+    assert_eq!(wire, json!({"version": 1, "id": "a", "type": "ok"}));
+    let wire: Value = serde_json::to_value(Response { version: 1, id: None, reply: Reply::Error { message: "no".to_string() } }).unwrap();
+    assert_eq!(wire, json!({"version": 1, "type": "error", "payload": {"message": "no"}}));
+
+    let response: Response = serde_json::from_str(r#"{"version": 1, "type": "pong", "payload": {"pid": 42, "app": "Click-To-Call", "version": "1.0.0"}}"#).unwrap();
+    assert_eq!(response.reply, Reply::Pong { pid: 42, app: "Click-To-Call".to_string(), version: "1.0.0".to_string() });
+}
+
+#[test]
+fn calls_without_settings_are_refused_headless() {
+    config::set_current_settings(&AppState::default());
+    let origin = CallOrigin::Socket { peer_pid: None };
+    let call = Command::Call { number: "5551234567".to_string(), private: false };
+    assert!(ipc::dispatch(call, origin, &None).unwrap_err().contains("settings aren't configured"));
+
+    let garbled = Command::Call { number: "tel:555-CALL-NOW".to_string(), private: false };
+    assert!(ipc::dispatch(garbled, origin, &None).is_err());
+    assert!(matches!(ipc::dispatch(Command::Ping, origin, &None), Ok(Reply::Pong { .. })));
+}
+
+#[test]
+fn plain_messages_take_every_kind_of_call_link() {
+    config::set_current_settings(&AppState::default());
+    let origin = CallOrigin::Socket { peer_pid: None };
+    // Taken as calls, and refused only for the missing settings
+    for link in ["tel:5551234567", "callto:5551234567", "sip:5551234567@pbx.example.com"] {
+        let line = ipc::handle_message(link, origin, &None).line;
+        assert!(line.starts_with("error:") && line.contains("settings aren't configured"), "{}: {}", link, line);
+    }
+    assert_eq!(ipc::handle_message("ping-1", origin, &None).line, "ok\n");
+    assert!(ipc::handle_message("hello", origin, &None).line.contains("unknown message"));
+}
+
+fn http_request(text: &str) -> HttpRequest {
+    http_api::read_request(&mut text.as_bytes()).unwrap()
+}
+
+#[test]
+fn http_requests_are_read_with_their_token_and_body() {
+    let request = http_request("POST /call/ HTTP/1.1\r\nHost: 127.0.0.1\r\nauthorization: Bearer abc\r\nContent-Length: 26\r\n\r\n{\"number\": \"5551234567\"}  ");
+    assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/call"));
+    assert_eq!(request.authorization.as_deref(), Some("Bearer abc"));
+    assert_eq!(request.body, br#"{"number": "5551234567"}  "#);
+
+    let request = http_request("GET /history?limit=5 HTTP/1.1\r\n\r\n");
+    assert_eq!((request.path.as_str(), request.query.as_str()), ("/history", "limit=5"));
+    assert!(http_api::read_request(&mut "GET /ping HTTP/1.1\r\nHost: x\r\n".as_bytes()).is_err());
+}
+
+#[test]
+fn http_calls_need_the_token_and_go_the_socket_way() {
+    config::set_current_settings(&AppState::default());
+    let call = "POST /call HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 24\r\n\r\n{\"number\": \"5551234567\"}";
+    assert_eq!(http_api::respond(&http_request(call), "other", &None).status, 401);
+    assert_eq!(http_api::respond(&http_request(call), "", &None).status, 401);
+
+    let refused = http_api::respond(&http_request(call), "secret", &None);
+    assert_eq!(refused.status, 422);
+    assert!(refused.body.contains("settings aren't configured"), "{}", refused.body);
+
+    let ping = http_api::respond(&http_request("GET /ping HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n"), "secret", &None);
+    assert_eq!(ping.status, 200);
+    assert!(ping.body.contains("pong"), "{}", ping.body);
+    let unknown = http_request("DELETE /history HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n");
+    assert_eq!(http_api::respond(&unknown, "secret", &None).status, 405);
+}
+
+#[test]
+fn the_http_api_needs_a_token_to_be_turned_on() {
+    assert_eq!(http_api::parse_port("", ""), Ok(None));
+    assert_eq!(http_api::parse_port(" 8765 ", "t"), Ok(Some(8765)));
+    assert!(http_api::parse_port("8765", " ").is_err());
+    for wrong in ["80", "70000", "http"] {
+        assert!(http_api::parse_port(wrong, "t").is_err(), "{}", wrong);
+    }
+}
+
+#[test]
+fn native_messages_are_framed_by_their_length() {
+    let mut wire = Vec::new();
+    native_messaging::write_message(&mut wire, br#"{"type":"ok","version":1}"#).unwrap();
+    native_messaging::write_message(&mut wire, b"{}").unwrap();
+    assert_eq!(&wire[..4], &25u32.to_ne_bytes());
+
+    let mut input = wire.as_slice();
+    assert_eq!(native_messaging::read_message(&mut input).unwrap().unwrap(), br#"{"type":"ok","version":1}"#);
+    assert_eq!(native_messaging::read_message(&mut input).unwrap().unwrap(), b"{}");
+    assert_eq!(native_messaging::read_message(&mut input).unwrap(), None);
+
+    let mut oversized = (64 * 1024 * 1024u32).to_ne_bytes().as_slice().to_vec();
+    oversized.extend_from_slice(b"{}");
+    assert!(native_messaging::read_message(&mut oversized.as_slice()).is_err());
+    // Cut off mid-message
+    let mut cut = 10u32.to_ne_bytes().to_vec();
+    cut.extend_from_slice(b"{}");
+    assert!(native_messaging::read_message(&mut cut.as_slice()).is_err());
+}
+
+#[test]
+fn native_messages_that_arent_requests_are_answered_with_an_error() {
+    let response = native_messaging::answer(r#"{"type": "dance", "version": 1}"#);
+    assert!(matches!(response.reply, Reply::Error { .. }));
+    assert_eq!(response.version, PROTOCOL_VERSION);
+}
+
+#[test]
+fn native_host_manifests_name_the_extension_the_browser_s_way() {
+    let chrome = native_messaging::manifest("chrome", "abcdefghijklmnop", "/tmp/host");
+    assert_eq!(chrome["name"], json!(native_messaging::host_name()));
+    assert_eq!(chrome["type"], json!("stdio"));
+    assert_eq!(chrome["path"], json!("/tmp/host"));
+    assert_eq!(chrome["allowed_origins"], json!(["chrome-extension://abcdefghijklmnop/"]));
+    assert!(chrome.get("allowed_extensions").is_none());
+
+    let firefox = native_messaging::manifest("firefox", "calls@example.com", "/tmp/host");
+    assert_eq!(firefox["allowed_extensions"], json!(["calls@example.com"]));
+    assert!(firefox.get("allowed_origins").is_none());
+
+    // Browsers only take lowercase letters, digits, dots and underscores
+    assert!(native_messaging::host_name().chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '_'));
+}
@@ -0,0 +1,53 @@
+use crate::tls::{self, Tls};
+use crate::{base_url, runtime};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+// Comfortably below the usual server-side idle timeouts (nginx defaults to 75s)
+const INTERVAL: Duration = Duration::from_secs(45);
+
+// Domain to keep warm and its certificate settings, None while the feature is switched off
+static TARGET: Mutex<Option<(String, Tls)>> = Mutex::new(None);
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+// Keep a pooled connection to this PBX open, or stop with None.
+// Enabling warms the connection straight away so the next dial is fast.
+pub fn set_target(domain: Option<String>, tls: Tls) {
+    let target = domain.map(|domain| (domain, tls));
+    *TARGET.lock().unwrap() = target.clone();
+
+    if let Some((domain, tls)) = target {
+        if !STARTED.swap(true, Ordering::SeqCst) {
+            runtime().spawn(keep_alive_loop());
+        }
+        runtime().spawn(async move { ping(&domain, &tls).await });
+    }
+}
+
+async fn keep_alive_loop() {
+    loop {
+        tokio::time::sleep(INTERVAL).await;
+
+        let target = TARGET.lock().unwrap().clone();
+        if let Some((domain, tls)) = target {
+            ping(&domain, &tls).await;
+        }
+    }
+}
+
+// A HEAD request is enough to open (or reuse) the TLS connection in the pool
+// calls to this PBX use
+async fn ping(domain: &str, tls: &Tls) {
+    let client = match tls::client(tls) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Keep-alive request to {} not sent: {}", domain, e);
+            return;
+        }
+    };
+    if let Err(e) = client.head(base_url(domain)).send().await {
+        warn!("Keep-alive request to {} failed: {}", domain, e);
+    }
+}
@@ -0,0 +1,180 @@
+// Secrets kept in the login Keychain instead of the preferences file
+
+// Account name for the PBX API key (or backend password)
+pub const API_KEY: &str = "api-key";
+
+// Account name for the corporate directory's LDAP or CardDAV password
+pub const CORPORATE_DIRECTORY_PASSWORD: &str = "corporate-directory-password";
+
+// Account name for the passphrase of the PBX client certificate
+pub const CLIENT_CERTIFICATE_PASSPHRASE: &str = "client-certificate-passphrase";
+
+// Account name for the password or token of the web server in front of the PBX
+pub const HTTP_AUTH_PASSWORD: &str = "http-auth-password";
+
+// Account name for the password of FreeSWITCH's event socket, for following calls
+pub const EVENT_SOCKET_PASSWORD: &str = "event-socket-password";
+
+// Account name for the OAuth2 refresh token Sign In leaves, one per profile
+pub const OAUTH_REFRESH_TOKEN: &str = "oauth-refresh-token";
+
+// Account name for the token the local HTTP API asks for
+pub const HTTP_API_TOKEN: &str = "http-api-token";
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::c_void;
+
+    pub const ERR_SEC_SUCCESS: i32 = 0;
+    pub const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+
+    #[link(name = "Security", kind = "framework")]
+    extern "C" {
+        pub static kSecClass: *const c_void;
+        pub static kSecClassGenericPassword: *const c_void;
+        pub static kSecAttrService: *const c_void;
+        pub static kSecAttrAccount: *const c_void;
+        pub static kSecValueData: *const c_void;
+        pub static kSecReturnData: *const c_void;
+        pub static kSecMatchLimit: *const c_void;
+        pub static kSecMatchLimitOne: *const c_void;
+
+        pub fn SecItemAdd(attributes: *const c_void, result: *mut *const c_void) -> i32;
+        pub fn SecItemCopyMatching(query: *const c_void, result: *mut *const c_void) -> i32;
+        pub fn SecItemUpdate(query: *const c_void, attributes: *const c_void) -> i32;
+        pub fn SecItemDelete(query: *const c_void) -> i32;
+        pub fn SecCopyErrorMessageString(status: i32, reserved: *const c_void) -> *const c_void;
+    }
+}
+
+// Query matching our generic password item for `account`, as a mutable
+// dictionary so callers can add to it
+#[cfg(target_os = "macos")]
+unsafe fn item_query(account: &str) -> *mut objc::runtime::Object {
+    use crate::identity::identity;
+    use crate::macos::ns_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+
+    let dictionary_class = Class::get("NSMutableDictionary").unwrap();
+    let query: *mut Object = msg_send![dictionary_class, dictionary];
+    set(query, ffi::kSecClass, ffi::kSecClassGenericPassword as *mut Object);
+    // The app's items are filed under its bundle identifier
+    set(query, ffi::kSecAttrService, ns_string(&identity().bundle_id));
+    set(query, ffi::kSecAttrAccount, ns_string(account));
+    query
+}
+
+// The Security constants are CFStrings, toll-free bridged to NSString
+#[cfg(target_os = "macos")]
+unsafe fn set(dictionary: *mut objc::runtime::Object, key: *const std::ffi::c_void, value: *mut objc::runtime::Object) {
+    use objc::{msg_send, sel, sel_impl};
+
+    let _: () = msg_send![dictionary, setObject:value forKey:key as *mut objc::runtime::Object];
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn error_message(status: i32) -> String {
+    use crate::macos::rust_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::Object;
+
+    let message = ffi::SecCopyErrorMessageString(status, std::ptr::null()) as *mut Object;
+    let text = rust_string(message);
+    if !message.is_null() {
+        let _: () = msg_send![message, release];
+    }
+    match text {
+        Some(text) => format!("{} (OSStatus {})", text, status),
+        None => format!("OSStatus {}", status),
+    }
+}
+
+// The secret stored for `account`, if there is one
+#[cfg(target_os = "macos")]
+pub fn password(account: &str) -> Option<String> {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+    use std::ffi::c_void;
+    use tracing::warn;
+
+    unsafe {
+        let number_class = Class::get("NSNumber").unwrap();
+        let return_data: *mut Object = msg_send![number_class, numberWithBool: objc::runtime::YES];
+        let query = item_query(account);
+        set(query, ffi::kSecReturnData, return_data);
+        set(query, ffi::kSecMatchLimit, ffi::kSecMatchLimitOne as *mut Object);
+
+        let mut result: *const c_void = std::ptr::null();
+        let status = ffi::SecItemCopyMatching(query as *const c_void, &mut result);
+        if status != ffi::ERR_SEC_SUCCESS {
+            if status != ffi::ERR_SEC_ITEM_NOT_FOUND {
+                warn!("Couldn't read {} from the Keychain: {}", account, error_message(status));
+            }
+            return None;
+        }
+
+        // We own the returned CFData
+        let data = result as *mut Object;
+        let length: usize = msg_send![data, length];
+        let bytes: *const u8 = msg_send![data, bytes];
+        let secret = if bytes.is_null() {
+            String::new()
+        } else {
+            String::from_utf8_lossy(std::slice::from_raw_parts(bytes, length)).into_owned()
+        };
+        let _: () = msg_send![data, release];
+        Some(secret)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn password(_account: &str) -> Option<String> {
+    // No Keychain outside macOS
+    None
+}
+
+// Store `secret` for `account`, replacing any existing one; an empty secret
+// removes the item. Returns Ok(false) where there's no Keychain to store it
+// in, so the caller can keep it elsewhere.
+#[cfg(target_os = "macos")]
+pub fn set_password(account: &str, secret: &str) -> Result<bool, String> {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+    use std::ffi::c_void;
+
+    unsafe {
+        let query = item_query(account);
+
+        if secret.is_empty() {
+            let status = ffi::SecItemDelete(query as *const c_void);
+            if status != ffi::ERR_SEC_SUCCESS && status != ffi::ERR_SEC_ITEM_NOT_FOUND {
+                return Err(format!("Couldn't remove {} from the Keychain: {}", account, error_message(status)));
+            }
+            return Ok(true);
+        }
+
+        let data_class = Class::get("NSData").unwrap();
+        let data: *mut Object = msg_send![data_class, dataWithBytes:secret.as_ptr() length:secret.len()];
+
+        let dictionary_class = Class::get("NSMutableDictionary").unwrap();
+        let attributes: *mut Object = msg_send![dictionary_class, dictionary];
+        set(attributes, ffi::kSecValueData, data);
+
+        let mut status = ffi::SecItemUpdate(query as *const c_void, attributes as *const c_void);
+        if status == ffi::ERR_SEC_ITEM_NOT_FOUND {
+            set(query, ffi::kSecValueData, data);
+            status = ffi::SecItemAdd(query as *const c_void, std::ptr::null_mut());
+        }
+
+        if status != ffi::ERR_SEC_SUCCESS {
+            return Err(format!("Couldn't save {} to the Keychain: {}", account, error_message(status)));
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn set_password(_account: &str, _secret: &str) -> Result<bool, String> {
+    Ok(false)
+}
@@ -0,0 +1,696 @@
+// Click-To-Call: places calls through a PBX for tel: links, hotkeys, the
+// menu bar and the command line. The executable only calls run().
+
+use druid::{AppLauncher, PlatformError, WindowDesc};
+use reqwest::{Client, ClientBuilder};
+use std::thread;
+use std::env;
+#[cfg(target_os = "macos")]
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{info, warn};
+
+mod active_calls;
+mod appearance;
+mod audit;
+// Public so tests/ can point the backends at a stand-in PBX
+pub mod backend;
+mod bug_report;
+mod call;
+mod call_events;
+mod call_links;
+mod call_options;
+mod caller_id_template;
+mod campaign;
+mod clipboard_watch;
+#[cfg(feature = "cli")]
+mod cli;
+mod config;
+mod config_watch;
+#[cfg(test)]
+mod config_tests;
+mod contacts;
+mod corporate_directory;
+mod daemon;
+#[cfg(test)]
+mod delegate_tests;
+#[cfg(test)]
+mod ipc_tests;
+mod dial_plan;
+mod directory;
+mod duplicates;
+mod effective_config;
+mod emergency;
+mod failover;
+mod favorites;
+mod health;
+mod history;
+mod http_api;
+mod hotkey;
+mod identity;
+mod ipc;
+mod keep_alive;
+mod keychain;
+mod logging;
+#[cfg(target_os = "macos")]
+mod macos;
+mod managed;
+mod menu_bar;
+mod native_messaging;
+mod notify;
+mod number_format;
+mod number_lists;
+#[cfg(test)]
+mod number_tests;
+mod paths;
+mod phone_text;
+mod presence;
+mod profile_color;
+mod profiles;
+mod provisioning;
+mod proxy;
+mod queue;
+mod reminders;
+mod retry;
+mod ringback;
+mod screen_lookup;
+mod scripting;
+mod services;
+mod share;
+mod spotlight;
+#[cfg(test)]
+mod spotlight_tests;
+mod srv;
+mod stats;
+mod storage;
+mod tel_uri;
+mod tls;
+mod url_handler;
+mod ui;
+mod voicemail;
+mod wake;
+
+use audit::CallOrigin;
+use call::{dial_tel_link, logged_number, CONFIRM_TEL_LINKS};
+use config::{load_preferences, AppState};
+use ui::{build_ui, Delegate, PbxDialer, CLIPBOARD_HOTKEY_ID, LOOKUP_HOTKEY_ID};
+
+// Runs every request to the PBX and the webhooks, so none of them needs a thread of its own
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+// HTTP client shared by every request so connections to the PBX are pooled and reused
+static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
+
+// Set when a tel: link was handed to this process by another app (e.g. a browser),
+// so the app should stay in the background once launching has finished
+static TEL_HANDOFF: AtomicBool = AtomicBool::new(false);
+
+// How often, and how far apart, a tel: link is offered to a primary instance
+// that holds the lock but isn't answering yet, e.g. because it's starting up
+const HAND_OFF_ATTEMPTS: u32 = 5;
+const HAND_OFF_WAIT: std::time::Duration = std::time::Duration::from_millis(400);
+
+// Socket path for inter-process communication
+fn get_socket_path() -> PathBuf {
+    paths::socket_file()
+}
+
+// The async runtime, started on first use
+fn runtime() -> &'static tokio::runtime::Runtime {
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("click-to-call-io")
+            .enable_all()
+            .build()
+            .expect("Couldn't start the async runtime")
+    })
+}
+
+// Wait for `future` from synchronous code, whether or not that code is
+// itself running on the runtime
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => runtime().block_on(future),
+    }
+}
+
+// How every HTTP client is set up, before any certificate settings for a PBX
+fn client_builder() -> ClientBuilder {
+    Client::builder()
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .proxy(proxy::system_proxy())
+}
+
+// Shared HTTP client, created on first use
+fn http_client() -> &'static Client {
+    HTTP_CLIENT.get_or_init(|| client_builder().build().unwrap_or_else(|_| Client::new()))
+}
+
+// Base URL for the PBX, adding https:// unless the domain already has a scheme
+fn base_url(domain: &str) -> String {
+    if domain.starts_with("http://") || domain.starts_with("https://") {
+        domain.to_string()
+    } else {
+        format!("https://{}", domain)
+    }
+}
+
+// Set up the extra notification sinks from the current settings.
+// The status line sink is only added when there is a UI to show it in.
+fn configure_notification_sinks(state: &AppState, event_sink: Option<druid::ExtEventSink>) {
+    let mut sinks: Vec<Box<dyn notify::NotificationSink>> = Vec::new();
+    
+    if let Some(event_sink) = event_sink {
+        sinks.push(Box::new(notify::InAppSink { event_sink }));
+    }
+    
+    if !state.webhook_url.is_empty() {
+        sinks.push(Box::new(notify::WebhookSink {
+            url: state.webhook_url.clone(),
+            source: format!("{}@{}", state.extension, state.domain),
+        }));
+    }
+    
+    notify::set_sinks(sinks);
+}
+
+// Domain the keep-alive should target, if it's switched on and configured
+// Only the HTTP backends keep a pooled connection worth warming.
+fn keep_alive_target(state: &AppState) -> Option<String> {
+    if state.keep_alive && state.backend.uses_http() && !state.domain.is_empty() {
+        Some(state.domain.clone())
+    } else {
+        None
+    }
+}
+
+// Extension whose presence is shown, None if that's off
+fn presence_target(state: &AppState) -> Option<presence::Target> {
+    if state.show_presence && !state.domain.is_empty() && !state.extension.is_empty() {
+        Some(presence::Target::from_state(state))
+    } else {
+        None
+    }
+}
+
+// Extension whose voicemail is counted, None if that's off
+fn voicemail_target(state: &AppState) -> Option<presence::Target> {
+    if state.show_voicemail && !state.domain.is_empty() && !state.extension.is_empty() {
+        Some(presence::Target::from_state(state))
+    } else {
+        None
+    }
+}
+
+// PBX for the background health check, None if it's off
+fn health_target(state: &AppState) -> Option<health::Target> {
+    if state.health_check && !state.domain.is_empty() {
+        Some(health::Target::from_state(state))
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn hide_app_from_dock() {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+    
+    unsafe {
+        // Get the shared application
+        let cls = Class::get("NSApplication").unwrap();
+        let app: *mut Object = msg_send![cls, sharedApplication];
+        
+        // Set activation policy to prohibit the app from showing in the Dock
+        let _: () = msg_send![app, setActivationPolicy:1]; // NSApplicationActivationPolicyAccessory = 1
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn hide_app_from_dock() {
+    // No-op for non-macOS platforms
+}
+
+// Bring the app to the front, e.g. so the menu bar popover gets keyboard focus.
+// Accessory apps aren't activated by showing a window.
+#[cfg(target_os = "macos")]
+fn activate_app() {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, YES};
+    
+    unsafe {
+        let cls = Class::get("NSApplication").unwrap();
+        let app: *mut Object = msg_send![cls, sharedApplication];
+        let _: () = msg_send![app, activateIgnoringOtherApps: YES];
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn activate_app() {
+    // No-op for non-macOS platforms
+}
+
+// Give focus back to the app that sent us a tel: link (usually the browser).
+// druid activates the app when launching finishes, so the hide is queued on the
+// run loop instead of done immediately; that way it runs after druid's activation.
+#[cfg(target_os = "macos")]
+fn return_focus_to_sender() {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+    
+    TEL_HANDOFF.store(true, Ordering::SeqCst);
+    
+    unsafe {
+        let cls = Class::get("NSApplication").unwrap();
+        let app: *mut Object = msg_send![cls, sharedApplication];
+        let nil: *const Object = std::ptr::null();
+        let delay: f64 = 0.0;
+        
+        // Hiding the app deactivates it and activates the next app in line, which is the sender
+        let _: () = msg_send![app, performSelector:sel!(hide:) withObject:nil afterDelay:delay];
+    }
+}
+
+// How this process was launched, decided before any window exists
+enum LaunchMode {
+    // Launched for a tel: link with usable settings: dial and exit, no UI at all.
+    // The flags say whether the link asked for a private call, and whether
+    // its scheme's rule says to ask first.
+    AutoCall(String, bool, bool),
+    // Launched for a tel: link but settings are incomplete: show the UI pre-filled
+    NeedsSettings(String),
+    // Regular launch from Finder/Dock
+    Interactive,
+    // --daemon: serve tel: links and the socket without any window
+    Daemon,
+}
+
+// Start the app the way the arguments ask for: a CLI subcommand, the
+// headless daemon, a call for a tel: link or the settings window
+pub fn run() -> Result<(), PlatformError> {
+    logging::init();
+    let identity = identity::identity();
+    info!("Running as {} ({}, files under \"{}\", identity from the {})", identity.name, identity.bundle_id, identity.slug, identity.source);
+    
+    // Terminal subcommands don't touch the socket or any window
+    #[cfg(feature = "cli")]
+    if let Some(code) = cli::run(&env::args().collect::<Vec<_>>()) {
+        std::process::exit(code);
+    }
+    
+    // A browser started this for its extension: stdin and stdout are its messages
+    if env::args().any(|arg| arg == "--native-messaging") {
+        std::process::exit(native_messaging::run());
+    }
+    
+    // Check if the app is already running
+    let socket_path = get_socket_path();
+    let is_primary = ipc::claim_primary();
+    
+    // Print all args for debugging
+    info!("Received arguments: {:?}", env::args().collect::<Vec<_>>());
+    
+    // On macOS, the URL is passed through the process arguments
+    let args: Vec<String> = env::args().collect();
+    let daemon = args.iter().any(|arg| arg == "--daemon");
+    
+    // Setting up or removing the login item is all these do
+    if args.iter().any(|arg| arg == "--install-daemon") {
+        match daemon::install_launch_agent() {
+            Ok(path) => info!("Installed {}; tel: links are now handled from login without opening the app", path.display()),
+            Err(message) => warn!("Couldn't install the launch agent: {}", message),
+        }
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--uninstall-daemon") {
+        match daemon::uninstall_launch_agent() {
+            Ok(path) => info!("Removed {}", path.display()),
+            Err(message) => warn!("Couldn't remove the launch agent: {}", message),
+        }
+        return Ok(());
+    }
+    // Letting a browser extension place calls, or no longer
+    if let Some(position) = args.iter().position(|arg| arg == "--install-native-host") {
+        let (Some(browser), Some(extension_id)) = (args.get(position + 1), args.get(position + 2)) else {
+            warn!("--install-native-host needs the browser and the extension's id, e.g. --install-native-host chrome abcdefghijklmnopabcdefghijklmnop");
+            std::process::exit(2);
+        };
+        match native_messaging::install_host(browser, extension_id) {
+            Ok(path) => info!("Installed {}; the extension can now connect to {}", path.display(), native_messaging::host_name()),
+            Err(message) => {
+                warn!("Couldn't install the native messaging host: {}", message);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    if let Some(position) = args.iter().position(|arg| arg == "--uninstall-native-host") {
+        let Some(browser) = args.get(position + 1) else {
+            warn!("--uninstall-native-host needs the browser, e.g. --uninstall-native-host chrome");
+            std::process::exit(2);
+        };
+        match native_messaging::uninstall_host(browser) {
+            Ok(path) => info!("Removed {}", path.display()),
+            Err(message) => {
+                warn!("Couldn't remove the native messaging host: {}", message);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    // Zero-touch setup: fetch the settings, save them and tell the running
+    // app, if any, to use them
+    if let Some(position) = args.iter().position(|arg| arg == "--provision") {
+        let Some(url) = args.get(position + 1) else {
+            warn!("--provision needs the URL of a settings file");
+            std::process::exit(2);
+        };
+        match provisioning::provision(url) {
+            Ok(count) => info!("Provisioned {} profiles from {}", count, url),
+            Err(message) => {
+                warn!("Couldn't provision from {}: {}", url, message);
+                std::process::exit(1);
+            }
+        }
+        if !is_primary {
+            if let Err(message) = ipc::reload_primary() {
+                warn!("{}", message);
+            }
+        }
+        return Ok(());
+    }
+    if daemon && !is_primary {
+        info!("Another instance already serves {}, exiting", socket_path.display());
+        return Ok(());
+    }
+    let mut has_tel_url = false;
+    let mut tel_link = String::new();
+    let mut tel_number = String::new();
+    let mut tel_private = false;
+    let mut tel_ask = false;
+    let mut tel_callback = None;
+    
+    // Check for tel: URL in app arguments
+    if args.len() > 1 {
+        // Look for tel: URL in all arguments
+        for arg in &args[1..] {
+            // A tel: link, a callto: or SIP link, or one of the app's own
+            if let Some(link) = call_links::read(arg) {
+                match link.and_then(|link| Ok((tel_uri::parse(&link.tel)?, link))) {
+                    Ok((uri, link)) => {
+                        info!("Found tel: URL with number: {}", logged_number(&uri.number, uri.private));
+                        has_tel_url = true;
+                        tel_link = arg.clone();
+                        tel_number = uri.dial_string();
+                        tel_private = uri.private;
+                        tel_ask = link.ask;
+                        tel_callback = link.callback;
+                    }
+                    Err(reason) => {
+                        warn!("Ignoring {}: {}", arg, reason);
+                        call_links::report_failure(arg, call_links::BAD_LINK, &reason);
+                    }
+                }
+                break;
+            }
+        }
+    }
+    
+    // If we're handling a tel: URL and this is a primary instance, hide from dock
+    if has_tel_url && is_primary {
+        hide_app_from_dock();
+    }
+    
+    // Handle the tel: URL if present
+    if has_tel_url {
+        // If this is not the primary instance, try to send the URL to the primary instance
+        if !is_primary {
+            // The primary instance may still be starting up and not listening yet
+            for attempt in 0..HAND_OFF_ATTEMPTS {
+                if attempt > 0 {
+                    thread::sleep(HAND_OFF_WAIT);
+                }
+                if ipc::hand_off(&socket_path, &tel_link, tel_private) {
+                    // The primary instance took the call, exit this one
+                    info!("Sent URL to primary instance and exiting");
+                    return Ok(());
+                }
+            }
+            // Nobody took it, so it's dialed from here
+            warn!("The primary instance isn't answering on {}, calling from this one", socket_path.display());
+        }
+    }
+    
+    // Decide the launch mode up front so an auto-call never instantiates a window
+    let mut initial_state = load_preferences();
+    logging::set_debug(initial_state.debug_logging);
+    CONFIRM_TEL_LINKS.store(initial_state.confirm_tel_links, Ordering::SeqCst);
+    let launch_mode = if daemon {
+        LaunchMode::Daemon
+    } else if !has_tel_url {
+        LaunchMode::Interactive
+    } else if !initial_state.domain.is_empty() && !initial_state.extension.is_empty() {
+        LaunchMode::AutoCall(tel_number, tel_private, tel_ask)
+    } else {
+        LaunchMode::NeedsSettings(tel_number)
+    };
+    
+    match launch_mode {
+        LaunchMode::AutoCall(number, private, ask) => {
+            configure_notification_sinks(&initial_state, None);
+            
+            // Make the call and wait for the request to finish before exiting,
+            // otherwise the process would end before the HTTP request is sent
+            if let Some(call) = dial_tel_link(&initial_state, &number, CallOrigin::TelLink { sender_pid: None }, private, ask, tel_callback, None) {
+                runtime().block_on(call).ok();
+            }
+            return Ok(());
+        }
+        LaunchMode::NeedsSettings(number) => {
+            // If we get here, we need to show the UI to configure settings
+            info!("Settings not configured, need to show UI");
+            if let Some(callback) = tel_callback {
+                callback.not_configured();
+            }
+            initial_state.status_message = format!("Configure settings to call {}", number);
+            initial_state.phone_number = number;
+            initial_state.private_call = tel_private;
+        }
+        LaunchMode::Daemon => {
+            daemon::run(initial_state);
+            return Ok(());
+        }
+        LaunchMode::Interactive => {}
+    }
+    
+    // Register apple event handler for MacOS URL scheme (only for primary instance)
+    #[cfg(target_os = "macos")]
+    if is_primary {
+        configure_apple_event_handler();
+    }
+
+    // Nothing saved and nothing managed: walk through the setup first
+    let first_run = !initial_state.kiosk && initial_state.domain.is_empty() && !paths::preferences_file().exists();
+    
+    // Create the main window
+    let main_window = WindowDesc::new(build_ui(initial_state.kiosk))
+        .title(identity::identity().name.clone())
+        .window_size((460.0, 1640.0));
+    
+    // Create delegate with proper flags
+    let delegate = Delegate {
+        is_primary,
+        main_window: None,
+        popover: None,
+        pending_export: None,
+        first_run,
+        setup_window: None,
+        dialer: Box::new(PbxDialer),
+    };
+    
+    // Launch the application
+    let launcher = AppLauncher::with_window(main_window)
+        .configure_env(|env, _data| appearance::add_defaults(env))
+        .delegate(delegate);
+    
+    launcher.launch(initial_state)?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn configure_apple_event_handler() {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, Sel};
+    
+    unsafe {
+        extern "C" fn handle_url_event(_this: &Object, _: Sel, event: *const Object, _: *const Object) {
+            // Apple Event constants
+            const KEY_DIRECT_OBJECT: u32 = 0x2D2D2D2D; // ---- in UTF-8 (keyDirectObject)
+            const KEY_SENDER_PID: u32 = 0x73706964; // spid in UTF-8 (keySenderPIDAttr)
+            
+            unsafe {
+                // Who opened the link, for the audit log
+                let sender: *const Object = msg_send![event, attributeDescriptorForKeyword: KEY_SENDER_PID];
+                let sender_pid: Option<i32> = if sender.is_null() {
+                    None
+                } else {
+                    let pid: i32 = msg_send![sender, int32Value];
+                    Some(pid)
+                };
+                let origin = CallOrigin::TelLink { sender_pid };
+                
+                let desc: *const Object = msg_send![event, paramDescriptorForKeyword: KEY_DIRECT_OBJECT];
+                let url_str: *const Object = msg_send![desc, stringValue];
+                let ns_string: *const Object = msg_send![url_str, UTF8String];
+                let c_str = std::ffi::CStr::from_ptr(ns_string as *const i8);
+                
+                if let Ok(url) = c_str.to_str() {
+                    info!("Received URL: {}", url);
+                    // A tel: link, a callto: or SIP link, or one of the app's own
+                    if let Some(link) = call_links::read(url) {
+                        // Hide the app from dock when processing tel URLs
+                        hide_app_from_dock();
+                        return_focus_to_sender();
+                        
+                        // Try to connect to existing instance; it reads the link with its own settings
+                        let socket_path = get_socket_path();
+                        if ipc::hand_off(&socket_path, url, false) {
+                            // The existing instance took the call and we're done
+                            info!("Sent URL to existing instance");
+                            return;
+                        }
+                        
+                        // If we couldn't connect, try to handle it directly
+                        let (uri, link) = match link.and_then(|link| Ok((tel_uri::parse(&link.tel)?, link))) {
+                            Ok(parsed) => parsed,
+                            Err(reason) => {
+                                warn!("Ignoring {}: {}", url, reason);
+                                call_links::report_failure(url, call_links::BAD_LINK, &reason);
+                                return;
+                            }
+                        };
+                        // Load preferences and check if we can make a direct call
+                        let app_state = load_preferences();
+                        if !app_state.domain.is_empty() && !app_state.extension.is_empty() {
+                            // Directly call the API endpoint; asking first would hold up the event loop
+                            thread::spawn(move || dial_tel_link(&app_state, &uri.dial_string(), origin, uri.private, link.ask, link.callback, None));
+                        } else if let Some(callback) = link.callback {
+                            callback.not_configured();
+                        }
+                    }
+                }
+            }
+        }
+        
+        let cls = Class::get("NSAppleEventManager").unwrap();
+        let manager: *const Object = msg_send![cls, sharedAppleEventManager];
+        
+        // Register handler for URL events
+        let app_delegate_class = Class::get("NSObject").unwrap();
+        let sel_handle_url = sel!(handleURLEvent:withReplyEvent:);
+        
+        // Apple Event class and ID for URL handling
+        // 'GURL' in UTF-8 (Generic URL)
+        const GURL_EVENT_CLASS: u32 = 0x4755524C; // 'GURL'
+        const GURL_EVENT_ID: u32 = 0x4755524C;    // 'GURL'
+        
+        // Create C string for method signature
+        let types = CString::new("v@:@@").unwrap();
+        
+        class_addMethod(
+            app_delegate_class,
+            sel_handle_url,
+            handle_url_event as extern "C" fn(&Object, Sel, *const Object, *const Object),
+            types.as_ptr()
+        );
+        
+        let delegate: *const Object = msg_send![app_delegate_class, new];
+        let _: () = msg_send![manager, 
+                      setEventHandler:delegate 
+                      andSelector:sel_handle_url 
+                      forEventClass:GURL_EVENT_CLASS 
+                      andEventID:GURL_EVENT_ID];
+    }
+}
+
+// (Re)register global hotkeys from the current settings.
+// Returns false if one of them couldn't be registered.
+// Put saved settings into effect without restarting. Returns a warning
+// for any setting that couldn't be applied.
+fn apply_settings(state: &AppState, event_sink: druid::ExtEventSink) -> Option<&'static str> {
+    config::set_current_settings(state);
+    // Another instance serves the socket, e.g. the background agent; it has
+    // to read the new settings too
+    if !ipc::is_primary() {
+        thread::spawn(|| {
+            if let Err(message) = ipc::reload_primary() {
+                warn!("Couldn't pass the new settings on: {}", message);
+            }
+        });
+    }
+    keep_alive::set_target(keep_alive_target(state), tls::Tls::from_state(state));
+    health::set_target(health_target(state));
+    presence::set_target(presence_target(state));
+    voicemail::set_target(voicemail_target(state));
+    CONFIRM_TEL_LINKS.store(state.confirm_tel_links, Ordering::SeqCst);
+    logging::set_debug(state.debug_logging);
+    storage::set_limits(storage::limits(state));
+    queue::set_limit(queue::limit(state));
+    configure_notification_sinks(state, Some(event_sink.clone()));
+    http_api::configure(state, Some(event_sink.clone()));
+    if !state.spotlight_recents {
+        spotlight::remove_callees();
+    }
+    favorites::index(&state.favorites);
+    directory::set_source(&state.directory_url);
+    corporate_directory::set_server(corporate_directory::Server::from_state(state));
+    if state.use_contacts {
+        thread::spawn(contacts::refresh);
+    } else {
+        contacts::clear();
+    }
+    menu_bar::set_enabled(event_sink, state.menu_bar, &profile_color::color_for(&state.color));
+    // One instance offering each copied number is enough
+    clipboard_watch::set_enabled(state.watch_clipboard && ipc::is_primary());
+    
+    let summary_time = stats::parse_summary_time(&state.summary_time);
+    stats::set_summary_time(summary_time);
+    
+    let wake_grace_period = wake::parse_grace_period(&state.wake_grace_minutes);
+    wake::set_grace_period(wake_grace_period.unwrap_or_default());
+    
+    if !register_hotkeys(state) {
+        Some("a hotkey is invalid or already in use")
+    } else if summary_time.is_none() && !state.summary_time.trim().is_empty() {
+        Some("the summary time should look like 17:30")
+    } else if wake_grace_period.is_none() {
+        Some("the wait after wake should be a number of minutes")
+    } else {
+        None
+    }
+}
+
+// Returns false if any of the hotkeys couldn't be registered
+fn register_hotkeys(state: &AppState) -> bool {
+    // Without screen look-up the hotkey would find nothing
+    let lookup = !cfg!(feature = "screen-lookup") || hotkey::register(LOOKUP_HOTKEY_ID, &state.lookup_hotkey);
+    let clipboard = hotkey::register(CLIPBOARD_HOTKEY_ID, &state.clipboard_hotkey);
+    lookup && clipboard
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn class_addMethod(
+        cls: *const objc::runtime::Class,
+        name: objc::runtime::Sel,
+        imp: extern "C" fn(&objc::runtime::Object, objc::runtime::Sel, *const objc::runtime::Object, *const objc::runtime::Object),
+        types: *const libc::c_char,
+    ) -> bool;
+    // We still need this for URL handling, but not for notifications
+}
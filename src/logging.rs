@@ -0,0 +1,103 @@
+// The app's log: standard error as before, and a file under
+// ~/Library/Logs/click-to-call/ that starts afresh each day and is kept for a
+// week unless the settings say otherwise, so what happened to a tel: link can
+// be looked at after the fact. Debug logging adds what the app decided at each
+// step, and the requests it sent.
+
+use crate::identity::identity;
+use crate::paths;
+use chrono::Local;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing::level_filters::LevelFilter;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Registry;
+
+// Days of log files kept at most; storage.rs deletes them sooner, after the
+// days set in the settings
+const MAX_LOG_FILES: usize = 365;
+
+// Always points at today's log file
+const LATEST_LOG: &str = "latest.log";
+
+// Changes which messages are kept once logging has started
+static FILTER: OnceLock<reload::Handle<Targets, Registry>> = OnceLock::new();
+
+// Times in the log are local, like everything else the app shows
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        write!(w, "{}", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"))
+    }
+}
+
+// Messages kept: the app's own debug messages when asked for, and warnings
+// and up from druid and the libraries in any case
+fn filter(debug: bool) -> Targets {
+    let app_level = if debug { LevelFilter::DEBUG } else { LevelFilter::INFO };
+    Targets::new().with_default(LevelFilter::WARN).with_target(env!("CARGO_CRATE_NAME"), app_level)
+}
+
+// Start logging. Should be done first thing, before anything is logged; if
+// the log folder can't be written to, the log only goes to standard error.
+pub fn init() {
+    let (filter, handle) = reload::Layer::new(filter(false));
+    let stderr = tracing_subscriber::fmt::layer().with_timer(LocalTime).with_writer(std::io::stderr);
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(&identity().slug)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .latest_symlink(LATEST_LOG)
+        .build(paths::log_dir());
+    let (file, file_error) = match appender {
+        Ok(appender) => (Some(tracing_subscriber::fmt::layer().with_timer(LocalTime).with_ansi(false).with_writer(appender)), None),
+        Err(e) => (None, Some(e)),
+    };
+
+    if tracing_subscriber::registry().with(filter).with(stderr).with(file).try_init().is_ok() {
+        FILTER.set(handle).ok();
+    }
+    if let Some(e) = file_error {
+        tracing::warn!("Couldn't open a log file in {}: {}", paths::log_dir().display(), e);
+    }
+}
+
+// Keep debug messages from now on, or stop keeping them
+pub fn set_debug(debug: bool) {
+    if let Some(handle) = FILTER.get() {
+        if let Err(e) = handle.reload(filter(debug)) {
+            tracing::warn!("Couldn't change the log level: {}", e);
+        }
+    }
+}
+
+// Today's log file
+pub fn latest_log_file() -> PathBuf {
+    paths::log_dir().join(LATEST_LOG)
+}
+
+// Show today's log in Console
+pub fn open_log() -> Result<(), String> {
+    let file = latest_log_file();
+    if !file.exists() {
+        return Err(format!("there's no log file in {} yet", paths::log_dir().display()));
+    }
+    let status = std::process::Command::new("open")
+        .arg(&file)
+        .status()
+        .map_err(|e| format!("couldn't run open: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("couldn't open {}", file.display()))
+    }
+}
@@ -0,0 +1,23 @@
+// Small helpers shared by the modules that talk to Cocoa directly
+use objc::{msg_send, sel, sel_impl};
+use objc::runtime::{Class, Object};
+
+// Create an autoreleased NSString from a Rust string
+pub unsafe fn ns_string(value: &str) -> *mut Object {
+    let ns_string_class = Class::get("NSString").unwrap();
+    let c_str = std::ffi::CString::new(value).unwrap_or_default();
+    let ns_value: *mut Object = msg_send![ns_string_class, stringWithUTF8String:c_str.as_ptr()];
+    ns_value
+}
+
+// Copy an NSString (or toll-free bridged CFString) into a Rust string
+pub unsafe fn rust_string(ns_value: *mut Object) -> Option<String> {
+    if ns_value.is_null() {
+        return None;
+    }
+    let utf8: *const libc::c_char = msg_send![ns_value, UTF8String];
+    if utf8.is_null() {
+        return None;
+    }
+    Some(std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned())
+}
@@ -0,0 +1,218 @@
+use crate::config::{AppState, AutoAnswerOverride};
+
+// Settings pushed by an MDM configuration profile for the app's bundle identifier,
+// com.click-to-call.app unless the build or Info.plist says otherwise.
+// Only keys the administrator has forced are set; everything else is None.
+#[derive(Default)]
+pub struct ManagedSettings {
+    pub domain: Option<String>,
+    pub extension: Option<String>,
+    pub key: Option<String>,
+    pub auto_answer: Option<bool>,
+    pub country: Option<String>,
+    pub audit_webhook_url: Option<String>,
+    pub audit_webhook_secret: Option<String>,
+    pub allowed_numbers: Option<String>,
+    pub blocked_numbers: Option<String>,
+    pub directory_url: Option<String>,
+    pub corporate_directory_url: Option<String>,
+    pub corporate_directory_user: Option<String>,
+    pub corporate_directory_password: Option<String>,
+    pub corporate_directory_base: Option<String>,
+    pub large_text: Option<bool>,
+    pub high_contrast: Option<bool>,
+    pub kiosk: bool,
+}
+
+impl ManagedSettings {
+    // Overlay the managed values on top of the user's own settings.
+    // Managed values always win, matching how macOS treats forced preferences.
+    pub fn apply(&self, state: &mut AppState) {
+        if let Some(domain) = &self.domain {
+            state.domain = domain.clone();
+        }
+        if let Some(extension) = &self.extension {
+            state.extension = extension.clone();
+        }
+        if let Some(key) = &self.key {
+            state.key = key.clone();
+        }
+        if let Some(auto_answer) = self.auto_answer {
+            // A forced value applies on every Mac, so there's nothing left to override
+            state.auto_answer = auto_answer;
+            state.auto_answer_override = AutoAnswerOverride::Default;
+        }
+        if let Some(country) = &self.country {
+            state.country = country.clone();
+        }
+        if let Some(url) = &self.audit_webhook_url {
+            state.audit_webhook_url = url.clone();
+        }
+        if let Some(secret) = &self.audit_webhook_secret {
+            state.audit_webhook_secret = secret.clone();
+        }
+        if let Some(allowed) = &self.allowed_numbers {
+            state.allowed_numbers = allowed.clone();
+        }
+        if let Some(blocked) = &self.blocked_numbers {
+            state.blocked_numbers = blocked.clone();
+        }
+        if let Some(url) = &self.directory_url {
+            state.directory_url = url.clone();
+        }
+        if let Some(url) = &self.corporate_directory_url {
+            state.corporate_directory_url = url.clone();
+        }
+        if let Some(user) = &self.corporate_directory_user {
+            state.corporate_directory_user = user.clone();
+        }
+        if let Some(password) = &self.corporate_directory_password {
+            state.corporate_directory_password = password.clone();
+        }
+        if let Some(base) = &self.corporate_directory_base {
+            state.corporate_directory_base = base.clone();
+        }
+        if let Some(large_text) = self.large_text {
+            state.large_text = large_text;
+        }
+        if let Some(high_contrast) = self.high_contrast {
+            state.high_contrast = high_contrast;
+        }
+        state.kiosk = self.kiosk;
+    }
+
+    // Names of the AppState fields this overrides, for the diagnostics view
+    pub fn forced_fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.domain.is_some() {
+            fields.push("domain");
+        }
+        if self.extension.is_some() {
+            fields.push("extension");
+        }
+        if self.key.is_some() {
+            fields.push("key");
+        }
+        if self.auto_answer.is_some() {
+            fields.push("auto_answer");
+            fields.push("auto_answer_override");
+        }
+        if self.country.is_some() {
+            fields.push("country");
+        }
+        if self.audit_webhook_url.is_some() {
+            fields.push("audit_webhook_url");
+        }
+        if self.audit_webhook_secret.is_some() {
+            fields.push("audit_webhook_secret");
+        }
+        if self.allowed_numbers.is_some() {
+            fields.push("allowed_numbers");
+        }
+        if self.blocked_numbers.is_some() {
+            fields.push("blocked_numbers");
+        }
+        if self.directory_url.is_some() {
+            fields.push("directory_url");
+        }
+        if self.corporate_directory_url.is_some() {
+            fields.push("corporate_directory_url");
+        }
+        if self.corporate_directory_user.is_some() {
+            fields.push("corporate_directory_user");
+        }
+        if self.corporate_directory_password.is_some() {
+            fields.push("corporate_directory_password");
+        }
+        if self.corporate_directory_base.is_some() {
+            fields.push("corporate_directory_base");
+        }
+        if self.large_text.is_some() {
+            fields.push("large_text");
+        }
+        if self.high_contrast.is_some() {
+            fields.push("high_contrast");
+        }
+        fields
+    }
+}
+
+// Read managed preferences through NSUserDefaults, which already merges in
+// the forced values from /Library/Managed Preferences for our bundle id
+#[cfg(target_os = "macos")]
+pub fn load_managed_settings() -> ManagedSettings {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+
+    let mut settings = ManagedSettings::default();
+
+    unsafe {
+        let defaults_class = Class::get("NSUserDefaults").unwrap();
+        let defaults: *mut Object = msg_send![defaults_class, standardUserDefaults];
+
+        settings.domain = forced_string(defaults, "Domain");
+        settings.extension = forced_string(defaults, "Extension");
+        settings.key = forced_string(defaults, "Key");
+        settings.auto_answer = forced_bool(defaults, "AutoAnswer");
+        settings.country = forced_string(defaults, "Country");
+        settings.audit_webhook_url = forced_string(defaults, "AuditWebhookURL");
+        settings.audit_webhook_secret = forced_string(defaults, "AuditWebhookSecret");
+        settings.allowed_numbers = forced_string(defaults, "AllowedNumbers");
+        settings.blocked_numbers = forced_string(defaults, "BlockedNumbers");
+        settings.directory_url = forced_string(defaults, "DirectoryURL");
+        settings.corporate_directory_url = forced_string(defaults, "CorporateDirectoryURL");
+        settings.corporate_directory_user = forced_string(defaults, "CorporateDirectoryUser");
+        settings.corporate_directory_password = forced_string(defaults, "CorporateDirectoryPassword");
+        settings.corporate_directory_base = forced_string(defaults, "CorporateDirectoryBase");
+        settings.large_text = forced_bool(defaults, "LargeText");
+        settings.high_contrast = forced_bool(defaults, "HighContrast");
+        settings.kiosk = forced_bool(defaults, "KioskMode").unwrap_or(false);
+    }
+
+    settings
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn load_managed_settings() -> ManagedSettings {
+    // Managed preferences only exist on macOS
+    ManagedSettings::default()
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn is_forced(defaults: *mut objc::runtime::Object, key: *mut objc::runtime::Object) -> bool {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{BOOL, NO};
+
+    let forced: BOOL = msg_send![defaults, objectIsForcedForKey: key];
+    forced != NO
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn forced_string(defaults: *mut objc::runtime::Object, key: &str) -> Option<String> {
+    use crate::macos::{ns_string, rust_string};
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::Object;
+
+    let ns_key = ns_string(key);
+    if !is_forced(defaults, ns_key) {
+        return None;
+    }
+
+    let value: *mut Object = msg_send![defaults, stringForKey: ns_key];
+    rust_string(value)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn forced_bool(defaults: *mut objc::runtime::Object, key: &str) -> Option<bool> {
+    use crate::macos::ns_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{BOOL, NO};
+
+    let ns_key = ns_string(key);
+    if !is_forced(defaults, ns_key) {
+        return None;
+    }
+
+    let value: BOOL = msg_send![defaults, boolForKey: ns_key];
+    Some(value != NO)
+}
@@ -0,0 +1,236 @@
+use druid::{Color, ExtEventSink, Rect, Selector};
+use std::sync::{Mutex, OnceLock};
+use crate::config::AppState;
+
+// Sent to the delegate when the menu bar icon is clicked
+pub const STATUS_ITEM_CLICKED: Selector = Selector::new("app.status-item-clicked");
+
+// Where clicks are forwarded, set once the app is running
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+// The NSStatusItem while the icon is shown, 0 otherwise. Only touched on the main thread.
+static STATUS_ITEM: Mutex<usize> = Mutex::new(0);
+// Problem the PBX health check found, shown on the icon; None while all's well
+static WARNING: Mutex<Option<String>> = Mutex::new(None);
+// The extension's presence, added to the icon's tooltip; None while it isn't asked
+static PRESENCE: Mutex<Option<String>> = Mutex::new(None);
+// New voicemail messages, shown next to the icon while there are any
+static VOICEMAIL: Mutex<u32> = Mutex::new(0);
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct NSPoint {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct NSSize {
+        pub width: f64,
+        pub height: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct NSRect {
+        pub origin: NSPoint,
+        pub size: NSSize,
+    }
+
+    // NSVariableStatusItemLength
+    pub const VARIABLE_LENGTH: f64 = -1.0;
+}
+
+// Show or remove the menu bar icon, tinted with the configuration's color.
+// Must be called on the main thread.
+#[cfg(target_os = "macos")]
+pub fn set_enabled(sink: ExtEventSink, enabled: bool, color: &Color) {
+    use druid::Target;
+    use objc::declare::ClassDecl;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, Sel};
+
+    extern "C" fn clicked(_this: &Object, _: Sel, _sender: *mut Object) {
+        if let Some(sink) = EVENT_SINK.get() {
+            sink.submit_command(STATUS_ITEM_CLICKED, (), Target::Auto).ok();
+        }
+    }
+
+    EVENT_SINK.set(sink).ok();
+    let mut status_item = STATUS_ITEM.lock().unwrap();
+
+    unsafe {
+        let status_bar_class = Class::get("NSStatusBar").unwrap();
+        let status_bar: *mut Object = msg_send![status_bar_class, systemStatusBar];
+
+        if !enabled {
+            if *status_item != 0 {
+                let item = *status_item as *mut Object;
+                let _: () = msg_send![status_bar, removeStatusItem: item];
+                let _: () = msg_send![item, release];
+                *status_item = 0;
+            }
+            return;
+        }
+
+        if *status_item == 0 {
+            // Click target for the button, declared once
+            let target_class = match Class::get("ClickToCallStatusItemTarget") {
+                Some(class) => class,
+                None => {
+                    let superclass = Class::get("NSObject").unwrap();
+                    let mut decl = ClassDecl::new("ClickToCallStatusItemTarget", superclass).unwrap();
+                    decl.add_method(sel!(statusItemClicked:), clicked as extern "C" fn(&Object, Sel, *mut Object));
+                    decl.register()
+                }
+            };
+            // Buttons don't retain their target, so this one is never released
+            let target: *mut Object = msg_send![target_class, new];
+
+            let item: *mut Object = msg_send![status_bar, statusItemWithLength: ffi::VARIABLE_LENGTH];
+            let _: *mut Object = msg_send![item, retain];
+            let button: *mut Object = msg_send![item, button];
+            show_warning(button, WARNING.lock().unwrap().as_deref());
+
+            let _: () = msg_send![button, setTarget: target];
+            let _: () = msg_send![button, setAction: sel!(statusItemClicked:)];
+            *status_item = item as usize;
+        }
+
+        // The symbol is a template image, so the tint shows which PBX calls go through
+        let (red, green, blue, alpha) = color.as_rgba();
+        let color_class = Class::get("NSColor").unwrap();
+        let tint: *mut Object = msg_send![color_class, colorWithSRGBRed:red green:green blue:blue alpha:alpha];
+        let button: *mut Object = msg_send![*status_item as *mut Object, button];
+        let _: () = msg_send![button, setContentTintColor: tint];
+    }
+}
+
+// Put a warning sign on the icon with `warning` as its tooltip, or go back to
+// the phone with None. Can be called from any thread.
+pub fn set_warning(warning: Option<String>) {
+    *WARNING.lock().unwrap() = warning;
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(|_data: &mut AppState| update_warning());
+    }
+}
+
+// Say `presence` in the icon's tooltip after the identity, or stop with None.
+// Can be called from any thread.
+pub fn set_presence(presence: Option<String>) {
+    *PRESENCE.lock().unwrap() = presence;
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(|_data: &mut AppState| update_warning());
+    }
+}
+
+// Show `count` new voicemail messages next to the icon, or nothing with 0.
+// Can be called from any thread.
+pub fn set_voicemail(count: u32) {
+    *VOICEMAIL.lock().unwrap() = count;
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(|_data: &mut AppState| update_warning());
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn update_warning() {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::Object;
+
+    let status_item = *STATUS_ITEM.lock().unwrap();
+    if status_item == 0 {
+        return;
+    }
+    unsafe {
+        let button: *mut Object = msg_send![status_item as *mut Object, button];
+        show_warning(button, WARNING.lock().unwrap().as_deref());
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn update_warning() {}
+
+// The phone, or a warning sign while there's a `warning`, on the icon's button
+#[cfg(target_os = "macos")]
+unsafe fn show_warning(button: *mut objc::runtime::Object, warning: Option<&str>) {
+    use crate::macos::ns_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, BOOL, NO};
+
+    let mut identity = match PRESENCE.lock().unwrap().as_deref() {
+        Some(presence) => format!("{} ({})", crate::identity::identity().name, presence),
+        None => crate::identity::identity().name.clone(),
+    };
+    let voicemail = *VOICEMAIL.lock().unwrap();
+    if voicemail > 0 {
+        identity.push_str(&format!(", {} new voicemail", voicemail));
+    }
+    let (symbol, text, tooltip) = match warning {
+        Some(warning) => ("exclamationmark.triangle.fill", "⚠", warning),
+        None => ("phone.fill", "☎", identity.as_str()),
+    };
+
+    // SF Symbols need macOS 11; older systems get a text icon
+    let image_class = Class::get("NSImage").unwrap();
+    let has_symbols: BOOL = msg_send![image_class, respondsToSelector: sel!(imageWithSystemSymbolName:accessibilityDescription:)];
+    if has_symbols != NO {
+        let image: *mut Object = msg_send![image_class,
+            imageWithSystemSymbolName: ns_string(symbol)
+            accessibilityDescription: ns_string(tooltip)];
+        let _: () = msg_send![button, setImage: image];
+        // The count of new voicemail sits next to the symbol, like a badge
+        let badge = if voicemail > 0 { format!(" {}", voicemail) } else { String::new() };
+        let _: () = msg_send![button, setTitle: ns_string(&badge)];
+    } else if voicemail > 0 {
+        let _: () = msg_send![button, setTitle: ns_string(&format!("{} {}", text, voicemail))];
+    } else {
+        let _: () = msg_send![button, setTitle: ns_string(text)];
+    }
+    let _: () = msg_send![button, setToolTip: ns_string(tooltip)];
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn set_enabled(sink: ExtEventSink, enabled: bool, _color: &Color) {
+    // There's no menu bar to put an icon in outside macOS
+    EVENT_SINK.set(sink).ok();
+    *STATUS_ITEM.lock().unwrap() = usize::from(enabled);
+}
+
+// Where the icon is on screen, in points from the top left of the main display,
+// so the dial popover can be placed right below it
+#[cfg(target_os = "macos")]
+pub fn icon_bounds() -> Option<Rect> {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+
+    let status_item = *STATUS_ITEM.lock().unwrap();
+    if status_item == 0 {
+        return None;
+    }
+
+    unsafe {
+        let button: *mut Object = msg_send![status_item as *mut Object, button];
+        let window: *mut Object = msg_send![button, window];
+        if window.is_null() {
+            return None;
+        }
+        let frame: ffi::NSRect = msg_send![window, frame];
+
+        // Cocoa measures from the bottom left of the main display
+        let screen_class = Class::get("NSScreen").unwrap();
+        let screens: *mut Object = msg_send![screen_class, screens];
+        let main_screen: *mut Object = msg_send![screens, objectAtIndex: 0usize];
+        let screen_frame: ffi::NSRect = msg_send![main_screen, frame];
+
+        let top = screen_frame.size.height - frame.origin.y - frame.size.height;
+        Some(Rect::from_origin_size((frame.origin.x, top), (frame.size.width, frame.size.height)))
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn icon_bounds() -> Option<Rect> {
+    None
+}
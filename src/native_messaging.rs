@@ -0,0 +1,203 @@
+// Native messaging (--native-messaging): how a browser extension talks to the
+// app, e.g. one that turns the numbers on web pages into buttons. The browser
+// starts this process and exchanges messages with it on stdin and stdout,
+// each a JSON object after its length as 4 bytes in native byte order. The
+// messages are the socket's (see ipc), so the extension sends
+// {"type": "call", "version": 1, "payload": {"number": "..."}} and gets the
+// running app's answer back. With no app running, a call starts the app for
+// it the way a clicked tel: link does.
+//
+// --install-native-host <browser> <extension id> writes the host manifest
+// the browser looks for, and a small script that starts this in the mode.
+
+use crate::identity::identity;
+use crate::ipc::{self, Command, Reply, RequestError, Response, PROTOCOL_VERSION};
+use crate::{call_links, get_socket_path, paths, tel_uri};
+use serde_json::json;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tracing::{info, warn};
+
+// Browsers send at most 4 GB, but the app only ever takes a number
+const MAX_MESSAGE_SIZE: u32 = 1024 * 1024;
+
+// Browsers that look for native messaging hosts, with the folder of each
+// under ~/Library/Application Support
+const BROWSERS: &[(&str, &str)] = &[
+    ("chrome", "Google/Chrome"),
+    ("chromium", "Chromium"),
+    ("edge", "Microsoft Edge"),
+    ("brave", "BraveSoftware/Brave-Browser"),
+    ("firefox", "Mozilla"),
+];
+
+// The host's name, which the extension connects to: the bundle identifier in
+// the letters browsers allow, e.g. com.click_to_call.app
+pub fn host_name() -> String {
+    identity()
+        .bundle_id
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '_' })
+        .collect()
+}
+
+// Read one message; None once the browser has closed the connection
+pub fn read_message(input: &mut impl Read) -> Result<Option<Vec<u8>>, String> {
+    let mut length = [0u8; 4];
+    match input.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(format!("couldn't read a message: {}", e)),
+    }
+    let length = u32::from_ne_bytes(length);
+    if length > MAX_MESSAGE_SIZE {
+        return Err(format!("message longer than {} bytes", MAX_MESSAGE_SIZE));
+    }
+    let mut message = vec![0; length as usize];
+    input.read_exact(&mut message).map_err(|e| format!("couldn't read a message: {}", e))?;
+    Ok(Some(message))
+}
+
+pub fn write_message(output: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    output.write_all(&(message.len() as u32).to_ne_bytes())?;
+    output.write_all(message)?;
+    output.flush()
+}
+
+// Answer the browser's messages until it closes the connection. Returns the
+// exit code; stdout carries nothing but messages, the log goes to stderr.
+pub fn run() -> i32 {
+    info!("Serving native messaging for {}", host_name());
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return 0,
+            Err(reason) => {
+                warn!("Native messaging: {}", reason);
+                return 1;
+            }
+        };
+        let response = answer(&String::from_utf8_lossy(&message));
+        // Only strings and numbers go in, which always serialize
+        let response = serde_json::to_vec(&response).unwrap_or_default();
+        if let Err(e) = write_message(&mut output, &response) {
+            warn!("Native messaging: couldn't answer: {}", e);
+            return 1;
+        }
+    }
+}
+
+// The answer to one message, from the running app if there is one
+pub fn answer(message: &str) -> Response {
+    let request = match ipc::parse_request(message) {
+        Ok(request) => request,
+        Err(message) => return Response { version: PROTOCOL_VERSION, id: None, reply: Reply::Error { message } },
+    };
+    let reply = match ipc::request(&get_socket_path(), request.command.clone()) {
+        Ok(reply) => reply,
+        Err(RequestError::NotRunning) => answer_here(request.command).unwrap_or_else(|message| Reply::Error { message }),
+        Err(RequestError::NoReply(reason)) => Reply::Error { message: format!("the app didn't answer: {}", reason) },
+    };
+    Response { version: PROTOCOL_VERSION, id: request.id, reply }
+}
+
+// With no app running there's nothing to ping, reload or shut down, and a
+// call starts the app with the link, which dials it and quits
+fn answer_here(command: Command) -> Result<Reply, String> {
+    match command {
+        Command::Call { number, private } => {
+            // Private calls go as a private tel: link, others as they came, callbacks and all
+            let link = match call_links::read(&number) {
+                Some(link) if private => tel_uri::link(&tel_uri::parse(&link?.tel)?.dial_string(), true),
+                Some(link) => {
+                    link?;
+                    number.trim().to_string()
+                }
+                None => tel_uri::link(number.trim(), private),
+            };
+            let executable = std::env::current_exe().map_err(|e| format!("couldn't find the app: {}", e))?;
+            // Its own process group, so it isn't stopped with this one once the browser has its answer
+            std::process::Command::new(executable)
+                .arg(&link)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .process_group(0)
+                .spawn()
+                .map_err(|e| format!("couldn't start the app: {}", e))?;
+            Ok(Reply::Ok)
+        }
+        Command::Ping => Err(format!("{} isn't running", identity().name)),
+        Command::ReloadConfig | Command::Shutdown => Ok(Reply::Ok),
+    }
+}
+
+// The host manifest `browser` reads, for the script at `path`
+pub fn manifest(browser: &str, extension_id: &str, path: &str) -> serde_json::Value {
+    let mut manifest = json!({
+        "name": host_name(),
+        "description": format!("Places calls with {}", identity().name),
+        "path": path,
+        "type": "stdio",
+    });
+    // Firefox names extensions by their id, Chrome and its relatives by origin
+    if browser == "firefox" {
+        manifest["allowed_extensions"] = json!([extension_id]);
+    } else {
+        manifest["allowed_origins"] = json!([format!("chrome-extension://{}/", extension_id)]);
+    }
+    manifest
+}
+
+fn manifest_file(browser: &str) -> Result<PathBuf, String> {
+    let Some((_, folder)) = BROWSERS.iter().find(|(name, _)| *name == browser) else {
+        let names: Vec<_> = BROWSERS.iter().map(|(name, _)| *name).collect();
+        return Err(format!("Unknown browser {}, expected one of {}", browser, names.join(", ")));
+    };
+    let support = dirs::config_dir().ok_or("Couldn't find ~/Library/Application Support")?;
+    Ok(support.join(folder).join("NativeMessagingHosts").join(format!("{}.json", host_name())))
+}
+
+// The script browsers start, since a manifest can't pass arguments
+fn host_script() -> PathBuf {
+    paths::config_dir().join("native-messaging-host")
+}
+
+// Let the extension `extension_id` in `browser` talk to this app. Returns the manifest's path.
+pub fn install_host(browser: &str, extension_id: &str) -> Result<PathBuf, String> {
+    let browser = browser.to_lowercase();
+    let path = manifest_file(&browser)?;
+    if extension_id.trim().is_empty() {
+        return Err("Give the extension's id, as the browser's extensions page shows it".to_string());
+    }
+    let executable = std::env::current_exe().map_err(|e| format!("Couldn't find this executable: {}", e))?;
+
+    let script = host_script();
+    std::fs::create_dir_all(paths::config_dir()).map_err(|e| format!("Couldn't create {}: {}", paths::config_dir().display(), e))?;
+    let text = format!("#!/bin/sh\n# Started by the browser for its extension\nexec \"{}\" --native-messaging \"$@\"\n", executable.display());
+    std::fs::write(&script, text).map_err(|e| format!("Couldn't write {}: {}", script.display(), e))?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).map_err(|e| format!("Couldn't make {} executable: {}", script.display(), e))?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+    }
+    let manifest = manifest(&browser, extension_id.trim(), &script.display().to_string());
+    let text = serde_json::to_string_pretty(&manifest).map_err(|e| format!("Couldn't write the manifest: {}", e))?;
+    std::fs::write(&path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// Remove the manifest for `browser`. Returns its path.
+pub fn uninstall_host(browser: &str) -> Result<PathBuf, String> {
+    let path = manifest_file(&browser.to_lowercase())?;
+    if !path.exists() {
+        return Err(format!("No native messaging host at {}", path.display()));
+    }
+    std::fs::remove_file(&path).map_err(|e| format!("Couldn't remove {}: {}", path.display(), e))?;
+    Ok(path)
+}
@@ -0,0 +1,349 @@
+use crate::config::AppState;
+use crate::{block_on, http_client, proxy};
+use druid::{ExtEventSink, Selector};
+use std::sync::{OnceLock, RwLock};
+use tracing::warn;
+
+// Sent to the delegate with the action of a notification the user clicked
+pub const NOTIFICATION_CLICKED: Selector<String> = Selector::new("app.notification-clicked");
+
+// Notification actions
+pub const ACTION_SHOW_STATS: &str = "stats";
+// Followed by the number to dial
+pub const ACTION_CALL_AGAIN: &str = "call:";
+// Like ACTION_CALL_AGAIN, but asks before dialing
+pub const ACTION_CONFIRM_CALL: &str = "confirm-call:";
+// Followed by the number to put on the clipboard
+pub const ACTION_COPY_NUMBER: &str = "copy:";
+// Followed by a number that was copied, to dial
+pub const ACTION_CALL_COPIED: &str = "call-copied:";
+
+// Where a notification keeps the action for a click on the notification
+// itself; each button's action is kept under the button's identifier
+const CLICK_KEY: &str = "action";
+const REDIAL_BUTTON: &str = "redial";
+const COPY_NUMBER_BUTTON: &str = "copy-number";
+const CALL_BUTTON: &str = "call";
+// Notifications in this category show both buttons
+const CALL_FAILED_CATEGORY: &str = "call-failed";
+// Notifications in this one show Call
+const NUMBER_COPIED_CATEGORY: &str = "number-copied";
+
+// Somewhere call events get reported: Notification Center, the status line,
+// a chat webhook, ...
+pub trait NotificationSink: Send + Sync {
+    fn notify(&self, title: &str, message: &str);
+}
+
+// Extra sinks on top of Notification Center, which is always used
+static SINKS: RwLock<Vec<Box<dyn NotificationSink>>> = RwLock::new(Vec::new());
+// Where notification clicks are forwarded
+static CLICK_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+// Report a call event to every configured sink
+pub fn notify(title: &str, message: &str) {
+    SystemSink.notify(title, message);
+    for sink in SINKS.read().unwrap().iter() {
+        sink.notify(title, message);
+    }
+}
+
+// Replace the extra sinks, e.g. after settings were saved
+pub fn set_sinks(sinks: Vec<Box<dyn NotificationSink>>) {
+    *SINKS.write().unwrap() = sinks;
+}
+
+// macOS Notification Center
+pub struct SystemSink;
+
+impl NotificationSink for SystemSink {
+    fn notify(&self, title: &str, message: &str) {
+        show_notification(title, message);
+    }
+}
+
+// Mirrors events in the main window's status line, so calls placed from
+// tel: links or the socket show up there too
+pub struct InAppSink {
+    pub event_sink: ExtEventSink,
+}
+
+impl NotificationSink for InAppSink {
+    fn notify(&self, title: &str, message: &str) {
+        let status = format!("{}: {}", title, message);
+        self.event_sink.add_idle_callback(move |data: &mut AppState| {
+            data.status_message = status;
+        });
+    }
+}
+
+// Posts events to a Slack-compatible incoming webhook ({"text": ...}),
+// which Mattermost, Rocket.Chat and most internal chat tools also accept
+pub struct WebhookSink {
+    pub url: String,
+    // Who placed the call, e.g. "101@pbx.example.com", so supervisors can tell users apart
+    pub source: String,
+}
+
+impl NotificationSink for WebhookSink {
+    fn notify(&self, title: &str, message: &str) {
+        let body = serde_json::json!({
+            "text": format!("[{}] {}: {}", self.source, title, message),
+        });
+
+        // Call events are reported from the request tasks, so waiting here is fine
+        // and lets a dial-and-exit launch deliver the webhook before quitting
+        match block_on(async {
+            proxy::prepare(&self.url).await;
+            http_client().post(&self.url).json(&body).send().await
+        }) {
+            Ok(response) if !response.status().is_success() => {
+                warn!("Notification webhook returned HTTP status {}", response.status());
+            }
+            Err(e) => warn!("Notification webhook failed: {}", e),
+            _ => {}
+        }
+    }
+}
+
+// Function to show a notification
+pub fn show_notification(title: &str, message: &str) {
+    deliver_notification(title, message, None, &[]);
+}
+
+// Show a notification that sends NOTIFICATION_CLICKED with `action` when clicked
+pub fn show_notification_with_action(title: &str, message: &str, action: &str) {
+    deliver_notification(title, message, None, &[(CLICK_KEY, action.to_string())]);
+}
+
+// Report a failed call to every sink. The notification gets Redial and Copy
+// Number buttons for `number`.
+pub fn notify_call_failed(number: &str, message: &str) {
+    let title = "Call Failed";
+    let actions = [
+        (REDIAL_BUTTON, format!("{}{}", ACTION_CALL_AGAIN, number)),
+        (COPY_NUMBER_BUTTON, format!("{}{}", ACTION_COPY_NUMBER, number)),
+    ];
+    deliver_notification(title, message, Some(CALL_FAILED_CATEGORY), &actions);
+    for sink in SINKS.read().unwrap().iter() {
+        sink.notify(title, message);
+    }
+}
+
+// Offer to call `number`, which was just copied, shown as `shown`. Only
+// Notification Center hears of it; it's not a call event.
+pub fn notify_number_copied(number: &str, shown: &str) {
+    let action = format!("{}{}", ACTION_CALL_COPIED, number);
+    let actions = [(CLICK_KEY, action.clone()), (CALL_BUTTON, action)];
+    deliver_notification("Call Copied Number?", &format!("{} is on the clipboard", shown), Some(NUMBER_COPIED_CATEGORY), &actions);
+}
+
+// Identifier of the single notification we show; a new one replaces the last
+#[cfg(target_os = "macos")]
+const NOTIFICATION_ID: &str = "click-to-call";
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    // UserNotifications is only used through the Objective-C runtime, but has to be loaded
+    #[link(name = "UserNotifications", kind = "framework")]
+    extern "C" {}
+}
+
+// Notification Center only serves apps with a bundle identifier; asking for
+// it from a bare binary (cargo run) throws
+#[cfg(target_os = "macos")]
+unsafe fn notification_center() -> Option<*mut objc::runtime::Object> {
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+
+    let bundle: *mut Object = msg_send![Class::get("NSBundle")?, mainBundle];
+    let identifier: *mut Object = msg_send![bundle, bundleIdentifier];
+    if identifier.is_null() {
+        return None;
+    }
+    let center: *mut Object = msg_send![Class::get("UNUserNotificationCenter")?, currentNotificationCenter];
+    Some(center)
+}
+
+// Ask to show alerts and register the notification buttons. macOS only asks
+// the user once; later calls just find out the answer.
+#[cfg(target_os = "macos")]
+fn request_authorization() {
+    use crate::macos::ns_string;
+    use block::ConcreteBlock;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, BOOL, NO};
+    use std::sync::Once;
+
+    static REQUESTED: Once = Once::new();
+    REQUESTED.call_once(|| unsafe {
+        let center = match notification_center() {
+            Some(center) => center,
+            None => {
+                warn!("Not running from the app bundle, notifications won't be shown");
+                return;
+            }
+        };
+
+        // UNAuthorizationOptionSound | UNAuthorizationOptionAlert
+        let options: usize = (1 << 1) | (1 << 2);
+        let on_answer = ConcreteBlock::new(|granted: BOOL, _error: *mut Object| {
+            if granted == NO {
+                warn!("Notifications aren't allowed; turn them on in System Settings > Notifications");
+            }
+        })
+        .copy();
+        let _: () = msg_send![center, requestAuthorizationWithOptions:options completionHandler:&*on_answer];
+
+        // Call Failed has both buttons, a copied number has Call; none of
+        // them needs the app in front
+        let action_class = Class::get("UNNotificationAction").unwrap();
+        let redial: *mut Object = msg_send![action_class, actionWithIdentifier:ns_string(REDIAL_BUTTON) title:ns_string("Redial") options:0usize];
+        let copy: *mut Object = msg_send![action_class, actionWithIdentifier:ns_string(COPY_NUMBER_BUTTON) title:ns_string("Copy Number") options:0usize];
+        let call: *mut Object = msg_send![action_class, actionWithIdentifier:ns_string(CALL_BUTTON) title:ns_string("Call") options:0usize];
+        let array_class = Class::get("NSArray").unwrap();
+        let failed_actions: *mut Object = msg_send![array_class, arrayWithObjects:[redial, copy].as_ptr() count:2usize];
+        let copied_actions: *mut Object = msg_send![array_class, arrayWithObject: call];
+        let empty: *mut Object = msg_send![array_class, array];
+
+        let category_class = Class::get("UNNotificationCategory").unwrap();
+        let failed: *mut Object = msg_send![category_class, categoryWithIdentifier:ns_string(CALL_FAILED_CATEGORY) actions:failed_actions intentIdentifiers:empty options:0usize];
+        let copied: *mut Object = msg_send![category_class, categoryWithIdentifier:ns_string(NUMBER_COPIED_CATEGORY) actions:copied_actions intentIdentifiers:empty options:0usize];
+        let set_class = Class::get("NSSet").unwrap();
+        let categories: *mut Object = msg_send![set_class, setWithObjects:[failed, copied].as_ptr() count:2usize];
+        let _: () = msg_send![center, setNotificationCategories: categories];
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn deliver_notification(title: &str, message: &str, category: Option<&str>, actions: &[(&str, String)]) {
+    use crate::macos::ns_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+    use tracing::debug;
+    
+    debug!("Showing notification - Title: '{}', Message: '{}'", title, message);
+    request_authorization();
+    
+    unsafe {
+        let center = match notification_center() {
+            Some(center) => center,
+            None => return,
+        };
+        
+        let content_class = Class::get("UNMutableNotificationContent").unwrap();
+        let content: *mut Object = msg_send![content_class, new];
+        let _: () = msg_send![content, setTitle: ns_string(title)];
+        let _: () = msg_send![content, setBody: ns_string(message)];
+        if let Some(category) = category {
+            let _: () = msg_send![content, setCategoryIdentifier: ns_string(category)];
+        }
+        
+        // Remember what clicking it, or each of its buttons, should do
+        if !actions.is_empty() {
+            let dictionary_class = Class::get("NSMutableDictionary").unwrap();
+            let user_info: *mut Object = msg_send![dictionary_class, dictionary];
+            for (key, action) in actions {
+                let _: () = msg_send![user_info, setObject:ns_string(action) forKey:ns_string(key)];
+            }
+            let _: () = msg_send![content, setUserInfo: user_info];
+        }
+        
+        // No trigger means right away
+        let request_class = Class::get("UNNotificationRequest").unwrap();
+        let nil: *mut Object = std::ptr::null_mut();
+        let request: *mut Object = msg_send![request_class, requestWithIdentifier:ns_string(NOTIFICATION_ID) content:content trigger:nil];
+        let _: () = msg_send![center, addNotificationRequest:request withCompletionHandler:nil];
+        let _: () = msg_send![content, release];
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn deliver_notification(_title: &str, _message: &str, _category: Option<&str>, _actions: &[(&str, String)]) {
+    // Placeholder for other platforms
+}
+
+// Forward clicks on notifications and their buttons to the delegate, and
+// ask for permission to show them. Safe to call more than once.
+#[cfg(target_os = "macos")]
+pub fn install_click_handler(sink: ExtEventSink) {
+    use crate::macos::{ns_string, rust_string};
+    use block::Block;
+    use druid::Target;
+    use objc::declare::ClassDecl;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, Sel};
+    
+    // UNNotificationDefaultActionIdentifier, sent for a click on the notification itself
+    const DEFAULT_ACTION: &str = "com.apple.UNNotificationDefaultActionIdentifier";
+    
+    extern "C" fn did_receive(_this: &Object, _: Sel, _center: *mut Object, response: *mut Object, completion: *mut Object) {
+        unsafe {
+            let button: *mut Object = msg_send![response, actionIdentifier];
+            let key = match rust_string(button) {
+                Some(button) if button != DEFAULT_ACTION => button,
+                _ => CLICK_KEY.to_string(),
+            };
+            
+            let notification: *mut Object = msg_send![response, notification];
+            let request: *mut Object = msg_send![notification, request];
+            let content: *mut Object = msg_send![request, content];
+            let user_info: *mut Object = msg_send![content, userInfo];
+            if !user_info.is_null() {
+                let action: *mut Object = msg_send![user_info, objectForKey: ns_string(&key)];
+                if let (Some(action), Some(sink)) = (rust_string(action), CLICK_SINK.get()) {
+                    sink.submit_command(NOTIFICATION_CLICKED, action, Target::Auto).ok();
+                }
+            }
+            
+            let completion = &*(completion as *mut Block<(), ()>);
+            completion.call(());
+        }
+    }
+    
+    // Without this, notifications aren't shown while the app is in front
+    extern "C" fn will_present(_this: &Object, _: Sel, _center: *mut Object, _notification: *mut Object, completion: *mut Object) {
+        // UNNotificationPresentationOptionAlert
+        let options: usize = 1 << 2;
+        unsafe {
+            let completion = &*(completion as *mut Block<(usize,), ()>);
+            completion.call((options,));
+        }
+    }
+    
+    if CLICK_SINK.set(sink).is_err() {
+        return;
+    }
+    request_authorization();
+    
+    unsafe {
+        let center = match notification_center() {
+            Some(center) => center,
+            None => return,
+        };
+        let superclass = Class::get("NSObject").unwrap();
+        let mut decl = match ClassDecl::new("ClickToCallNotificationDelegate", superclass) {
+            Some(decl) => decl,
+            None => return,
+        };
+        decl.add_method(
+            sel!(userNotificationCenter:didReceiveNotificationResponse:withCompletionHandler:),
+            did_receive as extern "C" fn(&Object, Sel, *mut Object, *mut Object, *mut Object),
+        );
+        decl.add_method(
+            sel!(userNotificationCenter:willPresentNotification:withCompletionHandler:),
+            will_present as extern "C" fn(&Object, Sel, *mut Object, *mut Object, *mut Object),
+        );
+        let delegate_class = decl.register();
+        
+        // The notification center doesn't retain its delegate, so this one is never released
+        let delegate: *mut Object = msg_send![delegate_class, new];
+        let _: () = msg_send![center, setDelegate: delegate];
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn install_click_handler(sink: ExtEventSink) {
+    // No notifications to click on other platforms
+    CLICK_SINK.set(sink).ok();
+}
@@ -0,0 +1,260 @@
+use crate::emergency;
+use std::ops::RangeInclusive;
+
+// Fewer digits than this is an extension or short code, which every region allows
+const MIN_PUBLIC_DIGITS: usize = 7;
+
+// How numbers are written in a country
+struct Region {
+    // ISO 3166 codes sharing this numbering plan
+    countries: &'static [&'static str],
+    calling_code: &'static str,
+    // Dialed before national numbers, e.g. the 0 in "02 9876 5432"
+    trunk_prefix: &'static str,
+    // Dialed before a calling code to call abroad, e.g. 011 from the US
+    international_prefix: &'static str,
+    // Length of the national significant number (without the trunk prefix)
+    lengths: RangeInclusive<usize>,
+    // Digit groups for a national significant number
+    groups: fn(&str) -> &'static [usize],
+    // Numbers dialed as they are rather than as national numbers, written as
+    // for the Short Codes setting (see `matches_short_code`)
+    short_codes: &'static [&'static str],
+}
+
+const REGIONS: &[Region] = &[
+    Region {
+        countries: &["US", "CA"],
+        calling_code: "1",
+        trunk_prefix: "1",
+        international_prefix: "011",
+        lengths: 10..=10,
+        groups: |_| &[3, 3, 4],
+        // Carrier access codes (101XXXX) dialed in front of the number
+        short_codes: &["101xxxx*"],
+    },
+    Region {
+        countries: &["GB"],
+        calling_code: "44",
+        trunk_prefix: "0",
+        international_prefix: "00",
+        lengths: 9..=10,
+        // London-style "020 7946 0958", otherwise "07700 900123"
+        groups: |nsn| if nsn.starts_with('2') { &[2, 4, 4] } else { &[4, 6] },
+        short_codes: &[],
+    },
+    Region {
+        countries: &["AU"],
+        calling_code: "61",
+        trunk_prefix: "0",
+        international_prefix: "0011",
+        lengths: 9..=9,
+        // Mobiles "0412 345 678", landlines "02 9876 5432"
+        groups: |nsn| if nsn.starts_with('4') { &[3, 3, 3] } else { &[1, 4, 4] },
+        // 13, 1300, 1800 and 1900 business numbers, and 1831 to hide caller ID
+        short_codes: &["13xxxx", "1300xxxxxx", "1800xxxxxx", "1900xxxxxx", "1831*"],
+    },
+    Region {
+        countries: &["NZ"],
+        calling_code: "64",
+        trunk_prefix: "0",
+        international_prefix: "00",
+        lengths: 8..=10,
+        // Mobiles "021 123 4567", landlines "09 123 4567"
+        groups: |nsn| if nsn.starts_with('2') { &[2, 3, 5] } else { &[1, 3, 4] },
+        short_codes: &[],
+    },
+    Region {
+        countries: &["FR"],
+        calling_code: "33",
+        trunk_prefix: "0",
+        international_prefix: "00",
+        lengths: 9..=9,
+        groups: |_| &[1, 2, 2, 2, 2],
+        short_codes: &[],
+    },
+];
+
+fn region_for_country(country: &str) -> Option<&'static Region> {
+    let country = country.trim().to_uppercase();
+    REGIONS.iter().find(|region| region.countries.contains(&country.as_str()))
+}
+
+// The region and national significant number `number` belongs to, reading it
+// as a number dialed from `home`
+fn parse(number: &str, home: Option<&'static Region>) -> Option<(&'static Region, String)> {
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    if number.trim_start().starts_with('+') {
+        return REGIONS.iter().find_map(|region| {
+            let nsn = digits.strip_prefix(region.calling_code)?;
+            region.lengths.contains(&nsn.len()).then(|| (region, nsn.to_string()))
+        });
+    }
+
+    let home = home?;
+    match digits.strip_prefix(home.trunk_prefix) {
+        Some(nsn) if home.lengths.contains(&nsn.len()) => Some((home, nsn.to_string())),
+        _ if home.lengths.contains(&digits.len()) => Some((home, digits)),
+        _ => None,
+    }
+}
+
+// Whether `digits` fit a short code pattern: digits stand for themselves, x
+// for any one digit, and a * at the end for anything after, e.g. "13xxxx"
+// or "1831*"
+fn matches_short_code(digits: &str, pattern: &str) -> bool {
+    let (pattern, open_ended) = match pattern.strip_suffix('*') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    if digits.len() < pattern.len() || (!open_ended && digits.len() != pattern.len()) {
+        return false;
+    }
+    pattern.chars().zip(digits.chars()).all(|(expected, digit)| expected == 'x' || expected == digit)
+}
+
+// Read short code patterns separated by commas, e.g. "13xxxx, 1831*"
+pub fn parse_short_codes(short_codes: &str) -> Result<Vec<String>, String> {
+    let mut parsed = Vec::new();
+    for code in short_codes.split(',').map(str::trim).filter(|code| !code.is_empty()) {
+        let code = code.to_lowercase();
+        let fixed = code.strip_suffix('*').unwrap_or(&code);
+        if fixed.is_empty() || !fixed.chars().all(|c| c.is_ascii_digit() || c == 'x') {
+            return Err(format!("Short code \"{}\" can only have digits, x for any digit and * at the end", code));
+        }
+        parsed.push(code);
+    }
+    Ok(parsed)
+}
+
+// Whether `number` is dialed exactly as typed in `country`: an emergency
+// number, one of the country's short codes or one from `short_codes` (the
+// profile's own patterns, as read by `parse_short_codes`)
+pub fn is_short_code(number: &str, country: &str, short_codes: &str) -> bool {
+    if number.trim_start().starts_with('+') {
+        return false;
+    }
+    if emergency::is_emergency_number(number, country) {
+        return true;
+    }
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() {
+        return false;
+    }
+
+    let built_in = region_for_country(country).map_or(&[][..], |region| region.short_codes);
+    let configured = parse_short_codes(short_codes).unwrap_or_default();
+    built_in
+        .iter()
+        .copied()
+        .chain(configured.iter().map(String::as_str))
+        .any(|pattern| matches_short_code(&digits, pattern))
+}
+
+fn grouped(nsn: &str, groups: &[usize]) -> String {
+    let mut parts = Vec::new();
+    let mut rest = nsn;
+    for (i, size) in groups.iter().enumerate() {
+        // The last group takes whatever is left
+        if i == groups.len() - 1 || rest.len() <= *size {
+            break;
+        }
+        let (part, remainder) = rest.split_at(*size);
+        parts.push(part);
+        rest = remainder;
+    }
+    parts.push(rest);
+    parts.join(" ")
+}
+
+// How `number` is written in `country`: national format for the country's own
+// numbers, international format for other countries we know, and unchanged otherwise
+pub fn format_number(number: &str, country: &str) -> String {
+    let home = region_for_country(country);
+    let (region, nsn) = match parse(number, home) {
+        Some(parsed) => parsed,
+        None => return number.to_string(),
+    };
+
+    let groups = (region.groups)(&nsn);
+    if home.is_some_and(|home| std::ptr::eq(home, region)) {
+        // North American numbers are written "(555) 123-4567" without the 1
+        if region.calling_code == "1" {
+            return format!("({}) {}-{}", &nsn[..3], &nsn[3..6], &nsn[6..]);
+        }
+        format!("{}{}", region.trunk_prefix, grouped(&nsn, groups))
+    } else {
+        format!("+{} {}", region.calling_code, grouped(&nsn, groups))
+    }
+}
+
+// Whether `number` could be dialed from `country`. Extensions and short codes
+// always pass, as does anything in a country we have no numbering plan for.
+pub fn is_plausible(number: &str, country: &str, short_codes: &str) -> bool {
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() < MIN_PUBLIC_DIGITS || is_short_code(number, country, short_codes) {
+        return true;
+    }
+
+    let home = region_for_country(country);
+    if number.trim_start().starts_with('+') {
+        // E.164 allows up to 15 digits for countries we don't know
+        let calling_code_known = REGIONS.iter().any(|region| digits.starts_with(region.calling_code));
+        return if calling_code_known { parse(number, home).is_some() } else { digits.len() <= 15 };
+    }
+
+    home.is_none() || parse(number, home).is_some()
+}
+
+// Hint shown under the phone number field: the number as it'll be read in
+// `country`, or a warning when it doesn't fit the country's numbering plan
+pub fn dial_hint(number: &str, country: &str, short_codes: &str) -> String {
+    if number.trim().is_empty() {
+        return String::new();
+    }
+    let digits = number.chars().filter(|c| c.is_ascii_digit()).count();
+    if digits >= MIN_PUBLIC_DIGITS && is_short_code(number, country, short_codes) {
+        return "Short code, dialed as typed".to_string();
+    }
+    if !is_plausible(number, country, short_codes) {
+        return match region_for_country(country) {
+            Some(_) => format!("Doesn't look like a valid {} number", country.trim().to_uppercase()),
+            None => "Doesn't look like a valid phone number".to_string(),
+        };
+    }
+
+    let formatted = format_number(number, country);
+    if formatted == number.trim() {
+        String::new()
+    } else {
+        formatted
+    }
+}
+
+// `number` in E.164 format (+ and digits only), reading it as dialed from
+// `country`: the international prefix (011, 00, 0011, ...) becomes +, and
+// national numbers lose their trunk prefix and gain the calling code.
+// Extensions, short codes (see `is_short_code`) and numbers we can't place
+// are returned as they are.
+pub fn to_e164(number: &str, country: &str, short_codes: &str) -> String {
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() < MIN_PUBLIC_DIGITS || number.contains(['*', '#']) || is_short_code(number, country, short_codes) {
+        return number.to_string();
+    }
+    if number.trim_start().starts_with('+') {
+        return format!("+{}", digits);
+    }
+
+    let home = region_for_country(country);
+    // Most of the world uses 00 to dial abroad, so assume it when the country isn't known
+    let international_prefix = home.map_or("00", |home| home.international_prefix);
+    if let Some(international) = digits.strip_prefix(international_prefix) {
+        return format!("+{}", international);
+    }
+
+    match parse(number, home) {
+        Some((region, nsn)) => format!("+{}{}", region.calling_code, nsn),
+        None => number.to_string(),
+    }
+}
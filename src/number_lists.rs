@@ -0,0 +1,92 @@
+// Allow and block lists for the numbers calls may go to, e.g. to keep anyone
+// from dialing premium-rate prefixes by accident. Checked before every call,
+// whichever way it was asked for.
+
+use crate::number_format;
+use regex::Regex;
+
+pub enum Pattern {
+    // The whole number, e.g. +442079460958
+    Exact(String),
+    // Numbers starting with this, written with a * after it, e.g. +44909*
+    Prefix(String),
+    // A regular expression between slashes, e.g. /^\+1(900|976)/
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn matches(&self, number: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => number == exact,
+            Pattern::Prefix(prefix) => number.starts_with(prefix.as_str()),
+            Pattern::Regex(regex) => regex.is_match(number),
+        }
+    }
+}
+
+// A list entry and what it was written as, to name it when a call is blocked
+pub struct Entry {
+    pub text: String,
+    pub pattern: Pattern,
+}
+
+// The number without separators: digits, * and #, and a leading +
+fn compact(number: &str) -> String {
+    let number = number.trim();
+    let digits: String = number.chars().filter(|c| c.is_ascii_digit() || *c == '*' || *c == '#').collect();
+    if number.starts_with('+') {
+        format!("+{}", digits)
+    } else {
+        digits
+    }
+}
+
+// Read a list written one entry per line. Blank lines and lines starting with
+// // are skipped; the error names the first line that's wrong.
+pub fn parse(list: &str) -> Result<Vec<Entry>, String> {
+    let mut parsed = Vec::new();
+    for (index, line) in list.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let pattern = if let Some(regex) = line.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            let regex = Regex::new(regex).map_err(|e| format!("Line {} has an invalid regular expression: {}", index + 1, e))?;
+            Pattern::Regex(regex)
+        } else if let Some(prefix) = line.strip_suffix('*') {
+            Pattern::Prefix(compact(prefix))
+        } else {
+            Pattern::Exact(compact(line))
+        };
+        if matches!(&pattern, Pattern::Exact(number) | Pattern::Prefix(number) if number.is_empty() || number == "+") {
+            return Err(format!("Line {} has no number; write regular expressions between slashes", index + 1));
+        }
+        parsed.push(Entry { text: line.to_string(), pattern });
+    }
+    Ok(parsed)
+}
+
+// Why `number` mustn't be called, if it mustn't. It's checked as typed and in
+// E.164 format as read in `country`, so "+1900*" also catches 1-900 numbers
+// dialed nationally. The block list wins over the allow list; a non-empty
+// allow list blocks everything it doesn't have.
+pub fn check(number: &str, country: &str, short_codes: &str, allowed: &str, blocked: &str) -> Result<(), String> {
+    let forms = [compact(number), compact(&number_format::to_e164(number, country, short_codes))];
+    let matching = |entries: &[Entry]| {
+        entries
+            .iter()
+            .find(|entry| forms.iter().any(|form| entry.pattern.matches(form)))
+            .map(|entry| entry.text.clone())
+    };
+
+    let blocked = parse(blocked).map_err(|e| format!("the block list can't be read: {}", e))?;
+    if let Some(entry) = matching(&blocked) {
+        return Err(format!("{} is on the block list ({})", number, entry));
+    }
+    let allowed = parse(allowed).map_err(|e| format!("the allow list can't be read: {}", e))?;
+    if !allowed.is_empty() && matching(&allowed).is_none() {
+        return Err(format!("{} isn't on the allow list", number));
+    }
+    Ok(())
+}
@@ -0,0 +1,281 @@
+// Tests for how numbers are cleaned up on their way to the PBX: picked out of
+// text or what's copied, read from tel:, callto:, SIP and clicktocall: links and put in the form the PBX is sent.
+
+use crate::call::pbx_number;
+use crate::config::AppState;
+use crate::clipboard_watch::{self, Debounce};
+use crate::campaign::{self, Entry};
+use crate::duplicates::{self, RecentCalls};
+use crate::caller_id_template::{self, Values};
+use crate::{call_links, call_options, identity, number_format, phone_text, tel_uri};
+use std::time::{Duration, Instant};
+
+#[test]
+fn finds_a_number_in_text_without_its_separators() {
+    assert_eq!(phone_text::find_phone_number("Call me on (555) 123-4567 today").as_deref(), Some("5551234567"));
+    assert_eq!(phone_text::find_phone_number("Office: +44 20 7946 0958").as_deref(), Some("+442079460958"));
+    assert_eq!(phone_text::find_phone_number("555.123.4567").as_deref(), Some("5551234567"));
+}
+
+#[test]
+fn skips_dates_prices_and_references() {
+    assert_eq!(phone_text::find_phone_number("Invoice 4417, due 12/05, $1,250.00"), None);
+    assert_eq!(phone_text::find_phone_number("Ref 1234 or call 555-123-4567").as_deref(), Some("5551234567"));
+    assert_eq!(phone_text::find_phone_number(""), None);
+}
+
+#[test]
+fn only_copies_that_are_a_phone_number_are_offered() {
+    assert_eq!(clipboard_watch::copied_number(" (555) 123-4567\n").as_deref(), Some("5551234567"));
+    assert_eq!(clipboard_watch::copied_number("Mobile: +44 7700 900123").as_deref(), Some("+447700900123"));
+    assert_eq!(clipboard_watch::copied_number("tel:+15551234567").as_deref(), Some("+15551234567"));
+    assert_eq!(clipboard_watch::copied_number("5551234567").as_deref(), Some("5551234567"));
+    assert_eq!(clipboard_watch::copied_number("555-1234").as_deref(), Some("5551234"));
+
+    // Codes and order numbers, more than one number, and passages
+    assert_eq!(clipboard_watch::copied_number("48213377"), None);
+    assert_eq!(clipboard_watch::copied_number("555-123-4567 or 555-987-6543"), None);
+    assert_eq!(clipboard_watch::copied_number("Order 555-123-4567 ships on 12/05"), None);
+    assert_eq!(clipboard_watch::copied_number("Please call our support line at 555-123-4567"), None);
+    assert_eq!(clipboard_watch::copied_number(&format!("{} 555-123-4567", "word ".repeat(20))), None);
+}
+
+#[test]
+fn copies_are_read_once_settled_and_offered_once() {
+    let start = Instant::now();
+    let mut debounce = Debounce::default();
+    // What's on the clipboard when watching starts isn't a new copy
+    assert!(!debounce.settled(1, start));
+    assert!(!debounce.settled(1, start + Duration::from_secs(5)));
+
+    // A burst of copies is read once, after the last one settles
+    assert!(!debounce.settled(2, start + Duration::from_secs(6)));
+    assert!(!debounce.settled(3, start + Duration::from_millis(6500)));
+    assert!(!debounce.settled(3, start + Duration::from_millis(7000)));
+    assert!(debounce.settled(3, start + Duration::from_millis(7500)));
+    assert!(!debounce.settled(3, start + Duration::from_secs(8)));
+
+    assert!(debounce.offer("+15551234567", start));
+    assert!(!debounce.offer("+15551234567", start + Duration::from_secs(10)));
+    assert!(debounce.offer("+15559876543", start + Duration::from_secs(11)));
+    assert!(debounce.offer("+15551234567", start + Duration::from_secs(12)));
+    assert!(!debounce.offer("+15551234567", start + Duration::from_secs(13)));
+    assert!(debounce.offer("+15551234567", start + Duration::from_secs(80)));
+}
+
+#[test]
+fn campaign_numbers_come_from_the_phone_column_of_a_csv() {
+    let entry = |number: &str, name: Option<&str>| Entry { number: number.to_string(), name: name.map(str::to_string), result: None };
+
+    let csv = "Name,Company,Phone Number\r\n\"Doe, Jane\",Acme,(555) 123-4567\r\nNo Phone,Acme,\r\n\nJohn Roe,\"Roe \"\"Co\"\"\",+44 20 7946 0958\r\n";
+    assert_eq!(campaign::parse_csv(csv), Ok(vec![entry("(555) 123-4567", Some("Doe, Jane")), entry("+44 20 7946 0958", Some("John Roe"))]));
+
+    // Without a header, the first column with a number in it
+    assert_eq!(campaign::parse_csv("Jane;555-1234\nJohn;555-9876"), Ok(vec![entry("555-1234", Some("Jane")), entry("555-9876", Some("John"))]));
+
+    assert!(campaign::parse_csv("").is_err());
+    assert!(campaign::parse_csv("Name,Email\nJane,jane@example.com").is_err());
+}
+
+#[test]
+fn repeat_requests_for_a_number_are_ignored_for_a_while() {
+    assert_eq!(duplicates::parse_window(""), Ok(Duration::from_secs(5)));
+    assert_eq!(duplicates::parse_window(" 0 "), Ok(Duration::ZERO));
+    assert!(duplicates::parse_window("forever").is_err());
+    // A hand-edited file doesn't turn suppression off
+    assert_eq!(duplicates::window(&AppState { duplicate_window: "forever".to_string(), ..Default::default() }), Duration::from_secs(5));
+
+    let start = Instant::now();
+    let window = Duration::from_secs(5);
+    let mut recent = RecentCalls::default();
+    assert!(!recent.repeat("+15551234567", window, start));
+    assert!(recent.repeat("+15551234567", window, start + Duration::from_secs(1)));
+    assert!(!recent.repeat("+15559876543", window, start + Duration::from_secs(2)));
+    // Counted from the request that went through, not the ignored ones
+    assert!(!recent.repeat("+15551234567", window, start + Duration::from_secs(5)));
+}
+
+#[test]
+fn tel_uri_loses_its_visual_separators() {
+    let uri = tel_uri::parse("tel:+1-555-123-4567").unwrap();
+    assert_eq!(uri.number, "+15551234567");
+    assert!(uri.extension.is_none());
+    assert!(uri.post_dial.is_none());
+    assert!(!uri.private);
+
+    let uri = tel_uri::parse("  TEL://(555)\u{a0}123.4567?subject=x  ").unwrap();
+    assert_eq!(uri.number, "5551234567");
+}
+
+#[test]
+fn tel_uri_splits_off_extensions_and_post_dial_digits() {
+    let uri = tel_uri::parse("tel:+1-555-123-4567;ext=22").unwrap();
+    assert_eq!(uri.number, "+15551234567");
+    assert_eq!(uri.extension.as_deref(), Some("22"));
+
+    let uri = tel_uri::parse("tel:5551234567 ext. 22").unwrap();
+    assert_eq!(uri.number, "5551234567");
+    assert_eq!(uri.extension.as_deref(), Some("22"));
+
+    let uri = tel_uri::parse("tel:5551234567;postd=pp1234").unwrap();
+    assert_eq!(uri.number, "5551234567");
+    assert_eq!(uri.post_dial.as_deref(), Some("pp1234"));
+}
+
+#[test]
+fn typed_numbers_keep_what_is_dialed_once_the_call_connects() {
+    assert_eq!(tel_uri::split_post_dial("5551234,,101#"), ("5551234", Some("pp101#".to_string())));
+    assert_eq!(tel_uri::split_post_dial("(555) 123-4567 x22"), ("(555) 123-4567", Some("p22".to_string())));
+    assert_eq!(tel_uri::split_post_dial("+1 555 123 4567"), ("+1 555 123 4567", None));
+    // Letters that aren't a pause or an extension stay for the usual checks to turn down
+    assert_eq!(tel_uri::split_post_dial("1-800-FLOWERS"), ("1-800-FLOWERS", None));
+
+    // tel: links hand theirs on the same way, so they reach the PBX as typed ones do
+    let uri = tel_uri::parse("tel:+15551234567;ext=22;postd=pp1%23").unwrap();
+    assert_eq!(uri.dial_string(), "+15551234567,22,,1#");
+    assert_eq!(tel_uri::split_post_dial(&uri.dial_string()), ("+15551234567", Some("p22pp1#".to_string())));
+    let uri = tel_uri::parse("tel:5551234567;postd=1234").unwrap();
+    assert_eq!(tel_uri::split_post_dial(&uri.dial_string()), ("5551234567", Some("p1234".to_string())));
+}
+
+#[test]
+fn tel_uri_adds_a_global_phone_context() {
+    assert_eq!(tel_uri::parse("tel:5551234;phone-context=+1-212").unwrap().number, "+12125551234");
+    assert_eq!(tel_uri::parse("tel:5551234;phone-context=example.com").unwrap().number, "5551234");
+    assert_eq!(tel_uri::parse("tel:+15551234;phone-context=+1-212").unwrap().number, "+15551234");
+}
+
+#[test]
+fn tel_uri_keeps_private_and_round_trips_through_link() {
+    for private in [false, true] {
+        let uri = tel_uri::parse(&tel_uri::link("+15551234567", private)).unwrap();
+        assert_eq!(uri.number, "+15551234567");
+        assert_eq!(uri.private, private);
+    }
+    assert!(tel_uri::parse("tel:5551234567;private=no").is_ok_and(|uri| !uri.private));
+}
+
+#[test]
+fn tel_uri_rejects_what_isnt_a_number() {
+    assert!(tel_uri::parse("mailto:someone@example.com").is_err());
+    assert!(tel_uri::parse("tel:").is_err());
+    assert!(tel_uri::parse("tel:+").is_err());
+    assert!(tel_uri::parse("tel:5551234;ext=abc").is_err());
+}
+
+#[test]
+fn e164_from_national_and_international_numbers() {
+    assert_eq!(number_format::to_e164("(555) 123-4567", "US", ""), "+15551234567");
+    assert_eq!(number_format::to_e164("011 44 20 7946 0958", "US", ""), "+442079460958");
+    assert_eq!(number_format::to_e164("020 7946 0958", "GB", ""), "+442079460958");
+    assert_eq!(number_format::to_e164("+44 (20) 7946-0958", "US", ""), "+442079460958");
+}
+
+#[test]
+fn e164_leaves_extensions_and_short_codes_alone() {
+    assert_eq!(number_format::to_e164("201", "US", ""), "201");
+    assert_eq!(number_format::to_e164("911", "US", ""), "911");
+    assert_eq!(number_format::to_e164("*97", "US", ""), "*97");
+    assert_eq!(number_format::to_e164("1831234", "AU", "1831*"), "1831234");
+}
+
+#[test]
+fn pbx_number_applies_e164_then_the_dial_plan() {
+    let mut state = AppState { country: "US".to_string(), ..Default::default() };
+    assert_eq!(pbx_number("(555) 123-4567", &state).unwrap(), "(555) 123-4567");
+
+    state.dial_e164 = true;
+    assert_eq!(pbx_number("(555) 123-4567", &state).unwrap(), "+15551234567");
+
+    state.dial_plan = "^\\+1 => 9".to_string();
+    assert_eq!(pbx_number("(555) 123-4567", &state).unwrap(), "95551234567");
+}
+
+#[test]
+fn only_tel_links_are_dialed_without_a_scheme_of_our_own() {
+    assert_eq!(identity::tel_link("tel:+15551234567").as_deref(), Some("tel:+15551234567"));
+    assert_eq!(identity::tel_link("TEL:5551234567").as_deref(), Some("TEL:5551234567"));
+    assert_eq!(identity::tel_link("sip:201@pbx.example.com"), None);
+    assert_eq!(identity::tel_link("5551234567"), None);
+}
+
+// The tel: link a callto: or SIP link stands for, with `rules`
+fn call_link(url: &str, rules: &str) -> Result<(String, bool), String> {
+    call_links::read_with(url, rules).expect("a link to call").map(|link| (link.tel, link.ask))
+}
+
+#[test]
+fn callto_and_sip_links_dial_their_number() {
+    assert_eq!(call_link("callto:+15551234567", ""), Ok(("tel:+15551234567".to_string(), false)));
+    assert_eq!(call_link("callto://+1 555 123 4567", ""), Ok(("tel:+1 555 123 4567".to_string(), false)));
+    assert_eq!(call_link("sip:+15551234567@pbx.example.com;user=phone", ""), Ok(("tel:+15551234567".to_string(), false)));
+    assert_eq!(call_link("SIPS:201:secret@pbx.example.com", ""), Ok(("tel:201".to_string(), false)));
+    assert!(call_link("sip:pbx.example.com", "").is_err());
+    assert!(tel_uri::parse(&call_link("sip:alice@example.com", "").unwrap().0).is_err());
+    assert!(call_links::read_with("mailto:alice@example.com", "").is_none());
+    assert!(call_links::read_with("5551234567", "").is_none());
+}
+
+#[test]
+fn link_rules_can_ask_first_or_turn_a_scheme_off() {
+    let rules = "callto=ask, sip=off";
+    assert_eq!(call_link("callto:5551234567", rules), Ok(("tel:5551234567".to_string(), true)));
+    assert!(call_link("sip:201@pbx.example.com", rules).is_err());
+    assert_eq!(call_link("sips:201@pbx.example.com", rules), Ok(("tel:201".to_string(), false)));
+    // tel: links follow Confirm before dialing, not the rules
+    assert_eq!(call_link("tel:5551234567", rules), Ok(("tel:5551234567".to_string(), false)));
+
+    assert!(call_links::parse_rules(" callto = dial ,sip:=ask").is_ok());
+    for wrong in ["callto", "tel=off", "sip=later"] {
+        assert!(call_links::parse_rules(wrong).is_err(), "{}", wrong);
+    }
+}
+
+#[test]
+fn clicktocall_links_dial_and_say_where_to_call_back() {
+    let url = "clicktocall://x-callback-url/call?number=+1%20555%20123%204567&x-success=raycast%3A%2F%2Fdone&x-error=shortcuts://x-callback-url/error";
+    let link = call_links::read_with(url, "").expect("a link to call").unwrap();
+    assert_eq!(tel_uri::parse(&link.tel).unwrap().number, "+15551234567");
+    let callback = link.callback.expect("a callback");
+    assert_eq!(callback.success_url("+15551234567").as_deref(), Some("raycast://done?number=%2B15551234567"));
+    assert_eq!(
+        callback.error_url("unreachable", "PBX down").as_deref(),
+        Some("shortcuts://x-callback-url/error?errorCode=unreachable&errorMessage=PBX+down")
+    );
+    assert_eq!(callback.cancel, None);
+
+    let link = call_links::read_with("clicktocall://call?number=201%23&private=1", "").unwrap().unwrap();
+    let uri = tel_uri::parse(&link.tel).unwrap();
+    assert_eq!((uri.number.as_str(), uri.private), ("201#", true));
+    assert!(link.callback.is_none());
+    assert_eq!(call_link("clicktocall:+15551234567", ""), Ok(("tel:+15551234567".to_string(), false)));
+
+    assert!(call_links::read_with("clicktocall://call?x-error=app://e", "").unwrap().is_err());
+    assert!(call_links::read_with("clicktocall://hangup", "").unwrap().is_err());
+    assert!(call_links::callback("clicktocall://call?number=1&x-success=file:///tmp/x").unwrap().success_url("1").is_none());
+}
+
+#[test]
+fn caller_ids_are_sent_without_their_formatting() {
+    assert_eq!(call_options::parse_caller_id(""), Ok(None));
+    assert_eq!(call_options::parse_caller_id(" +1 (555) 010-0100 "), Ok(Some("+15550100100".to_string())));
+    assert_eq!(call_options::parse_caller_id("201"), Ok(Some("201".to_string())));
+    assert!(call_options::parse_caller_id("Sales").is_err());
+    assert!(call_options::parse_caller_id("555+0100").is_err());
+    assert!(call_options::parse_caller_id("1234567890123456").is_err());
+}
+
+#[test]
+fn caller_id_templates_fill_in_the_call() {
+    let values = Values { number: "+15551234567", contact: Some("Jane Doe"), extension: "201", profile: "Acme" };
+    assert_eq!(caller_id_template::render("{contact} ({profile})", &values).as_deref(), Some("Jane Doe (Acme)"));
+    assert_eq!(caller_id_template::render("Ext {extension}", &values).as_deref(), Some("Ext 201"));
+    assert_eq!(caller_id_template::render("", &values), None);
+
+    let unknown = Values { contact: None, ..values };
+    assert_eq!(caller_id_template::render("{contact}", &unknown).as_deref(), Some("+15551234567"));
+
+    assert!(caller_id_template::check("{number}").is_ok());
+    assert!(caller_id_template::check("{name}").is_err());
+    assert!(caller_id_template::check("{number").is_err());
+}
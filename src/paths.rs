@@ -0,0 +1,118 @@
+use crate::identity::identity;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::info;
+
+// A directory we settled on, and how we got there
+pub struct Location {
+    pub path: PathBuf,
+    pub source: &'static str,
+}
+
+static CONFIG_DIR: OnceLock<Location> = OnceLock::new();
+static RUNTIME_DIR: OnceLock<Location> = OnceLock::new();
+
+// Where preferences and stats live. Sandboxed builds and odd HOME setups can
+// leave `dirs` without an answer, so fall back to ~/Library/Application Support
+// and then the temp directory rather than silently not saving anything.
+pub fn config_location() -> &'static Location {
+    CONFIG_DIR.get_or_init(|| {
+        let location = if let Some(dir) = dirs::config_dir() {
+            Location { path: dir.join(&identity().slug), source: "system configuration directory" }
+        } else if let Some(home) = std::env::var_os("HOME").map(PathBuf::from).filter(|home| home.is_absolute()) {
+            Location {
+                path: home.join("Library").join("Application Support").join(&identity().slug),
+                source: "fallback: $HOME/Library/Application Support",
+            }
+        } else {
+            Location {
+                path: std::env::temp_dir().join(&identity().slug),
+                source: "fallback: temporary directory, settings won't survive a reboot",
+            }
+        };
+
+        info!("Configuration directory: {} ({})", location.path.display(), location.source);
+        location
+    })
+}
+
+// Where the instance socket lives. macOS has no runtime directory, so there
+// the per-user temp directory is the normal choice, not a fallback.
+pub fn runtime_location() -> &'static Location {
+    RUNTIME_DIR.get_or_init(|| match dirs::runtime_dir() {
+        Some(dir) => Location { path: dir, source: "system runtime directory" },
+        None => Location { path: std::env::temp_dir(), source: "temporary directory" },
+    })
+}
+
+pub fn config_dir() -> &'static Path {
+    &config_location().path
+}
+
+pub fn preferences_file() -> PathBuf {
+    config_dir().join("preferences.json")
+}
+
+pub fn stats_file() -> PathBuf {
+    config_dir().join("stats.json")
+}
+
+pub fn profiles_file() -> PathBuf {
+    config_dir().join("profiles.json")
+}
+
+pub fn history_database() -> PathBuf {
+    config_dir().join("history.sqlite")
+}
+
+// Where the history was kept before it moved to SQLite
+pub fn history_file() -> PathBuf {
+    config_dir().join("history.json")
+}
+
+pub fn reminders_file() -> PathBuf {
+    config_dir().join("reminders.json")
+}
+
+pub fn directory_file() -> PathBuf {
+    config_dir().join("directory.json")
+}
+
+pub fn retry_queue_file() -> PathBuf {
+    config_dir().join("retry_queue.json")
+}
+
+pub fn daemon_log_file() -> PathBuf {
+    config_dir().join("daemon.log")
+}
+
+// Where the log files go: ~/Library/Logs, where Console looks for them
+pub fn log_dir() -> PathBuf {
+    match dirs::home_dir() {
+        Some(home) => home.join("Library").join("Logs").join(&identity().slug),
+        None => std::env::temp_dir().join(&identity().slug).join("Logs"),
+    }
+}
+
+// The folder the instance socket and lock file are kept in, only this user's
+// to open. In a runtime directory everyone can write to, like /tmp on Linux,
+// it's named after the user's id as well, so users don't get in each other's way.
+pub fn socket_dir() -> PathBuf {
+    let runtime = &runtime_location().path;
+    let shared = std::fs::metadata(runtime).is_ok_and(|metadata| metadata.mode() & 0o002 != 0);
+    if shared {
+        runtime.join(format!("{}-{}", identity().slug, unsafe { libc::geteuid() }))
+    } else {
+        runtime.join(&identity().slug)
+    }
+}
+
+pub fn socket_file() -> PathBuf {
+    socket_dir().join(format!("{}.sock", identity().slug))
+}
+
+// Locked by the primary instance for as long as it runs
+pub fn lock_file() -> PathBuf {
+    socket_dir().join(format!("{}.lock", identity().slug))
+}
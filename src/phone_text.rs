@@ -0,0 +1,41 @@
+// Characters that may appear between the digits of a written phone number
+const SEPARATORS: &str = " -.()/";
+
+// Find the first thing that looks like a phone number in arbitrary text
+// (a selection, OCR output, the clipboard) and return it with the
+// separators removed, keeping a leading plus sign.
+pub fn find_phone_number(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if !(c == '+' || c == '(' || c.is_ascii_digit()) {
+            i += 1;
+            continue;
+        }
+
+        // Collect a run of digits and separators
+        let mut number = String::new();
+        let mut j = i;
+        while j < chars.len() {
+            let c = chars[j];
+            if c.is_ascii_digit() || (c == '+' && number.is_empty()) {
+                number.push(c);
+            } else if !SEPARATORS.contains(c) {
+                break;
+            }
+            j += 1;
+        }
+
+        // Anything shorter is more likely a date, price or reference number
+        let digits = number.chars().filter(|c| c.is_ascii_digit()).count();
+        if (7..=15).contains(&digits) {
+            return Some(number);
+        }
+
+        i = j.max(i + 1);
+    }
+
+    None
+}
@@ -0,0 +1,197 @@
+// Presence: whether the extension's phone is registered with the PBX and
+// whether it's on a call, asked every 15 seconds while the profile has it on.
+// A call to a phone that isn't registered can't ring it, and the PBX takes
+// the request all the same, so it's shown next to the extension and in the
+// menu bar icon's tooltip before anything is dialed. FreeSWITCH and FusionPBX
+// are asked over the event socket, Asterisk over ARI.
+
+use crate::backend::{self, Account, BackendKind, CallError, HttpAuth, Presence};
+use crate::config::AppState;
+use crate::tls::Tls;
+use crate::{base_url, call_events, menu_bar, notify, runtime, srv};
+use druid::ExtEventSink;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+use url::Url;
+
+// How often the PBX is asked
+const INTERVAL: Duration = Duration::from_secs(15);
+
+// Longest an answer may take before presence counts as unknown
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Extension to ask about, None while that's switched off
+static TARGET: Mutex<Option<Target>> = Mutex::new(None);
+static STARTED: AtomicBool = AtomicBool::new(false);
+// The last answer, or why there wasn't one; None before the first
+static LAST: Mutex<Option<Result<Presence, String>>> = Mutex::new(None);
+
+// Lets the UI show the presence next to the extension
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+// Whether the settings for showing presence are complete, before they're saved
+pub fn check(state: &AppState) -> Result<(), String> {
+    if !state.show_presence {
+        return Ok(());
+    }
+    match state.backend {
+        BackendKind::FreeSwitchEsl | BackendKind::AsteriskAri => Ok(()),
+        BackendKind::FusionPbx if state.event_socket_password.is_empty() => Err("Showing presence needs the event socket's password".to_string()),
+        BackendKind::FusionPbx => Ok(()),
+        backend => Err(format!("Presence can only be shown on FusionPBX, FreeSWITCH and Asterisk ARI, not {}", backend.label())),
+    }
+}
+
+// The extension and the PBX login to ask with, taken from the settings;
+// voicemail asks with them too
+#[derive(Clone)]
+pub struct Target {
+    // The profile's login, with the domain as its host
+    account: Account,
+    srv_lookup: bool,
+    event_socket: String,
+    event_socket_password: String,
+    extension: String,
+}
+
+impl Target {
+    pub fn from_state(state: &AppState) -> Self {
+        Target {
+            account: Account {
+                kind: state.backend,
+                host: state.domain.clone(),
+                username: state.backend_username.clone(),
+                secret: state.key.clone(),
+                context: state.backend_context.clone(),
+                tls: Tls::from_state(state),
+                http_request: state.http_request.clone(),
+                auth: HttpAuth::from_state(state),
+            },
+            srv_lookup: state.srv_lookup,
+            event_socket: state.event_socket.clone(),
+            event_socket_password: state.event_socket_password.clone(),
+            extension: state.extension.clone(),
+        }
+    }
+
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    pub fn same(&self, other: &Target) -> bool {
+        self.account.host == other.account.host && self.extension == other.extension
+    }
+
+    // Where to ask about the extension, and what it's called there
+    pub async fn resolve(&self) -> (Account, String) {
+        let mut account = self.account.clone();
+        // The server calls go to first
+        if self.srv_lookup {
+            account.host = srv::servers(&account.host).await.swap_remove(0);
+        }
+        let extension = match account.kind {
+            // FusionPBX keeps each domain's extensions apart, and its domain is the PBX's
+            BackendKind::FusionPbx => match Url::parse(&base_url(&self.account.host)).ok().and_then(|url| url.host_str().map(str::to_string)) {
+                Some(domain) => format!("{}@{}", self.extension, domain),
+                None => self.extension.clone(),
+            },
+            _ => self.extension.clone(),
+        };
+        let account = call_events::event_socket(&account, &self.event_socket, &self.event_socket_password).unwrap_or(account);
+        (account, extension)
+    }
+}
+
+// Ask `target`'s PBX about its extension
+async fn lookup(target: &Target) -> Result<Presence, CallError> {
+    let (account, extension) = target.resolve().await;
+    let backend = account.backend();
+    backend::within(LOOKUP_TIMEOUT, backend.presence(&extension)).await
+}
+
+// How `presence` reads next to the extension
+fn label(presence: &Result<Presence, String>) -> &'static str {
+    match presence {
+        Ok(Presence::Unregistered) => "not registered",
+        Ok(Presence::Idle) => "registered",
+        Ok(Presence::OnCall) => "on a call",
+        Err(_) => "presence unknown",
+    }
+}
+
+// Whether the PBX last said no phone is registered for the extension
+pub fn unregistered() -> bool {
+    matches!(*LAST.lock().unwrap(), Some(Ok(Presence::Unregistered)))
+}
+
+// Ask about this extension in the background, or stop with None. A new
+// target is asked about straight away.
+pub fn set_target(target: Option<Target>) {
+    let mut current = TARGET.lock().unwrap();
+    let changed = match (current.as_ref(), target.as_ref()) {
+        (Some(current), Some(target)) => !current.same(target),
+        (None, None) => false,
+        _ => true,
+    };
+    *current = target.clone();
+    drop(current);
+    if changed {
+        *LAST.lock().unwrap() = None;
+        show(String::new(), None);
+    }
+
+    if let Some(target) = target {
+        if !STARTED.swap(true, Ordering::SeqCst) {
+            runtime().spawn(lookup_loop());
+        }
+        runtime().spawn(async move { report(&target, lookup(&target).await) });
+    }
+}
+
+async fn lookup_loop() {
+    loop {
+        tokio::time::sleep(INTERVAL).await;
+
+        let target = TARGET.lock().unwrap().clone();
+        if let Some(target) = target {
+            let presence = lookup(&target).await;
+            report(&target, presence);
+        }
+    }
+}
+
+fn report(target: &Target, presence: Result<Presence, CallError>) {
+    // The settings may have changed while the PBX was asked
+    if !TARGET.lock().unwrap().as_ref().is_some_and(|current| current.same(target)) {
+        return;
+    }
+    let presence = presence.map_err(|e| e.to_string());
+    let previous = LAST.lock().unwrap().replace(presence.clone());
+    if previous.as_ref() == Some(&presence) {
+        return;
+    }
+
+    match &presence {
+        Ok(_) => info!("Extension {} is {}", target.extension, label(&presence)),
+        Err(e) => warn!("Couldn't ask whether extension {} is registered: {}", target.extension, e),
+    }
+    // Losing the registration is worth saying; at launch the label says it
+    if presence == Ok(Presence::Unregistered) && matches!(previous, Some(Ok(Presence::Idle | Presence::OnCall))) {
+        notify::show_notification("Phone Not Registered", &format!("The phone for extension {} isn't registered with the PBX anymore, so calls can't ring it", target.extension));
+    }
+    let text = label(&presence);
+    show(text.to_string(), Some(format!("Extension {}: {}", target.extension, text)));
+}
+
+fn show(label: String, tooltip: Option<String>) {
+    menu_bar::set_presence(tooltip);
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(move |data: &mut AppState| data.presence = label);
+    }
+}
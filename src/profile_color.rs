@@ -0,0 +1,59 @@
+use druid::kurbo::Circle;
+use druid::widget::{Flex, Label, Painter};
+use druid::{Color, Env, RenderContext, Widget, WidgetExt};
+use crate::config::AppState;
+
+// Colors a configuration can be tagged with, as (setting value, label, color)
+pub const PALETTE: &[(&str, &str, Color)] = &[
+    ("blue", "Blue", Color::rgb8(0x0A, 0x84, 0xFF)),
+    ("green", "Green", Color::rgb8(0x30, 0xD1, 0x58)),
+    ("orange", "Orange", Color::rgb8(0xFF, 0x9F, 0x0A)),
+    ("red", "Red", Color::rgb8(0xFF, 0x45, 0x3A)),
+    ("purple", "Purple", Color::rgb8(0xBF, 0x5A, 0xF2)),
+];
+
+// Used until a color has been picked
+const DEFAULT_COLOR: Color = Color::rgb8(0x8E, 0x8E, 0x93);
+
+pub fn color_for(name: &str) -> Color {
+    PALETTE
+        .iter()
+        .find(|(value, _, _)| *value == name)
+        .map(|(_, _, color)| *color)
+        .unwrap_or(DEFAULT_COLOR)
+}
+
+// Colored dot for the configuration's color
+pub fn color_dot() -> impl Widget<AppState> {
+    Painter::new(|ctx, data: &AppState, _env| {
+        let size = ctx.size();
+        let center = (size.width / 2.0, size.height / 2.0);
+        ctx.fill(Circle::new(center, 5.0), &color_for(&data.color));
+    })
+    .fix_size(12.0, 12.0)
+}
+
+// Dot plus "extension@domain", so it's always clear which PBX a call will use
+pub fn identity_badge() -> impl Widget<AppState> {
+    let identity = Label::dynamic(|data: &AppState, _env: &Env| {
+        if data.domain.is_empty() {
+            "Not configured".to_string()
+        } else if data.profile.is_empty() {
+            format!("{}@{}", data.extension, data.domain)
+        } else {
+            format!("{}: {}@{}", data.profile, data.extension, data.domain)
+        }
+    });
+    // Whether the extension's phone is registered, while that's asked
+    let presence = Label::dynamic(|data: &AppState, _env: &Env| match data.presence.is_empty() {
+        true => String::new(),
+        false => format!("({})", data.presence),
+    });
+
+    Flex::row()
+        .with_child(color_dot())
+        .with_spacer(6.0)
+        .with_child(identity)
+        .with_spacer(6.0)
+        .with_child(presence)
+}
@@ -0,0 +1,334 @@
+use crate::backend::{BackendKind, HttpAuthKind};
+use crate::config::AppState;
+use crate::{keychain, paths, spotlight};
+use serde::{Deserialize, Serialize};
+
+// Profile name used when settings are saved before any profile was named
+pub const DEFAULT_PROFILE: &str = "Default";
+
+// One PBX account: everything that differs between tenants. Per-Mac choices
+// (auto-answer override, menu bar, hotkeys, ...) stay in AppState.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub domain: String,
+    pub fallback_domains: String,
+    pub srv_lookup: bool,
+    pub extension: String,
+    // Kept in the Keychain; only written to the profiles file where there isn't one
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub key: String,
+    pub auto_answer: bool,
+    pub backend: BackendKind,
+    pub backend_username: String,
+    pub backend_context: String,
+    pub country: String,
+    pub dial_e164: bool,
+    pub dial_plan: String,
+    pub routes: String,
+    pub short_codes: String,
+    pub ringback: String,
+    pub record_calls: bool,
+    pub caller_id: String,
+    pub caller_id_name_template: String,
+    pub caller_id_number_template: String,
+    pub click_to_call_parameters: String,
+    pub http_request: String,
+    pub request_timeout: String,
+    pub retry_attempts: String,
+    pub retry_delay: String,
+    pub max_concurrent_calls: String,
+    pub ca_certificate: String,
+    pub accept_invalid_certificates: bool,
+    pub client_certificate: String,
+    pub client_key: String,
+    // Kept in the Keychain like the key
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub client_certificate_passphrase: String,
+    pub http_auth: HttpAuthKind,
+    pub http_auth_username: String,
+    // Kept in the Keychain too
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub http_auth_password: String,
+    pub oauth_token_url: String,
+    pub oauth_authorize_url: String,
+    pub oauth_scope: String,
+    pub track_calls: bool,
+    pub event_socket: String,
+    // Kept in the Keychain too
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub event_socket_password: String,
+    pub show_presence: bool,
+    pub show_voicemail: bool,
+    pub voicemail_number: String,
+    pub color: String,
+}
+
+impl Profile {
+    // The profile as currently configured in `state`
+    pub fn from_state(state: &AppState) -> Profile {
+        Profile {
+            name: state.profile.clone(),
+            domain: state.domain.clone(),
+            fallback_domains: state.fallback_domains.clone(),
+            srv_lookup: state.srv_lookup,
+            extension: state.extension.clone(),
+            key: state.key.clone(),
+            auto_answer: state.auto_answer,
+            backend: state.backend,
+            backend_username: state.backend_username.clone(),
+            backend_context: state.backend_context.clone(),
+            country: state.country.clone(),
+            dial_e164: state.dial_e164,
+            dial_plan: state.dial_plan.clone(),
+            routes: state.routes.clone(),
+            short_codes: state.short_codes.clone(),
+            ringback: state.ringback.clone(),
+            record_calls: state.record_calls,
+            caller_id: state.caller_id.clone(),
+            caller_id_name_template: state.caller_id_name_template.clone(),
+            caller_id_number_template: state.caller_id_number_template.clone(),
+            click_to_call_parameters: state.click_to_call_parameters.clone(),
+            http_request: state.http_request.clone(),
+            request_timeout: state.request_timeout.clone(),
+            retry_attempts: state.retry_attempts.clone(),
+            retry_delay: state.retry_delay.clone(),
+            max_concurrent_calls: state.max_concurrent_calls.clone(),
+            ca_certificate: state.ca_certificate.clone(),
+            accept_invalid_certificates: state.accept_invalid_certificates,
+            client_certificate: state.client_certificate.clone(),
+            client_key: state.client_key.clone(),
+            client_certificate_passphrase: state.client_certificate_passphrase.clone(),
+            http_auth: state.http_auth,
+            http_auth_username: state.http_auth_username.clone(),
+            http_auth_password: state.http_auth_password.clone(),
+            oauth_token_url: state.oauth_token_url.clone(),
+            oauth_authorize_url: state.oauth_authorize_url.clone(),
+            oauth_scope: state.oauth_scope.clone(),
+            track_calls: state.track_calls,
+            event_socket: state.event_socket.clone(),
+            event_socket_password: state.event_socket_password.clone(),
+            show_presence: state.show_presence,
+            show_voicemail: state.show_voicemail,
+            voicemail_number: state.voicemail_number.clone(),
+            color: state.color.clone(),
+        }
+    }
+
+    // Make this the active profile in `state`
+    pub fn apply(&self, state: &mut AppState) {
+        state.profile = self.name.clone();
+        state.domain = self.domain.clone();
+        state.fallback_domains = self.fallback_domains.clone();
+        state.srv_lookup = self.srv_lookup;
+        state.extension = self.extension.clone();
+        state.key = self.key.clone();
+        state.auto_answer = self.auto_answer;
+        state.backend = self.backend;
+        state.backend_username = self.backend_username.clone();
+        state.backend_context = self.backend_context.clone();
+        state.country = self.country.clone();
+        state.dial_e164 = self.dial_e164;
+        state.dial_plan = self.dial_plan.clone();
+        state.routes = self.routes.clone();
+        state.short_codes = self.short_codes.clone();
+        state.ringback = self.ringback.clone();
+        state.record_calls = self.record_calls;
+        state.caller_id = self.caller_id.clone();
+        state.caller_id_name_template = self.caller_id_name_template.clone();
+        state.caller_id_number_template = self.caller_id_number_template.clone();
+        state.click_to_call_parameters = self.click_to_call_parameters.clone();
+        state.http_request = self.http_request.clone();
+        state.request_timeout = self.request_timeout.clone();
+        state.retry_attempts = self.retry_attempts.clone();
+        state.retry_delay = self.retry_delay.clone();
+        state.max_concurrent_calls = self.max_concurrent_calls.clone();
+        state.ca_certificate = self.ca_certificate.clone();
+        state.accept_invalid_certificates = self.accept_invalid_certificates;
+        state.client_certificate = self.client_certificate.clone();
+        state.client_key = self.client_key.clone();
+        state.client_certificate_passphrase = self.client_certificate_passphrase.clone();
+        state.http_auth = self.http_auth;
+        state.http_auth_username = self.http_auth_username.clone();
+        state.http_auth_password = self.http_auth_password.clone();
+        state.oauth_token_url = self.oauth_token_url.clone();
+        state.oauth_authorize_url = self.oauth_authorize_url.clone();
+        state.oauth_scope = self.oauth_scope.clone();
+        state.track_calls = self.track_calls;
+        state.event_socket = self.event_socket.clone();
+        state.event_socket_password = self.event_socket_password.clone();
+        state.show_presence = self.show_presence;
+        state.show_voicemail = self.show_voicemail;
+        state.voicemail_number = self.voicemail_number.clone();
+        // Another PBX's prefixes mean nothing here
+        state.route.clear();
+        state.color = self.color.clone();
+    }
+}
+
+// A starting point for a new profile, with what each PBX needs filled in
+pub struct Template {
+    pub name: &'static str,
+    // What's left to fill in, shown next to the template
+    pub description: &'static str,
+    pub backend: BackendKind,
+    pub backend_context: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "FusionPBX",
+        description: "Domain and click_to_call API key",
+        backend: BackendKind::FusionPbx,
+        backend_context: "",
+    },
+    Template {
+        name: "Asterisk AMI",
+        description: "Host, manager user and secret; port 5038",
+        backend: BackendKind::AsteriskAmi,
+        backend_context: "from-internal",
+    },
+    Template {
+        name: "Asterisk ARI",
+        description: "HTTP server with its port, ARI user and password",
+        backend: BackendKind::AsteriskAri,
+        backend_context: "from-internal",
+    },
+    Template {
+        name: "FreeSWITCH ESL",
+        description: "Host and event socket password; port 8021",
+        backend: BackendKind::FreeSwitchEsl,
+        backend_context: "default",
+    },
+    Template {
+        name: "3CX",
+        description: "Host, API client id and secret",
+        backend: BackendKind::ThreeCx,
+        backend_context: "",
+    },
+    Template {
+        name: "Generic HTTP",
+        description: "The request your gateway takes, under HTTP Request…",
+        backend: BackendKind::GenericHttp,
+        backend_context: "",
+    },
+];
+
+impl Template {
+    // A new profile called `name` for this template's PBX. Account details
+    // start empty; how numbers are written carries over from `base`, since
+    // that's about where the user is rather than which PBX they use.
+    pub fn profile(&self, name: String, base: &Profile) -> Profile {
+        Profile {
+            name,
+            backend: self.backend,
+            backend_context: self.backend_context.to_string(),
+            auto_answer: base.auto_answer,
+            country: base.country.clone(),
+            dial_e164: base.dial_e164,
+            short_codes: base.short_codes.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+// `base`, or `base` with a number after it if a profile already has that name
+pub fn unique_name(base: &str) -> String {
+    let taken: Vec<String> = load().into_iter().map(|profile| profile.name).collect();
+    let mut name = base.to_string();
+    let mut number = 2;
+    while taken.contains(&name) {
+        name = format!("{} {}", base, number);
+        number += 1;
+    }
+    name
+}
+
+fn keychain_account(name: &str) -> String {
+    format!("{}:{}", keychain::API_KEY, name)
+}
+
+fn passphrase_account(name: &str) -> String {
+    format!("{}:{}", keychain::CLIENT_CERTIFICATE_PASSPHRASE, name)
+}
+
+fn http_auth_account(name: &str) -> String {
+    format!("{}:{}", keychain::HTTP_AUTH_PASSWORD, name)
+}
+
+fn event_socket_account(name: &str) -> String {
+    format!("{}:{}", keychain::EVENT_SOCKET_PASSWORD, name)
+}
+
+// Every saved profile, in the order they were created
+pub fn load() -> Vec<Profile> {
+    let mut profiles: Vec<Profile> = std::fs::read_to_string(paths::profiles_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    for profile in profiles.iter_mut().filter(|profile| profile.key.is_empty()) {
+        profile.key = keychain::password(&keychain_account(&profile.name)).unwrap_or_default();
+    }
+    for profile in profiles.iter_mut().filter(|profile| profile.client_certificate_passphrase.is_empty()) {
+        profile.client_certificate_passphrase = keychain::password(&passphrase_account(&profile.name)).unwrap_or_default();
+    }
+    for profile in profiles.iter_mut().filter(|profile| profile.http_auth_password.is_empty()) {
+        profile.http_auth_password = keychain::password(&http_auth_account(&profile.name)).unwrap_or_default();
+    }
+    for profile in profiles.iter_mut().filter(|profile| profile.event_socket_password.is_empty()) {
+        profile.event_socket_password = keychain::password(&event_socket_account(&profile.name)).unwrap_or_default();
+    }
+    profiles
+}
+
+pub fn find(name: &str) -> Option<Profile> {
+    load().into_iter().find(|profile| profile.name == name)
+}
+
+fn save(profiles: &[Profile]) -> Result<(), String> {
+    // Keys go to the Keychain, like the active one in the preferences file
+    let mut stored = profiles.to_vec();
+    for profile in &mut stored {
+        if keychain::set_password(&keychain_account(&profile.name), &profile.key)? {
+            profile.key.clear();
+        }
+        if keychain::set_password(&passphrase_account(&profile.name), &profile.client_certificate_passphrase)? {
+            profile.client_certificate_passphrase.clear();
+        }
+        if keychain::set_password(&http_auth_account(&profile.name), &profile.http_auth_password)? {
+            profile.http_auth_password.clear();
+        }
+        if keychain::set_password(&event_socket_account(&profile.name), &profile.event_socket_password)? {
+            profile.event_socket_password.clear();
+        }
+    }
+
+    let path = paths::profiles_file();
+    let json = serde_json::to_string_pretty(&stored)
+        .map_err(|e| format!("Couldn't serialize profiles: {}", e))?;
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+}
+
+// Add the profile, or replace the saved one with the same name
+pub fn store(profile: Profile) -> Result<(), String> {
+    let mut profiles = load();
+    let name = profile.name.clone();
+    match profiles.iter_mut().find(|existing| existing.name == profile.name) {
+        Some(existing) => *existing = profile,
+        None => profiles.push(profile),
+    }
+    save(&profiles)?;
+    spotlight::index_profile(&name);
+    Ok(())
+}
+
+pub fn remove(name: &str) -> Result<(), String> {
+    let mut profiles = load();
+    profiles.retain(|profile| profile.name != name);
+    save(&profiles)?;
+    spotlight::remove_profile(name);
+    keychain::set_password(&keychain_account(name), "")?;
+    keychain::set_password(&passphrase_account(name), "").map(|_| ())
+}
@@ -0,0 +1,164 @@
+// Moving settings between Macs: every profile and the settings around them in
+// one file, JSON or TOML by its extension. The file is exported and imported
+// from the settings window, or fetched from a provisioning URL to set up many
+// desks at once (`--provision <url>`). Keys and passwords are left out of
+// exports; a file that has them, e.g. one written for provisioning, puts them
+// in the Keychain like Save Settings does.
+
+use crate::config::{self, load_preferences, save_preferences, AppState};
+use crate::profiles::{self, Profile};
+use crate::{block_on, http_client, managed, proxy};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Format of the file, so a newer app's file isn't half understood
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsFile {
+    pub version: u32,
+    // Everything in the settings window, the active profile included
+    pub settings: AppState,
+    // Every saved profile
+    pub profiles: Vec<Profile>,
+}
+
+// TOML for a .toml file, JSON for anything else
+pub fn is_toml(name: &str) -> bool {
+    name.to_lowercase().ends_with(".toml")
+}
+
+// The settings as they'd be exported, without what's secret
+pub fn export() -> SettingsFile {
+    let mut settings = load_preferences();
+    settings.key.clear();
+    settings.corporate_directory_password.clear();
+    settings.client_certificate_passphrase.clear();
+    settings.http_auth_password.clear();
+    settings.event_socket_password.clear();
+    settings.audit_webhook_secret.clear();
+    settings.http_api_token.clear();
+    let mut saved = profiles::load();
+    for profile in &mut saved {
+        profile.key.clear();
+        profile.client_certificate_passphrase.clear();
+        profile.http_auth_password.clear();
+        profile.event_socket_password.clear();
+    }
+    SettingsFile { version: FORMAT_VERSION, settings, profiles: saved }
+}
+
+pub fn to_string(file: &SettingsFile, toml: bool) -> Result<String, String> {
+    if toml {
+        toml::to_string_pretty(file).map_err(|e| format!("Couldn't write the settings as TOML: {}", e))
+    } else {
+        serde_json::to_string_pretty(file).map_err(|e| format!("Couldn't serialize settings: {}", e))
+    }
+}
+
+pub fn parse(text: &str, toml: bool) -> Result<SettingsFile, String> {
+    let file: SettingsFile = if toml {
+        toml::from_str(text).map_err(|e| format!("isn't a settings file: {}", e))?
+    } else {
+        serde_json::from_str(text).map_err(|e| format!("isn't a settings file: {}", e))?
+    };
+    if file.version > FORMAT_VERSION {
+        return Err(format!("was written by a newer version (format {}), update the app to import it", file.version));
+    }
+    Ok(file)
+}
+
+// Write every profile and the settings to `path`. Returns how many profiles were written.
+pub fn export_to(path: &Path) -> Result<usize, String> {
+    let file = export();
+    let text = to_string(&file, is_toml(&path.to_string_lossy()))?;
+    std::fs::write(path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+    Ok(file.profiles.len())
+}
+
+pub fn import_from(path: &Path) -> Result<usize, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    let file = parse(&text, is_toml(&path.to_string_lossy())).map_err(|e| format!("{} {}", path.display(), e))?;
+    import(file)
+}
+
+// Fetch the settings file at `url` and import it
+pub fn provision(url: &str) -> Result<usize, String> {
+    let lower = url.trim().to_lowercase();
+    if !lower.starts_with("https://") && !lower.starts_with("http://") {
+        return Err("the provisioning URL should be an http(s) URL".to_string());
+    }
+    let text = block_on(async {
+        proxy::prepare(url.trim()).await;
+        let response = http_client().get(url.trim()).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP status {}", response.status()));
+        }
+        response.text().await.map_err(|e| e.to_string())
+    })
+    .map_err(|e| format!("Couldn't fetch {}: {}", url, e))?;
+    // The extension decides, ignoring any query string
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let file = parse(&text, is_toml(path)).map_err(|e| format!("{} {}", url, e))?;
+    import(file)
+}
+
+// Save the profiles and settings from `file`, replacing profiles with the same
+// name. Secrets the file leaves out are kept from what's saved here, so a
+// file exported on this Mac can be imported again. Nothing is saved unless
+// every profile's settings make sense. Returns how many profiles were imported.
+pub fn import(mut file: SettingsFile) -> Result<usize, String> {
+    let current = load_preferences();
+    if current.kiosk {
+        return Err("settings are managed by your administrator".to_string());
+    }
+
+    let settings = &mut file.settings;
+    if settings.profile.trim().is_empty() {
+        settings.profile = profiles::DEFAULT_PROFILE.to_string();
+    }
+    let existing = profiles::find(&settings.profile)
+        .or_else(|| (current.profile == settings.profile).then(|| Profile::from_state(&current)))
+        .unwrap_or_default();
+    keep_secret(&mut settings.key, &existing.key);
+    keep_secret(&mut settings.client_certificate_passphrase, &existing.client_certificate_passphrase);
+    keep_secret(&mut settings.http_auth_password, &existing.http_auth_password);
+    keep_secret(&mut settings.event_socket_password, &existing.event_socket_password);
+    keep_secret(&mut settings.corporate_directory_password, &current.corporate_directory_password);
+    keep_secret(&mut settings.audit_webhook_secret, &current.audit_webhook_secret);
+    keep_secret(&mut settings.http_api_token, &current.http_api_token);
+    config::check(settings)?;
+
+    for profile in &mut file.profiles {
+        if profile.name.trim().is_empty() {
+            return Err("a profile in the file has no name".to_string());
+        }
+        if let Some(existing) = profiles::find(&profile.name) {
+            keep_secret(&mut profile.key, &existing.key);
+            keep_secret(&mut profile.client_certificate_passphrase, &existing.client_certificate_passphrase);
+            keep_secret(&mut profile.http_auth_password, &existing.http_auth_password);
+            keep_secret(&mut profile.event_socket_password, &existing.event_socket_password);
+        }
+        let mut state = settings.clone();
+        profile.apply(&mut state);
+        config::check(&state).map_err(|e| format!("Profile {}: {}", profile.name, e))?;
+    }
+
+    for profile in &file.profiles {
+        profiles::store(profile.clone())?;
+    }
+    // The active profile is a profile even when the file doesn't list it
+    profiles::store(Profile::from_state(settings))?;
+    // Forced settings still win over the file's
+    managed::load_managed_settings().apply(settings);
+    save_preferences(settings)?;
+    Ok(file.profiles.len())
+}
+
+fn keep_secret(imported: &mut String, existing: &str) {
+    if imported.is_empty() {
+        *imported = existing.to_string();
+    }
+}
+
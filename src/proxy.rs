@@ -0,0 +1,310 @@
+// Proxies for the requests to the PBX, picked the way macOS picks them for
+// Safari: a proxy auto-configuration (PAC) script or WPAD where the network
+// settings have one, otherwise the manual HTTP and HTTPS proxies and their
+// exceptions. reqwest on its own only knows the manual proxies, and many
+// corporate Macs only get a PAC URL from their profile.
+
+use reqwest::{NoProxy, Proxy};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+use url::Url;
+
+// How long the proxy picked for a host is reused before asking again. Running
+// a PAC script can mean downloading it, and it can be slow.
+const CACHE_TTL: Duration = Duration::from_secs(300);
+
+// Proxy picked for each scheme://host:port, and when
+type Cache = HashMap<String, (Instant, Option<Url>)>;
+
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+// Proxy for the shared HTTP client. HTTPS_PROXY, HTTP_PROXY and ALL_PROXY
+// still win when they're set, and NO_PROXY is honored, as before.
+pub fn system_proxy() -> Proxy {
+    Proxy::custom(proxy_for).no_proxy(NoProxy::from_env())
+}
+
+// Look up the proxy for `url` ahead of a request to it. The lookup can block
+// while a PAC script downloads and runs, so it's done on the blocking pool,
+// and the request then finds it in the cache.
+pub async fn prepare(url: &str) {
+    let Ok(url) = Url::parse(url) else { return };
+    if from_environment(url.scheme()).is_some() || cached(&url).is_some_and(|(fresh, _)| fresh) {
+        return;
+    }
+    tokio::task::spawn_blocking(move || look_up(&url)).await.ok();
+}
+
+fn cache_key(url: &Url) -> String {
+    format!("{}://{}:{}", url.scheme(), url.host_str().unwrap_or_default(), url.port_or_known_default().unwrap_or_default())
+}
+
+// The proxy last picked for `url`'s host, and whether that's recent enough to reuse
+fn cached(url: &Url) -> Option<(bool, Option<Url>)> {
+    let cache = CACHE.lock().unwrap();
+    let (found_at, proxy) = cache.as_ref()?.get(&cache_key(url))?;
+    Some((found_at.elapsed() < CACHE_TTL, proxy.clone()))
+}
+
+// Ask the system for the proxy and remember it. Blocks while a PAC script runs.
+fn look_up(url: &Url) -> Option<Url> {
+    let key = cache_key(url);
+    let proxy = system_proxy_for(url);
+    if let Some(proxy) = &proxy {
+        debug!("Using proxy {} for {}", proxy, key);
+    }
+    CACHE.lock().unwrap().get_or_insert_with(HashMap::new).insert(key, (Instant::now(), proxy.clone()));
+    proxy
+}
+
+fn proxy_for(url: &Url) -> Option<Url> {
+    if let Some(proxy) = from_environment(url.scheme()) {
+        return Some(proxy);
+    }
+
+    match (cached(url), tokio::runtime::Handle::try_current()) {
+        (Some((true, proxy)), _) => proxy,
+        // Outside the runtime there's nothing to hold up
+        (_, Err(_)) => look_up(url),
+        // A request that wasn't prepared mustn't stall the runtime: it goes
+        // the way it went last time, or direct, while the lookup runs
+        (cached, Ok(handle)) => {
+            let target = url.clone();
+            handle.spawn_blocking(move || look_up(&target));
+            cached.and_then(|(_, proxy)| proxy)
+        }
+    }
+}
+
+fn from_environment(scheme: &str) -> Option<Url> {
+    let names: &[&str] = match scheme {
+        "https" => &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"],
+        _ => &["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"],
+    };
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+        .and_then(|value| proxy_url(value.trim()))
+}
+
+// Proxies are often written without a scheme, e.g. proxy.example.com:3128
+fn proxy_url(value: &str) -> Option<Url> {
+    if value.contains("://") {
+        Url::parse(value).ok()
+    } else {
+        Url::parse(&format!("http://{}", value)).ok()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use std::ffi::c_void;
+
+    pub type PacCallback = extern "C" fn(client: *mut c_void, proxies: *const c_void, error: *const c_void);
+
+    #[repr(C)]
+    pub struct CFStreamClientContext {
+        pub version: isize,
+        pub info: *mut c_void,
+        pub retain: *const c_void,
+        pub release: *const c_void,
+        pub copy_description: *const c_void,
+    }
+
+    #[link(name = "CFNetwork", kind = "framework")]
+    extern "C" {
+        pub static kCFProxyTypeKey: *const c_void;
+        pub static kCFProxyHostNameKey: *const c_void;
+        pub static kCFProxyPortNumberKey: *const c_void;
+        pub static kCFProxyAutoConfigurationURLKey: *const c_void;
+        pub static kCFProxyAutoConfigurationJavaScriptKey: *const c_void;
+        pub static kCFProxyTypeNone: *const c_void;
+        pub static kCFProxyTypeHTTP: *const c_void;
+        pub static kCFProxyTypeHTTPS: *const c_void;
+        pub static kCFProxyTypeAutoConfigurationURL: *const c_void;
+        pub static kCFProxyTypeAutoConfigurationJavaScript: *const c_void;
+
+        pub fn CFNetworkCopySystemProxySettings() -> *const c_void;
+        pub fn CFNetworkCopyProxiesForURL(url: *const c_void, proxy_settings: *const c_void) -> *const c_void;
+        pub fn CFNetworkCopyProxiesForAutoConfigurationScript(
+            script: *const c_void,
+            target_url: *const c_void,
+            error: *mut *const c_void,
+        ) -> *const c_void;
+        pub fn CFNetworkExecuteProxyAutoConfigurationURL(
+            pac_url: *const c_void,
+            target_url: *const c_void,
+            callback: PacCallback,
+            context: *mut CFStreamClientContext,
+        ) -> *const c_void;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFRetain(cf: *const c_void) -> *const c_void;
+        pub fn CFRelease(cf: *const c_void);
+        pub fn CFRunLoopGetCurrent() -> *const c_void;
+        pub fn CFRunLoopStop(run_loop: *const c_void);
+        pub fn CFRunLoopAddSource(run_loop: *const c_void, source: *const c_void, mode: *const c_void);
+        pub fn CFRunLoopRemoveSource(run_loop: *const c_void, source: *const c_void, mode: *const c_void);
+        pub fn CFRunLoopRunInMode(mode: *const c_void, seconds: f64, return_after_source_handled: u8) -> i32;
+    }
+}
+
+// Longest wait for a PAC script to download and run before going direct
+#[cfg(target_os = "macos")]
+const PAC_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Ask CFNetwork which proxy the network settings choose for `url`, running
+// the PAC script if they name one
+#[cfg(target_os = "macos")]
+fn system_proxy_for(url: &Url) -> Option<Url> {
+    use crate::macos::ns_string;
+    use objc::runtime::{Class, Object};
+    use objc::{msg_send, sel, sel_impl};
+    use std::ffi::c_void;
+
+    unsafe {
+        let settings = ffi::CFNetworkCopySystemProxySettings();
+        if settings.is_null() {
+            return None;
+        }
+        let url_class = Class::get("NSURL").unwrap();
+        let target: *mut Object = msg_send![url_class, URLWithString: ns_string(url.as_str())];
+        if target.is_null() {
+            ffi::CFRelease(settings);
+            return None;
+        }
+        let proxies = ffi::CFNetworkCopyProxiesForURL(target as *const c_void, settings);
+        ffi::CFRelease(settings);
+        if proxies.is_null() {
+            return None;
+        }
+        let proxy = first_usable(proxies as *mut Object, target, 0);
+        ffi::CFRelease(proxies);
+        proxy
+    }
+}
+
+// Go through CFNetwork's list of proxies in order of preference and take the
+// first one reqwest can use. DIRECT ends the search; SOCKS and FTP proxies
+// are passed over. `depth` stops a PAC script from sending us round in circles.
+#[cfg(target_os = "macos")]
+unsafe fn first_usable(proxies: *mut objc::runtime::Object, target: *mut objc::runtime::Object, depth: u32) -> Option<Url> {
+    use crate::macos::rust_string;
+    use objc::runtime::{Object, BOOL, YES};
+    use objc::{msg_send, sel, sel_impl};
+    use std::ffi::c_void;
+    use tracing::warn;
+
+    let is = |value: *mut Object, constant: *const c_void| -> bool {
+        let equal: BOOL = msg_send![value, isEqual: constant as *mut Object];
+        equal == YES
+    };
+
+    let count: usize = msg_send![proxies, count];
+    for i in 0..count {
+        let proxy: *mut Object = msg_send![proxies, objectAtIndex: i];
+        let kind: *mut Object = msg_send![proxy, objectForKey: ffi::kCFProxyTypeKey as *mut Object];
+        if kind.is_null() {
+            continue;
+        }
+
+        if is(kind, ffi::kCFProxyTypeNone) {
+            return None;
+        }
+        if is(kind, ffi::kCFProxyTypeHTTP) || is(kind, ffi::kCFProxyTypeHTTPS) {
+            let host: *mut Object = msg_send![proxy, objectForKey: ffi::kCFProxyHostNameKey as *mut Object];
+            let Some(host) = rust_string(host).filter(|host| !host.is_empty()) else { continue };
+            let port: *mut Object = msg_send![proxy, objectForKey: ffi::kCFProxyPortNumberKey as *mut Object];
+            let port: i32 = if port.is_null() { 0 } else { msg_send![port, intValue] };
+            let port = if port > 0 { port } else { 8080 };
+            if let Some(url) = proxy_url(&format!("{}:{}", host, port)) {
+                return Some(url);
+            }
+            continue;
+        }
+        if depth > 0 {
+            continue;
+        }
+
+        let resolved = if is(kind, ffi::kCFProxyTypeAutoConfigurationURL) {
+            let pac_url: *mut Object = msg_send![proxy, objectForKey: ffi::kCFProxyAutoConfigurationURLKey as *mut Object];
+            if pac_url.is_null() {
+                continue;
+            }
+            run_pac_url(pac_url as *const c_void, target as *const c_void)
+        } else if is(kind, ffi::kCFProxyTypeAutoConfigurationJavaScript) {
+            let script: *mut Object = msg_send![proxy, objectForKey: ffi::kCFProxyAutoConfigurationJavaScriptKey as *mut Object];
+            if script.is_null() {
+                continue;
+            }
+            let mut error: *const c_void = std::ptr::null();
+            let resolved = ffi::CFNetworkCopyProxiesForAutoConfigurationScript(script as *const c_void, target as *const c_void, &mut error);
+            if !error.is_null() {
+                ffi::CFRelease(error);
+            }
+            resolved
+        } else {
+            continue;
+        };
+
+        // A PAC script that couldn't be fetched or run leaves the next entry to try
+        if resolved.is_null() {
+            warn!("Couldn't evaluate the proxy auto-configuration script for {}", rust_string(msg_send![target, absoluteString]).unwrap_or_default());
+            continue;
+        }
+        let proxy = first_usable(resolved as *mut Object, target, depth + 1);
+        ffi::CFRelease(resolved);
+        return proxy;
+    }
+    None
+}
+
+// Download and run the PAC script at `pac_url`. CFNetwork reports back on a
+// run loop source, so this thread runs its run loop in a private mode until
+// the answer is in or PAC_TIMEOUT passes. Returns a retained array or null.
+#[cfg(target_os = "macos")]
+unsafe fn run_pac_url(pac_url: *const std::ffi::c_void, target: *const std::ffi::c_void) -> *const std::ffi::c_void {
+    use crate::macos::ns_string;
+    use std::ffi::c_void;
+
+    extern "C" fn done(client: *mut c_void, proxies: *const c_void, error: *const c_void) {
+        unsafe {
+            let result = client as *mut *const c_void;
+            if error.is_null() && !proxies.is_null() {
+                *result = ffi::CFRetain(proxies);
+            }
+            ffi::CFRunLoopStop(ffi::CFRunLoopGetCurrent());
+        }
+    }
+
+    let mut result: *const c_void = std::ptr::null();
+    let mut context = ffi::CFStreamClientContext {
+        version: 0,
+        info: &mut result as *mut *const c_void as *mut c_void,
+        retain: std::ptr::null(),
+        release: std::ptr::null(),
+        copy_description: std::ptr::null(),
+    };
+    let source = ffi::CFNetworkExecuteProxyAutoConfigurationURL(pac_url, target, done, &mut context);
+    if source.is_null() {
+        return std::ptr::null();
+    }
+
+    let run_loop = ffi::CFRunLoopGetCurrent();
+    let mode = ns_string("com.click-to-call.proxy-auto-configuration") as *const c_void;
+    ffi::CFRunLoopAddSource(run_loop, source, mode);
+    ffi::CFRunLoopRunInMode(mode, PAC_TIMEOUT.as_secs_f64(), 0);
+    ffi::CFRunLoopRemoveSource(run_loop, source, mode);
+    ffi::CFRelease(source);
+    result
+}
+
+#[cfg(not(target_os = "macos"))]
+fn system_proxy_for(_url: &Url) -> Option<Url> {
+    None
+}
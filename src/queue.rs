@@ -0,0 +1,202 @@
+// Calls waiting for their turn with the PBX. As many originations run at once
+// as the settings allow, one unless they say otherwise; calls asked for
+// meanwhile wait here in order and go as earlier ones are placed, fail or
+// time out. The Call Queue window can reorder them, cancel them or send one
+// right away.
+
+use crate::audit::CallOrigin;
+use crate::config::AppState;
+use chrono::{DateTime, Local};
+use druid::ExtEventSink;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+// Originations running at once when the setting is empty; the rest wait in the queue
+const DEFAULT_LIMIT: usize = 1;
+// More than this at once is what the queue is there to prevent
+const MAX_LIMIT: usize = 20;
+
+static LIMIT: AtomicUsize = AtomicUsize::new(DEFAULT_LIMIT);
+
+#[derive(Clone)]
+pub struct QueuedCall {
+    pub id: u64,
+    pub number: String,
+    pub origin: CallOrigin,
+    pub queued_at: DateTime<Local>,
+}
+
+// A queued call and how to tell it whether to go ahead (true) or give up
+struct Waiting {
+    call: QueuedCall,
+    start: oneshot::Sender<bool>,
+}
+
+struct Queue {
+    waiting: VecDeque<Waiting>,
+    // Calls being originated right now
+    dialing: Vec<QueuedCall>,
+    next_id: u64,
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue { waiting: VecDeque::new(), dialing: Vec::new(), next_id: 0 });
+
+// Lets the UI know the queue changed so the Call Queue window is redone
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+// The number of calls to originate at once, as typed in the settings
+pub fn parse_limit(limit: &str) -> Result<usize, String> {
+    let limit = limit.trim();
+    if limit.is_empty() {
+        return Ok(DEFAULT_LIMIT);
+    }
+    match limit.parse::<usize>() {
+        Ok(limit) if (1..=MAX_LIMIT).contains(&limit) => Ok(limit),
+        _ => Err(format!("Calls at once should be a whole number from 1 to {}, not \"{}\"", MAX_LIMIT, limit)),
+    }
+}
+
+// The active profile's limit. Settings are checked when they're saved, so the
+// default only stands in for a hand-edited file.
+pub fn limit(state: &AppState) -> usize {
+    parse_limit(&state.max_concurrent_calls).unwrap_or_else(|e| {
+        warn!("Originating one call at a time: {}", e);
+        DEFAULT_LIMIT
+    })
+}
+
+// Originate up to `limit` calls at once. Waiting calls start right away if
+// there's now room for them.
+pub fn set_limit(limit: usize) {
+    if LIMIT.swap(limit, Ordering::SeqCst) == limit {
+        return;
+    }
+    let mut queue = QUEUE.lock().unwrap();
+    let waiting = queue.waiting.len();
+    dispatch(&mut queue);
+    let started = waiting != queue.waiting.len();
+    drop(queue);
+    if started {
+        changed();
+    }
+}
+
+// Have the Call Queue window show the calls as they are now
+pub fn changed() {
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(|data: &mut AppState| data.queue_revision += 1);
+    }
+}
+
+// Held while a call is being originated; the next call in line goes once it's dropped
+pub struct Turn {
+    id: u64,
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        let mut queue = QUEUE.lock().unwrap();
+        queue.dialing.retain(|call| call.id != self.id);
+        dispatch(&mut queue);
+        drop(queue);
+        changed();
+    }
+}
+
+// Start waiting calls while there's room
+fn dispatch(queue: &mut Queue) {
+    while queue.dialing.len() < LIMIT.load(Ordering::SeqCst) {
+        let Some(next) = queue.waiting.pop_front() else { break };
+        start(queue, next);
+    }
+}
+
+fn start(queue: &mut Queue, waiting: Waiting) {
+    queue.dialing.push(waiting.call.clone());
+    // Whoever was waiting has gone away; don't hold the slot for them
+    if waiting.start.send(true).is_err() {
+        queue.dialing.retain(|call| call.id != waiting.call.id);
+    }
+}
+
+// Wait until a call to `number` may be originated. None if it was cancelled
+// from the queue meanwhile.
+pub async fn wait_turn(number: &str, origin: CallOrigin) -> Option<Turn> {
+    let (id, started) = {
+        let mut queue = QUEUE.lock().unwrap();
+        queue.next_id += 1;
+        let call = QueuedCall { id: queue.next_id, number: number.to_string(), origin, queued_at: Local::now() };
+        let id = call.id;
+
+        if queue.waiting.is_empty() && queue.dialing.len() < LIMIT.load(Ordering::SeqCst) {
+            queue.dialing.push(call);
+            (id, None)
+        } else {
+            // Numbers stay out of the log; private calls wait here too
+            info!("Queuing call {} behind {} other call(s)", id, queue.waiting.len() + queue.dialing.len());
+            let (start, started) = oneshot::channel();
+            queue.waiting.push_back(Waiting { call, start });
+            (id, Some(started))
+        }
+    };
+    changed();
+
+    match started {
+        None => Some(Turn { id }),
+        Some(started) => match started.await {
+            Ok(true) => Some(Turn { id }),
+            _ => None,
+        },
+    }
+}
+
+// Calls waiting, next one first
+pub fn waiting() -> Vec<QueuedCall> {
+    QUEUE.lock().unwrap().waiting.iter().map(|waiting| waiting.call.clone()).collect()
+}
+
+// Calls being originated right now
+pub fn dialing() -> Vec<QueuedCall> {
+    QUEUE.lock().unwrap().dialing.clone()
+}
+
+fn take(queue: &mut Queue, id: u64) -> Option<Waiting> {
+    let index = queue.waiting.iter().position(|waiting| waiting.call.id == id)?;
+    queue.waiting.remove(index)
+}
+
+// Drop a waiting call; it's reported as cancelled
+pub fn cancel(id: u64) {
+    let taken = take(&mut QUEUE.lock().unwrap(), id);
+    if let Some(waiting) = taken {
+        waiting.start.send(false).ok();
+        changed();
+    }
+}
+
+// Originate a waiting call now, alongside whatever is already being dialed
+pub fn dial_now(id: u64) {
+    let mut queue = QUEUE.lock().unwrap();
+    if let Some(waiting) = take(&mut queue, id) {
+        start(&mut queue, waiting);
+        drop(queue);
+        changed();
+    }
+}
+
+// Move a waiting call one place towards the front (-1) or the back (1)
+pub fn move_by(id: u64, offset: isize) {
+    let mut queue = QUEUE.lock().unwrap();
+    let Some(index) = queue.waiting.iter().position(|waiting| waiting.call.id == id) else { return };
+    let Some(target) = index.checked_add_signed(offset).filter(|target| *target < queue.waiting.len()) else { return };
+    queue.waiting.swap(index, target);
+    drop(queue);
+    changed();
+}
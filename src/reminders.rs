@@ -0,0 +1,147 @@
+use crate::history::{self, HistoryEntry};
+use crate::{notify, paths, wake};
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+// How often the scheduler looks for reminders that are due
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// "Tomorrow" reminders go off at this time
+const MORNING: (u32, u32) = (9, 0);
+
+// Serializes read-modify-write of the reminders file
+static REMINDERS_LOCK: Mutex<()> = Mutex::new(());
+static SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+// When to be reminded to try an unanswered call again
+#[derive(Clone, Copy)]
+pub enum FollowUp {
+    OneHour,
+    ThreeHours,
+    Tomorrow,
+}
+
+impl FollowUp {
+    pub const ALL: [FollowUp; 3] = [FollowUp::OneHour, FollowUp::ThreeHours, FollowUp::Tomorrow];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FollowUp::OneHour => "In 1 hour",
+            FollowUp::ThreeHours => "In 3 hours",
+            FollowUp::Tomorrow => "Tomorrow morning",
+        }
+    }
+
+    fn due(&self, now: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            FollowUp::OneHour => now + ChronoDuration::hours(1),
+            FollowUp::ThreeHours => now + ChronoDuration::hours(3),
+            FollowUp::Tomorrow => {
+                let morning = NaiveTime::from_hms_opt(MORNING.0, MORNING.1, 0).unwrap_or_default();
+                let tomorrow = now.date_naive().succ_opt().unwrap_or(now.date_naive()).and_time(morning);
+                // A DST gap at 9am is unlikely, but fall back to a day from now
+                Local.from_local_datetime(&tomorrow).earliest().unwrap_or(now + ChronoDuration::days(1))
+            }
+        }
+    }
+}
+
+// A pending reminder, tied to the history entry of the unanswered call
+#[derive(Serialize, Deserialize)]
+struct Reminder {
+    due: DateTime<Local>,
+    number: String,
+    name: Option<String>,
+    // Time of the history entry
+    call_time: DateTime<Local>,
+}
+
+fn load() -> Vec<Reminder> {
+    std::fs::read_to_string(paths::reminders_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(reminders: &[Reminder]) -> Result<(), String> {
+    let path = paths::reminders_file();
+    let json = serde_json::to_string(reminders).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(paths::config_dir())
+        .and_then(|_| std::fs::write(&path, json))
+        .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+}
+
+// Remind the user to call `entry`'s number again. Returns when the reminder is due.
+pub fn schedule(entry: &HistoryEntry, follow_up: FollowUp) -> Result<DateTime<Local>, String> {
+    let due = follow_up.due(Local::now());
+    {
+        let _guard = REMINDERS_LOCK.lock().unwrap();
+        let mut reminders = load();
+        // A new reminder for the same call replaces the old one
+        reminders.retain(|reminder| reminder.call_time != entry.time);
+        reminders.push(Reminder {
+            due,
+            number: entry.number.clone(),
+            name: entry.name.clone(),
+            call_time: entry.time,
+        });
+        save(&reminders)?;
+    }
+
+    history::set_follow_up(entry.time, due);
+    Ok(due)
+}
+
+// Start checking for due reminders, including ones saved before a restart.
+// Safe to call more than once.
+pub fn start() {
+    if !SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        thread::spawn(|| loop {
+            send_due_reminders();
+            thread::sleep(CHECK_INTERVAL);
+        });
+    }
+}
+
+fn send_due_reminders() {
+    // Reminders that came due while the Mac was asleep wait out the grace
+    // period, so they don't all go off as the lid opens
+    wake::check();
+    if wake::in_grace_period() {
+        return;
+    }
+
+    let _guard = REMINDERS_LOCK.lock().unwrap();
+    let now = Local::now();
+    let grace_period_end = wake::grace_period_end();
+    let (due, pending): (Vec<Reminder>, Vec<Reminder>) = load().into_iter().partition(|reminder| reminder.due <= now);
+    if due.is_empty() {
+        return;
+    }
+    if let Err(e) = save(&pending) {
+        // Better to skip this round than to remind twice
+        warn!("Failed to update reminders: {}", e);
+        return;
+    }
+
+    for reminder in due {
+        let callee = match &reminder.name {
+            Some(name) => format!("{} ({})", name, reminder.number),
+            None => reminder.number.clone(),
+        };
+        // Clicking the notification calls the number again, after asking if
+        // the reminder was held back over a sleep
+        let held = grace_period_end.is_some_and(|end| reminder.due <= end);
+        let action = if held { notify::ACTION_CONFIRM_CALL } else { notify::ACTION_CALL_AGAIN };
+        notify::show_notification_with_action(
+            "Call Back",
+            &format!("No answer from {} at {}. Click to call again.", callee, reminder.call_time.format("%H:%M")),
+            &format!("{}{}", action, reminder.number),
+        );
+    }
+}
@@ -0,0 +1,265 @@
+// Calls that failed because the PBX couldn't be reached at all, e.g. while a
+// VPN reconnects, are tried again with growing delays instead of being lost.
+// They're kept in retry_queue.json, so they survive a restart and calls made
+// from Terminal are retried by the running app. How long a request may take,
+// how often a call is retried and how long the first wait is are profile
+// settings; empty means the defaults below.
+
+use crate::audit::CallOrigin;
+use crate::call::{place_call, CALL_TIMEOUT};
+use crate::config::{load_preferences, AppState};
+use crate::{dial_plan, notify, paths, queue, runtime};
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+// How often the scheduler looks for retries that are due
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Wait before the first retry unless the profile says otherwise; it doubles
+// after each one that fails
+const FIRST_DELAY: Duration = Duration::from_secs(30);
+
+// Longest wait between two tries
+const MAX_DELAY: Duration = Duration::from_secs(10 * 60);
+
+// Retries after the first attempt before giving up, about half an hour in
+// all, unless the profile says otherwise
+const MAX_RETRIES: u32 = 7;
+
+// Limits on the profile settings, so a typo can't mean a call that waits all
+// day or a PBX that's asked every second
+const MAX_TIMEOUT_SECS: u64 = 300;
+const MAX_RETRIES_SETTING: u32 = 20;
+const MAX_FIRST_DELAY_SECS: u64 = 600;
+
+// A call still waiting after this long, e.g. because the app wasn't running,
+// is given up on rather than dialed out of the blue
+const MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+// Serializes read-modify-write of the retry queue file
+static RETRIES_LOCK: Mutex<()> = Mutex::new(());
+static SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+// A call waiting to be tried again
+#[derive(Serialize, Deserialize)]
+struct Retry {
+    due: DateTime<Local>,
+    number: String,
+    note: Option<String>,
+    // Prefix of the route it was dialed through, looked up again when it's retried
+    route: Option<String>,
+    // Which retry this is, from 1
+    retry: u32,
+    first_failed: DateTime<Local>,
+}
+
+// A call waiting to be tried again, as the Call Queue window lists it
+pub struct PendingRetry {
+    pub number: String,
+    pub due: DateTime<Local>,
+    pub retry: u32,
+}
+
+// A profile's timeout and retry settings
+#[derive(Clone, Copy)]
+pub struct Policy {
+    // How long a request to the PBX may take before the call is given up on
+    pub timeout: Duration,
+    // Retries after the first attempt when the PBX can't be reached
+    pub retries: u32,
+    // Wait before the first retry, doubling after each
+    pub first_delay: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy { timeout: CALL_TIMEOUT, retries: MAX_RETRIES, first_delay: FIRST_DELAY }
+    }
+}
+
+// A whole number from `min` to `max` in a setting, or `default` when it's empty
+fn parse_setting<T: std::str::FromStr + PartialOrd + std::fmt::Display + Copy>(value: &str, default: T, min: T, max: T, what: &str) -> Result<T, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(default);
+    }
+    match value.parse::<T>() {
+        Ok(number) if number >= min && number <= max => Ok(number),
+        _ => Err(format!("the {} should be a whole number from {} to {}", what, min, max)),
+    }
+}
+
+// Read the timeout in seconds, the number of retries and the first retry's
+// wait in seconds, as typed in the settings
+pub fn parse_policy(timeout: &str, retries: &str, first_delay: &str) -> Result<Policy, String> {
+    let defaults = Policy::default();
+    Ok(Policy {
+        timeout: Duration::from_secs(parse_setting(timeout, defaults.timeout.as_secs(), 1, MAX_TIMEOUT_SECS, "request timeout")?),
+        retries: parse_setting(retries, defaults.retries, 0, MAX_RETRIES_SETTING, "number of retries")?,
+        first_delay: Duration::from_secs(parse_setting(first_delay, defaults.first_delay.as_secs(), 1, MAX_FIRST_DELAY_SECS, "retry wait")?),
+    })
+}
+
+// The active profile's policy. Settings are checked when they're saved, so
+// the defaults only stand in for a hand-edited file.
+pub fn policy(state: &AppState) -> Policy {
+    parse_policy(&state.request_timeout, &state.retry_attempts, &state.retry_delay).unwrap_or_else(|e| {
+        warn!("Using the default timeout and retries: {}", e);
+        Policy::default()
+    })
+}
+
+// What happens next to a call that didn't reach the PBX
+pub enum Plan {
+    // Tried again at this time
+    RetryAt(DateTime<Local>),
+    // Given up on after this many attempts
+    GiveUp { attempts: u32 },
+}
+
+fn load() -> Vec<Retry> {
+    std::fs::read_to_string(paths::retry_queue_file())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(retries: &[Retry]) -> Result<(), String> {
+    let path = paths::retry_queue_file();
+    let json = serde_json::to_string(retries).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(paths::config_dir())
+        .and_then(|_| std::fs::write(&path, json))
+        .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+}
+
+// Wait before retry number `retry`: `first_delay`, doubling up to MAX_DELAY
+fn delay(retry: u32, first_delay: Duration) -> Duration {
+    first_delay.saturating_mul(1 << retry.saturating_sub(1).min(16)).min(MAX_DELAY)
+}
+
+// Queue a call to `number` that couldn't reach the PBX to be tried again.
+// `origin` tells a retry that failed again apart from a first attempt.
+pub fn schedule(number: &str, origin: CallOrigin, note: Option<&str>, route: Option<&str>, policy: &Policy) -> Plan {
+    let (retry, first_failed) = match origin {
+        CallOrigin::Retry { retry, first_failed } => (retry + 1, first_failed),
+        _ => (1, Local::now()),
+    };
+    if retry > policy.retries || Local::now() - first_failed > ChronoDuration::from_std(MAX_AGE).unwrap_or_default() {
+        return Plan::GiveUp { attempts: retry };
+    }
+
+    let due = Local::now() + ChronoDuration::from_std(delay(retry, policy.first_delay)).unwrap_or_default();
+    let _guard = RETRIES_LOCK.lock().unwrap();
+    let mut retries = load();
+    retries.push(Retry {
+        due,
+        number: number.to_string(),
+        note: note.map(str::to_string),
+        route: route.map(str::to_string),
+        retry,
+        first_failed,
+    });
+    match save(&retries) {
+        Ok(()) => {
+            queue::changed();
+            Plan::RetryAt(due)
+        }
+        Err(e) => {
+            warn!("Failed to queue the call for a retry: {}", e);
+            Plan::GiveUp { attempts: retry }
+        }
+    }
+}
+
+// Calls waiting to be tried again, soonest first
+pub fn pending() -> Vec<PendingRetry> {
+    let _guard = RETRIES_LOCK.lock().unwrap();
+    let mut pending: Vec<PendingRetry> = load()
+        .into_iter()
+        .map(|retry| PendingRetry { number: retry.number, due: retry.due, retry: retry.retry })
+        .collect();
+    pending.sort_by_key(|retry| retry.due);
+    pending
+}
+
+// Stop trying the call to `number` that's due at `due`
+pub fn cancel(number: &str, due: DateTime<Local>) {
+    {
+        let _guard = RETRIES_LOCK.lock().unwrap();
+        let mut retries = load();
+        retries.retain(|retry| !(retry.number == number && retry.due == due));
+        if let Err(e) = save(&retries) {
+            warn!("Failed to update the retry queue: {}", e);
+        }
+    }
+    queue::changed();
+}
+
+// Start trying queued calls again when they're due, including ones saved
+// before a restart. Safe to call more than once.
+pub fn start() {
+    if !SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        thread::spawn(|| loop {
+            retry_due_calls();
+            thread::sleep(CHECK_INTERVAL);
+        });
+    }
+}
+
+fn retry_due_calls() {
+    let due = {
+        let _guard = RETRIES_LOCK.lock().unwrap();
+        let now = Local::now();
+        let (due, pending): (Vec<Retry>, Vec<Retry>) = load().into_iter().partition(|retry| retry.due <= now);
+        if due.is_empty() {
+            return;
+        }
+        if let Err(e) = save(&pending) {
+            // Better to wait for the next round than to dial twice
+            warn!("Failed to update the retry queue: {}", e);
+            return;
+        }
+        due
+    };
+    queue::changed();
+
+    let max_age = ChronoDuration::from_std(MAX_AGE).unwrap_or_default();
+    for retry in due {
+        if Local::now() - retry.first_failed > max_age {
+            notify::notify_call_failed(
+                &retry.number,
+                &format!("Gave up calling {}: the PBX couldn't be reached since {}", retry.number, retry.first_failed.format("%H:%M")),
+            );
+            continue;
+        }
+
+        // The settings as they are now, in case the PBX address was fixed meanwhile
+        let preferences = load_preferences();
+        info!("Trying the call to {} again (retry {} of {})", retry.number, retry.retry, policy(&preferences).retries);
+        runtime().spawn(async move {
+            let route = retry.route.as_ref().and_then(|prefix| {
+                dial_plan::parse_routes(&preferences.routes)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|route| route.prefix == *prefix)
+            });
+            let origin = CallOrigin::Retry { retry: retry.retry, first_failed: retry.first_failed };
+            let result = place_call(
+                &preferences,
+                &retry.number,
+                origin,
+                retry.note.as_deref(),
+                route.as_ref(),
+                false,
+                None,
+            )
+            .await;
+            info!("{}", result.unwrap_or_else(|failure| failure.message));
+        });
+    }
+}
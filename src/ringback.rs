@@ -0,0 +1,52 @@
+// The ringback tone played to the extension while the number rings, picked
+// to sound like a call to the destination's country. The names are tone
+// variables FreeSWITCH and FusionPBX define in vars.xml.
+
+use crate::number_format;
+
+// Used when neither the destination nor the home country has its own tone
+const DEFAULT_TONE: &str = "us-ring";
+
+// Tones by calling code, with the countries that use them
+const TONES: &[(&str, &[&str], &str)] = &[
+    ("1", &["US", "CA"], "us-ring"),
+    ("44", &["GB"], "uk-ring"),
+    ("353", &["IE"], "uk-ring"),
+    ("33", &["FR"], "fr-ring"),
+    ("7", &["RU"], "rs-ring"),
+    ("39", &["IT"], "it-ring"),
+];
+
+// Whether `tone` can be sent as the ringback setting: a tone variable's
+// name, or empty to pick by destination
+pub fn check(tone: &str) -> Result<(), String> {
+    if tone.trim().chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(format!("the ringback should be a tone name such as uk-ring, not {:?}", tone.trim()))
+    }
+}
+
+// Tone for a call to `number` dialed from `country`. A non-empty `tone`
+// setting wins. Numbers abroad get their country's tone; extensions and
+// short codes get the home country's.
+pub fn tone_for(number: &str, country: &str, short_codes: &str, tone: &str) -> String {
+    let tone = tone.trim();
+    if !tone.is_empty() {
+        return tone.to_string();
+    }
+
+    let e164 = number_format::to_e164(number, country, short_codes);
+    let by_destination = e164.strip_prefix('+').and_then(|digits| {
+        TONES
+            .iter()
+            .filter(|(calling_code, _, _)| digits.starts_with(calling_code))
+            .max_by_key(|(calling_code, _, _)| calling_code.len())
+    });
+    let country = country.trim().to_uppercase();
+    let by_home = || TONES.iter().find(|(_, countries, _)| countries.contains(&country.as_str()));
+    match by_destination.or_else(by_home) {
+        Some((_, _, tone)) => tone.to_string(),
+        None => DEFAULT_TONE.to_string(),
+    }
+}
@@ -0,0 +1,231 @@
+use crate::phone_text::find_phone_number;
+use druid::Rect;
+use tracing::debug;
+
+// Size of the screen area around the pointer that gets OCR'd
+#[cfg(all(target_os = "macos", feature = "screen-lookup"))]
+const CAPTURE_WIDTH: f64 = 640.0;
+#[cfg(all(target_os = "macos", feature = "screen-lookup"))]
+const CAPTURE_HEIGHT: f64 = 200.0;
+
+// A phone number found on screen. `bounds` is set when OCR located it,
+// in screen points with the origin at the top left of the main display.
+#[derive(Clone)]
+pub struct DetectedNumber {
+    pub number: String,
+    pub bounds: Option<Rect>,
+}
+
+// Find the number the user is looking at: the selected text in the focused
+// app first (via Accessibility), then OCR of the area around the pointer
+pub fn number_at_focus() -> Option<DetectedNumber> {
+    if let Some(text) = selected_text() {
+        if let Some(number) = find_phone_number(&text) {
+            debug!("Found number in selection: {}", number);
+            // The selection is already highlighted by the app that owns it
+            return Some(DetectedNumber { number, bounds: None });
+        }
+    }
+
+    number_near_pointer()
+}
+
+#[cfg(all(target_os = "macos", feature = "screen-lookup"))]
+mod ffi {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct CGPoint {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct CGSize {
+        pub width: f64,
+        pub height: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct CGRect {
+        pub origin: CGPoint,
+        pub size: CGSize,
+    }
+
+    pub const WINDOW_LIST_ON_SCREEN_ONLY: u32 = 1;
+    pub const NULL_WINDOW_ID: u32 = 0;
+    pub const WINDOW_IMAGE_DEFAULT: u32 = 0;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        pub fn AXIsProcessTrustedWithOptions(options: *const c_void) -> u8;
+        pub fn AXUIElementCreateSystemWide() -> *mut c_void;
+        pub fn AXUIElementCopyAttributeValue(
+            element: *mut c_void,
+            attribute: *const c_void,
+            value: *mut *mut c_void,
+        ) -> i32;
+        pub fn CGEventCreate(source: *const c_void) -> *mut c_void;
+        pub fn CGEventGetLocation(event: *const c_void) -> CGPoint;
+        pub fn CGWindowListCreateImage(rect: CGRect, list_option: u32, window_id: u32, image_option: u32) -> *mut c_void;
+        pub fn CGImageRelease(image: *mut c_void);
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFRelease(cf: *const c_void);
+    }
+
+    // Vision is only used through the Objective-C runtime, but has to be loaded
+    #[link(name = "Vision", kind = "framework")]
+    extern "C" {}
+}
+
+// Ask the focused UI element for its selected text.
+// Prompts for Accessibility permission the first time.
+#[cfg(all(target_os = "macos", feature = "screen-lookup"))]
+fn selected_text() -> Option<String> {
+    use crate::macos::{ns_string, rust_string};
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, YES};
+    use std::ffi::c_void;
+    use tracing::warn;
+
+    unsafe {
+        // Show the system prompt if we haven't been granted Accessibility access yet
+        let number_class = Class::get("NSNumber").unwrap();
+        let dictionary_class = Class::get("NSDictionary").unwrap();
+        let prompt: *mut Object = msg_send![number_class, numberWithBool: YES];
+        let options: *mut Object = msg_send![dictionary_class, dictionaryWithObject:prompt forKey:ns_string("AXTrustedCheckOptionPrompt")];
+        if ffi::AXIsProcessTrustedWithOptions(options as *const c_void) == 0 {
+            warn!("Accessibility access not granted, skipping selection lookup");
+            return None;
+        }
+
+        let system = ffi::AXUIElementCreateSystemWide();
+        let mut focused: *mut c_void = std::ptr::null_mut();
+        let status = ffi::AXUIElementCopyAttributeValue(system, ns_string("AXFocusedUIElement") as *const c_void, &mut focused);
+        ffi::CFRelease(system);
+        if status != 0 || focused.is_null() {
+            return None;
+        }
+
+        let mut selected: *mut c_void = std::ptr::null_mut();
+        let status = ffi::AXUIElementCopyAttributeValue(focused, ns_string("AXSelectedText") as *const c_void, &mut selected);
+        ffi::CFRelease(focused);
+        if status != 0 || selected.is_null() {
+            return None;
+        }
+
+        // AXSelectedText is a CFString, which is toll-free bridged to NSString
+        let text = rust_string(selected as *mut Object);
+        ffi::CFRelease(selected);
+        text
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "screen-lookup")))]
+fn selected_text() -> Option<String> {
+    None
+}
+
+// Capture the screen around the pointer and OCR it, picking the number
+// closest to the pointer
+#[cfg(all(target_os = "macos", feature = "screen-lookup"))]
+fn number_near_pointer() -> Option<DetectedNumber> {
+    use crate::macos::rust_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, BOOL};
+    use tracing::warn;
+
+    // Text recognition needs macOS 10.15 or later
+    let handler_class = Class::get("VNImageRequestHandler")?;
+    let request_class = Class::get("VNRecognizeTextRequest")?;
+
+    unsafe {
+        // Pointer location in global coordinates (top left origin), same as the capture API
+        let event = ffi::CGEventCreate(std::ptr::null());
+        let pointer = ffi::CGEventGetLocation(event);
+        ffi::CFRelease(event);
+
+        let capture = ffi::CGRect {
+            origin: ffi::CGPoint {
+                x: pointer.x - CAPTURE_WIDTH / 2.0,
+                y: pointer.y - CAPTURE_HEIGHT / 2.0,
+            },
+            size: ffi::CGSize { width: CAPTURE_WIDTH, height: CAPTURE_HEIGHT },
+        };
+
+        // Requires Screen Recording permission; macOS prompts on first use
+        let image = ffi::CGWindowListCreateImage(
+            capture,
+            ffi::WINDOW_LIST_ON_SCREEN_ONLY,
+            ffi::NULL_WINDOW_ID,
+            ffi::WINDOW_IMAGE_DEFAULT,
+        );
+        if image.is_null() {
+            warn!("Screen capture failed, is Screen Recording permission granted?");
+            return None;
+        }
+
+        let dictionary_class = Class::get("NSDictionary").unwrap();
+        let array_class = Class::get("NSArray").unwrap();
+
+        let options: *mut Object = msg_send![dictionary_class, dictionary];
+        let handler: *mut Object = msg_send![handler_class, alloc];
+        let handler: *mut Object = msg_send![handler, initWithCGImage:image options:options];
+        let request: *mut Object = msg_send![request_class, alloc];
+        let request: *mut Object = msg_send![request, init];
+        let requests: *mut Object = msg_send![array_class, arrayWithObject: request];
+
+        // Runs synchronously on this (background) thread
+        let error: *mut *mut Object = std::ptr::null_mut();
+        let _: BOOL = msg_send![handler, performRequests:requests error:error];
+
+        let mut best: Option<(f64, DetectedNumber)> = None;
+        let results: *mut Object = msg_send![request, results];
+        let count: usize = if results.is_null() { 0 } else { msg_send![results, count] };
+
+        for i in 0..count {
+            let observation: *mut Object = msg_send![results, objectAtIndex: i];
+            let candidates: *mut Object = msg_send![observation, topCandidates: 1usize];
+            let candidate_count: usize = msg_send![candidates, count];
+            if candidate_count == 0 {
+                continue;
+            }
+
+            let candidate: *mut Object = msg_send![candidates, objectAtIndex: 0usize];
+            let text: *mut Object = msg_send![candidate, string];
+            let number = match rust_string(text).and_then(|text| find_phone_number(&text)) {
+                Some(number) => number,
+                None => continue,
+            };
+
+            // Vision returns normalized coordinates with the origin at the bottom left
+            let bounds: ffi::CGRect = msg_send![observation, boundingBox];
+            let x = capture.origin.x + bounds.origin.x * CAPTURE_WIDTH;
+            let y = capture.origin.y + (1.0 - bounds.origin.y - bounds.size.height) * CAPTURE_HEIGHT;
+            let rect = Rect::from_origin_size((x, y), (bounds.size.width * CAPTURE_WIDTH, bounds.size.height * CAPTURE_HEIGHT));
+
+            let distance = (rect.center().x - pointer.x).powi(2) + (rect.center().y - pointer.y).powi(2);
+            if best.as_ref().map_or(true, |(best_distance, _)| distance < *best_distance) {
+                best = Some((distance, DetectedNumber { number, bounds: Some(rect) }));
+            }
+        }
+
+        let _: () = msg_send![request, release];
+        let _: () = msg_send![handler, release];
+        ffi::CGImageRelease(image);
+
+        best.map(|(_, detected)| detected)
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "screen-lookup")))]
+fn number_near_pointer() -> Option<DetectedNumber> {
+    // No screen capture or OCR outside macOS
+    None
+}
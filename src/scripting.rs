@@ -0,0 +1,161 @@
+// AppleScript commands, for scripts, Automator and the Run AppleScript action
+// in Shortcuts: place call, get recent calls and set active profile.
+// ClickToCall.sdef in the bundle declares them; this registers the
+// NSScriptCommand subclasses it names, which hand the work to the delegate
+// the way the buttons do.
+
+use crate::history::{self, HistoryEntry};
+use crate::ui::SWITCH_PROFILE;
+use crate::{call_links, profiles, tel_uri};
+use druid::{ExtEventSink, Selector, Target};
+use std::sync::OnceLock;
+
+// Sent to the delegate with the number and whether the call is private
+pub const SCRIPT_CALL: Selector<(String, bool)> = Selector::new("app.script-call");
+
+// How many calls get recent calls returns unless the script says
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const RECENT_CALLS: usize = 20;
+
+// Where commands are forwarded, set once the app is running. Only macOS has
+// AppleScript, so elsewhere the commands are never asked for.
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn event_sink() -> Result<&'static ExtEventSink, String> {
+    EVENT_SINK.get().ok_or_else(|| "the app is still starting, try again in a moment".to_string())
+}
+
+// place call: a number, or a tel:, callto:, SIP or clicktocall: link
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn place_call(text: &str, private: bool) -> Result<(), String> {
+    let link = match call_links::read(text) {
+        Some(link) => link?.tel,
+        None => tel_uri::link(text.trim(), false),
+    };
+    let uri = tel_uri::parse(&link)?;
+    event_sink()?.submit_command(SCRIPT_CALL, (uri.dial_string(), private || uri.private), Target::Auto).map_err(|e| e.to_string())
+}
+
+// get recent calls, newest first
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn recent_calls(limit: Option<usize>) -> Vec<HistoryEntry> {
+    history::load().into_iter().take(limit.unwrap_or(RECENT_CALLS)).collect()
+}
+
+// set active profile, as the profile buttons do
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn set_active_profile(name: &str) -> Result<(), String> {
+    let profile = profiles::find(name.trim()).ok_or_else(|| format!("there's no profile named {}", name.trim()))?;
+    event_sink()?.submit_command(SWITCH_PROFILE, profile.name, Target::Auto).map_err(|e| e.to_string())
+}
+
+// Start answering AppleScript. Safe to call more than once.
+#[cfg(target_os = "macos")]
+pub fn install(sink: ExtEventSink) {
+    use crate::macos::{ns_string, rust_string};
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Object, Sel, BOOL, NO, YES};
+    use objc::{msg_send, sel, sel_impl};
+    use std::ptr;
+    use tracing::{info, warn};
+
+    // Fail the command with errAEEventFailed and `message`, which the script sees
+    unsafe fn fail(command: &Object, message: &str) -> *mut Object {
+        warn!("AppleScript command failed: {}", message);
+        let _: () = msg_send![command, setScriptErrorNumber: -10000i64];
+        let _: () = msg_send![command, setScriptErrorString: ns_string(message)];
+        ptr::null_mut()
+    }
+
+    // The named argument of a command, nil when the script left it out
+    unsafe fn argument(command: &Object, key: &str) -> *mut Object {
+        let arguments: *mut Object = msg_send![command, evaluatedArguments];
+        msg_send![arguments, objectForKey: ns_string(key)]
+    }
+
+    extern "C" fn place_call_command(this: &Object, _: Sel) -> *mut Object {
+        unsafe {
+            let number: *mut Object = msg_send![this, directParameter];
+            let Some(number) = rust_string(number) else {
+                return fail(this, "Give the number to call, e.g. place call \"+15551234567\"");
+            };
+            let private = argument(this, "Private");
+            let private = !private.is_null() && {
+                let value: BOOL = msg_send![private, boolValue];
+                value != NO
+            };
+            info!("AppleScript asked for a call");
+            match place_call(&number, private) {
+                Ok(()) => ptr::null_mut(),
+                Err(message) => fail(this, &format!("Didn't call {}: {}", number, message)),
+            }
+        }
+    }
+
+    extern "C" fn recent_calls_command(this: &Object, _: Sel) -> *mut Object {
+        unsafe {
+            let limit = argument(this, "Limit");
+            let limit = if limit.is_null() {
+                None
+            } else {
+                let value: i64 = msg_send![limit, integerValue];
+                Some(value.max(0) as usize)
+            };
+
+            let array: *mut Object = msg_send![Class::get("NSMutableArray").unwrap(), array];
+            for entry in recent_calls(limit) {
+                let record: *mut Object = msg_send![Class::get("NSMutableDictionary").unwrap(), dictionary];
+                let time: *mut Object = msg_send![Class::get("NSDate").unwrap(), dateWithTimeIntervalSince1970: entry.time.timestamp() as f64];
+                let succeeded: *mut Object = msg_send![Class::get("NSNumber").unwrap(), numberWithBool: if entry.succeeded { YES } else { NO }];
+                let _: () = msg_send![record, setObject: ns_string(&entry.number) forKey: ns_string("number")];
+                let _: () = msg_send![record, setObject: ns_string(entry.name.as_deref().unwrap_or_default()) forKey: ns_string("name")];
+                let _: () = msg_send![record, setObject: time forKey: ns_string("time")];
+                let _: () = msg_send![record, setObject: succeeded forKey: ns_string("succeeded")];
+                let _: () = msg_send![record, setObject: ns_string(&entry.result) forKey: ns_string("result")];
+                let _: () = msg_send![array, addObject: record];
+            }
+            array
+        }
+    }
+
+    extern "C" fn set_profile_command(this: &Object, _: Sel) -> *mut Object {
+        unsafe {
+            let name: *mut Object = msg_send![this, directParameter];
+            let Some(name) = rust_string(name) else {
+                return fail(this, "Give the profile's name, e.g. set active profile \"Office\"");
+            };
+            match set_active_profile(&name) {
+                Ok(()) => ptr::null_mut(),
+                Err(message) => fail(this, &format!("Didn't switch profiles: {}", message)),
+            }
+        }
+    }
+
+    if EVENT_SINK.set(sink).is_err() {
+        return;
+    }
+
+    // Named by the <cocoa class> of each command in ClickToCall.sdef
+    let commands: [(&str, extern "C" fn(&Object, Sel) -> *mut Object); 3] = [
+        ("ClickToCallPlaceCallCommand", place_call_command),
+        ("ClickToCallRecentCallsCommand", recent_calls_command),
+        ("ClickToCallSetProfileCommand", set_profile_command),
+    ];
+    let superclass = Class::get("NSScriptCommand").unwrap();
+    for (name, perform) in commands {
+        let Some(mut decl) = ClassDecl::new(name, superclass) else {
+            continue;
+        };
+        unsafe {
+            decl.add_method(sel!(performDefaultImplementation), perform);
+        }
+        decl.register();
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn install(sink: ExtEventSink) {
+    // There's no AppleScript outside macOS
+    EVENT_SINK.set(sink).ok();
+}
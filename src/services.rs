@@ -0,0 +1,92 @@
+// "Call with Click-To-Call" in the Services menu: select a number in any app,
+// pick the service and it's dialed. Info.plist declares the service; this
+// registers the object macOS hands the selection to.
+
+use crate::{phone_text, tel_uri};
+use druid::{ExtEventSink, Selector};
+use std::sync::OnceLock;
+
+// Sent to the delegate with the selected text
+pub const CALL_SELECTION: Selector<String> = Selector::new("app.call-selection");
+
+// Where selections are forwarded, set once the app is running
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+// The number to call for a selection. A tel: link, or a selection that's only
+// a number (with an extension or pause after it, as tel: links allow), is
+// taken whole, so extensions and short codes work too. Otherwise it's the
+// first phone number in the text, e.g. in a line of an email signature.
+pub fn number_in_selection(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.get(..4).is_some_and(|scheme| scheme.eq_ignore_ascii_case("tel:")) {
+        return tel_uri::parse(text).ok().map(|uri| uri.dial_string());
+    }
+    if !text.contains('\n') {
+        if let Ok(uri) = tel_uri::parse(&format!("tel:{}", text)) {
+            return Some(uri.dial_string());
+        }
+    }
+    phone_text::find_phone_number(text)
+}
+
+// Start answering the Services menu. Safe to call more than once.
+#[cfg(target_os = "macos")]
+pub fn install(sink: ExtEventSink) {
+    use crate::macos::{ns_string, rust_string};
+    use druid::Target;
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Object, Sel};
+    use objc::{msg_send, sel, sel_impl};
+    use std::ffi::c_void;
+    use tracing::info;
+
+    // Named by NSMessage in Info.plist
+    extern "C" fn call_number(_this: &Object, _: Sel, pasteboard: *mut Object, _user_data: *mut Object, _error: *mut c_void) {
+        unsafe {
+            let text: *mut Object = msg_send![pasteboard, stringForType: ns_string("public.utf8-plain-text")];
+            match (rust_string(text), EVENT_SINK.get()) {
+                (Some(text), Some(sink)) => {
+                    sink.submit_command(CALL_SELECTION, text, Target::Auto).ok();
+                }
+                _ => info!("Services menu sent no text"),
+            }
+        }
+    }
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {
+        fn NSUpdateDynamicServices();
+    }
+
+    if EVENT_SINK.set(sink).is_err() {
+        return;
+    }
+
+    unsafe {
+        let superclass = Class::get("NSObject").unwrap();
+        let mut decl = match ClassDecl::new("ClickToCallServicesProvider", superclass) {
+            Some(decl) => decl,
+            None => return,
+        };
+        decl.add_method(
+            sel!(callNumber:userData:error:),
+            call_number as extern "C" fn(&Object, Sel, *mut Object, *mut Object, *mut c_void),
+        );
+        let provider_class = decl.register();
+
+        // NSApplication doesn't retain its services provider, so this one is never released
+        let provider: *mut Object = msg_send![provider_class, new];
+        let app_class = Class::get("NSApplication").unwrap();
+        let app: *mut Object = msg_send![app_class, sharedApplication];
+        let _: () = msg_send![app, setServicesProvider: provider];
+
+        // Pick up the service right away after an update instead of at next login
+        NSUpdateDynamicServices();
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn install(sink: ExtEventSink) {
+    // There's no Services menu outside macOS
+    EVENT_SINK.set(sink).ok();
+}
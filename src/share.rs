@@ -0,0 +1,76 @@
+// Sharing a dialed number from the status line, e.g. to follow up a call with
+// a text: the share menu macOS shows for the Share button in other apps, with
+// Messages, Mail, AirDrop and whatever else is set up.
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct NSPoint {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct NSSize {
+        pub width: f64,
+        pub height: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct NSRect {
+        pub origin: NSPoint,
+        pub size: NSSize,
+    }
+
+    // NSMinYEdge, so the menu opens below the pointer
+    pub const MIN_Y_EDGE: usize = 1;
+}
+
+// Show the share menu for `text` under the pointer in the key window, which
+// is where the button that asked for it was clicked. Must be called on the
+// main thread.
+#[cfg(target_os = "macos")]
+pub fn share_text(text: &str) -> Result<(), String> {
+    use crate::macos::ns_string;
+    use objc::runtime::{Class, Object};
+    use objc::{msg_send, sel, sel_impl};
+    use std::sync::Mutex;
+
+    // The picker last shown, kept until the next one so it outlives its menu
+    static PICKER: Mutex<usize> = Mutex::new(0);
+
+    let mut previous = PICKER.lock().unwrap();
+    unsafe {
+        let app: *mut Object = msg_send![Class::get("NSApplication").unwrap(), sharedApplication];
+        let window: *mut Object = msg_send![app, keyWindow];
+        if window.is_null() {
+            return Err("there's no window to share from".to_string());
+        }
+        let picker_class = Class::get("NSSharingServicePicker").ok_or("sharing isn't available on this version of macOS")?;
+
+        let view: *mut Object = msg_send![window, contentView];
+        let pointer: ffi::NSPoint = msg_send![window, mouseLocationOutsideOfEventStream];
+        let pointer: ffi::NSPoint = msg_send![view, convertPoint: pointer fromView: std::ptr::null_mut::<Object>()];
+        let anchor = ffi::NSRect { origin: pointer, size: ffi::NSSize { width: 1.0, height: 1.0 } };
+
+        let items: *mut Object = msg_send![Class::get("NSArray").unwrap(), arrayWithObject: ns_string(text)];
+        let picker: *mut Object = msg_send![picker_class, alloc];
+        let picker: *mut Object = msg_send![picker, initWithItems: items];
+        let _: () = msg_send![picker, showRelativeToRect: anchor ofView: view preferredEdge: ffi::MIN_Y_EDGE];
+
+        if *previous != 0 {
+            let _: () = msg_send![*previous as *mut Object, release];
+        }
+        *previous = picker as usize;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn share_text(_text: &str) -> Result<(), String> {
+    // The share menu is a macOS one
+    Err("sharing is only available on macOS".to_string())
+}
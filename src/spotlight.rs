@@ -0,0 +1,297 @@
+use crate::favorites::Favorite;
+use druid::ExtEventSink;
+use std::sync::{Mutex, OnceLock};
+
+// Spotlight domains, so each kind of item can be removed as a group: numbers
+// we've called, favorites and profiles
+const RECENTS_DOMAIN: &str = "recents";
+const FAVORITES_DOMAIN: &str = "favorites";
+const PROFILES_DOMAIN: &str = "profiles";
+
+// What Spotlight is given for one item
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    // Unique across domains; picking the item hands it back
+    pub identifier: String,
+    pub domain: &'static str,
+    pub content_type: &'static str,
+    pub title: String,
+    pub phone_number: Option<String>,
+}
+
+// "Call <name or number>" for a number we've called. The identifier is the
+// tel: URL, so picking it goes down the same path as a tel: link.
+pub fn callee_item(number: &str, name: Option<&str>, app: &str) -> Item {
+    Item {
+        identifier: format!("tel:{}", number),
+        domain: RECENTS_DOMAIN,
+        content_type: "public.contact",
+        title: format!("Call {} ({})", name.unwrap_or(number), app),
+        phone_number: Some(number.to_string()),
+    }
+}
+
+// "Call <label>" for a favorite. Called numbers already use the tel: URL as
+// their identifier, so a favorite's is prefixed to keep the two apart.
+pub fn favorite_item(favorite: &Favorite, app: &str) -> Item {
+    Item {
+        identifier: format!("favorite:tel:{}", favorite.number),
+        domain: FAVORITES_DOMAIN,
+        content_type: "public.contact",
+        title: format!("Call {} ({})", favorite.label, app),
+        phone_number: Some(favorite.number.clone()),
+    }
+}
+
+// "Switch to <profile>" for a saved profile
+pub fn profile_item(name: &str, app: &str) -> Item {
+    Item {
+        identifier: format!("profile:{}", name),
+        domain: PROFILES_DOMAIN,
+        content_type: "public.item",
+        title: format!("Switch to {} ({})", name, app),
+        phone_number: None,
+    }
+}
+
+// What picking an item in Spotlight does
+#[derive(Debug, PartialEq)]
+pub enum Selection {
+    // Handle the tel: URL like a tel: link
+    Dial(String),
+    SwitchProfile(String),
+}
+
+// The selection for a picked item's identifier, None for one we didn't index
+#[cfg_attr(not(all(target_os = "macos", feature = "spotlight")), allow(dead_code))]
+pub fn selection(identifier: &str) -> Option<Selection> {
+    if identifier.starts_with("tel:") {
+        Some(Selection::Dial(identifier.to_string()))
+    } else if let Some(url) = identifier.strip_prefix("favorite:").filter(|url| url.starts_with("tel:")) {
+        Some(Selection::Dial(url.to_string()))
+    } else {
+        identifier.strip_prefix("profile:").filter(|name| !name.is_empty()).map(|name| Selection::SwitchProfile(name.to_string()))
+    }
+}
+
+// Where Spotlight selections are forwarded, set once the main window exists
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+// A selection that arrived before the sink was ready
+static PENDING: Mutex<Option<Selection>> = Mutex::new(None);
+
+#[cfg(all(target_os = "macos", feature = "spotlight"))]
+mod ffi {
+    // Core Spotlight is only used through the Objective-C runtime, but has to be loaded
+    #[link(name = "CoreSpotlight", kind = "framework")]
+    extern "C" {}
+}
+
+// Add (or refresh) an item in Spotlight
+#[cfg(all(target_os = "macos", feature = "spotlight"))]
+fn index(item: &Item) {
+    use crate::macos::ns_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+
+    // Core Spotlight needs macOS 10.13 or later
+    let (attributes_class, item_class, index_class) = match (
+        Class::get("CSSearchableItemAttributeSet"),
+        Class::get("CSSearchableItem"),
+        Class::get("CSSearchableIndex"),
+    ) {
+        (Some(attributes), Some(item), Some(index)) => (attributes, item, index),
+        _ => return,
+    };
+
+    unsafe {
+        let array_class = Class::get("NSArray").unwrap();
+
+        let attributes: *mut Object = msg_send![attributes_class, alloc];
+        let attributes: *mut Object = msg_send![attributes, initWithItemContentType: ns_string(item.content_type)];
+        let title = ns_string(&item.title);
+        let _: () = msg_send![attributes, setTitle: title];
+        let _: () = msg_send![attributes, setDisplayName: title];
+        if let Some(number) = &item.phone_number {
+            let phone_numbers: *mut Object = msg_send![array_class, arrayWithObject: ns_string(number)];
+            let _: () = msg_send![attributes, setPhoneNumbers: phone_numbers];
+        }
+
+        let searchable: *mut Object = msg_send![item_class, alloc];
+        let searchable: *mut Object = msg_send![searchable,
+            initWithUniqueIdentifier: ns_string(&item.identifier)
+            domainIdentifier: ns_string(item.domain)
+            attributeSet: attributes];
+
+        // Indexing happens in the background; we don't need to hear back
+        let items: *mut Object = msg_send![array_class, arrayWithObject: searchable];
+        let index: *mut Object = msg_send![index_class, defaultSearchableIndex];
+        let nil: *const Object = std::ptr::null();
+        let _: () = msg_send![index, indexSearchableItems:items completionHandler:nil];
+
+        let _: () = msg_send![searchable, release];
+        let _: () = msg_send![attributes, release];
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "spotlight")))]
+fn index(_item: &Item) {
+    // Spotlight only exists on macOS
+}
+
+// Remove the item with this identifier, or every item in this domain
+#[cfg(all(target_os = "macos", feature = "spotlight"))]
+fn remove(identifier: Option<&str>, domain: &str) {
+    use crate::macos::ns_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object};
+
+    let index_class = match Class::get("CSSearchableIndex") {
+        Some(class) => class,
+        None => return,
+    };
+
+    unsafe {
+        let array_class = Class::get("NSArray").unwrap();
+        let index: *mut Object = msg_send![index_class, defaultSearchableIndex];
+        let nil: *const Object = std::ptr::null();
+        match identifier {
+            Some(identifier) => {
+                let identifiers: *mut Object = msg_send![array_class, arrayWithObject: ns_string(identifier)];
+                let _: () = msg_send![index, deleteSearchableItemsWithIdentifiers:identifiers completionHandler:nil];
+            }
+            None => {
+                let domains: *mut Object = msg_send![array_class, arrayWithObject: ns_string(domain)];
+                let _: () = msg_send![index, deleteSearchableItemsWithDomainIdentifiers:domains completionHandler:nil];
+            }
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "spotlight")))]
+fn remove(_identifier: Option<&str>, _domain: &str) {
+    // Spotlight only exists on macOS
+}
+
+// Add (or refresh) a "Call <name or number>" item for a number we've called
+pub fn index_callee(number: &str, name: Option<&str>) {
+    index(&callee_item(number, name, &crate::identity::identity().name));
+}
+
+// Remove every number we've added to Spotlight
+pub fn remove_callees() {
+    remove(None, RECENTS_DOMAIN);
+}
+
+pub fn index_favorite(favorite: &Favorite) {
+    index(&favorite_item(favorite, &crate::identity::identity().name));
+}
+
+pub fn remove_favorite(favorite: &Favorite) {
+    remove(Some(&favorite_item(favorite, "").identifier), FAVORITES_DOMAIN);
+}
+
+pub fn index_profile(name: &str) {
+    index(&profile_item(name, &crate::identity::identity().name));
+}
+
+pub fn remove_profile(name: &str) {
+    remove(Some(&profile_item(name, "").identifier), PROFILES_DOMAIN);
+}
+
+// Hand a picked Spotlight item to the delegate: a number as if it were a
+// tel: link, a profile as if it were picked in the menu
+fn select(selection: Selection) {
+    use druid::Target;
+
+    let sink = match EVENT_SINK.get() {
+        Some(sink) => sink,
+        None => {
+            *PENDING.lock().unwrap() = Some(selection);
+            return;
+        }
+    };
+    match selection {
+        Selection::Dial(url) => sink.submit_command(crate::ui::PROCESS_TEL_URL, url, Target::Auto).ok(),
+        Selection::SwitchProfile(name) => sink.submit_command(crate::ui::SWITCH_PROFILE, name, Target::Auto).ok(),
+    };
+}
+
+// Start accepting Spotlight selections. Has to run before launching finishes:
+// when Spotlight starts the app, the selection is delivered right after that.
+#[cfg(all(target_os = "macos", feature = "spotlight"))]
+pub fn install(sink: ExtEventSink) {
+    use crate::macos::rust_string;
+    use objc::{msg_send, sel, sel_impl};
+    use objc::runtime::{Class, Object, Sel, BOOL, NO, YES};
+    use std::ffi::{c_void, CString};
+    use tracing::{debug, warn};
+
+    extern "C" fn continue_user_activity(
+        _this: &Object,
+        _: Sel,
+        _application: *mut Object,
+        activity: *mut Object,
+        _restoration_handler: *mut c_void,
+    ) -> BOOL {
+        unsafe {
+            // CSSearchableItemActionType
+            let activity_type: *mut Object = msg_send![activity, activityType];
+            if rust_string(activity_type).as_deref() != Some("com.apple.corespotlightitem") {
+                return NO;
+            }
+
+            // CSSearchableItemActivityIdentifier holds the item's identifier
+            let user_info: *mut Object = msg_send![activity, userInfo];
+            let identifier: *mut Object = msg_send![user_info, objectForKey: crate::macos::ns_string("kCSSearchableItemActivityIdentifier")];
+            match rust_string(identifier).as_deref().and_then(selection) {
+                Some(selection) => {
+                    debug!("Spotlight selection: {:?}", selection);
+                    select(selection);
+                    YES
+                }
+                None => NO,
+            }
+        }
+    }
+
+    if EVENT_SINK.set(sink).is_err() {
+        // Already installed
+        return;
+    }
+
+    if let Some(selection) = PENDING.lock().unwrap().take() {
+        select(selection);
+    }
+
+    // druid owns the NSApplication delegate, so the handler is added to its class
+    let delegate_class = match Class::get("DruidAppDelegate") {
+        Some(class) => class,
+        None => {
+            warn!("App delegate class not found, Spotlight selections won't be handled");
+            return;
+        }
+    };
+
+    let types = CString::new("c@:@@@?").unwrap();
+    unsafe {
+        let imp: objc::runtime::Imp = std::mem::transmute(
+            continue_user_activity as extern "C" fn(&Object, Sel, *mut Object, *mut Object, *mut c_void) -> BOOL,
+        );
+        objc::runtime::class_addMethod(
+            delegate_class as *const Class as *mut Class,
+            sel!(application:continueUserActivity:restorationHandler:),
+            imp,
+            types.as_ptr(),
+        );
+    }
+}
+
+#[cfg(not(all(target_os = "macos", feature = "spotlight")))]
+pub fn install(sink: ExtEventSink) {
+    // No Spotlight outside macOS, but keep the sink so behaviour matches
+    if EVENT_SINK.set(sink).is_ok() {
+        if let Some(selection) = PENDING.lock().unwrap().take() {
+            select(selection);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Tests for what Spotlight is given for called numbers, favorites and
+// profiles, and what picking each of them does.
+
+use crate::favorites::Favorite;
+use crate::spotlight::{self, Item, Selection};
+
+#[test]
+fn a_called_number_is_a_contact_in_the_recents_domain() {
+    assert_eq!(spotlight::callee_item("5551234567", Some("Alice"), "Click-To-Call"), Item {
+        identifier: "tel:5551234567".to_string(),
+        domain: "recents",
+        content_type: "public.contact",
+        title: "Call Alice (Click-To-Call)".to_string(),
+        phone_number: Some("5551234567".to_string()),
+    });
+    assert_eq!(spotlight::callee_item("5551234567", None, "Click-To-Call").title, "Call 5551234567 (Click-To-Call)");
+}
+
+#[test]
+fn a_favorite_is_a_contact_in_its_own_domain() {
+    let favorite = Favorite { label: "Front desk".to_string(), number: "100".to_string() };
+    assert_eq!(spotlight::favorite_item(&favorite, "Click-To-Call"), Item {
+        identifier: "favorite:tel:100".to_string(),
+        domain: "favorites",
+        content_type: "public.contact",
+        title: "Call Front desk (Click-To-Call)".to_string(),
+        phone_number: Some("100".to_string()),
+    });
+    // Removing the favorite mustn't take the called number with it
+    assert_ne!(spotlight::favorite_item(&favorite, "").identifier, spotlight::callee_item("100", None, "").identifier);
+}
+
+#[test]
+fn a_profile_is_an_item_in_its_own_domain_without_a_number() {
+    assert_eq!(spotlight::profile_item("Office", "Click-To-Call"), Item {
+        identifier: "profile:Office".to_string(),
+        domain: "profiles",
+        content_type: "public.item",
+        title: "Switch to Office (Click-To-Call)".to_string(),
+        phone_number: None,
+    });
+}
+
+#[test]
+fn picking_an_item_dials_its_number_or_switches_to_its_profile() {
+    let favorite = Favorite { label: "Front desk".to_string(), number: "100".to_string() };
+    let pick = |item: Item| spotlight::selection(&item.identifier);
+
+    assert_eq!(pick(spotlight::callee_item("5551234567", None, "")), Some(Selection::Dial("tel:5551234567".to_string())));
+    assert_eq!(pick(spotlight::favorite_item(&favorite, "")), Some(Selection::Dial("tel:100".to_string())));
+    assert_eq!(pick(spotlight::profile_item("Office", "")), Some(Selection::SwitchProfile("Office".to_string())));
+    assert_eq!(spotlight::selection("favorite:https://example.com"), None);
+    assert_eq!(spotlight::selection("profile:"), None);
+    assert_eq!(spotlight::selection("something else"), None);
+}
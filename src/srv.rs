@@ -0,0 +1,88 @@
+// DNS SRV discovery: with the setting on, Domain is the PBX's DNS domain
+// rather than its exact web host, and the servers come from the
+// _clicktocall._tcp SRV records under it, or the SIP ones when there are
+// none. Answers are cached by the resolver for as long as their TTL says.
+// The servers are tried in priority order, the same way fallback domains are.
+
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::TokioAsyncResolver;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+// Looked up in this order; the first with records wins
+const SERVICES: [&str; 3] = ["_clicktocall._tcp", "_sips._tcp", "_sip._tcp"];
+
+// One for the whole app, so its cache lasts
+static RESOLVER: OnceLock<Option<TokioAsyncResolver>> = OnceLock::new();
+
+fn resolver() -> Option<&'static TokioAsyncResolver> {
+    RESOLVER
+        .get_or_init(|| match TokioAsyncResolver::tokio_from_system_conf() {
+            Ok(resolver) => Some(resolver),
+            Err(e) => {
+                warn!("Couldn't read the DNS settings for SRV lookups: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+// One SRV record
+pub struct Record {
+    pub priority: u16,
+    pub weight: u16,
+    pub target: String,
+    pub port: u16,
+}
+
+// The addresses to try for `domain`, as typed in the settings, from
+// `records` of `service`: lowest priority first and, among equals, the
+// heaviest first rather than at random as RFC 2782 has it, so which server a
+// call goes to doesn't change from one call to the next. A scheme typed in
+// front of the domain stays in front of each server. The port of a SIP
+// record is the SIP server's, so only its host is used.
+pub fn addresses(domain: &str, service: &str, mut records: Vec<Record>) -> Vec<String> {
+    let scheme = ["https://", "http://"].into_iter().find(|scheme| domain.trim().starts_with(scheme)).unwrap_or_default();
+    records.sort_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)));
+    records
+        .into_iter()
+        .map(|record| (record.target.trim_end_matches('.').to_string(), record.port))
+        // A target of "." says the service isn't offered there
+        .filter(|(target, _)| !target.is_empty())
+        .map(|(target, port)| match service == SERVICES[0] {
+            true => format!("{}{}:{}", scheme, target, port),
+            false => format!("{}{}", scheme, target),
+        })
+        .collect()
+}
+
+// The servers for `domain`, or the domain itself when it has no SRV records
+// or they can't be looked up
+pub async fn servers(domain: &str) -> Vec<String> {
+    let name = domain.trim().trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/');
+    let Some(resolver) = resolver().filter(|_| !name.is_empty() && !name.contains(['/', ':'])) else {
+        return vec![domain.to_string()];
+    };
+    for service in SERVICES {
+        let query = format!("{}.{}.", service, name);
+        match resolver.srv_lookup(query.as_str()).await {
+            Ok(lookup) => {
+                let records = lookup
+                    .iter()
+                    .map(|srv| Record { priority: srv.priority(), weight: srv.weight(), target: srv.target().to_utf8(), port: srv.port() })
+                    .collect();
+                let found = addresses(domain, service, records);
+                if !found.is_empty() {
+                    debug!("{} has the servers {}", query, found.join(", "));
+                    return found;
+                }
+            }
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {}
+            Err(e) => {
+                warn!("Couldn't look up {}: {}; using {} as it is", query, e, domain);
+                break;
+            }
+        }
+    }
+    vec![domain.to_string()]
+}
@@ -0,0 +1,132 @@
+use crate::{notify, paths};
+use chrono::{Local, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+// How often the summary scheduler checks the clock
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Call counters for a single day
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyStats {
+    pub date: Option<NaiveDate>,
+    pub placed: u32,
+    pub failed: u32,
+    // Private calls, counted apart and with nothing else about them kept
+    pub private: u32,
+    // Stays at zero until call state tracking can tell how long calls lasted
+    pub talk_seconds: u64,
+    // Whether today's summary notification has already gone out
+    pub summary_sent: bool,
+}
+
+// Serializes read-modify-write of the stats file between call threads
+static STATS_LOCK: Mutex<()> = Mutex::new(());
+// Time of day to send the summary, None when switched off
+static SUMMARY_TIME: Mutex<Option<NaiveTime>> = Mutex::new(None);
+static SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+
+
+// Today's counters; a new day starts again from zero
+pub fn today() -> DailyStats {
+    let today = Local::now().date_naive();
+    let stats = std::fs::read_to_string(paths::stats_file())
+        .ok()
+        .and_then(|content| serde_json::from_str::<DailyStats>(&content).ok())
+        .unwrap_or_default();
+
+    if stats.date == Some(today) {
+        stats
+    } else {
+        DailyStats { date: Some(today), ..Default::default() }
+    }
+}
+
+fn save(stats: &DailyStats) {
+    let path = paths::stats_file();
+    let json = serde_json::to_string(stats).unwrap_or_default();
+    let result = std::fs::create_dir_all(paths::config_dir()).and_then(|_| std::fs::write(&path, json));
+    if let Err(e) = result {
+        warn!("Failed to save call stats to {}: {}", path.display(), e);
+    }
+}
+
+// Count a call attempt
+pub fn record_call(succeeded: bool) {
+    let _guard = STATS_LOCK.lock().unwrap();
+    let mut stats = today();
+    stats.placed += 1;
+    if !succeeded {
+        stats.failed += 1;
+    }
+    save(&stats);
+}
+
+// Count a private call: only that there was one
+pub fn record_private_call() {
+    let _guard = STATS_LOCK.lock().unwrap();
+    let mut stats = today();
+    stats.private += 1;
+    save(&stats);
+}
+
+// One-line summary, e.g. "12 calls placed, 2 failed"
+pub fn summary_text(stats: &DailyStats) -> String {
+    let mut text = format!(
+        "{} call{} placed, {} failed",
+        stats.placed,
+        if stats.placed == 1 { "" } else { "s" },
+        stats.failed
+    );
+    if stats.private > 0 {
+        text.push_str(&format!(", {} private", stats.private));
+    }
+    if stats.talk_seconds > 0 {
+        text.push_str(&format!(", {} min talk time", stats.talk_seconds / 60));
+    }
+    text
+}
+
+// Parse the "HH:MM" summary time from settings; empty or invalid means off
+pub fn parse_summary_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+// Send the daily summary at this time of day, or stop with None
+pub fn set_summary_time(time: Option<NaiveTime>) {
+    *SUMMARY_TIME.lock().unwrap() = time;
+
+    if time.is_some() && !SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        thread::spawn(|| loop {
+            thread::sleep(CHECK_INTERVAL);
+            send_summary_if_due();
+        });
+    }
+}
+
+fn send_summary_if_due() {
+    let time = match *SUMMARY_TIME.lock().unwrap() {
+        Some(time) => time,
+        None => return,
+    };
+
+    if Local::now().time() < time {
+        return;
+    }
+
+    let _guard = STATS_LOCK.lock().unwrap();
+    let mut stats = today();
+    if stats.summary_sent {
+        return;
+    }
+    stats.summary_sent = true;
+    save(&stats);
+
+    // Clicking the notification opens the stats window
+    notify::show_notification_with_action("Today's Calls", &summary_text(&stats), notify::ACTION_SHOW_STATS);
+}
@@ -0,0 +1,289 @@
+// Keeping what the app writes to disk in check, so an agent left running for
+// months never quietly eats gigabytes. Log files and request details of failed
+// calls are deleted once they're older than the configured number of days,
+// and if the logs and the configuration folder together are still over the
+// size limit, the oldest logs and then the request details go first. The
+// primary instance does this at launch and every few hours.
+
+use crate::config::AppState;
+use crate::{history, paths};
+use chrono::{Duration as ChronoDuration, Local};
+use druid::ExtEventSink;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+// How often the limits are applied while the app runs
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+// Limits used when the settings are left empty
+const DEFAULT_LIMIT_MB: u64 = 100;
+const DEFAULT_KEEP_DAYS: u32 = 7;
+
+// What the settings may be set to
+const MIN_LIMIT_MB: u64 = 10;
+const MAX_LIMIT_MB: u64 = 10_000;
+const MAX_KEEP_DAYS: u32 = 365;
+
+const MB: u64 = 1024 * 1024;
+
+// The background agent's output, which launchd only ever appends to, is cut
+// down to its last part once it takes more than this share of the limit
+const DAEMON_LOG_SHARE: u64 = 10;
+
+// The history was moved out of this file into the database
+const IMPORTED_HISTORY: &str = "history.json.imported";
+
+#[derive(Clone, Copy)]
+pub struct Limits {
+    // Most the logs and the configuration folder may take together
+    pub bytes: u64,
+    // Log files and request details older than this are deleted
+    pub keep_days: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { bytes: DEFAULT_LIMIT_MB * MB, keep_days: DEFAULT_KEEP_DAYS }
+    }
+}
+
+// Space taken, by what it's taken by
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    pub logs: u64,
+    pub history: u64,
+    pub other: u64,
+}
+
+impl Usage {
+    pub fn total(&self) -> u64 {
+        self.logs + self.history + self.other
+    }
+
+    // e.g. "12.4 MB (logs 2.1 MB, call history 10.1 MB, other 180 KB)"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} (logs {}, call history {}, other {})",
+            format_size(self.total()),
+            format_size(self.logs),
+            format_size(self.history),
+            format_size(self.other),
+        )
+    }
+}
+
+static LIMITS: Mutex<Option<Limits>> = Mutex::new(None);
+// Where the space used is reported, set once the app is running
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+static SCHEDULER_STARTED: AtomicBool = AtomicBool::new(false);
+// One clean-up at a time, whether scheduled or asked for
+static PRUNING: Mutex<()> = Mutex::new(());
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else {
+        format!("{} KB", bytes.div_ceil(1024))
+    }
+}
+
+// The size limit in megabytes and the days logs are kept, as typed in the
+// settings; empty means the default
+pub fn parse_limits(limit_mb: &str, keep_days: &str) -> Result<Limits, String> {
+    let defaults = Limits::default();
+    let limit_mb = limit_mb.trim();
+    let bytes = if limit_mb.is_empty() {
+        defaults.bytes
+    } else {
+        match limit_mb.parse::<u64>() {
+            Ok(mb) if (MIN_LIMIT_MB..=MAX_LIMIT_MB).contains(&mb) => mb * MB,
+            _ => return Err(format!("the storage limit should be a number of MB from {} to {}", MIN_LIMIT_MB, MAX_LIMIT_MB)),
+        }
+    };
+    let keep_days = keep_days.trim();
+    let keep_days = if keep_days.is_empty() {
+        defaults.keep_days
+    } else {
+        match keep_days.parse::<u32>() {
+            Ok(days) if (1..=MAX_KEEP_DAYS).contains(&days) => days,
+            _ => return Err(format!("logs should be kept for 1 to {} days", MAX_KEEP_DAYS)),
+        }
+    };
+    Ok(Limits { bytes, keep_days })
+}
+
+// The limits in the settings. They're checked when they're saved, so the
+// defaults only stand in for a hand-edited file.
+pub fn limits(state: &AppState) -> Limits {
+    parse_limits(&state.storage_limit_mb, &state.log_retention_days).unwrap_or_else(|e| {
+        warn!("Using the default storage limits: {}", e);
+        Limits::default()
+    })
+}
+
+pub fn set_limits(limits: Limits) {
+    *LIMITS.lock().unwrap() = Some(limits);
+}
+
+// Start reporting the space used to the settings
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+// Apply the limits now and every few hours. Safe to call more than once.
+pub fn start() {
+    if !SCHEDULER_STARTED.swap(true, Ordering::SeqCst) {
+        thread::spawn(|| loop {
+            prune_now();
+            thread::sleep(CHECK_INTERVAL);
+        });
+    }
+}
+
+// Apply the limits and report the space left in use. Blocks while files are
+// deleted, so not for the UI thread.
+pub fn prune_now() {
+    let limits = LIMITS.lock().unwrap().unwrap_or_default();
+    let usage = {
+        let _guard = PRUNING.lock().unwrap();
+        prune(&limits)
+    };
+    report(usage);
+}
+
+// Report the space used without deleting anything, for instances that
+// leave the clean-up to the primary one
+pub fn refresh() {
+    report(usage());
+}
+
+fn report(usage: Usage) {
+    if let Some(sink) = EVENT_SINK.get() {
+        let summary = usage.summary();
+        sink.add_idle_callback(move |data: &mut AppState| data.storage_used = summary);
+    }
+}
+
+// A file the app wrote
+struct StoredFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+// The regular files directly in `dir`; links like latest.log don't count
+fn files_in(dir: &Path) -> Vec<StoredFile> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = fs::symlink_metadata(entry.path()).ok()?;
+            metadata.is_file().then(|| StoredFile {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            })
+        })
+        .collect()
+}
+
+fn is_history(path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("history.sqlite"))
+}
+
+pub fn usage() -> Usage {
+    let mut usage = Usage { logs: files_in(&paths::log_dir()).iter().map(|file| file.size).sum(), ..Default::default() };
+    for file in files_in(paths::config_dir()) {
+        if is_history(&file.path) {
+            usage.history += file.size;
+        } else {
+            usage.other += file.size;
+        }
+    }
+    usage
+}
+
+fn remove(file: &StoredFile, why: &str) -> bool {
+    match fs::remove_file(&file.path) {
+        Ok(()) => {
+            info!("Deleted {} ({}, {})", file.path.display(), format_size(file.size), why);
+            true
+        }
+        Err(e) => {
+            warn!("Couldn't delete {}: {}", file.path.display(), e);
+            false
+        }
+    }
+}
+
+// Keep only the last `keep` bytes of `path`, from the first whole line
+fn keep_end(path: &Path, keep: u64) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let length = file.metadata()?.len();
+    file.seek(SeekFrom::Start(length.saturating_sub(keep)))?;
+    let mut end = Vec::new();
+    file.read_to_end(&mut end)?;
+    let start = end.iter().position(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&end[start..])
+}
+
+fn prune(limits: &Limits) -> Usage {
+    let cutoff = SystemTime::now() - Duration::from_secs(u64::from(limits.keep_days) * 24 * 60 * 60);
+
+    // Whatever's past its age, except today's log, which is still being written
+    let mut logs = files_in(&paths::log_dir());
+    logs.sort_by_key(|file| file.modified);
+    logs.pop();
+    logs.retain(|file| !(file.modified < cutoff && remove(file, "older than the logs are kept")));
+    let imported = paths::config_dir().join(IMPORTED_HISTORY);
+    if let Some(file) = files_in(paths::config_dir()).into_iter().find(|file| file.path == imported) {
+        if file.modified < cutoff {
+            remove(&file, "moved into the history database");
+        }
+    }
+    let cleared = history::clear_transcripts(Local::now() - ChronoDuration::days(limits.keep_days.into()));
+    if cleared > 0 {
+        info!("Deleted the request details of {} calls older than {} days", cleared, limits.keep_days);
+    }
+
+    let daemon_log = paths::daemon_log_file();
+    let daemon_log_limit = limits.bytes / DAEMON_LOG_SHARE;
+    if fs::metadata(&daemon_log).is_ok_and(|metadata| metadata.len() > daemon_log_limit) {
+        match keep_end(&daemon_log, daemon_log_limit / 2) {
+            Ok(()) => info!("Cut {} down to its last {}", daemon_log.display(), format_size(daemon_log_limit / 2)),
+            Err(e) => warn!("Couldn't cut down {}: {}", daemon_log.display(), e),
+        }
+    }
+
+    // Still too much: the oldest logs, then every call's request details
+    let mut used = usage();
+    for file in &logs {
+        if used.total() <= limits.bytes {
+            break;
+        }
+        if remove(file, "over the storage limit") {
+            used.logs -= file.size;
+        }
+    }
+    if used.total() > limits.bytes {
+        let cleared = history::clear_transcripts(Local::now());
+        if cleared > 0 {
+            info!("Deleted the request details of {} calls to stay under the storage limit", cleared);
+        }
+        used = usage();
+    }
+    if used.total() > limits.bytes {
+        warn!("The app's files take {}, over the limit of {}, with nothing left to delete", format_size(used.total()), format_size(limits.bytes));
+    }
+    used
+}
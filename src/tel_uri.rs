@@ -0,0 +1,222 @@
+// Parsing of tel: URIs (RFC 3966), as they come from browsers, Outlook,
+// Spotlight and the socket
+
+// Visual separators allowed between digits, plus the spaces and
+// non-breaking spaces web pages put in anyway
+const VISUAL_SEPARATORS: &[char] = &['-', '.', '(', ')', ' ', '\u{a0}', '\t'];
+
+// Words CRM exports and vendors put before an extension, in the languages
+// we've seen: "x22", "ext. 22", "poste 22", "Durchwahl 22", "ramal 22", ...
+// Longer words come first so "extension" isn't read as "ext" + "ension".
+const EXTENSION_KEYWORDS: &[&str] = &[
+    "extension", "durchwahl", "interno", "toestel", "anexo", "poste", "ramal", "ext", "int", "dw", "x",
+];
+
+// Spelled-out pause and wait, as in "5551234 pause 22" or ";postd=wait22"
+const PAUSE_KEYWORDS: &[(&str, char)] = &[("pause", 'p'), ("wait", 'w')];
+
+// A parsed tel: URI
+pub struct TelUri {
+    // The number to dial: digits, * and #, with a leading + for global numbers
+    pub number: String,
+    // ;ext= parameter, dialed after the call connects
+    pub extension: Option<String>,
+    // What to dial after the call connects: digits, * and #, with p for a
+    // one-second pause and w to wait for the caller. From a pause or wait
+    // character or keyword after the number, or a ;postd= parameter.
+    pub post_dial: Option<String>,
+    // ;private parameter: dial it, but keep it out of history, stats and webhooks
+    pub private: bool,
+}
+
+impl TelUri {
+    // What to dial after the call connects, the extension first after a pause
+    pub fn post_dial_digits(&self) -> Option<String> {
+        match (&self.extension, &self.post_dial) {
+            (Some(extension), Some(post_dial)) => Some(format!("p{}{}", extension, post_dial)),
+            (Some(extension), None) => Some(format!("p{}", extension)),
+            (None, post_dial) => post_dial.clone(),
+        }
+    }
+
+    // The number with what's dialed after it connects, as it would be typed,
+    // e.g. "5551234,,101#". Digits to dial straight away get a pause in front,
+    // which is what keeps them apart from the number.
+    pub fn dial_string(&self) -> String {
+        match self.post_dial_digits() {
+            Some(post_dial) if post_dial.starts_with(['p', 'w']) => format!("{}{}", self.number, post_dial.replace('p', ",")),
+            Some(post_dial) => format!("{},{}", self.number, post_dial.replace('p', ",")),
+            None => self.number.clone(),
+        }
+    }
+}
+
+// Parse a tel: URI such as "tel:+1-555-123-4567;ext=22" or
+// "tel:5551234;phone-context=+1-212". Local numbers with a global
+// phone-context get the context's digits prepended. Vendor suffixes like
+// ";postd=pp1234", "pause 1234" or "x22" become post-dial digits or the
+// extension instead of part of the number.
+pub fn parse(uri: &str) -> Result<TelUri, String> {
+    let uri = uri.trim();
+    let rest = match uri.get(..4) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("tel:") => &uri[4..],
+        _ => return Err("not a tel: URL".to_string()),
+    };
+
+    // Some apps write tel://number, and queries and fragments aren't part of tel:.
+    // A # to dial has to be written %23, so it survives this.
+    let rest = rest.trim_start_matches("//");
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+
+    let mut parts = rest.split(';');
+    let subscriber = percent_decode(parts.next().unwrap_or_default())?;
+
+    let mut extension = None;
+    let mut phone_context = None;
+    let mut postd = None;
+    let mut private = false;
+    for parameter in parts {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let value = percent_decode(value)?;
+        match name.to_ascii_lowercase().as_str() {
+            "ext" => extension = Some(strip_separators(&value)),
+            "phone-context" => phone_context = Some(value),
+            "postd" => postd = post_dial_digits(&value)?,
+            // A bare ;private counts, as do ;private=1, yes and true
+            "private" => private = matches!(value.to_ascii_lowercase().as_str(), "" | "1" | "yes" | "true"),
+            // isub, npdi, rn, ... don't change what we dial
+            _ => {}
+        }
+    }
+
+    // The number ends at the first character that can't be part of one
+    let end = subscriber
+        .find(|c: char| !(c.is_ascii_digit() || "+*#".contains(c) || VISUAL_SEPARATORS.contains(&c)))
+        .unwrap_or(subscriber.len());
+    let number = strip_separators(&subscriber[..end]);
+    let suffix = subscriber[end..].trim_start_matches(VISUAL_SEPARATORS);
+
+    let mut post_dial = postd;
+    if !suffix.is_empty() {
+        match extension_keyword(suffix) {
+            Some(digits) => {
+                if extension.is_none() {
+                    extension = Some(strip_separators(digits));
+                }
+            }
+            None => post_dial = post_dial_digits(suffix)?,
+        }
+    }
+
+    let global = number.starts_with('+');
+    let digits = number.trim_start_matches('+');
+    if digits.is_empty() || !digits.chars().any(|c| c.is_ascii_digit()) {
+        return Err("tel: URL has no number".to_string());
+    }
+    if let Some(c) = digits.chars().find(|c| !(c.is_ascii_digit() || *c == '*' || *c == '#')) {
+        return Err(format!("tel: URL has an invalid character in its number: {:?}", c));
+    }
+    if let Some(extension) = &extension {
+        if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_digit()) {
+            return Err("tel: URL has an invalid extension".to_string());
+        }
+    }
+
+    // A global phone-context is the prefix for a local number; a domain name
+    // context only says where the number is valid
+    let number = match phone_context.map(|context| strip_separators(&context)) {
+        Some(context) if !global && context.starts_with('+') => format!("{}{}", context, digits),
+        _ => number,
+    };
+
+    Ok(TelUri { number, extension, post_dial, private })
+}
+
+// A tel: link for `number` to hand to another instance, keeping the call private if it was.
+// A # has to be escaped, or it would start the link's fragment.
+pub fn link(number: &str, private: bool) -> String {
+    let number = number.replace('#', "%23");
+    if private {
+        format!("tel:{};private", number)
+    } else {
+        format!("tel:{}", number)
+    }
+}
+
+// Split a number as typed into the number and what to dial after the call
+// connects, in the p/w notation: "5551234,,101#" is 5551234 and "pp101#",
+// "555-1234 x22" is 555-1234 and "p22". Anything else after the number stays
+// part of it, for the usual checks to turn down.
+pub fn split_post_dial(text: &str) -> (&str, Option<String>) {
+    let text = text.trim();
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || "+*#".contains(c) || VISUAL_SEPARATORS.contains(&c)))
+        .unwrap_or(text.len());
+    let suffix = text[end..].trim_start_matches(VISUAL_SEPARATORS);
+    let post_dial = match extension_keyword(suffix) {
+        Some(digits) => Some(format!("p{}", strip_separators(digits))).filter(|extension| extension[1..].chars().all(|c| c.is_ascii_digit())),
+        None => post_dial_digits(suffix).ok().flatten(),
+    };
+    let number = &text[..end];
+    match post_dial {
+        Some(post_dial) if number.contains(|c: char| c.is_ascii_digit()) => (number.trim_end_matches(VISUAL_SEPARATORS), Some(post_dial)),
+        _ => (text, None),
+    }
+}
+
+// The digits after an extension keyword at the start of `suffix`, if it starts with one
+fn extension_keyword(suffix: &str) -> Option<&str> {
+    EXTENSION_KEYWORDS.iter().find_map(|keyword| {
+        if !suffix.get(..keyword.len())?.eq_ignore_ascii_case(keyword) {
+            return None;
+        }
+        let digits = suffix[keyword.len()..].trim_start_matches(|c: char| c == ':' || VISUAL_SEPARATORS.contains(&c));
+        digits.starts_with(|c: char| c.is_ascii_digit()).then_some(digits)
+    })
+}
+
+// Normalise post-dial text such as "pp1234", ",,1234" or "wait 22#" to the
+// p/w notation. Returns None when there's nothing to dial.
+fn post_dial_digits(text: &str) -> Result<Option<String>, String> {
+    let mut lower = text.to_lowercase();
+    for (keyword, symbol) in PAUSE_KEYWORDS {
+        lower = lower.replace(keyword, &symbol.to_string());
+    }
+
+    let mut digits = String::new();
+    for c in lower.chars() {
+        match c {
+            '0'..='9' | '*' | '#' | 'p' | 'w' => digits.push(c),
+            ',' => digits.push('p'),
+            c if VISUAL_SEPARATORS.contains(&c) => {}
+            c => return Err(format!("tel: URL has an invalid character after its number: {:?}", c)),
+        }
+    }
+
+    let dials_something = digits.chars().any(|c| c.is_ascii_digit() || c == '*' || c == '#');
+    Ok(dials_something.then_some(digits))
+}
+
+fn strip_separators(text: &str) -> String {
+    text.chars().filter(|c| !VISUAL_SEPARATORS.contains(c)).collect()
+}
+
+pub fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| "tel: URL has a broken %-escape".to_string())?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "tel: URL isn't valid UTF-8 once decoded".to_string())
+}
@@ -0,0 +1,710 @@
+// What the app does with commands: placing calls, tel: links, hotkeys,
+// profiles and the windows that come and go
+
+use super::*;
+use crate::audit::CallOrigin;
+use crate::call::{logged_number, place_call, InFlightCall, EMERGENCY_MESSAGE};
+use crate::config::{self, load_preferences, save_preferences, AppState};
+use crate::notify::show_notification;
+use crate::{activate_app, active_calls, apply_settings, audit, bug_report, call_events, call_options, campaign, clipboard_watch, config_watch, configure_notification_sinks, contacts, corporate_directory, dial_plan, directory, emergency, favorites, health, health_target, hide_app_from_dock, history, hotkey, http_api, ipc, keep_alive, keep_alive_target, managed, menu_bar, notify, number_format, phone_text, presence, presence_target, profile_color, profiles, provisioning, queue, register_hotkeys, reminders, retry, runtime, screen_lookup, scripting, services, spotlight, stats, storage, tel_uri, tls, url_handler, voicemail, voicemail_target, wake, TEL_HANDOFF};
+
+// What the next path picked in the save panel is for
+pub enum PendingExport {
+    BugReport,
+    History,
+    Settings,
+}
+
+// App delegate to handle custom commands
+pub struct Delegate {
+    pub is_primary: bool,
+    // The settings window; other windows (prompts, highlights) come and go
+    pub main_window: Option<druid::WindowId>,
+    // The menu bar dial popover while it's open
+    pub popover: Option<druid::WindowId>,
+    pub pending_export: Option<PendingExport>,
+    // No settings yet: the setup window comes up instead of the settings
+    pub first_run: bool,
+    // The setup window while it's open
+    pub setup_window: Option<druid::WindowId>,
+    // Places the calls MAKE_CALL lets through
+    pub dialer: Box<dyn Dialer>,
+}
+
+// Hands a call to the PBX. The delegate only decides whether and what to
+// dial, so tests can swap this for one that records the calls instead.
+pub trait Dialer {
+    // `on_result` gets the message for the status line once the request is done
+    fn dial(&self, call: PendingCall, on_result: Box<dyn FnOnce(String) + Send>);
+}
+
+// Everything place_call needs, taken from the state when the call was asked for
+pub struct PendingCall {
+    // The settings in effect, PBX login included
+    pub settings: AppState,
+    pub phone_number: String,
+    pub origin: CallOrigin,
+    pub note: Option<String>,
+    pub route: Option<dial_plan::Route>,
+    pub private: bool,
+    // Cancelled from the Cancel button that replaces Place Call
+    pub in_flight: InFlightCall,
+}
+
+// Dials through the configured backend on the runtime
+pub struct PbxDialer;
+
+impl Dialer for PbxDialer {
+    fn dial(&self, call: PendingCall, on_result: Box<dyn FnOnce(String) + Send>) {
+        runtime().spawn(async move {
+            let result = place_call(
+                &call.settings,
+                &call.phone_number,
+                call.origin,
+                call.note.as_deref(),
+                call.route.as_ref(),
+                call.private,
+                Some(&call.in_flight),
+            )
+            .await;
+            call.in_flight.finish();
+            on_result(result.unwrap_or_else(|failure| failure.message));
+        });
+    }
+}
+
+// What MAKE_CALL did with the request
+pub enum CallStart {
+    // Something needed for the call is missing
+    Rejected,
+    // The number is an emergency number; the caller audits and alerts
+    Emergency { note: Option<String> },
+    // Handed to the dialer
+    Dialing,
+}
+
+// Check the state, update the status line and hand the call to `dialer`
+pub fn start_call(data: &mut AppState, origin: CallOrigin, dialer: &dyn Dialer, on_result: Box<dyn FnOnce(String) + Send>) -> CallStart {
+    // A note only ever belongs to the call it was typed for
+    let note = std::mem::take(&mut data.call_note).trim().to_string();
+    let note = if note.is_empty() { None } else { Some(note) };
+    // So is marking it private
+    let private = std::mem::take(&mut data.private_call);
+    // Copy and Share only ever offer the call that was just asked for
+    data.last_dialed.clear();
+    
+    // Make sure we have the necessary data
+    if data.domain.is_empty() || data.extension.is_empty() || data.phone_number.is_empty() {
+        data.status_message = "Error: Missing domain, extension or phone number".to_string();
+        return CallStart::Rejected;
+    }
+    
+    // Emergency calls must never go out as a PBX callback; say so loudly
+    if emergency::is_emergency_number(&data.phone_number, &data.country) {
+        data.status_message = EMERGENCY_MESSAGE.to_string();
+        return CallStart::Emergency { note };
+    }
+    
+    let call = PendingCall {
+        settings: data.clone(),
+        phone_number: data.phone_number.clone(),
+        origin,
+        note,
+        // Only a prefix that's still configured; the routes may have been edited since it was picked
+        route: dial_plan::parse_routes(&data.routes)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|route| route.prefix == data.route),
+        private,
+        in_flight: InFlightCall::default(),
+    };
+    
+    // Update UI immediately
+    data.status_message = format!("Initiating call to {}...", call.phone_number);
+    // The PBX takes calls for a phone that isn't there without a word
+    if presence::unregistered() {
+        data.status_message.push_str(" Your phone isn't registered with the PBX, so it can't ring.");
+    }
+    data.in_flight = Some(call.in_flight.clone());
+    if !call.private {
+        // Without the digits dialed once it connects
+        let (number, _) = tel_uri::split_post_dial(&call.phone_number);
+        data.last_dialed = number_format::to_e164(number, &data.country, &data.short_codes);
+    }
+    dialer.dial(call, on_result);
+    CallStart::Dialing
+}
+
+// Take the number of an opened tel: link. Returns whether it should be called,
+// which needs the domain and extension to be configured.
+pub fn accept_tel_uri(data: &mut AppState, uri: tel_uri::TelUri) -> bool {
+    info!("Processing tel: URL with number: {}", logged_number(&uri.number, uri.private));
+    
+    if data.domain.is_empty() || data.extension.is_empty() {
+        return false;
+    }
+    data.status_message = format!("Processing tel: URL: {}", uri.number);
+    data.phone_number = uri.dial_string();
+    data.private_call = uri.private;
+    true
+}
+
+// What PROCESS_TEL_URL does with a link
+#[derive(Debug, PartialEq)]
+pub enum TelLinkCall {
+    // The link couldn't be read; the status line says why
+    Unreadable,
+    // There's nothing to call with
+    Ignored,
+    // Confirm Before Dialing is on
+    Confirm,
+    Dial,
+}
+
+// Take the number of an opened tel: link, and say whether to call it right away or ask first
+pub fn open_tel_link(data: &mut AppState, url: &str) -> TelLinkCall {
+    let uri = match tel_uri::parse(url) {
+        Ok(uri) => uri,
+        Err(reason) => {
+            warn!("Ignoring {}: {}", url, reason);
+            data.status_message = format!("Error: {}", reason);
+            return TelLinkCall::Unreadable;
+        }
+    };
+    match accept_tel_uri(data, uri) {
+        false => TelLinkCall::Ignored,
+        true if data.confirm_tel_links => TelLinkCall::Confirm,
+        true => TelLinkCall::Dial,
+    }
+}
+
+// Whether the launch should offer to make the app the tel: link handler: once,
+// from the primary instance, when nothing else is about to ask
+pub fn offers_tel_handler(data: &AppState, is_primary: bool, first_run: bool) -> bool {
+    is_primary && !first_run && !data.kiosk && !data.handles_tel_links && !data.tel_handler_prompted
+}
+
+impl Delegate {
+    // Place the call for the number in the dialer, asked for from `origin`
+    fn make_call(&self, ctx: &mut DelegateCtx, data: &mut AppState, origin: CallOrigin) {
+        // Create event sink to update UI after the request
+        let event_sink = ctx.get_external_handle();
+        let on_result = move |message: String| {
+            event_sink.add_idle_callback(move |data: &mut AppState| {
+                data.status_message = message;
+                // Another call may have started since; it stays cancellable
+                if data.in_flight.as_ref().is_some_and(InFlightCall::is_finished) {
+                    data.in_flight = None;
+                }
+            });
+        };
+        
+        if let CallStart::Emergency { note } = start_call(data, origin, self.dialer.as_ref(), Box::new(on_result)) {
+            // Refused attempts are audited too
+            let (webhook_url, webhook_secret) = (data.audit_webhook_url.clone(), data.audit_webhook_secret.clone());
+            let (domain, extension) = (data.domain.clone(), data.extension.clone());
+            let phone_number = data.phone_number.clone();
+            runtime().spawn(async move {
+                audit::record(&webhook_url, &webhook_secret, &audit::AuditEvent {
+                    origin,
+                    domain: &domain,
+                    extension: &extension,
+                    number: &phone_number,
+                    outcome: audit::Outcome::Blocked,
+                    http_status: None,
+                    detail: EMERGENCY_MESSAGE,
+                    note: note.as_deref(),
+                });
+            });
+
+            ctx.new_window(alert_window("Emergency Number", EMERGENCY_MESSAGE));
+        }
+    }
+}
+
+impl AppDelegate<AppState> for Delegate {
+    fn command(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut AppState,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(origin) = cmd.get(MAKE_CALL) {
+            let origin = *origin;
+            // The options dialog places the call with CALL_WITH_OPTIONS
+            if data.ask_call_options && call_options::asks_first(origin) && !data.phone_number.trim().is_empty() {
+                ctx.new_window(call_options_window(data, origin));
+                return Handled::Yes;
+            }
+            self.make_call(ctx, data, origin);
+            return Handled::Yes;
+        } else if let Some(origin) = cmd.get(CALL_WITH_OPTIONS) {
+            // Kept with the profile as the last used, for the next call
+            let saved = config::check(data)
+                .and_then(|_| save_preferences(data))
+                .and_then(|_| profiles::store(profiles::Profile::from_state(data)));
+            match saved {
+                Ok(()) => self.make_call(ctx, data, *origin),
+                Err(message) => data.status_message = format!("Error: {}", message),
+            }
+            return Handled::Yes;
+        } else if cmd.is(APP_INITIALIZED) {
+            // Auto-call launches never reach this point (see main), so only
+            // the socket listener needs starting here.
+            
+            // druid switches to a regular Dock app when launching finishes; if we were
+            // launched for a tel: link, go back to being a background accessory app
+            if TEL_HANDOFF.load(Ordering::SeqCst) {
+                hide_app_from_dock();
+            }
+            
+            // Report call events in the status line as well as through the configured sinks
+            configure_notification_sinks(data, Some(ctx.get_external_handle()));
+            
+            // Keep the frequent numbers above the phone field current
+            history::set_event_sink(ctx.get_external_handle());
+            
+            // Keep the Call Queue window current
+            queue::set_event_sink(ctx.get_external_handle());
+            
+            // Keep the Active Calls section current
+            active_calls::set_event_sink(ctx.get_external_handle());
+            
+            // Say how followed calls are going in the status line
+            call_events::set_event_sink(ctx.get_external_handle());
+            
+            // Show whether the extension's phone is registered
+            presence::set_event_sink(ctx.get_external_handle());
+            
+            // Show how many voicemail messages are waiting
+            voicemail::set_event_sink(ctx.get_external_handle());
+            
+            // Keep the shared directory current, and the views built from it
+            directory::set_event_sink(ctx.get_external_handle());
+            directory::set_source(&data.directory_url);
+            
+            // Same for names looked up in the corporate directory
+            corporate_directory::set_event_sink(ctx.get_external_handle());
+            corporate_directory::set_server(corporate_directory::Server::from_state(data));
+            
+            // Reading the address book can take a moment, and asks for access the first time
+            if data.use_contacts {
+                thread::spawn(contacts::refresh);
+            }
+            
+            // Global hotkeys, the keep-alive and the daily summary belong to the primary instance only
+            if self.is_primary {
+                hotkey::install(ctx.get_external_handle());
+                register_hotkeys(data);
+                keep_alive::set_target(keep_alive_target(data), tls::Tls::from_state(data));
+                health::set_target(health_target(data));
+                presence::set_target(presence_target(data));
+                voicemail::set_target(voicemail_target(data));
+                notify::install_click_handler(ctx.get_external_handle());
+                services::install(ctx.get_external_handle());
+                scripting::install(ctx.get_external_handle());
+                stats::set_summary_time(stats::parse_summary_time(&data.summary_time));
+                wake::set_grace_period(wake::parse_grace_period(&data.wake_grace_minutes).unwrap_or_default());
+                wake::start();
+                reminders::start();
+                retry::start();
+                storage::start();
+                menu_bar::set_enabled(ctx.get_external_handle(), data.menu_bar, &profile_color::color_for(&data.color));
+                
+                // druid made us a regular Dock app when launching finished; the icon is enough
+                if data.menu_bar {
+                    hide_app_from_dock();
+                }
+            }
+            
+            // Only the primary instance deletes anything; the others just show the space used
+            storage::set_event_sink(ctx.get_external_handle());
+            storage::set_limits(storage::limits(data));
+            if !self.is_primary {
+                thread::spawn(storage::refresh);
+            }
+            
+            // Offer once to open tel: links, unless the setup is about to
+            data.handles_tel_links = url_handler::is_default("tel");
+            if offers_tel_handler(data, self.is_primary, self.first_run) {
+                data.tel_handler_prompted = true;
+                if let Err(message) = save_preferences(data) {
+                    warn!("Couldn't note that tel: links were offered: {}", message);
+                }
+                ctx.new_window(tel_handler_window());
+            }
+            
+            // Changes to the preferences file made outside the app
+            config_watch::start(Some(ctx.get_external_handle()));
+            
+            // If this is the primary instance, start the socket listener
+            if self.is_primary {
+                config::set_current_settings(data);
+                ipc::listen(Some(ctx.get_external_handle()));
+                http_api::configure(data, Some(ctx.get_external_handle()));
+                clipboard_watch::set_enabled(data.watch_clipboard);
+            }
+            
+            return Handled::Yes;
+        } else if cmd.is(menu_bar::STATUS_ITEM_CLICKED) {
+            // Clicking the icon again dismisses the popover, like a native one
+            if let Some(popover) = self.popover.take() {
+                ctx.submit_command(druid::commands::CLOSE_WINDOW.to(popover));
+            } else {
+                let popover = dial_popover(menu_bar::icon_bounds(), data);
+                self.popover = Some(popover.id);
+                ctx.new_window(popover);
+                activate_app();
+            }
+            return Handled::Yes;
+        } else if let Some(name) = cmd.get(SWITCH_PROFILE) {
+            let profile = match profiles::find(name) {
+                Some(profile) => profile,
+                None => {
+                    data.status_message = format!("No profile named {}", name);
+                    return Handled::Yes;
+                }
+            };
+            
+            profile.apply(data);
+            // Forced settings still win over whatever the profile says
+            managed::load_managed_settings().apply(data);
+            if let Err(message) = save_preferences(data) {
+                data.status_message = format!("Switched to {}, but it won't stick: {}", name, message);
+                return Handled::Yes;
+            }
+            data.status_message = match apply_settings(data, ctx.get_external_handle()) {
+                Some(warning) => format!("Switched to {}, but {}", name, warning),
+                None => format!("Switched to {}", name),
+            };
+            return Handled::Yes;
+        } else if cmd.is(TOGGLE_CLIPBOARD_WATCH) {
+            data.watch_clipboard = !data.watch_clipboard;
+            clipboard_watch::set_enabled(data.watch_clipboard && self.is_primary);
+            let watching = if data.watch_clipboard { "Watching the clipboard for phone numbers" } else { "Stopped watching the clipboard" };
+            data.status_message = match save_preferences(data) {
+                Ok(()) => watching.to_string(),
+                Err(message) => format!("{}, but it won't stick: {}", watching, message),
+            };
+            return Handled::Yes;
+        } else if cmd.is(RELOAD_SETTINGS) {
+            // Half-edited or wrong settings don't replace the ones that work
+            match config::read_preferences().and_then(|saved| config::check(&saved).map(|_| saved)) {
+                Ok(saved) => data.reload(saved),
+                Err(message) => {
+                    warn!("Kept the settings in effect: {}", message);
+                    data.status_message = format!("Settings not reloaded: {}", message);
+                    return Handled::Yes;
+                }
+            }
+            data.status_message = match apply_settings(data, ctx.get_external_handle()) {
+                Some(warning) => format!("Settings reloaded, but {}", warning),
+                None => "Settings reloaded".to_string(),
+            };
+            return Handled::Yes;
+        } else if cmd.is(SHOW_SETTINGS) {
+            if let Some(main_window) = self.main_window {
+                ctx.submit_command(druid::commands::SHOW_WINDOW.to(main_window));
+                activate_app();
+            }
+            return Handled::Yes;
+        } else if let Some(url) = cmd.get(PROCESS_TEL_URL) {
+            let call = open_tel_link(data, url);
+            if call == TelLinkCall::Unreadable {
+                return Handled::Yes;
+            }
+            
+            // On macOS, hide the app from dock when processing tel URLs
+            #[cfg(target_os = "macos")]
+            {
+                use objc::{msg_send, sel, sel_impl};
+                use objc::runtime::{Class, Object};
+                
+                unsafe {
+                    // Don't activate the app when processing tel URLs
+                    let cls = Class::get("NSApplication").unwrap();
+                    let app: *mut Object = msg_send![cls, sharedApplication];
+                    let _: () = msg_send![app, setActivationPolicy:1]; // NSApplicationActivationPolicyAccessory = 1
+                }
+            }
+            
+            // Don't bring window to front, just initiate the call silently
+            let origin = CallOrigin::TelLink { sender_pid: None };
+            match call {
+                TelLinkCall::Confirm => ctx.submit_command(CONFIRM_CALL.with((data.phone_number.clone(), origin, data.private_call))),
+                TelLinkCall::Dial => ctx.submit_command(MAKE_CALL.with(origin)),
+                TelLinkCall::Unreadable | TelLinkCall::Ignored => {}
+            }
+            return Handled::Yes;
+        } else if let Some((number, origin, private)) = cmd.get(CONFIRM_CALL) {
+            data.call_note.clear();
+            data.private_call = *private;
+            ctx.new_window(confirmation_window(number.clone(), data, *origin));
+            return Handled::Yes;
+        } else if let Some(id) = cmd.get(hotkey::HOTKEY_PRESSED) {
+            if *id == LOOKUP_HOTKEY_ID {
+                let event_sink = ctx.get_external_handle();
+                
+                // Accessibility and OCR can take a moment, keep them off the UI thread
+                thread::spawn(move || {
+                    match screen_lookup::number_at_focus() {
+                        Some(detected) => {
+                            event_sink.submit_command(SHOW_DETECTED_NUMBER, detected, Target::Auto).ok();
+                        }
+                        None => {
+                            show_notification("No Number Found", "Couldn't find a phone number in the selection or near the pointer");
+                        }
+                    }
+                });
+            } else if *id == CLIPBOARD_HOTKEY_ID {
+                let text = druid::Application::global().clipboard().get_string().unwrap_or_default();
+                match phone_text::find_phone_number(&text) {
+                    Some(number) => {
+                        // The Call Initiated notification says what was dialed
+                        info!("Dialing {} from the clipboard", number);
+                        data.phone_number = number;
+                        ctx.submit_command(MAKE_CALL.with(CallOrigin::ClipboardHotkey));
+                    }
+                    None => {
+                        show_notification("No Number on Clipboard", "Copy a phone number, then press the hotkey again");
+                    }
+                }
+            }
+            return Handled::Yes;
+        } else if let Some(text) = cmd.get(services::CALL_SELECTION) {
+            match services::number_in_selection(text) {
+                Some(number) => {
+                    info!("Dialing {} from the Services menu", number);
+                    data.phone_number = number;
+                    ctx.submit_command(MAKE_CALL.with(CallOrigin::Services));
+                }
+                None => {
+                    show_notification("No Number Found", "Couldn't find a phone number in the selected text");
+                }
+            }
+            return Handled::Yes;
+        } else if let Some((number, private)) = cmd.get(scripting::SCRIPT_CALL) {
+            info!("Dialing {} for AppleScript", logged_number(number, *private));
+            data.phone_number = number.clone();
+            data.private_call = *private;
+            ctx.submit_command(MAKE_CALL.with(CallOrigin::AppleScript));
+            return Handled::Yes;
+        } else if let Some(detected) = cmd.get(SHOW_DETECTED_NUMBER) {
+            // Briefly outline where the number was found so the user can check it's the right one
+            if let Some(bounds) = detected.bounds {
+                let highlight = highlight_window(bounds);
+                let highlight_id = highlight.id;
+                ctx.new_window(highlight);
+                
+                let handle = ctx.get_external_handle();
+                thread::spawn(move || {
+                    thread::sleep(HIGHLIGHT_DURATION);
+                    handle.submit_command(druid::commands::CLOSE_WINDOW, (), Target::Window(highlight_id)).ok();
+                });
+            }
+            
+            // Nothing is dialed until the user confirms
+            data.call_note.clear();
+            ctx.new_window(confirmation_window(detected.number.clone(), data, CallOrigin::LookupHotkey));
+            return Handled::Yes;
+        } else if cmd.is(EXPORT_BUG_REPORT) {
+            if let Some(window) = self.main_window {
+                let options = FileDialogOptions::new()
+                    .default_name("click-to-call-report.md")
+                    .allowed_types(vec![FileSpec::new("Markdown", &["md"])]);
+                self.pending_export = Some(PendingExport::BugReport);
+                ctx.submit_command(druid::commands::SHOW_SAVE_PANEL.with(options).to(Target::Window(window)));
+            }
+            return Handled::Yes;
+        } else if cmd.is(EXPORT_HISTORY) {
+            if let Some(window) = self.main_window {
+                let options = FileDialogOptions::new()
+                    .default_name("click-to-call-history.json")
+                    .allowed_types(vec![FileSpec::new("JSON", &["json"])]);
+                self.pending_export = Some(PendingExport::History);
+                ctx.submit_command(druid::commands::SHOW_SAVE_PANEL.with(options).to(Target::Window(window)));
+            }
+            return Handled::Yes;
+        } else if cmd.is(EXPORT_SETTINGS) {
+            if let Some(window) = self.main_window {
+                let options = FileDialogOptions::new()
+                    .default_name("click-to-call-settings.json")
+                    .allowed_types(vec![FileSpec::new("Settings", &["json", "toml"])]);
+                self.pending_export = Some(PendingExport::Settings);
+                ctx.submit_command(druid::commands::SHOW_SAVE_PANEL.with(options).to(Target::Window(window)));
+            }
+            return Handled::Yes;
+        } else if cmd.is(IMPORT_SETTINGS) {
+            if let Some(window) = self.main_window {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![FileSpec::new("Settings", &["json", "toml"])])
+                    .accept_command(SETTINGS_FILE_CHOSEN);
+                ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options).to(Target::Window(window)));
+            }
+            return Handled::Yes;
+        } else if let Some(file) = cmd.get(SETTINGS_FILE_CHOSEN) {
+            if let Err(message) = provisioning::import_from(file.path()) {
+                warn!("Couldn't import settings: {}", message);
+                data.status_message = format!("Settings not imported: {}", message);
+                return Handled::Yes;
+            }
+            data.reload(load_preferences());
+            data.status_message = match apply_settings(data, ctx.get_external_handle()) {
+                Some(warning) => format!("Imported settings from {}, but {}", file.path().display(), warning),
+                None => format!("Imported settings from {}", file.path().display()),
+            };
+            return Handled::Yes;
+        } else if cmd.is(IMPORT_HISTORY) {
+            if let Some(window) = self.main_window {
+                let options = FileDialogOptions::new().allowed_types(vec![FileSpec::new("JSON", &["json"])]);
+                ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options).to(Target::Window(window)));
+            }
+            return Handled::Yes;
+        } else if let Some(file) = cmd.get(druid::commands::SAVE_FILE_AS) {
+            match self.pending_export.take() {
+                Some(PendingExport::BugReport) => {
+                    let report = bug_report::build_report(data);
+                    data.status_message = match fs::write(file.path(), report) {
+                        Ok(()) => format!("Bug report saved to {}", file.path().display()),
+                        Err(e) => format!("Error: couldn't save bug report: {}", e),
+                    };
+                }
+                Some(PendingExport::Settings) => {
+                    data.status_message = match provisioning::export_to(file.path()) {
+                        Ok(count) => format!("Exported the settings and {} profiles to {}; keys and passwords were left out", count, file.path().display()),
+                        Err(e) => format!("Error: couldn't export the settings: {}", e),
+                    };
+                }
+                Some(PendingExport::History) => {
+                    data.status_message = match history::export_json(file.path()) {
+                        Ok(count) => format!("Exported {} calls to {}", count, file.path().display()),
+                        Err(e) => format!("Error: couldn't export the call history: {}", e),
+                    };
+                }
+                None => return Handled::No,
+            }
+            return Handled::Yes;
+        } else if let Some(file) = cmd.get(CAMPAIGN_FILE_CHOSEN) {
+            let imported = fs::read_to_string(file.path())
+                .map_err(|e| format!("couldn't read {}: {}", file.path().display(), e))
+                .and_then(|text| campaign::parse_csv(&text))
+                .and_then(|entries| {
+                    let count = entries.len();
+                    campaign::import(entries).map(|()| count)
+                });
+            data.status_message = match imported {
+                Ok(count) => format!("Imported {} numbers to call from {}", count, file.path().display()),
+                Err(message) => format!("Error: Campaign not imported: {}", message),
+            };
+            data.campaign_revision += 1;
+            return Handled::Yes;
+        } else if cmd.is(START_CAMPAIGN) {
+            let delay = match campaign::parse_delay(&data.campaign_delay) {
+                Ok(delay) => delay,
+                Err(message) => {
+                    data.status_message = format!("Error: {}", message);
+                    return Handled::Yes;
+                }
+            };
+            if data.domain.is_empty() || data.extension.is_empty() {
+                data.status_message = "Error: Set the domain and extension before starting a campaign".to_string();
+                return Handled::Yes;
+            }
+            // The time between calls is kept for the next campaign
+            if let Err(message) = save_preferences(data) {
+                warn!("Couldn't save the time between campaign calls: {}", message);
+            }
+            campaign::start(delay, ctx.get_external_handle());
+            data.status_message = "Campaign under way".to_string();
+            return Handled::Yes;
+        } else if let Some(file) = cmd.get(CLIENT_CERTIFICATE_CHOSEN) {
+            data.client_certificate = file.path().display().to_string();
+            return Handled::Yes;
+        } else if let Some(file) = cmd.get(CLIENT_KEY_CHOSEN) {
+            data.client_key = file.path().display().to_string();
+            return Handled::Yes;
+        } else if let Some(file) = cmd.get(druid::commands::OPEN_FILE) {
+            // Importing the history is all the open panel is used for
+            data.status_message = match history::import_json(file.path()) {
+                Ok(count) => format!("Imported {} calls from {}", count, file.path().display()),
+                Err(e) => format!("Error: couldn't import the call history: {}", e),
+            };
+            return Handled::Yes;
+        } else if let Some(action) = cmd.get(notify::NOTIFICATION_CLICKED) {
+            if action == notify::ACTION_SHOW_STATS {
+                ctx.new_window(stats_window(data));
+            } else if let Some(number) = action.strip_prefix(notify::ACTION_CALL_AGAIN) {
+                data.phone_number = number.to_string();
+                ctx.submit_command(MAKE_CALL.with(CallOrigin::History));
+            } else if let Some(number) = action.strip_prefix(notify::ACTION_CONFIRM_CALL) {
+                data.call_note.clear();
+                ctx.new_window(confirmation_window(number.to_string(), data, CallOrigin::History));
+            } else if let Some(number) = action.strip_prefix(notify::ACTION_CALL_COPIED) {
+                data.phone_number = number.to_string();
+                ctx.submit_command(MAKE_CALL.with(CallOrigin::ClipboardWatch));
+            } else if let Some(number) = action.strip_prefix(notify::ACTION_COPY_NUMBER) {
+                clipboard_watch::copied_here();
+                druid::Application::global().clipboard().put_string(number);
+            }
+            return Handled::Yes;
+        }
+        Handled::No
+    }
+
+    fn window_added(
+        &mut self,
+        id: druid::WindowId,
+        _handle: druid::WindowHandle,
+        data: &mut AppState,
+        _env: &Env,
+        ctx: &mut DelegateCtx,
+    ) {
+        // Only the settings window drives app initialization
+        if self.main_window.is_some() {
+            return;
+        }
+        self.main_window = Some(id);
+        
+        // In menu bar mode the settings window is only shown on request
+        if data.menu_bar && self.is_primary {
+            ctx.submit_command(druid::commands::HIDE_WINDOW.to(id));
+        }
+        
+        // The settings window waits until the setup is done or closed
+        if self.first_run {
+            ctx.submit_command(druid::commands::HIDE_WINDOW.to(id));
+            let setup = onboarding::onboarding_window();
+            self.setup_window = Some(setup.id);
+            ctx.new_window(setup);
+        }
+        
+        // Has to be in place before launching finishes, when Spotlight hands over its selection
+        spotlight::install(ctx.get_external_handle());
+        favorites::index(&data.favorites);
+        for profile in profiles::load() {
+            spotlight::index_profile(&profile.name);
+        }
+        
+        // Window is created, but might not be fully ready
+        // Schedule APP_INITIALIZED command with a small delay
+        let handle = ctx.get_external_handle();
+        thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(1000));
+            handle.submit_command(APP_INITIALIZED, (), Target::Window(id)).ok();
+        });
+    }
+
+    fn window_removed(&mut self, id: druid::WindowId, _data: &mut AppState, _env: &Env, ctx: &mut DelegateCtx) {
+        if self.popover == Some(id) {
+            self.popover = None;
+        }
+        if self.setup_window == Some(id) {
+            self.setup_window = None;
+            if let Some(main_window) = self.main_window {
+                ctx.submit_command(druid::commands::SHOW_WINDOW.to(main_window));
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// The dial pad: a keypad for putting a number together with the mouse or
+// trackpad alone, shown below the phone number field. Pause adds a comma, so
+// digits after it are sent once the call connects, e.g. an extension or a
+// conference PIN ending in #.
+
+use super::lenses::PhoneNumberLens;
+use crate::config::AppState;
+use druid::widget::{Button, Flex};
+use druid::{Widget, WidgetExt};
+
+// Keys in the order they're laid out, three to a row
+const KEYS: [char; 15] = ['1', '2', '3', '4', '5', '6', '7', '8', '9', '*', '0', '#', '+', ',', BACKSPACE];
+const BACKSPACE: char = '⌫';
+const KEY_WIDTH: f64 = 64.0;
+
+// What `key` does to `number`: digits, * and # are added, + only starts a
+// number, a pause only follows something to dial and backspace takes off the
+// last character
+pub fn press_key(number: &mut String, key: char) {
+    match key {
+        BACKSPACE => {
+            number.pop();
+        }
+        '+' => {
+            if number.trim().is_empty() {
+                *number = "+".to_string();
+            }
+        }
+        ',' => {
+            if number.chars().any(|c| c.is_ascii_digit() || c == '*' || c == '#') {
+                number.push(',');
+            }
+        }
+        _ => number.push(key),
+    }
+}
+
+fn label(key: char) -> String {
+    match key {
+        ',' => "Pause".to_string(),
+        key => key.to_string(),
+    }
+}
+
+pub fn keypad() -> impl Widget<AppState> {
+    let mut layout = Flex::column();
+    for row_keys in KEYS.chunks(3) {
+        let mut row = Flex::row();
+        for (index, key) in row_keys.iter().copied().enumerate() {
+            if index > 0 {
+                row.add_spacer(6.0);
+            }
+            let button = Button::new(label(key))
+                .on_click(move |_ctx, number: &mut String, _env| press_key(number, key))
+                .fix_width(KEY_WIDTH);
+            row.add_child(button);
+        }
+        layout.add_child(row);
+        layout.add_spacer(6.0);
+    }
+    layout.lens(PhoneNumberLens)
+}
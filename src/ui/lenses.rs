@@ -0,0 +1,941 @@
+// A lens per setting, for the widgets that edit it
+
+use crate::backend::{BackendKind, HttpAuthKind};
+use crate::config::{AppState, AutoAnswerOverride};
+use druid::Lens;
+
+pub struct DomainLens;
+pub struct FallbackDomainsLens;
+pub struct SrvLookupLens;
+pub struct ExtensionLens;
+pub struct KeyLens;
+pub struct AutoAnswerLens;
+pub struct PhoneNumberLens;
+pub struct StatusMessageLens;
+pub struct LookupHotkeyLens;
+pub struct KeepAliveLens;
+pub struct HealthCheckLens;
+pub struct WebhookUrlLens;
+pub struct SummaryTimeLens;
+pub struct WakeGraceMinutesLens;
+pub struct CountryLens;
+pub struct ColorLens;
+pub struct AuditWebhookUrlLens;
+pub struct AuditWebhookSecretLens;
+pub struct SpotlightRecentsLens;
+pub struct MenuBarLens;
+pub struct UseContactsLens;
+pub struct AutoAnswerOverrideLens;
+pub struct BackendLens;
+pub struct BackendUsernameLens;
+pub struct BackendContextLens;
+pub struct CallNoteLens;
+pub struct ProfileLens;
+pub struct CaptureTranscriptsLens;
+pub struct DebugLoggingLens;
+pub struct StorageLimitLens;
+pub struct LogRetentionLens;
+pub struct DialE164Lens;
+pub struct DialPlanLens;
+pub struct RoutesLens;
+pub struct ShortCodesLens;
+pub struct RingbackLens;
+pub struct RecordCallsLens;
+pub struct CallerIdLens;
+pub struct CallerIdNameTemplateLens;
+pub struct CallerIdNumberTemplateLens;
+pub struct ClickToCallParametersLens;
+pub struct HttpRequestLens;
+pub struct HttpAuthLens;
+pub struct HttpAuthUsernameLens;
+pub struct HttpAuthPasswordLens;
+pub struct OAuthTokenUrlLens;
+pub struct OAuthAuthorizeUrlLens;
+pub struct OAuthScopeLens;
+pub struct TrackCallsLens;
+pub struct EventSocketLens;
+pub struct EventSocketPasswordLens;
+pub struct ShowPresenceLens;
+pub struct ShowVoicemailLens;
+pub struct VoicemailNumberLens;
+pub struct RequestTimeoutLens;
+pub struct RetryAttemptsLens;
+pub struct RetryDelayLens;
+pub struct MaxConcurrentCallsLens;
+pub struct CaCertificateLens;
+pub struct AcceptInvalidCertificatesLens;
+pub struct ClientCertificateLens;
+pub struct ClientKeyLens;
+pub struct ClientCertificatePassphraseLens;
+pub struct RouteLens;
+pub struct ConfirmTelLinksLens;
+pub struct AskCallOptionsLens;
+pub struct DuplicateWindowLens;
+pub struct NotifyDuplicateCallsLens;
+pub struct CampaignDelayLens;
+pub struct LinkRulesLens;
+pub struct HttpApiPortLens;
+pub struct HttpApiTokenLens;
+pub struct ClipboardHotkeyLens;
+pub struct WatchClipboardLens;
+pub struct LargeTextLens;
+pub struct HighContrastLens;
+pub struct FavoritesLens;
+pub struct AllowedNumbersLens;
+pub struct BlockedNumbersLens;
+pub struct PrivateCallLens;
+pub struct DirectoryUrlLens;
+pub struct CorporateDirectoryUrlLens;
+pub struct CorporateDirectoryUserLens;
+pub struct CorporateDirectoryPasswordLens;
+pub struct CorporateDirectoryBaseLens;
+
+impl Lens<AppState, String> for DomainLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.domain)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.domain)
+    }
+}
+
+impl Lens<AppState, String> for FallbackDomainsLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.fallback_domains)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.fallback_domains)
+    }
+}
+
+impl Lens<AppState, bool> for SrvLookupLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.srv_lookup)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.srv_lookup)
+    }
+}
+
+impl Lens<AppState, String> for ExtensionLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.extension)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.extension)
+    }
+}
+
+impl Lens<AppState, String> for KeyLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.key)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.key)
+    }
+}
+
+impl Lens<AppState, bool> for AutoAnswerLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.auto_answer)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.auto_answer)
+    }
+}
+
+impl Lens<AppState, String> for PhoneNumberLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.phone_number)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.phone_number)
+    }
+}
+
+impl Lens<AppState, String> for StatusMessageLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.status_message)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.status_message)
+    }
+}
+
+impl Lens<AppState, String> for LookupHotkeyLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.lookup_hotkey)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.lookup_hotkey)
+    }
+}
+
+impl Lens<AppState, bool> for HealthCheckLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.health_check)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.health_check)
+    }
+}
+
+impl Lens<AppState, bool> for KeepAliveLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.keep_alive)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.keep_alive)
+    }
+}
+
+impl Lens<AppState, String> for WebhookUrlLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.webhook_url)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.webhook_url)
+    }
+}
+
+impl Lens<AppState, String> for SummaryTimeLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.summary_time)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.summary_time)
+    }
+}
+
+impl Lens<AppState, String> for CountryLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.country)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.country)
+    }
+}
+
+impl Lens<AppState, String> for ColorLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.color)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.color)
+    }
+}
+
+impl Lens<AppState, bool> for SpotlightRecentsLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.spotlight_recents)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.spotlight_recents)
+    }
+}
+
+impl Lens<AppState, String> for AuditWebhookUrlLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.audit_webhook_url)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.audit_webhook_url)
+    }
+}
+
+impl Lens<AppState, String> for AuditWebhookSecretLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.audit_webhook_secret)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.audit_webhook_secret)
+    }
+}
+
+impl Lens<AppState, bool> for MenuBarLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.menu_bar)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.menu_bar)
+    }
+}
+
+impl Lens<AppState, AutoAnswerOverride> for AutoAnswerOverrideLens {
+    fn with<V, F: FnOnce(&AutoAnswerOverride) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.auto_answer_override)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut AutoAnswerOverride) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.auto_answer_override)
+    }
+}
+
+impl Lens<AppState, bool> for UseContactsLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.use_contacts)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.use_contacts)
+    }
+}
+
+impl Lens<AppState, BackendKind> for BackendLens {
+    fn with<V, F: FnOnce(&BackendKind) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.backend)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut BackendKind) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.backend)
+    }
+}
+
+impl Lens<AppState, String> for BackendUsernameLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.backend_username)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.backend_username)
+    }
+}
+
+impl Lens<AppState, String> for BackendContextLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.backend_context)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.backend_context)
+    }
+}
+
+impl Lens<AppState, String> for CallNoteLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.call_note)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.call_note)
+    }
+}
+
+impl Lens<AppState, String> for ProfileLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.profile)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.profile)
+    }
+}
+
+impl Lens<AppState, bool> for CaptureTranscriptsLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.capture_transcripts)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.capture_transcripts)
+    }
+}
+
+impl Lens<AppState, bool> for DebugLoggingLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.debug_logging)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.debug_logging)
+    }
+}
+
+impl Lens<AppState, String> for StorageLimitLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.storage_limit_mb)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.storage_limit_mb)
+    }
+}
+
+impl Lens<AppState, String> for LogRetentionLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.log_retention_days)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.log_retention_days)
+    }
+}
+
+impl Lens<AppState, bool> for DialE164Lens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.dial_e164)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.dial_e164)
+    }
+}
+
+impl Lens<AppState, String> for DialPlanLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.dial_plan)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.dial_plan)
+    }
+}
+
+impl Lens<AppState, String> for RoutesLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.routes)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.routes)
+    }
+}
+
+impl Lens<AppState, String> for ShortCodesLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.short_codes)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.short_codes)
+    }
+}
+
+impl Lens<AppState, String> for RingbackLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.ringback)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.ringback)
+    }
+}
+
+impl Lens<AppState, bool> for RecordCallsLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.record_calls)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.record_calls)
+    }
+}
+
+impl Lens<AppState, String> for CallerIdLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.caller_id)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.caller_id)
+    }
+}
+
+impl Lens<AppState, String> for CallerIdNameTemplateLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.caller_id_name_template)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.caller_id_name_template)
+    }
+}
+
+impl Lens<AppState, String> for CallerIdNumberTemplateLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.caller_id_number_template)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.caller_id_number_template)
+    }
+}
+
+impl Lens<AppState, String> for ClickToCallParametersLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.click_to_call_parameters)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.click_to_call_parameters)
+    }
+}
+
+impl Lens<AppState, String> for HttpRequestLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.http_request)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.http_request)
+    }
+}
+
+impl Lens<AppState, HttpAuthKind> for HttpAuthLens {
+    fn with<V, F: FnOnce(&HttpAuthKind) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.http_auth)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut HttpAuthKind) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.http_auth)
+    }
+}
+
+impl Lens<AppState, String> for HttpAuthUsernameLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.http_auth_username)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.http_auth_username)
+    }
+}
+
+impl Lens<AppState, String> for HttpAuthPasswordLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.http_auth_password)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.http_auth_password)
+    }
+}
+
+impl Lens<AppState, String> for OAuthTokenUrlLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.oauth_token_url)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.oauth_token_url)
+    }
+}
+
+impl Lens<AppState, String> for OAuthAuthorizeUrlLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.oauth_authorize_url)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.oauth_authorize_url)
+    }
+}
+
+impl Lens<AppState, String> for OAuthScopeLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.oauth_scope)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.oauth_scope)
+    }
+}
+
+impl Lens<AppState, bool> for TrackCallsLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.track_calls)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.track_calls)
+    }
+}
+
+impl Lens<AppState, String> for EventSocketLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.event_socket)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.event_socket)
+    }
+}
+
+impl Lens<AppState, String> for EventSocketPasswordLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.event_socket_password)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.event_socket_password)
+    }
+}
+
+impl Lens<AppState, bool> for ShowPresenceLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.show_presence)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.show_presence)
+    }
+}
+
+impl Lens<AppState, bool> for ShowVoicemailLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.show_voicemail)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.show_voicemail)
+    }
+}
+
+impl Lens<AppState, String> for VoicemailNumberLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.voicemail_number)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.voicemail_number)
+    }
+}
+
+impl Lens<AppState, String> for RequestTimeoutLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.request_timeout)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.request_timeout)
+    }
+}
+
+impl Lens<AppState, String> for RetryAttemptsLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.retry_attempts)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.retry_attempts)
+    }
+}
+
+impl Lens<AppState, String> for RetryDelayLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.retry_delay)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.retry_delay)
+    }
+}
+
+impl Lens<AppState, String> for MaxConcurrentCallsLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.max_concurrent_calls)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.max_concurrent_calls)
+    }
+}
+
+impl Lens<AppState, String> for RouteLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.route)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.route)
+    }
+}
+
+impl Lens<AppState, String> for CaCertificateLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.ca_certificate)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.ca_certificate)
+    }
+}
+
+impl Lens<AppState, bool> for AcceptInvalidCertificatesLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.accept_invalid_certificates)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.accept_invalid_certificates)
+    }
+}
+
+impl Lens<AppState, String> for ClientCertificateLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.client_certificate)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.client_certificate)
+    }
+}
+
+impl Lens<AppState, String> for ClientKeyLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.client_key)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.client_key)
+    }
+}
+
+impl Lens<AppState, String> for ClientCertificatePassphraseLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.client_certificate_passphrase)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.client_certificate_passphrase)
+    }
+}
+
+impl Lens<AppState, bool> for PrivateCallLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.private_call)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.private_call)
+    }
+}
+
+impl Lens<AppState, String> for DirectoryUrlLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.directory_url)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.directory_url)
+    }
+}
+
+impl Lens<AppState, String> for CorporateDirectoryUrlLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.corporate_directory_url)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.corporate_directory_url)
+    }
+}
+
+impl Lens<AppState, String> for CorporateDirectoryUserLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.corporate_directory_user)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.corporate_directory_user)
+    }
+}
+
+impl Lens<AppState, String> for CorporateDirectoryPasswordLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.corporate_directory_password)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.corporate_directory_password)
+    }
+}
+
+impl Lens<AppState, String> for CorporateDirectoryBaseLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.corporate_directory_base)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.corporate_directory_base)
+    }
+}
+
+impl Lens<AppState, String> for WakeGraceMinutesLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.wake_grace_minutes)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.wake_grace_minutes)
+    }
+}
+
+impl Lens<AppState, bool> for ConfirmTelLinksLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.confirm_tel_links)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.confirm_tel_links)
+    }
+}
+
+impl Lens<AppState, bool> for AskCallOptionsLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.ask_call_options)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.ask_call_options)
+    }
+}
+
+impl Lens<AppState, String> for DuplicateWindowLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.duplicate_window)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.duplicate_window)
+    }
+}
+
+impl Lens<AppState, bool> for NotifyDuplicateCallsLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.notify_duplicate_calls)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.notify_duplicate_calls)
+    }
+}
+
+impl Lens<AppState, String> for CampaignDelayLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.campaign_delay)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.campaign_delay)
+    }
+}
+
+impl Lens<AppState, String> for LinkRulesLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.link_rules)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.link_rules)
+    }
+}
+
+impl Lens<AppState, String> for HttpApiPortLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.http_api_port)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.http_api_port)
+    }
+}
+
+impl Lens<AppState, String> for HttpApiTokenLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.http_api_token)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.http_api_token)
+    }
+}
+
+impl Lens<AppState, String> for ClipboardHotkeyLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.clipboard_hotkey)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.clipboard_hotkey)
+    }
+}
+
+impl Lens<AppState, bool> for WatchClipboardLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.watch_clipboard)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.watch_clipboard)
+    }
+}
+
+impl Lens<AppState, bool> for LargeTextLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.large_text)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.large_text)
+    }
+}
+
+impl Lens<AppState, bool> for HighContrastLens {
+    fn with<V, F: FnOnce(&bool) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.high_contrast)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut bool) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.high_contrast)
+    }
+}
+
+impl Lens<AppState, String> for FavoritesLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.favorites)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.favorites)
+    }
+}
+
+impl Lens<AppState, String> for AllowedNumbersLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.allowed_numbers)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.allowed_numbers)
+    }
+}
+
+impl Lens<AppState, String> for BlockedNumbersLens {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&data.blocked_numbers)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut AppState, f: F) -> V {
+        f(&mut data.blocked_numbers)
+    }
+}
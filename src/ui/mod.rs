@@ -0,0 +1,2288 @@
+// The windows: settings and dialer, the menu bar popover and the smaller
+// windows opened from them
+
+mod delegate;
+pub mod keypad;
+mod lenses;
+mod onboarding;
+
+pub use delegate::*;
+pub use onboarding::SetupStep;
+use lenses::*;
+
+use druid::widget::{Button, Checkbox, Flex, Label, LineBreaking, Painter, RadioGroup, Scroll, TextBox};
+use druid::widget::Controller;
+use druid::{Event, EventCtx, Color, Env, FileDialogOptions, FileSpec, Rect, RenderContext, Widget, WidgetExt, WindowDesc};
+use druid::AppDelegate;
+use druid::Command;
+use druid::DelegateCtx;
+use druid::Selector;
+use druid::Target;
+use druid::Handled;
+use std::thread;
+use std::env;
+use std::fs;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::appearance::Wrap;
+use crate::audit::CallOrigin;
+use crate::backend::{self, BackendKind, HttpAuthKind};
+use crate::call::{contact_name, pbx_number};
+use crate::config::{self, save_preferences, AppState, AutoAnswerOverride};
+use crate::identity::identity;
+use crate::screen_lookup::DetectedNumber;
+use crate::{active_calls, appearance, apply_settings, campaign, clipboard_watch, contacts, corporate_directory, dial_plan, directory, effective_config, favorites, health, history, http_api, logging, managed, number_format, number_lists, paths, profile_color, profiles, queue, reminders, retry, runtime, share, stats, storage, url_handler, voicemail};
+
+
+// Define a custom command to initiate a call, carrying what asked for it
+const MAKE_CALL: Selector<CallOrigin> = Selector::new("app.make-call");
+// Command to place a call once its options were chosen in the options dialog
+const CALL_WITH_OPTIONS: Selector<CallOrigin> = Selector::new("app.call-with-options");
+// Command to run when app is fully initialized
+const APP_INITIALIZED: Selector = Selector::new("app.initialized");
+// Command to process external tel: URL
+pub const PROCESS_TEL_URL: Selector<String> = Selector::new("app.process-tel-url");
+// Command to read the saved settings again, e.g. after another tool changed them
+pub const RELOAD_SETTINGS: Selector = Selector::new("app.reload-settings");
+// Command to ask before calling a number from a tel: link
+// The number, where the request came from and whether the call is private
+pub const CONFIRM_CALL: Selector<(String, CallOrigin, bool)> = Selector::new("app.confirm-call");
+// Command to confirm a number found on screen before dialing it
+const SHOW_DETECTED_NUMBER: Selector<DetectedNumber> = Selector::new("app.show-detected-number");
+
+// Command to bring the settings window back, e.g. from the menu bar popover
+const SHOW_SETTINGS: Selector = Selector::new("app.show-settings");
+
+// Command to make the named profile the active one, from the settings or the menu bar
+pub const SWITCH_PROFILE: Selector<String> = Selector::new("app.switch-profile");
+
+// Command to turn watching the clipboard on or off from the menu bar, saved right away
+const TOGGLE_CLIPBOARD_WATCH: Selector = Selector::new("app.toggle-clipboard-watch");
+
+// Command to ask where to save a redacted bug report
+const EXPORT_BUG_REPORT: Selector = Selector::new("app.export-bug-report");
+
+// Commands to ask where to save the call history as JSON, and which JSON file to read calls from
+const EXPORT_HISTORY: Selector = Selector::new("app.export-history");
+const IMPORT_HISTORY: Selector = Selector::new("app.import-history");
+
+// Commands to ask where to export every profile and setting, and which file to import them from
+const EXPORT_SETTINGS: Selector = Selector::new("app.export-settings");
+const IMPORT_SETTINGS: Selector = Selector::new("app.import-settings");
+const SETTINGS_FILE_CHOSEN: Selector<druid::FileInfo> = Selector::new("app.settings-file-chosen");
+
+// Commands to start or resume the campaign, and the CSV file picked to import one from
+const START_CAMPAIGN: Selector = Selector::new("app.start-campaign");
+const CAMPAIGN_FILE_CHOSEN: Selector<druid::FileInfo> = Selector::new("app.campaign-file-chosen");
+
+// Files picked in the open panel for the PBX client certificate and its private key
+const CLIENT_CERTIFICATE_CHOSEN: Selector<druid::FileInfo> = Selector::new("app.client-certificate-chosen");
+const CLIENT_KEY_CHOSEN: Selector<druid::FileInfo> = Selector::new("app.client-key-chosen");
+
+// Hotkey id for "call the number I'm looking at"
+pub const LOOKUP_HOTKEY_ID: u32 = 1;
+// Hotkey id for "call the number on the clipboard"
+pub const CLIPBOARD_HOTKEY_ID: u32 = 2;
+// How many frequently called numbers are offered above the phone field
+const FREQUENT_NUMBERS: usize = 5;
+// How many recent numbers the menu bar popover offers
+const POPOVER_RECENT_NUMBERS: usize = 5;
+// How long a number found by OCR stays outlined on screen
+const HIGHLIGHT_DURATION: Duration = Duration::from_millis(1500);
+
+// In menu bar mode, closing the settings window only hides it so the
+// popover's Settings button can bring it back
+struct HideOnClose;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for HideOnClose {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        if let Event::WindowCloseRequested = event {
+            if data.menu_bar {
+                ctx.set_handled();
+                ctx.submit_command(druid::commands::HIDE_WINDOW.to(ctx.window_id()));
+                return;
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+}
+
+// In kiosk mode only the dialer is shown; settings come from managed preferences
+pub fn build_ui(kiosk: bool) -> impl Widget<AppState> {
+    // Create label-input pairs for each field
+    let profile_label = Label::new("Profile:");
+    let profile_input = TextBox::new()
+        .with_placeholder("Name, e.g. Tenant A (a new name saves a new profile)")
+        .lens(ProfileLens)
+        .expand_width();
+    
+    let domain_label = Label::new("Domain:");
+    let domain_input = TextBox::new()
+        .with_placeholder("Enter domain")
+        .lens(DomainLens)
+        .expand_width();
+    let fallback_domains_label = Label::new("Fallback Domains:");
+    let fallback_domains_input = TextBox::new()
+        .with_placeholder("Tried in order if the domain times out or fails, e.g. pbx2.example.com")
+        .lens(FallbackDomainsLens)
+        .expand_width();
+    let srv_lookup_checkbox = Checkbox::new("Find the servers from the domain's DNS SRV records")
+        .lens(SrvLookupLens);
+    
+    let extension_label = Label::new("Extension:");
+    let extension_input = TextBox::new()
+        .with_placeholder("Enter extension")
+        .lens(ExtensionLens)
+        .expand_width();
+
+    let key_label = Label::new("Key:");
+    let key_input = TextBox::new()
+        .with_placeholder("API key, or the backend's password/secret")
+        .lens(KeyLens)
+        .expand_width();
+    
+    let backend_label = Label::new("PBX:");
+    let backend_picker = RadioGroup::column(
+        BackendKind::ALL.iter().map(|kind| (kind.label(), *kind)),
+    )
+    .lens(BackendLens);
+    let http_request_button = Button::new("HTTP Request…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.new_window(http_request_window());
+        });
+    
+    let backend_username_label = Label::new("Username:");
+    let backend_username_input = TextBox::new()
+        .with_placeholder("AMI/ARI user or 3CX client id")
+        .lens(BackendUsernameLens)
+        .expand_width();
+    
+    let backend_context_label = Label::new("Context:");
+    let backend_context_input = TextBox::new()
+        .with_placeholder("Dialplan context (Asterisk, FreeSWITCH)")
+        .lens(BackendContextLens)
+        .expand_width();
+    
+    let country_label = Label::new("Country:");
+    let country_input = TextBox::new()
+        .with_placeholder("Two-letter code, e.g. US")
+        .lens(CountryLens)
+        .expand_width();
+    
+    let dial_e164_checkbox = Checkbox::new("Send numbers to the PBX as +<country code><number>")
+        .lens(DialE164Lens);
+    
+    let routes_label = Label::new("Routes:");
+    let routes_input = TextBox::new()
+        .with_placeholder("Trunk prefixes, e.g. 8=Backup carrier, 9=International")
+        .lens(RoutesLens)
+        .expand_width();
+    
+    let short_codes_label = Label::new("Short Codes:");
+    let short_codes_input = TextBox::new()
+        .with_placeholder("Dialed as typed, e.g. 13xxxx, 1831*")
+        .lens(ShortCodesLens)
+        .expand_width();
+    
+    let ringback_label = Label::new("Ringback:");
+    let ringback_input = TextBox::new()
+        .with_placeholder("Tone name, e.g. uk-ring (by destination if empty)")
+        .lens(RingbackLens)
+        .expand_width();
+    let record_calls_checkbox = Checkbox::new("Record")
+        .lens(RecordCallsLens);
+    
+    let caller_id_label = Label::new("Caller ID:");
+    let caller_id_input = TextBox::new()
+        .with_placeholder(CALLER_ID_PLACEHOLDER)
+        .lens(CallerIdLens)
+        .expand_width();
+    
+    let caller_id_template_label = Label::new("Your Phone Shows:");
+    let caller_id_name_template_input = TextBox::new()
+        .with_placeholder("Name, e.g. {contact} ({profile})")
+        .lens(CallerIdNameTemplateLens)
+        .expand_width();
+    let caller_id_number_template_input = TextBox::new()
+        .with_placeholder("Number, e.g. {number}")
+        .lens(CallerIdNumberTemplateLens)
+        .expand_width();
+    
+    let request_timeout_label = Label::new("Request Timeout:");
+    let request_timeout_input = TextBox::new()
+        .with_placeholder("Seconds (30 if empty)")
+        .lens(RequestTimeoutLens)
+        .expand_width();
+    
+    let retry_attempts_label = Label::new("Retries:");
+    let retry_attempts_input = TextBox::new()
+        .with_placeholder("7 if empty")
+        .lens(RetryAttemptsLens)
+        .expand_width();
+    
+    let retry_delay_label = Label::new("First After:");
+    let retry_delay_input = TextBox::new()
+        .with_placeholder("Seconds (30 if empty)")
+        .lens(RetryDelayLens)
+        .expand_width();
+    
+    let max_concurrent_calls_label = Label::new("Calls at Once:");
+    let max_concurrent_calls_input = TextBox::new()
+        .with_placeholder("1 if empty; the rest wait in the queue")
+        .lens(MaxConcurrentCallsLens)
+        .expand_width();
+    
+    let ca_certificate_label = Label::new("CA Certificate:");
+    let ca_certificate_input = TextBox::new()
+        .with_placeholder("PEM file for a PBX with an internal CA, e.g. ~/lab-ca.pem")
+        .lens(CaCertificateLens)
+        .expand_width();
+    
+    let accept_invalid_certificates_checkbox = Checkbox::new("Accept any certificate from this PBX (insecure)")
+        .lens(AcceptInvalidCertificatesLens);
+    
+    let client_certificate_label = Label::new("Client Certificate:");
+    let client_certificate_input = TextBox::new()
+        .with_placeholder("PKCS#12 or PEM file, for a PBX that asks for one")
+        .lens(ClientCertificateLens)
+        .expand_width();
+    let client_certificate_button = Button::new("Choose…").on_click(|ctx, _data: &mut AppState, _env| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![FileSpec::new("Certificate", &["p12", "pfx", "pem", "crt", "cer"])])
+            .accept_command(CLIENT_CERTIFICATE_CHOSEN);
+        ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options));
+    });
+    
+    let client_key_label = Label::new("Private Key:");
+    let client_key_input = TextBox::new()
+        .with_placeholder("PEM key, if it isn't in the certificate file")
+        .lens(ClientKeyLens)
+        .expand_width();
+    let client_key_button = Button::new("Choose…").on_click(|ctx, _data: &mut AppState, _env| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![FileSpec::new("Private Key", &["pem", "key"])])
+            .accept_command(CLIENT_KEY_CHOSEN);
+        ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options));
+    });
+    
+    let client_certificate_passphrase_label = Label::new("Passphrase:");
+    let client_certificate_passphrase_input = TextBox::new()
+        .with_placeholder("For a PKCS#12 file, kept in the Keychain")
+        .lens(ClientCertificatePassphraseLens)
+        .expand_width();
+    
+    // For a web server in front of the PBX, on top of the key
+    let http_auth_label = Label::new("Web Server Login:");
+    let http_auth_picker = RadioGroup::row(HttpAuthKind::ALL.iter().map(|kind| (kind.label(), *kind)))
+        .lens(HttpAuthLens);
+    let http_auth_username_input = TextBox::new()
+        .with_placeholder("Username, or OAuth2 client id")
+        .lens(HttpAuthUsernameLens)
+        .expand_width();
+    let http_auth_password_input = TextBox::new()
+        .with_placeholder("Password, token or client secret, kept in the Keychain")
+        .lens(HttpAuthPasswordLens)
+        .expand_width();
+    let oauth_token_url_input = TextBox::new()
+        .with_placeholder("OAuth2 token URL")
+        .lens(OAuthTokenUrlLens)
+        .expand_width();
+    let oauth_authorize_url_input = TextBox::new()
+        .with_placeholder("OAuth2 sign-in URL, for Sign In")
+        .lens(OAuthAuthorizeUrlLens)
+        .expand_width();
+    let oauth_scope_input = TextBox::new()
+        .with_placeholder("Scope")
+        .lens(OAuthScopeLens)
+        .expand_width();
+    let track_calls_checkbox = Checkbox::new("Follow calls over the event socket (FusionPBX and FreeSWITCH)")
+        .lens(TrackCallsLens);
+    let show_presence_checkbox = Checkbox::new("Show whether my phone is registered and on a call (FusionPBX, FreeSWITCH and Asterisk ARI)")
+        .lens(ShowPresenceLens);
+    let show_voicemail_checkbox = Checkbox::new("Count new voicemail (FusionPBX, FreeSWITCH and Asterisk AMI)")
+        .lens(ShowVoicemailLens);
+    let voicemail_number_input = TextBox::new()
+        .with_placeholder(format!("Voicemail number, empty for {}", voicemail::DEFAULT_NUMBER))
+        .lens(VoicemailNumberLens)
+        .expand_width();
+    let event_socket_input = TextBox::new()
+        .with_placeholder("Event socket for FusionPBX, e.g. pbx.example.com:8021")
+        .lens(EventSocketLens)
+        .expand_width();
+    let event_socket_password_input = TextBox::new()
+        .with_placeholder("Its password, kept in the Keychain")
+        .lens(EventSocketPasswordLens)
+        .expand_width();
+    // Runs the browser sign-in with what's in the form, saved or not
+    let oauth_sign_in_button = Button::new("Sign In…")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            let auth = backend::HttpAuth::from_state(data);
+            if auth.kind != HttpAuthKind::OAuth2 || auth.authorize_url.trim().is_empty() {
+                data.status_message = "Choose OAuth2 under Web Server Login and fill in the sign-in URL first".to_string();
+                return;
+            }
+            if let Err(message) = auth.check() {
+                data.status_message = message;
+                return;
+            }
+            data.status_message = "Finish signing in in the browser...".to_string();
+            let event_sink = ctx.get_external_handle();
+            runtime().spawn(async move {
+                let message = match backend::oauth_sign_in(auth).await {
+                    Ok(()) => "Signed in; calls get their tokens from the identity provider".to_string(),
+                    Err(message) => {
+                        warn!("OAuth2 sign-in failed: {}", message);
+                        message
+                    }
+                };
+                event_sink.add_idle_callback(move |data: &mut AppState| {
+                    data.status_message = message;
+                });
+            });
+        });
+    
+    let dial_plan_button = Button::new("Rewrite Rules…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.new_window(dial_plan_window());
+        });
+    
+    let parameters_button = Button::new("Parameters…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.new_window(parameters_window());
+        });
+    
+    let color_label = Label::new("Color:");
+    let color_picker = RadioGroup::row(
+        profile_color::PALETTE
+            .iter()
+            .map(|(value, label, _)| (*label, value.to_string())),
+    )
+    .lens(ColorLens);
+    
+    // Auto Answer checkbox
+    let auto_answer_override_label = Label::new("On this Mac:");
+    let auto_answer_override_picker = RadioGroup::row(vec![
+        ("Use default", AutoAnswerOverride::Default),
+        ("Always auto answer", AutoAnswerOverride::Always),
+        ("Never", AutoAnswerOverride::Never),
+    ])
+    .lens(AutoAnswerOverrideLens);
+    
+    let auto_answer_checkbox = Checkbox::new("Auto Answer")
+        .lens(AutoAnswerLens);
+    
+    let keep_alive_checkbox = Checkbox::new("Keep PBX connection warm")
+        .lens(KeepAliveLens);
+    
+    let health_check_checkbox = Checkbox::new("Check the PBX connection every 5 minutes")
+        .lens(HealthCheckLens);
+    
+    let test_connection_button = Button::new("Test Connection")
+        .on_click(|ctx, data: &mut AppState, _env| test_connection(ctx, data, |health, domain| health.summary(domain)));
+    
+    let confirm_tel_links_checkbox = Checkbox::new("Confirm before dialing tel: links")
+        .lens(ConfirmTelLinksLens);
+    let ask_call_options_checkbox = Checkbox::new("Choose recording, ringback and caller ID for each call")
+        .lens(AskCallOptionsLens);
+    
+    // Double-clicked links and browsers that fire twice
+    let duplicate_window_label = Label::new("Ignore Repeat Calls For:");
+    let duplicate_window_input = TextBox::new()
+        .with_placeholder("Seconds (5 if empty, 0 for never)")
+        .lens(DuplicateWindowLens)
+        .expand_width();
+    let notify_duplicate_calls_checkbox = Checkbox::new("Notify")
+        .lens(NotifyDuplicateCallsLens);
+    
+    // What callto: and SIP links do
+    let link_rules_label = Label::new("Other Links:");
+    let link_rules_input = TextBox::new()
+        .with_placeholder("callto: and sip: links, e.g. callto=ask, sip=off")
+        .lens(LinkRulesLens)
+        .expand_width();
+    
+    // The local HTTP API, for browser extensions and CRMs on this Mac
+    let http_api_label = Label::new("Local API Port:");
+    let http_api_port_input = TextBox::new()
+        .with_placeholder("Off")
+        .lens(HttpApiPortLens)
+        .fix_width(70.0);
+    let http_api_token_input = TextBox::new()
+        .with_placeholder("Token requests must send")
+        .lens(HttpApiTokenLens)
+        .expand_width();
+    let new_token_button = Button::new("New Token")
+        .on_click(|_ctx, data: &mut AppState, _env| match http_api::new_token() {
+            Ok(token) => {
+                data.http_api_token = token;
+                data.status_message = "Save Settings to use the new token".to_string();
+            }
+            Err(message) => data.status_message = format!("Error: {}", message),
+        });
+    
+    // Which app tel: links open in, and the button to make it this one
+    let tel_handler_label = Label::dynamic(|data: &AppState, _env: &Env| {
+        if data.handles_tel_links {
+            format!("tel: links open with {}", identity().name)
+        } else {
+            "tel: links open with another app".to_string()
+        }
+    });
+    let tel_handler_button = Button::new("Make Default for tel: Links")
+        .on_click(|_ctx, data: &mut AppState, _env| make_default_tel_handler(data));
+    
+    let number_lists_button = Button::new("Allow & Block Lists…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.new_window(number_lists_window());
+        });
+    
+    let capture_transcripts_checkbox = Checkbox::new("Keep request details of failed calls")
+        .lens(CaptureTranscriptsLens);
+    
+    let debug_logging_checkbox = Checkbox::new("Debug logging")
+        .lens(DebugLoggingLens);
+    
+    let open_log_button = Button::new("Open Log")
+        .on_click(|_ctx, data: &mut AppState, _env| {
+            if let Err(message) = logging::open_log() {
+                data.status_message = format!("Error: {}", message);
+            }
+        });
+    
+    // What the logs and the configuration folder may take, and what they do
+    let storage_limit_label = Label::new("Keep at Most:");
+    let storage_limit_input = TextBox::new()
+        .with_placeholder("100")
+        .lens(StorageLimitLens)
+        .fix_width(60.0);
+    let log_retention_label = Label::new("MB, Logs For:");
+    let log_retention_input = TextBox::new()
+        .with_placeholder("7")
+        .lens(LogRetentionLens)
+        .fix_width(50.0);
+    let storage_used = Label::dynamic(|data: &AppState, _env: &Env| {
+        if data.storage_used.is_empty() { "Storage used: …".to_string() } else { format!("Storage used: {}", data.storage_used) }
+    })
+        .with_text_size(appearance::SMALL_TEXT_SIZE)
+        .with_line_break_mode(LineBreaking::WordWrap);
+    let clean_up_button = Button::new("Clean Up Now")
+        .on_click(|_ctx, _data: &mut AppState, _env| {
+            thread::spawn(storage::prune_now);
+        });
+    
+    let menu_bar_checkbox = Checkbox::new("Live in the menu bar (hides this window at launch)")
+        .lens(MenuBarLens);
+    
+    let large_text_checkbox = Checkbox::new("Larger text")
+        .lens(LargeTextLens);
+    
+    let high_contrast_checkbox = Checkbox::new("High contrast")
+        .lens(HighContrastLens);
+    
+    let contacts_checkbox = Checkbox::new("Use Contacts for names and suggestions")
+        .lens(UseContactsLens);
+    
+    let spotlight_checkbox = Checkbox::new("Show called numbers in Spotlight")
+        .lens(SpotlightRecentsLens);
+    
+    let webhook_label = Label::new("Notify Webhook:");
+    let webhook_input = TextBox::new()
+        .with_placeholder("Slack or chat webhook URL (optional)")
+        .lens(WebhookUrlLens)
+        .expand_width();
+    
+    let directory_label = Label::new("Shared Directory:");
+    let directory_input = TextBox::new()
+        .with_placeholder("URL or file of the company phone book (optional)")
+        .lens(DirectoryUrlLens)
+        .expand_width();
+    
+    let corporate_directory_button = Button::new("LDAP/CardDAV…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.new_window(corporate_directory_window());
+        });
+    
+    let audit_webhook_label = Label::new("Audit Webhook:");
+    let audit_webhook_input = TextBox::new()
+        .with_placeholder("Receives every call attempt (optional)")
+        .lens(AuditWebhookUrlLens)
+        .expand_width();
+    
+    let audit_secret_label = Label::new("Audit Secret:");
+    let audit_secret_input = TextBox::new()
+        .with_placeholder("Signs audit requests (optional)")
+        .lens(AuditWebhookSecretLens)
+        .expand_width();
+    
+    let wake_grace_label = Label::new("After Wake, Wait:");
+    let wake_grace_input = TextBox::new()
+        .with_placeholder("Minutes before reminders go off (default 5)")
+        .lens(WakeGraceMinutesLens)
+        .expand_width();
+    
+    let summary_time_label = Label::new("Daily Summary At:");
+    let summary_time_input = TextBox::new()
+        .with_placeholder("HH:MM, e.g. 17:30 (optional)")
+        .lens(SummaryTimeLens)
+        .expand_width();
+    
+    let lookup_hotkey_label = Label::new("Look-up Hotkey:");
+    let lookup_hotkey_input = TextBox::new()
+        .with_placeholder("e.g. ctrl+alt+cmd+L")
+        .lens(LookupHotkeyLens)
+        .expand_width();
+    
+    let clipboard_hotkey_label = Label::new("Clipboard Hotkey:");
+    let clipboard_hotkey_input = TextBox::new()
+        .with_placeholder("e.g. ctrl+alt+cmd+V")
+        .lens(ClipboardHotkeyLens)
+        .expand_width();
+    let watch_clipboard_checkbox = Checkbox::new("Offer to call phone numbers when they're copied")
+        .lens(WatchClipboardLens);
+    
+    // Phone number input and call button
+    let phone_label = Label::new("Phone Number:");
+    let phone_input = TextBox::new()
+        .with_placeholder("Enter phone number")
+        .lens(PhoneNumberLens)
+        .expand_width();
+    let keypad_button = Button::new(|data: &AppState, _env: &Env| {
+        if data.show_keypad { "Hide Keypad" } else { "Keypad" }.to_string()
+    })
+    .on_click(|_ctx, data: &mut AppState, _env| {
+        data.show_keypad = !data.show_keypad;
+    });
+    
+    // The number as it reads in the configured country, or a warning if it doesn't fit
+    let dial_hint = Label::dynamic(|data: &AppState, _env: &Env| number_format::dial_hint(&data.phone_number, &data.country, &data.short_codes))
+        .with_text_size(appearance::SMALL_TEXT_SIZE)
+        .with_line_break_mode(LineBreaking::WordWrap);
+    
+    // Status message to show feedback
+    let status = Label::dynamic(|message: &String, _env: &Env| message.clone())
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .lens(StatusMessageLens);
+    
+    // Save button
+    let save_button = Button::new("Save Settings")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            data.status_message = match save_settings(data, ctx.get_external_handle()) {
+                Ok(Some(warning)) => format!("Settings saved, but {}", warning),
+                Ok(None) => "Settings saved successfully!".to_string(),
+                Err(message) => {
+                    ctx.new_window(alert_window("Settings Not Saved", &message));
+                    format!("Settings not saved: {}", message)
+                }
+            };
+        });
+    
+    let delete_profile_button = Button::new("Delete")
+        .on_click(|_ctx, data: &mut AppState, _env| {
+            let name = data.profile.clone();
+            data.status_message = match profiles::remove(&name) {
+                Ok(()) => format!("Deleted profile {}; Save Settings keeps these settings as a profile again", name),
+                Err(message) => format!("Couldn't delete profile {}: {}", name, message),
+            };
+            data.profiles_revision += 1;
+        });
+    
+    // Saves what's in the form as a new profile and switches to it
+    let duplicate_profile_button = Button::new("Duplicate")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            let mut copy = profiles::Profile::from_state(data);
+            let name = if data.profile.trim().is_empty() { profiles::DEFAULT_PROFILE } else { data.profile.trim() };
+            copy.name = profiles::unique_name(&format!("{} copy", name));
+            let copy_name = copy.name.clone();
+            match profiles::store(copy) {
+                Ok(()) => {
+                    data.profiles_revision += 1;
+                    ctx.submit_command(SWITCH_PROFILE.with(copy_name));
+                }
+                Err(message) => data.status_message = format!("Couldn't duplicate profile {}: {}", name, message),
+            }
+        });
+    
+    let template_button = Button::new("New from Template…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.new_window(template_window());
+        });
+    
+    let export_settings_button = Button::new("Export Settings…")
+        .on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(EXPORT_SETTINGS));
+    let import_settings_button = Button::new("Import Settings…")
+        .on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(IMPORT_SETTINGS));
+    
+    let bug_report_button = Button::new("Export Bug Report…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(EXPORT_BUG_REPORT);
+        });
+    
+    let diagnostics_button = Button::new("Diagnostics…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.new_window(diagnostics_window());
+        });
+    
+    // Place Call button, which cancels the call instead while it's being placed
+    let place_call_button = Button::new(|data: &AppState, _env: &Env| {
+        if data.in_flight.is_some() { "Cancel" } else { "Place Call" }.to_string()
+    })
+        .on_click(|ctx, data: &mut AppState, _env| {
+            match data.in_flight.take() {
+                Some(call) => {
+                    call.cancel();
+                    data.status_message = "Cancelling call...".to_string();
+                }
+                None => ctx.submit_command(MAKE_CALL.with(CallOrigin::Dialer)),
+            }
+        });
+    
+    let history_button = Button::new("Recent Calls")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            ctx.new_window(history_window(data));
+        });
+    
+    let queue_button = Button::new("Queue")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            ctx.new_window(queue_window(data));
+        });
+    
+    let campaign_button = Button::new("Campaign")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            ctx.new_window(campaign_window(data));
+        });
+    
+    let directory_button = Button::new("Directory")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            ctx.new_window(directory_window(data));
+        });
+    
+    let stats_button = Button::new("Today's Calls")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            ctx.new_window(stats_window(data));
+        });
+
+    // Create the layout
+    let mut layout = Flex::column();
+    
+    if !kiosk {
+        layout.add_child(Flex::row().with_child(profile_label).with_flex_child(profile_input, 1.0).with_spacer(10.0).with_child(duplicate_profile_button).with_spacer(10.0).with_child(delete_profile_button));
+        layout.add_child(profile_switcher());
+        layout.add_spacer(5.0);
+        layout.add_child(Flex::row().with_child(template_button).with_spacer(10.0).with_child(export_settings_button).with_spacer(10.0).with_child(import_settings_button));
+        layout.add_spacer(10.0);
+        layout.add_child(
+            Flex::row()
+                .cross_axis_alignment(druid::widget::CrossAxisAlignment::End)
+                .with_child(backend_label)
+                .with_child(backend_picker)
+                .with_spacer(10.0)
+                .with_child(http_request_button),
+        );
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(domain_label).with_flex_child(domain_input, 1.0));
+        layout.add_spacer(5.0);
+        layout.add_child(Flex::row().with_child(fallback_domains_label).with_flex_child(fallback_domains_input, 1.0));
+        layout.add_spacer(5.0);
+        layout.add_child(srv_lookup_checkbox);
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(extension_label).with_flex_child(extension_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(key_label).with_flex_child(key_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(backend_username_label).with_flex_child(backend_username_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(backend_context_label).with_flex_child(backend_context_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(country_label).with_flex_child(country_input, 1.0));
+        layout.add_spacer(5.0);
+        layout.add_child(Flex::row().with_child(dial_e164_checkbox).with_spacer(10.0).with_child(dial_plan_button));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(routes_label).with_flex_child(routes_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(short_codes_label).with_flex_child(short_codes_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(
+            Flex::row()
+                .with_child(ringback_label)
+                .with_flex_child(ringback_input, 1.0)
+                .with_spacer(10.0)
+                .with_child(record_calls_checkbox)
+                .with_spacer(10.0)
+                .with_child(parameters_button),
+        );
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(caller_id_label).with_flex_child(caller_id_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(
+            Flex::row()
+                .with_child(caller_id_template_label)
+                .with_flex_child(caller_id_name_template_input, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(caller_id_number_template_input, 1.0),
+        );
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(request_timeout_label).with_flex_child(request_timeout_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(
+            Flex::row()
+                .with_child(retry_attempts_label)
+                .with_flex_child(retry_attempts_input, 1.0)
+                .with_spacer(10.0)
+                .with_child(retry_delay_label)
+                .with_flex_child(retry_delay_input, 1.0),
+        );
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(max_concurrent_calls_label).with_flex_child(max_concurrent_calls_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(ca_certificate_label).with_flex_child(ca_certificate_input, 1.0));
+        layout.add_spacer(5.0);
+        layout.add_child(accept_invalid_certificates_checkbox);
+        layout.add_spacer(10.0);
+        layout.add_child(
+            Flex::row()
+                .with_child(client_certificate_label)
+                .with_flex_child(client_certificate_input, 1.0)
+                .with_spacer(5.0)
+                .with_child(client_certificate_button),
+        );
+        layout.add_spacer(5.0);
+        layout.add_child(
+            Flex::row()
+                .with_child(client_key_label)
+                .with_flex_child(client_key_input, 1.0)
+                .with_spacer(5.0)
+                .with_child(client_key_button),
+        );
+        layout.add_spacer(5.0);
+        layout.add_child(Flex::row().with_child(client_certificate_passphrase_label).with_flex_child(client_certificate_passphrase_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(http_auth_label).with_child(http_auth_picker));
+        layout.add_spacer(5.0);
+        layout.add_child(
+            Flex::row()
+                .with_flex_child(http_auth_username_input, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(http_auth_password_input, 1.0),
+        );
+        layout.add_spacer(5.0);
+        layout.add_child(
+            Flex::row()
+                .with_flex_child(oauth_token_url_input, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(oauth_authorize_url_input, 1.0),
+        );
+        layout.add_spacer(5.0);
+        layout.add_child(
+            Flex::row()
+                .with_flex_child(oauth_scope_input, 1.0)
+                .with_spacer(10.0)
+                .with_child(oauth_sign_in_button),
+        );
+        layout.add_spacer(10.0);
+        layout.add_child(track_calls_checkbox);
+        layout.add_spacer(5.0);
+        layout.add_child(
+            Flex::row()
+                .with_flex_child(event_socket_input, 1.0)
+                .with_spacer(10.0)
+                .with_flex_child(event_socket_password_input, 1.0),
+        );
+        layout.add_spacer(10.0);
+        layout.add_child(show_presence_checkbox);
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(show_voicemail_checkbox).with_spacer(10.0).with_flex_child(voicemail_number_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(color_label).with_child(color_picker));
+        layout.add_spacer(10.0);
+        layout.add_child(auto_answer_checkbox);
+        layout.add_spacer(5.0);
+        layout.add_child(Flex::row().with_child(auto_answer_override_label).with_child(auto_answer_override_picker));
+        layout.add_spacer(10.0);
+        layout.add_child(keep_alive_checkbox);
+        layout.add_spacer(10.0);
+        layout.add_child(health_check_checkbox);
+        layout.add_spacer(10.0);
+        // Integrations left out of this build have nothing to switch on
+        if cfg!(feature = "contacts") {
+            layout.add_child(contacts_checkbox);
+            layout.add_spacer(10.0);
+        }
+        if cfg!(feature = "spotlight") {
+            layout.add_child(spotlight_checkbox);
+            layout.add_spacer(10.0);
+        }
+        layout.add_child(menu_bar_checkbox);
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(large_text_checkbox).with_spacer(10.0).with_child(high_contrast_checkbox));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(confirm_tel_links_checkbox).with_spacer(10.0).with_child(number_lists_button));
+        layout.add_spacer(10.0);
+        layout.add_child(ask_call_options_checkbox);
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row()
+            .with_child(duplicate_window_label)
+            .with_flex_child(duplicate_window_input, 1.0)
+            .with_spacer(10.0)
+            .with_child(notify_duplicate_calls_checkbox));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(tel_handler_label).with_spacer(10.0).with_child(tel_handler_button));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(link_rules_label).with_flex_child(link_rules_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row()
+            .with_child(http_api_label)
+            .with_child(http_api_port_input)
+            .with_spacer(6.0)
+            .with_flex_child(http_api_token_input, 1.0)
+            .with_spacer(6.0)
+            .with_child(new_token_button));
+        layout.add_spacer(10.0);
+        layout.add_child(capture_transcripts_checkbox);
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(debug_logging_checkbox).with_spacer(10.0).with_child(open_log_button));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row()
+            .with_child(storage_limit_label)
+            .with_child(storage_limit_input)
+            .with_spacer(6.0)
+            .with_child(log_retention_label)
+            .with_child(log_retention_input)
+            .with_spacer(6.0)
+            .with_child(Label::new("days"))
+            .with_spacer(10.0)
+            .with_child(clean_up_button));
+        layout.add_spacer(5.0);
+        layout.add_child(storage_used);
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(webhook_label).with_flex_child(webhook_input, 1.0));
+        layout.add_spacer(10.0);
+        let mut directory_row = Flex::row().with_child(directory_label).with_flex_child(directory_input, 1.0);
+        if cfg!(feature = "corporate-directory") {
+            directory_row.add_spacer(10.0);
+            directory_row.add_child(corporate_directory_button);
+        }
+        layout.add_child(directory_row);
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(audit_webhook_label).with_flex_child(audit_webhook_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(audit_secret_label).with_flex_child(audit_secret_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(summary_time_label).with_flex_child(summary_time_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(Flex::row().with_child(wake_grace_label).with_flex_child(wake_grace_input, 1.0));
+        layout.add_spacer(10.0);
+        if cfg!(feature = "screen-lookup") {
+            layout.add_child(Flex::row().with_child(lookup_hotkey_label).with_flex_child(lookup_hotkey_input, 1.0));
+            layout.add_spacer(10.0);
+        }
+        layout.add_child(Flex::row().with_child(clipboard_hotkey_label).with_flex_child(clipboard_hotkey_input, 1.0));
+        layout.add_spacer(10.0);
+        layout.add_child(watch_clipboard_checkbox);
+        layout.add_spacer(20.0);
+        layout.add_child(Wrap::new(10.0).with_child(save_button).with_child(test_connection_button).with_child(bug_report_button).with_child(diagnostics_button));
+        layout.add_spacer(20.0);
+    }
+    
+    // Shared kiosk machines only ever call one number at a time
+    let mut call_buttons = Wrap::new(10.0).with_child(place_call_button).with_child(history_button).with_child(directory_button).with_child(queue_button);
+    if !kiosk {
+        call_buttons = call_buttons.with_child(campaign_button);
+    }
+    let call_buttons = call_buttons.with_child(stats_button).with_child(voicemail_button());
+    
+    let layout = layout
+        .with_child(profile_color::identity_badge())
+        .with_spacer(10.0)
+        .with_child(favorite_buttons())
+        .with_child(frequent_numbers())
+        .with_child(Flex::row().with_child(phone_label).with_flex_child(phone_input, 1.0).with_spacer(6.0).with_child(keypad_button))
+        .with_child(dial_hint)
+        .with_child(druid::widget::Either::new(
+            |data: &AppState, _env| data.show_keypad,
+            keypad::keypad().padding((0.0, 6.0, 0.0, 0.0)),
+            druid::widget::SizedBox::empty(),
+        ))
+        .with_child(route_picker())
+        .with_spacer(5.0)
+        .with_child(private_call_checkbox())
+        .with_child(contact_suggestions())
+        .with_spacer(10.0)
+        .with_child(call_buttons)
+        .with_spacer(10.0)
+        .with_child(Flex::row().with_flex_child(status, 1.0).with_child(number_actions()))
+        .with_child(active_calls_panel());
+    
+    // Larger text can make the window taller than the screen, so it scrolls
+    appearance::scoped(Scroll::new(layout.padding(20.0)).vertical().expand())
+        .controller(HideOnClose)
+}
+
+// Calls the PBX is placing, each with a Hang Up button; empty while there are none
+fn active_calls_panel() -> impl Widget<AppState> {
+    druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| data.active_calls_revision,
+        |_revision, _data, _env| {
+            let mut panel = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+            let calls = active_calls::list();
+            if !calls.is_empty() {
+                panel.add_spacer(10.0);
+                panel.add_child(Label::new("Active Calls").with_font(druid::theme::UI_FONT_BOLD));
+            }
+            for call in calls {
+                let details = Label::new(format!("{}, since {}", call.callee, call.started.format("%H:%M")))
+                    .with_line_break_mode(LineBreaking::WordWrap);
+                let call_id = call.call_id.clone();
+                let hang_up_button = Button::new("Hang Up")
+                    .on_click(move |ctx, data: &mut AppState, _env| {
+                        data.status_message = "Hanging up...".to_string();
+                        let call_id = call_id.clone();
+                        let event_sink = ctx.get_external_handle();
+                        runtime().spawn(async move {
+                            let message = active_calls::hang_up(&call_id).await.unwrap_or_else(|message| message);
+                            event_sink.add_idle_callback(move |data: &mut AppState| {
+                                data.status_message = message;
+                            });
+                        });
+                    });
+                let call_id = call.call_id.clone();
+                let dismiss_button = Button::new("Dismiss")
+                    .on_click(move |_ctx, _data: &mut AppState, _env| active_calls::dismiss(&call_id));
+                
+                panel.add_spacer(6.0);
+                panel.add_child(Flex::row()
+                    .with_flex_child(details, 1.0)
+                    .with_child(hang_up_button)
+                    .with_spacer(6.0)
+                    .with_child(dismiss_button));
+            }
+            Box::new(panel)
+        },
+    )
+}
+
+// Which trunk the next call goes out on, when the profile has routes set up
+fn route_picker() -> impl Widget<AppState> {
+    druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| data.routes.clone(),
+        |routes, _data, _env| {
+            let routes = dial_plan::parse_routes(routes).unwrap_or_default();
+            if routes.is_empty() {
+                return Box::new(Flex::column());
+            }
+            
+            let mut options = vec![("Default route".to_string(), String::new())];
+            options.extend(routes.into_iter().map(|route| (format!("{} ({})", route.label, route.prefix), route.prefix)));
+            let picker = RadioGroup::column(options).lens(RouteLens);
+            Box::new(Flex::column()
+                .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+                .with_spacer(5.0)
+                .with_child(Label::new("Route:"))
+                .with_child(picker))
+        },
+    )
+}
+
+// Marks the next call private, for numbers that shouldn't be kept anywhere
+fn private_call_checkbox() -> impl Widget<AppState> {
+    Checkbox::new("Private call (no history or webhooks)")
+        .lens(PrivateCallLens)
+}
+
+// Calls voicemail, with how many new messages there are; only while they're counted
+fn voicemail_button() -> impl Widget<AppState> {
+    let button = Button::new(|data: &AppState, _env: &Env| match data.new_voicemail {
+        0 => "Voicemail".to_string(),
+        count => format!("Voicemail ({} new)", count),
+    })
+    .on_click(|ctx, data: &mut AppState, _env| {
+        data.phone_number = voicemail::number(data);
+        ctx.submit_command(MAKE_CALL.with(CallOrigin::Dialer));
+    });
+    druid::widget::Either::new(
+        |data: &AppState, _env| data.show_voicemail,
+        button,
+        druid::widget::SizedBox::empty(),
+    )
+}
+
+// Copy and Share for the number just dialed, next to the status line
+fn number_actions() -> impl Widget<AppState> {
+    let copy_button = Button::new("Copy")
+        .on_click(|_ctx, data: &mut AppState, _env| {
+            clipboard_watch::copied_here();
+            druid::Application::global().clipboard().put_string(&data.last_dialed);
+            data.status_message = format!("Copied {}", data.last_dialed);
+        });
+    let share_button = Button::new("Share…")
+        .on_click(|_ctx, data: &mut AppState, _env| {
+            if let Err(e) = share::share_text(&data.last_dialed) {
+                data.status_message = format!("Couldn't share {}: {}", data.last_dialed, e);
+            }
+        });
+    druid::widget::Either::new(
+        |data: &AppState, _env| data.last_dialed.is_empty(),
+        druid::widget::SizedBox::empty(),
+        Flex::row().with_spacer(6.0).with_child(copy_button).with_spacer(6.0).with_child(share_button),
+    )
+}
+
+// Buttons for switching to the other saved profiles
+fn profile_switcher() -> impl Widget<AppState> {
+    druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.profiles_revision, data.profile.clone()),
+        |(_revision, active), _data, _env| {
+            let mut row = Flex::row();
+            let others: Vec<_> = profiles::load().into_iter().filter(|profile| profile.name != *active).collect();
+            if !others.is_empty() {
+                row.add_child(Label::new("Switch to:"));
+                for profile in others {
+                    row.add_spacer(6.0);
+                    row.add_child(Button::new(profile.name.clone()).on_click(move |ctx, _data: &mut AppState, _env| {
+                        ctx.submit_command(SWITCH_PROFILE.with(profile.name.clone()));
+                    }));
+                }
+            }
+            Box::new(row.padding((0.0, 5.0, 0.0, 0.0)))
+        },
+    )
+}
+
+// A button that calls `favorite`, shortened to fit a row of them; in the
+// popover it closes the window as well
+fn favorite_button(favorite: &favorites::Favorite, close_window: bool) -> impl Widget<AppState> {
+    let number = favorite.number.clone();
+    let mut label = favorite.label.clone();
+    if label.chars().count() > 18 {
+        label = format!("{}…", label.chars().take(17).collect::<String>());
+    }
+    Button::new(label)
+        .on_click(move |ctx, data: &mut AppState, _env| {
+            data.phone_number = number.clone();
+            ctx.submit_command(MAKE_CALL.with(CallOrigin::Favorite));
+            if close_window {
+                ctx.window().close();
+            }
+        })
+}
+
+// The favorites as a row of buttons, with one to edit them
+fn favorite_buttons() -> impl Widget<AppState> {
+    druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.favorites.clone(), data.kiosk),
+        |(favorites, kiosk), _data, _env| {
+            // Ones that don't parse were refused when saving
+            let favorites = favorites::parse(favorites).unwrap_or_default();
+            let mut row = Wrap::new(6.0);
+            for favorite in &favorites {
+                row = row.with_child(favorite_button(favorite, false));
+            }
+            // Kiosk settings are the administrator's
+            if !*kiosk {
+                let edit = if favorites.is_empty() { "Add Favorites…" } else { "Edit…" };
+                row = row.with_child(Button::new(edit).on_click(|ctx, _data: &mut AppState, _env| {
+                    ctx.new_window(favorites_window());
+                }));
+            }
+            if favorites.is_empty() && *kiosk {
+                Box::new(Flex::row())
+            } else {
+                Box::new(Flex::column().with_child(row).with_spacer(10.0))
+            }
+        },
+    )
+}
+
+// One-click chips for the numbers called most often, worked out from the history
+fn frequent_numbers() -> impl Widget<AppState> {
+    druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.history_revision, data.country.clone()),
+        |(_revision, country), _data, _env| {
+            let frequent = history::frequent_numbers(FREQUENT_NUMBERS);
+            let mut row = Flex::row();
+            for (number, name) in &frequent {
+                let number = number.clone();
+                // Keep five chips within the window's width
+                let mut label = name.clone().unwrap_or_else(|| number_format::format_number(&number, country));
+                if label.chars().count() > 14 {
+                    label = format!("{}…", label.chars().take(13).collect::<String>());
+                }
+                let button = Button::new(label)
+                    .on_click(move |ctx, data: &mut AppState, _env| {
+                        data.phone_number = number.clone();
+                        ctx.submit_command(MAKE_CALL.with(CallOrigin::History));
+                    });
+                row.add_child(button);
+                row.add_spacer(6.0);
+            }
+            
+            if frequent.is_empty() {
+                Box::new(row)
+            } else {
+                Box::new(Flex::column().with_child(row).with_spacer(10.0))
+            }
+        },
+    )
+}
+
+// Contacts matching what's typed in the phone number field; picking one fills in its number
+fn contact_suggestions() -> impl Widget<AppState> {
+    druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.use_contacts, data.directory_revision, data.phone_number.clone(), data.country.clone()),
+        |(use_contacts, _revision, query, country), _data, _env| {
+            let mut list = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+            let mut suggested = Vec::new();
+            if *use_contacts {
+                for suggestion in contacts::suggestions(query) {
+                    suggested.push((suggestion.name, suggestion.number));
+                }
+            }
+            // The shared and corporate directories' numbers, unless Contacts already offered them
+            for entry in directory::suggestions(query).into_iter().chain(corporate_directory::suggestions(query)) {
+                let known = suggested.iter().any(|(_, number)| contacts::same_number(&contacts::digits(number), &contacts::digits(&entry.number)));
+                if !known && suggested.len() < contacts::MAX_SUGGESTIONS {
+                    suggested.push((entry.name, entry.number));
+                }
+            }
+            
+            for (name, number) in suggested {
+                let label = format!("{} – {}", name, number_format::format_number(&number, country));
+                let number = contacts::dialable(&number);
+                let button = Button::new(label)
+                    .on_click(move |_ctx, data: &mut AppState, _env| {
+                        data.phone_number = number.clone();
+                    });
+                list.add_spacer(4.0);
+                list.add_child(button);
+            }
+            Box::new(list)
+        },
+    )
+}
+
+const CALLER_ID_PLACEHOLDER: &str = "Number to show, e.g. +15551234567 (PBX default if empty)";
+
+// Small always-on-top dialog to choose recording, ringback and caller ID
+// before a call, filled in with the ones last used with this profile
+fn call_options_window(state: &AppState, origin: CallOrigin) -> WindowDesc<AppState> {
+    let prompt = Label::new(format!("Call {}", state.phone_number.trim()))
+        .with_line_break_mode(LineBreaking::WordWrap);
+    
+    let ringback_input = TextBox::new()
+        .with_placeholder("Ringback tone, e.g. uk-ring (by destination if empty)")
+        .lens(RingbackLens)
+        .expand_width();
+    let caller_id_input = TextBox::new()
+        .with_placeholder(CALLER_ID_PLACEHOLDER)
+        .lens(CallerIdLens)
+        .expand_width();
+    
+    let call_button = Button::new("Call")
+        .on_click(move |ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(CALL_WITH_OPTIONS.with(origin));
+            ctx.window().close();
+        });
+    
+    // Cancel leaves the options as they were
+    let last_used = (state.record_calls, state.ringback.clone(), state.caller_id.clone());
+    let cancel_button = Button::new("Cancel")
+        .on_click(move |ctx, data: &mut AppState, _env| {
+            (data.record_calls, data.ringback, data.caller_id) = last_used.clone();
+            ctx.window().close();
+        });
+    
+    let layout = Flex::column()
+        .with_child(prompt)
+        .with_spacer(5.0)
+        .with_child(profile_color::identity_badge())
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Record this call").lens(RecordCallsLens))
+        .with_spacer(10.0)
+        .with_child(ringback_input)
+        .with_spacer(10.0)
+        .with_child(caller_id_input)
+        .with_spacer(15.0)
+        .with_child(Wrap::new(10.0).with_child(cancel_button).with_child(call_button))
+        .padding(15.0);
+    
+    WindowDesc::new(appearance::scoped(layout))
+        .title("Call Options")
+        .window_size(appearance::window_size(360.0, 260.0, state))
+        .resizable(false)
+        .set_always_on_top(true)
+}
+
+// Small always-on-top prompt asking whether to call a number, named with
+// the contact it belongs to if it's known
+fn confirmation_window(number: String, state: &AppState, origin: CallOrigin) -> WindowDesc<AppState> {
+    let prompt = Label::new(match contact_name(state, &number) {
+        Some(name) => format!("Call {} ({})?", name, number),
+        None => format!("Call {}?", number),
+    })
+    .with_line_break_mode(LineBreaking::WordWrap);
+    
+    let note_input = TextBox::new()
+        .with_placeholder("Reason or note (optional)")
+        .lens(CallNoteLens)
+        .expand_width();
+    
+    let call_button = Button::new("Call")
+        .on_click(move |ctx, data: &mut AppState, _env| {
+            data.phone_number = number.clone();
+            ctx.submit_command(MAKE_CALL.with(origin));
+            ctx.window().close();
+        });
+    
+    let cancel_button = Button::new("Cancel")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            data.call_note.clear();
+            data.private_call = false;
+            ctx.window().close();
+        });
+    
+    let layout = Flex::column()
+        .with_child(prompt)
+        .with_spacer(5.0)
+        .with_child(profile_color::identity_badge())
+        .with_spacer(10.0)
+        .with_child(note_input)
+        .with_spacer(10.0)
+        .with_child(private_call_checkbox())
+        .with_spacer(15.0)
+        .with_child(Wrap::new(10.0).with_child(cancel_button).with_child(call_button))
+        .padding(15.0);
+    
+    WindowDesc::new(appearance::scoped(layout))
+        .title("Confirm Call")
+        .window_size(appearance::window_size(300.0, 240.0, state))
+        .resizable(false)
+        .set_always_on_top(true)
+}
+
+// Small dialer shown below the menu bar icon: a number field, the numbers
+// called recently and a Place Call button
+fn dial_popover(icon_bounds: Option<Rect>, state: &AppState) -> WindowDesc<AppState> {
+    const WIDTH: f64 = 260.0;
+    
+    let phone_input = TextBox::new()
+        .with_placeholder("Enter phone number")
+        .lens(PhoneNumberLens)
+        .expand_width();
+    
+    let place_call_button = Button::new("Place Call")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(MAKE_CALL.with(CallOrigin::MenuBar));
+            ctx.window().close();
+        });
+    
+    let settings_button = Button::new("Settings…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(SHOW_SETTINGS);
+            ctx.window().close();
+        });
+    
+    let quit_button = Button::new("Quit")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(druid::commands::QUIT_APP);
+        });
+    
+    let clipboard_watch_button = Button::new(|data: &AppState, _env: &Env| {
+        if data.watch_clipboard { "Stop Watching Clipboard" } else { "Watch Clipboard" }.to_string()
+    })
+    .on_click(|ctx, _data: &mut AppState, _env| {
+        ctx.submit_command(TOGGLE_CLIPBOARD_WATCH);
+    });
+    
+    let mut layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(profile_color::identity_badge())
+        .with_spacer(10.0)
+        .with_child(phone_input)
+        .with_child(route_picker())
+        .with_spacer(5.0)
+        .with_child(private_call_checkbox())
+        .with_spacer(10.0);
+    
+    // One-click redial of the numbers called last
+    let recent = history::recent_numbers(POPOVER_RECENT_NUMBERS);
+    if !recent.is_empty() {
+        layout.add_child(Label::new("Recent:"));
+        layout.add_spacer(5.0);
+        for number in &recent {
+            let number = number.clone();
+            let button = Button::new(number_format::format_number(&number, &state.country))
+                .on_click(move |ctx, data: &mut AppState, _env| {
+                    data.phone_number = number.clone();
+                    ctx.submit_command(MAKE_CALL.with(CallOrigin::MenuBar));
+                    ctx.window().close();
+                });
+            layout.add_child(button);
+            layout.add_spacer(5.0);
+        }
+        layout.add_spacer(5.0);
+    }
+    
+    // The numbers dialed all day, a click away
+    let favorites = favorites::parse(&state.favorites).unwrap_or_default();
+    if !favorites.is_empty() {
+        layout.add_child(Label::new("Favorites:"));
+        layout.add_spacer(5.0);
+        let mut row = Wrap::new(6.0);
+        for favorite in &favorites {
+            row = row.with_child(favorite_button(favorite, true));
+        }
+        layout.add_child(row);
+        layout.add_spacer(10.0);
+    }
+    
+    // Switching tenants without opening the settings
+    let other_profiles: Vec<_> = profiles::load().into_iter().filter(|profile| profile.name != state.profile).collect();
+    if !other_profiles.is_empty() {
+        let mut row = Flex::row().with_child(Label::new("Switch to:"));
+        for profile in &other_profiles {
+            let name = profile.name.clone();
+            row.add_spacer(6.0);
+            row.add_child(Button::new(name.clone()).on_click(move |ctx, _data: &mut AppState, _env| {
+                ctx.submit_command(SWITCH_PROFILE.with(name.clone()));
+                ctx.window().close();
+            }));
+        }
+        layout.add_child(row);
+        layout.add_spacer(10.0);
+    }
+    
+    layout.add_child(clipboard_watch_button);
+    layout.add_spacer(10.0);
+    layout.add_child(Wrap::new(10.0)
+        .with_child(place_call_button)
+        .with_child(settings_button)
+        .with_child(quit_button));
+    
+    let routes = dial_plan::parse_routes(&state.routes).unwrap_or_default().len();
+    let height = 210.0
+        + if routes == 0 { 0.0 } else { 45.0 + routes as f64 * 24.0 }
+        + recent.len() as f64 * 35.0
+        + if recent.is_empty() { 0.0 } else { 30.0 }
+        + if favorites.is_empty() { 0.0 } else { 30.0 + favorites.len().div_ceil(2) as f64 * 35.0 }
+        + if other_profiles.is_empty() { 0.0 } else { 40.0 };
+    let size = appearance::window_size(WIDTH, height, state);
+    let mut window = WindowDesc::new(appearance::scoped(layout.padding(12.0).expand()))
+        .title(identity().name.clone())
+        .window_size(size)
+        .show_titlebar(false)
+        .resizable(false)
+        .set_always_on_top(true);
+    
+    // Hang it below the icon, right edges lined up, like a native popover
+    if let Some(bounds) = icon_bounds {
+        window = window.set_position(((bounds.x1 - size.0).max(0.0), bounds.y1 + 4.0));
+    }
+    window
+}
+
+// What was sent to the PBX and what it answered, for a failed call in the history
+fn transcript_window(transcript: &str) -> WindowDesc<AppState> {
+    let text = Label::new(transcript.to_string())
+        .with_font(druid::FontDescriptor::new(druid::FontFamily::MONOSPACE).with_size(11.0))
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    let scroll = druid::widget::Scroll::new(text.padding(10.0)).vertical();
+    
+    WindowDesc::new(scroll.expand())
+        .title("Call Details")
+        .window_size((560.0, 420.0))
+}
+
+// Editor for the rewrite rules, previewing what the number in the dialer
+// would be sent as. Saved with the other settings.
+fn dial_plan_window() -> WindowDesc<AppState> {
+    let help = Label::new("One rule per line: pattern => replacement. Patterns are regular expressions; $1, $2, ... in the replacement insert their groups. Add \"stop\" after the replacement to skip the rules below when one matches, e.g. ^\\+61 => 0 stop")
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let rules_input = TextBox::multiline()
+        .with_placeholder("^\\+61 => 0 stop")
+        .with_font(druid::FontDescriptor::new(druid::FontFamily::MONOSPACE).with_size(12.0))
+        .lens(DialPlanLens)
+        .expand_width()
+        .fix_height(140.0);
+    
+    let preview = Label::dynamic(|data: &AppState, _env: &Env| {
+        if data.phone_number.trim().is_empty() {
+            return "Type a number in the dialer to try the rules".to_string();
+        }
+        match pbx_number(&data.phone_number, data) {
+            Ok(dialed) => format!("{} is sent to the PBX as {}", data.phone_number, dialed),
+            Err(e) => e,
+        }
+    })
+    .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let close_button = Button::new("Close")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+    
+    let layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(help)
+        .with_spacer(10.0)
+        .with_child(rules_input)
+        .with_spacer(10.0)
+        .with_child(preview)
+        .with_spacer(15.0)
+        .with_child(close_button)
+        .padding(20.0);
+    
+    WindowDesc::new(layout)
+        .title("Rewrite Rules")
+        .window_size((420.0, 360.0))
+}
+
+// Edit what's sent to FusionPBX's click_to_call.php, with the request a call
+// to the number in the dialer would make
+fn parameters_window() -> WindowDesc<AppState> {
+    let help = Label::new("What FusionPBX's click_to_call.php is sent, for versions that want other parameters. One per line: name = value sets or adds one, and -name leaves it out, e.g. timeout = 60 or context = default. The other PBXs don't use these.")
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let parameters_input = TextBox::multiline()
+        .with_placeholder("timeout = 60")
+        .with_font(druid::FontDescriptor::new(druid::FontFamily::MONOSPACE).with_size(12.0))
+        .lens(ClickToCallParametersLens)
+        .expand_width()
+        .fix_height(120.0);
+    
+    let preview = Label::dynamic(|data: &AppState, _env: &Env| {
+        let changes = match backend::parse_parameters(&data.click_to_call_parameters) {
+            Ok(changes) => changes,
+            Err(e) => return e,
+        };
+        let number = match data.phone_number.trim() {
+            "" => "5551234567",
+            number => number,
+        };
+        let request = backend::CallRequest {
+            extension: &data.extension,
+            number,
+            auto_answer: data.auto_answer,
+            ringback: &data.ringback,
+            record: data.record_calls,
+            caller_id: None,
+            display_name: None,
+            display_number: None,
+            parameters: "",
+            post_dial: None,
+        };
+        let sent: Vec<String> = backend::click_to_call_parameters(&request, "…", &changes)
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        format!("A call to {} sends {}", number, sent.join("&"))
+    })
+    .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let close_button = Button::new("Close")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+    
+    let layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(help)
+        .with_spacer(10.0)
+        .with_child(parameters_input)
+        .with_spacer(10.0)
+        .with_child(preview)
+        .with_spacer(15.0)
+        .with_child(close_button)
+        .padding(20.0);
+    
+    WindowDesc::new(layout)
+        .title("click_to_call Parameters")
+        .window_size((440.0, 380.0))
+}
+
+// Write the request the Generic HTTP backend sends, with a check of it
+fn http_request_window() -> WindowDesc<AppState> {
+    let help = Label::new("The request the Generic HTTP backend sends for a call: the method and URL on the first line, headers below it, then a blank line and the body. {number}, {extension}, {key}, {username}, {domain}, {auto_answer} and {caller_id} are filled in. A URL that's only a path goes to the Domain.")
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let request_input = TextBox::multiline()
+        .with_placeholder("POST /api/call\nContent-Type: application/json\n\n{\"from\": \"{extension}\", \"to\": \"{number}\"}")
+        .with_font(druid::FontDescriptor::new(druid::FontFamily::MONOSPACE).with_size(12.0))
+        .lens(HttpRequestLens)
+        .expand_width()
+        .fix_height(160.0);
+    
+    let check = Label::dynamic(|data: &AppState, _env: &Env| {
+        if data.http_request.trim().is_empty() {
+            return String::new();
+        }
+        match backend::parse_http_request(&data.http_request) {
+            Ok(_) => "The request is written correctly".to_string(),
+            Err(e) => e,
+        }
+    })
+    .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let close_button = Button::new("Close")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+    
+    let layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(help)
+        .with_spacer(10.0)
+        .with_child(request_input)
+        .with_spacer(10.0)
+        .with_child(check)
+        .with_spacer(15.0)
+        .with_child(close_button)
+        .padding(20.0);
+    
+    WindowDesc::new(layout)
+        .title("HTTP Request")
+        .window_size((480.0, 420.0))
+}
+
+// Edit the numbers calls may and mayn't go to, with a check of the number in the dialer
+fn number_lists_window() -> WindowDesc<AppState> {
+    let help = Label::new("One entry per line: a whole number, a prefix with * after it, or a regular expression between slashes. Numbers are also checked in +<country code> form. A non-empty allow list blocks everything it doesn't have; the block list wins over it.")
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let allowed_input = TextBox::multiline()
+        .with_placeholder("+1555*")
+        .with_font(druid::FontDescriptor::new(druid::FontFamily::MONOSPACE).with_size(12.0))
+        .lens(AllowedNumbersLens)
+        .expand_width()
+        .fix_height(90.0);
+    
+    let blocked_input = TextBox::multiline()
+        .with_placeholder("+1900*\n/^\\+44(9|87)/")
+        .with_font(druid::FontDescriptor::new(druid::FontFamily::MONOSPACE).with_size(12.0))
+        .lens(BlockedNumbersLens)
+        .expand_width()
+        .fix_height(90.0);
+    
+    let preview = Label::dynamic(|data: &AppState, _env: &Env| {
+        if data.phone_number.trim().is_empty() {
+            return "Type a number in the dialer to try the lists".to_string();
+        }
+        match number_lists::check(&data.phone_number, &data.country, &data.short_codes, &data.allowed_numbers, &data.blocked_numbers) {
+            Ok(()) => format!("{} can be called", data.phone_number),
+            Err(reason) => format!("Blocked: {}", reason),
+        }
+    })
+    .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let close_button = Button::new("Close")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+    
+    let layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(help)
+        .with_spacer(10.0)
+        .with_child(Label::new("Allow only:"))
+        .with_child(allowed_input)
+        .with_spacer(10.0)
+        .with_child(Label::new("Never call:"))
+        .with_child(blocked_input)
+        .with_spacer(10.0)
+        .with_child(preview)
+        .with_spacer(15.0)
+        .with_child(close_button)
+        .padding(20.0);
+    
+    WindowDesc::new(layout)
+        .title("Allow & Block Lists")
+        .window_size((420.0, 460.0))
+}
+
+fn favorites_window() -> WindowDesc<AppState> {
+    let help = Label::new(format!("One favorite per line, written as label = number. Each gets a button in this window and the menu bar popover, up to {}.", favorites::MAX_FAVORITES))
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let favorites_input = TextBox::multiline()
+        .with_placeholder("Front desk = 201\nHead office = +44 20 7946 0958")
+        .lens(FavoritesLens)
+        .expand_width()
+        .fix_height(180.0);
+    
+    let save_button = Button::new("Save")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            match save_settings(data, ctx.get_external_handle()) {
+                Ok(warning) => {
+                    data.status_message = match warning {
+                        Some(warning) => format!("Favorites saved, but {}", warning),
+                        None => "Favorites saved".to_string(),
+                    };
+                    ctx.window().close();
+                }
+                Err(message) => data.status_message = format!("Favorites not saved: {}", message),
+            }
+        });
+    
+    let status = Label::dynamic(|data: &AppState, _env: &Env| data.status_message.clone())
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+    
+    let layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(help)
+        .with_spacer(10.0)
+        .with_child(favorites_input)
+        .with_spacer(10.0)
+        .with_child(status)
+        .with_spacer(15.0)
+        .with_child(save_button)
+        .padding(20.0);
+    
+    WindowDesc::new(layout)
+        .title("Favorites")
+        .window_size((420.0, 400.0))
+}
+
+// Where customer numbers are looked up besides Contacts and the shared directory
+fn corporate_directory_window() -> WindowDesc<AppState> {
+    let help = Label::new("Names and suggestions for numbers that aren't in Contacts are looked up on an LDAP server (ldap:// or ldaps://) or in a CardDAV address book (https://). The password is kept in the Keychain. Changes take effect with Save Settings.")
+        .with_line_break_mode(druid::widget::LineBreaking::WordWrap);
+
+    let url_input = TextBox::new()
+        .with_placeholder("ldaps://ldap.example.com or https://dav.example.com/addressbooks/sales/")
+        .lens(CorporateDirectoryUrlLens)
+        .expand_width();
+
+    let user_input = TextBox::new()
+        .with_placeholder("Bind DN or user name (empty for anonymous)")
+        .lens(CorporateDirectoryUserLens)
+        .expand_width();
+
+    let password_input = TextBox::new()
+        .with_placeholder("Password")
+        .lens(CorporateDirectoryPasswordLens)
+        .expand_width();
+
+    let base_input = TextBox::new()
+        .with_placeholder("LDAP only, e.g. ou=Customers,dc=example,dc=com")
+        .lens(CorporateDirectoryBaseLens)
+        .expand_width();
+
+    let close_button = Button::new("Close")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+
+    let layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(help)
+        .with_spacer(10.0)
+        .with_child(Label::new("Server:"))
+        .with_child(url_input)
+        .with_spacer(10.0)
+        .with_child(Label::new("User:"))
+        .with_child(user_input)
+        .with_spacer(10.0)
+        .with_child(Label::new("Password:"))
+        .with_child(password_input)
+        .with_spacer(10.0)
+        .with_child(Label::new("Search Base:"))
+        .with_child(base_input)
+        .with_spacer(15.0)
+        .with_child(close_button)
+        .padding(20.0);
+
+    WindowDesc::new(layout)
+        .title("Corporate Directory")
+        .window_size((460.0, 420.0))
+}
+
+// Pick a PBX to start a new profile for; it's saved once the rest is filled in
+// Test Connection: logs in to the PBX with what's in the form, saved or not,
+// and puts what `describe` makes of the result in the status line
+fn test_connection(ctx: &mut EventCtx, data: &mut AppState, describe: fn(health::Health, &str) -> String) {
+    let target = health::Target::from_state(data);
+    let domain = data.domain.clone();
+    data.status_message = format!("Testing the connection to {}...", domain);
+    let event_sink = ctx.get_external_handle();
+    runtime().spawn(async move {
+        let summary = describe(health::check(&target).await, &domain);
+        event_sink.add_idle_callback(move |data: &mut AppState| {
+            data.status_message = summary;
+        });
+    });
+}
+
+// Save Settings: check what's in the form, save it to the preferences and the
+// active profile, and put it into effect. Returns a warning for any setting
+// that couldn't be applied, or why nothing was saved.
+fn save_settings(data: &mut AppState, event_sink: druid::ExtEventSink) -> Result<Option<&'static str>, String> {
+    if data.profile.trim().is_empty() {
+        data.profile = profiles::DEFAULT_PROFILE.to_string();
+    }
+    
+    // Don't pretend the settings stuck if they couldn't be written
+    if let Err(message) = config::check(data)
+        .and_then(|_| save_preferences(data)).and_then(|_| profiles::store(profiles::Profile::from_state(data))) {
+        warn!("Failed to save settings: {}", message);
+        return Err(message);
+    }
+    data.profiles_revision += 1;
+    Ok(apply_settings(data, event_sink))
+}
+
+fn template_window() -> WindowDesc<AppState> {
+    let mut layout = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+    for (index, template) in profiles::TEMPLATES.iter().enumerate() {
+        if index > 0 {
+            layout.add_spacer(8.0);
+        }
+        let button = Button::new(template.name).on_click(move |ctx, data: &mut AppState, _env| {
+            let name = profiles::unique_name(template.name);
+            template.profile(name.clone(), &profiles::Profile::from_state(data)).apply(data);
+            // Forced settings still win over the template
+            managed::load_managed_settings().apply(data);
+            let needs = template.description[..1].to_lowercase() + &template.description[1..];
+            data.status_message = format!("New profile {}: fill in the {}, then Save Settings", name, needs);
+            ctx.window().close();
+        });
+        layout.add_child(Flex::row().with_child(button.fix_width(130.0)).with_spacer(10.0).with_child(Label::new(template.description)));
+    }
+    
+    let cancel_button = Button::new("Cancel")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+    layout.add_spacer(15.0);
+    layout.add_child(cancel_button);
+    
+    WindowDesc::new(layout.padding(20.0))
+        .title("New Profile from Template")
+        .window_size((460.0, 280.0))
+        .resizable(false)
+}
+
+// Make this app the one tel: links open with, and say how that went
+fn make_default_tel_handler(data: &mut AppState) {
+    let result = url_handler::register("tel");
+    data.handles_tel_links = url_handler::is_default("tel");
+    data.status_message = match result {
+        Ok(()) if data.handles_tel_links => format!("tel: links now open with {}", identity().name),
+        // Newer macOS versions ask first
+        Ok(()) => format!("Confirm the change macOS asks about to open tel: links with {}", identity().name),
+        Err(message) => format!("Error: {}", message),
+    };
+}
+
+// Asked once, for an app that isn't where tel: links go
+fn tel_handler_window() -> WindowDesc<AppState> {
+    let make_default_button = Button::new("Make Default")
+        .on_click(|ctx, data: &mut AppState, _env| {
+            make_default_tel_handler(data);
+            ctx.window().close();
+        });
+    let not_now_button = Button::new("Not Now")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+    
+    let message = format!(
+        "tel: links, like the phone numbers on web pages, open with another app. Open them with {} to call them from your extension? The button under the settings does this later too.",
+        identity().name
+    );
+    let layout = Flex::column()
+        .with_child(Label::new(message).with_line_break_mode(LineBreaking::WordWrap))
+        .with_spacer(15.0)
+        .with_child(Flex::row().with_child(not_now_button).with_spacer(10.0).with_child(make_default_button))
+        .padding(20.0);
+    
+    WindowDesc::new(layout)
+        .title("Open tel: Links")
+        .window_size((380.0, 180.0))
+        .resizable(false)
+        .set_always_on_top(true)
+}
+
+// Always-on-top message window with an OK button
+fn alert_window(title: &str, message: &str) -> WindowDesc<AppState> {
+    let ok_button = Button::new("OK")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.window().close();
+        });
+    
+    let layout = Flex::column()
+        .with_child(Label::new(message).with_line_break_mode(druid::widget::LineBreaking::WordWrap))
+        .with_spacer(15.0)
+        .with_child(ok_button)
+        .padding(20.0);
+    
+    WindowDesc::new(layout)
+        .title(title.to_string())
+        .window_size((340.0, 150.0))
+        .resizable(false)
+        .set_always_on_top(true)
+}
+
+// Today's call counters
+fn stats_window(state: &AppState) -> WindowDesc<AppState> {
+    let today = stats::today();
+    
+    let mut layout = Flex::column()
+        .with_child(Label::new(format!("Calls placed: {}", today.placed)))
+        .with_spacer(5.0)
+        .with_child(Label::new(format!("Failed: {}", today.failed)));
+    
+    if today.private > 0 {
+        layout.add_spacer(5.0);
+        layout.add_child(Label::new(format!("Private: {}", today.private)));
+    }
+    if today.talk_seconds > 0 {
+        layout.add_spacer(5.0);
+        layout.add_child(Label::new(format!("Talk time: {} min", today.talk_seconds / 60)));
+    }
+    
+    WindowDesc::new(appearance::scoped(layout.padding(20.0).expand()))
+        .title("Today's Calls")
+        .window_size(appearance::window_size(240.0, 140.0, state))
+        .resizable(false)
+}
+
+// Scrollable list of past calls, newest first, with a redial button on each
+fn history_window(state: &AppState) -> WindowDesc<AppState> {
+    // Rebuilt when calls are added or reminders set, so the list stays current
+    let list = druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.history_revision, data.country.clone()),
+        |(_revision, country), _data, _env| Box::new(history_list(country)),
+    );
+    let scroll = druid::widget::Scroll::new(list.padding(10.0)).vertical();
+    
+    let export_button = Button::new("Export…")
+        .on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(EXPORT_HISTORY));
+    let import_button = Button::new("Import…")
+        .on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(IMPORT_HISTORY));
+    let layout = Flex::column()
+        .with_flex_child(scroll.expand(), 1.0)
+        .with_child(Flex::row().with_child(export_button).with_spacer(10.0).with_child(import_button).padding(10.0));
+    
+    WindowDesc::new(appearance::scoped(layout))
+        .title("Recent Calls")
+        .window_size(appearance::window_size(420.0, 420.0, state))
+}
+
+// The shared directory, under its group headings, with a Call button per number
+fn directory_window(state: &AppState) -> WindowDesc<AppState> {
+    let list = druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.directory_revision, data.country.clone()),
+        |(_revision, country), _data, _env| Box::new(directory_list(country)),
+    );
+    let scroll = druid::widget::Scroll::new(list.padding(10.0)).vertical();
+    
+    WindowDesc::new(appearance::scoped(scroll.expand()))
+        .title("Directory")
+        .window_size(appearance::window_size(420.0, 420.0, state))
+}
+
+fn directory_list(country: &str) -> impl Widget<AppState> {
+    let mut list = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+    let entries = directory::entries();
+    if entries.is_empty() {
+        list.add_child(Label::new("No shared directory. Enter its URL in Shared Directory in the settings.")
+            .with_line_break_mode(LineBreaking::WordWrap));
+    }
+    
+    let mut groups: Vec<Option<String>> = Vec::new();
+    for entry in &entries {
+        if !groups.contains(&entry.group) {
+            groups.push(entry.group.clone());
+        }
+    }
+    for group in groups {
+        if let Some(group) = &group {
+            list.add_spacer(6.0);
+            list.add_child(Label::new(group.clone()).with_font(druid::theme::UI_FONT_BOLD));
+        }
+        for entry in entries.iter().filter(|entry| entry.group == group) {
+            let number = contacts::dialable(&entry.number);
+            let details = Label::new(format!("{}  {}", entry.name, number_format::format_number(&entry.number, country)))
+                .with_line_break_mode(LineBreaking::WordWrap);
+            let call_button = Button::new("Call")
+                .on_click(move |ctx, data: &mut AppState, _env| {
+                    data.phone_number = number.clone();
+                    ctx.submit_command(MAKE_CALL.with(CallOrigin::Directory));
+                });
+            list.add_spacer(4.0);
+            list.add_child(Flex::row().with_flex_child(details, 1.0).with_child(call_button));
+        }
+    }
+    
+    list
+}
+
+// Calls waiting for the PBX, with controls to reorder, cancel or dial each one now
+fn queue_window(state: &AppState) -> WindowDesc<AppState> {
+    let list = druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.queue_revision, data.country.clone()),
+        |(_revision, country), _data, _env| Box::new(queue_list(country)),
+    );
+    let scroll = druid::widget::Scroll::new(list.padding(10.0)).vertical();
+    
+    WindowDesc::new(appearance::scoped(scroll.expand()))
+        .title("Call Queue")
+        .window_size(appearance::window_size(420.0, 300.0, state))
+}
+
+fn queue_list(country: &str) -> impl Widget<AppState> {
+    let mut list = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+    
+    for call in queue::dialing() {
+        list.add_child(Label::new(format!("Dialing {}", number_format::format_number(&call.number, country))));
+        list.add_spacer(6.0);
+    }
+    
+    let waiting = queue::waiting();
+    if waiting.is_empty() {
+        list.add_child(Label::new("No calls waiting"));
+    }
+    let last = waiting.len().saturating_sub(1);
+    for (index, call) in waiting.into_iter().enumerate() {
+        let id = call.id;
+        let details = Label::new(format!(
+            "{}. {}  ({}, {})",
+            index + 1,
+            number_format::format_number(&call.number, country),
+            call.origin.channel(),
+            call.queued_at.format("%H:%M:%S"),
+        ))
+        .with_line_break_mode(LineBreaking::WordWrap);
+        let up_button = Button::new("↑")
+            .on_click(move |_ctx, _data: &mut AppState, _env| queue::move_by(id, -1))
+            .disabled_if(move |_data: &AppState, _env| index == 0);
+        let down_button = Button::new("↓")
+            .on_click(move |_ctx, _data: &mut AppState, _env| queue::move_by(id, 1))
+            .disabled_if(move |_data: &AppState, _env| index == last);
+        let cancel_button = Button::new("Cancel")
+            .on_click(move |_ctx, _data: &mut AppState, _env| queue::cancel(id));
+        let dial_button = Button::new("Dial Now")
+            .on_click(move |_ctx, _data: &mut AppState, _env| queue::dial_now(id));
+        
+        list.add_child(Flex::row()
+            .with_flex_child(details, 1.0)
+            .with_child(up_button)
+            .with_child(down_button)
+            .with_spacer(6.0)
+            .with_child(cancel_button)
+            .with_spacer(6.0)
+            .with_child(dial_button));
+        list.add_spacer(6.0);
+    }
+    
+    // Calls that couldn't reach the PBX, waiting for their next try
+    let retries = retry::pending();
+    if !retries.is_empty() {
+        list.add_spacer(10.0);
+        list.add_child(Label::new("Waiting to retry:"));
+        list.add_spacer(6.0);
+    }
+    for pending in retries {
+        let details = Label::new(format!(
+            "{}  (retry {} at {})",
+            number_format::format_number(&pending.number, country),
+            pending.retry,
+            pending.due.format("%H:%M:%S"),
+        ))
+        .with_line_break_mode(LineBreaking::WordWrap);
+        let cancel_button = Button::new("Cancel")
+            .on_click(move |_ctx, _data: &mut AppState, _env| retry::cancel(&pending.number, pending.due));
+        list.add_child(Flex::row().with_flex_child(details, 1.0).with_child(cancel_button));
+        list.add_spacer(6.0);
+    }
+    
+    list
+}
+
+// Calling down a list imported from a CSV file: the list with how each call
+// went, and controls to start, pause, resume or cancel
+fn campaign_window(state: &AppState) -> WindowDesc<AppState> {
+    let import_button = Button::new("Import CSV…")
+        .on_click(|ctx, _data: &mut AppState, _env| {
+            let options = FileDialogOptions::new()
+                .allowed_types(vec![FileSpec::new("CSV", &["csv", "txt"])])
+                .accept_command(CAMPAIGN_FILE_CHOSEN);
+            ctx.submit_command(druid::commands::SHOW_OPEN_PANEL.with(options));
+        });
+    let delay_label = Label::new("Between Calls:");
+    let delay_input = TextBox::new()
+        .with_placeholder("Seconds (30 if empty)")
+        .lens(CampaignDelayLens)
+        .expand_width();
+    
+    let controls = druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| data.campaign_revision,
+        |_revision, _data, _env| {
+            let (_, _, status) = campaign::snapshot();
+            let mut controls = Flex::row();
+            match status {
+                campaign::Status::Ready => {
+                    controls.add_child(Button::new("Start").on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(START_CAMPAIGN)));
+                }
+                campaign::Status::Running => {
+                    controls.add_child(Button::new("Pause").on_click(|_ctx, data: &mut AppState, _env| {
+                        campaign::pause();
+                        data.campaign_revision += 1;
+                    }));
+                }
+                campaign::Status::Paused => {
+                    controls.add_child(Button::new("Resume").on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(START_CAMPAIGN)));
+                }
+                campaign::Status::Cancelled | campaign::Status::Finished => {}
+            }
+            if matches!(status, campaign::Status::Running | campaign::Status::Paused) {
+                controls.add_spacer(10.0);
+                controls.add_child(Button::new("Cancel").on_click(|_ctx, data: &mut AppState, _env| {
+                    campaign::cancel();
+                    data.campaign_revision += 1;
+                }));
+            }
+            Box::new(controls)
+        },
+    );
+    
+    let list = druid::widget::ViewSwitcher::new(
+        |data: &AppState, _env| (data.campaign_revision, data.country.clone()),
+        |(_revision, country), _data, _env| Box::new(campaign_list(country)),
+    );
+    let status = Label::dynamic(|data: &AppState, _env| data.status_message.clone())
+        .with_line_break_mode(LineBreaking::WordWrap);
+    
+    let layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(Flex::row()
+            .with_child(import_button)
+            .with_spacer(10.0)
+            .with_child(delay_label)
+            .with_flex_child(delay_input, 1.0)
+            .with_spacer(10.0)
+            .with_child(controls))
+        .with_spacer(10.0)
+        .with_flex_child(druid::widget::Scroll::new(list).vertical().expand(), 1.0)
+        .with_spacer(10.0)
+        .with_child(status)
+        .padding(10.0);
+    
+    WindowDesc::new(appearance::scoped(layout.expand()))
+        .title("Campaign")
+        .window_size(appearance::window_size(480.0, 420.0, state))
+}
+
+fn campaign_list(country: &str) -> impl Widget<AppState> {
+    let mut list = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+    let (entries, next, status) = campaign::snapshot();
+    if entries.is_empty() {
+        list.add_child(Label::new("Import a CSV file with a column of phone numbers to call them one after another")
+            .with_line_break_mode(LineBreaking::WordWrap));
+        return list;
+    }
+    
+    let called = entries.iter().filter(|entry| entry.result.is_some()).count();
+    let failed = entries.iter().filter(|entry| matches!(entry.result, Some(Err(_)))).count();
+    let progress = match status {
+        campaign::Status::Ready => format!("{} numbers to call", entries.len()),
+        campaign::Status::Running => format!("Called {} of {}, {} failed", called, entries.len(), failed),
+        campaign::Status::Paused => format!("Paused after {} of {}, {} failed", called, entries.len(), failed),
+        campaign::Status::Cancelled => format!("Cancelled after {} of {}, {} failed", called, entries.len(), failed),
+        campaign::Status::Finished => format!("Finished: {} of {} placed", called - failed, entries.len()),
+    };
+    list.add_child(Label::new(progress).with_font(druid::theme::UI_FONT_BOLD));
+    list.add_spacer(6.0);
+    
+    for (index, entry) in entries.into_iter().enumerate() {
+        let number = number_format::format_number(&entry.number, country);
+        let callee = match &entry.name {
+            Some(name) => format!("{} ({})", name, number),
+            None => number,
+        };
+        let outcome = match &entry.result {
+            Some(Ok(_)) => "✓".to_string(),
+            Some(Err(message)) => format!("✗ {}", message.trim_start_matches("Error: ")),
+            None if index == next && status == campaign::Status::Running => "Calling…".to_string(),
+            None => String::new(),
+        };
+        list.add_child(Label::new(format!("{}. {}  {}", index + 1, callee, outcome)).with_line_break_mode(LineBreaking::WordWrap));
+        list.add_spacer(4.0);
+    }
+    
+    list
+}
+
+// Every call in the history. Numbers are written the way they're read in `country`.
+fn history_list(country: &str) -> impl Widget<AppState> {
+    let entries = history::load();
+    let mut list = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+    
+    if entries.is_empty() {
+        list.add_child(Label::new("No calls yet"));
+    }
+    
+    for entry in entries {
+        let color = profile_color::color_for(&entry.color);
+        let dot = Painter::new(move |ctx, _data: &AppState, _env| {
+            let size = ctx.size();
+            ctx.fill(druid::kurbo::Circle::new((size.width / 2.0, size.height / 2.0), 5.0), &color);
+        })
+        .fix_size(12.0, 12.0);
+        
+        let outcome = if entry.succeeded { "✓" } else { "✗" };
+        let callee = match &entry.name {
+            Some(name) => format!("{} ({})", name, number_format::format_number(&entry.number, country)),
+            None => number_format::format_number(&entry.number, country),
+        };
+        let details = Label::new(format!("{} {}  {}", outcome, entry.time.format("%b %-d %H:%M"), callee))
+            .with_line_break_mode(LineBreaking::WordWrap);
+        let number = entry.number.clone();
+        let redial_button = Button::new("Call")
+            .on_click(move |ctx, data: &mut AppState, _env| {
+                data.phone_number = number.clone();
+                ctx.submit_command(MAKE_CALL.with(CallOrigin::History));
+            });
+        let callee_for_prompt = callee.clone();
+        let entry_for_prompt = entry.clone();
+        let remind_button = Button::new("Remind…")
+            .on_click(move |ctx, _data: &mut AppState, _env| {
+                ctx.new_window(follow_up_window(entry_for_prompt.clone(), &callee_for_prompt));
+            });
+        
+        list.add_child(Flex::row()
+            .with_child(dot)
+            .with_spacer(6.0)
+            .with_flex_child(details, 1.0)
+            .with_child(remind_button)
+            .with_spacer(6.0)
+            .with_child(redial_button));
+        if let Some(follow_up) = entry.follow_up {
+            list.add_child(Label::new(format!("Call back reminder {}", follow_up.format("%b %-d %H:%M"))).with_text_size(appearance::SMALL_TEXT_SIZE).with_line_break_mode(LineBreaking::WordWrap).padding((18.0, 0.0, 0.0, 0.0)));
+        }
+        if let Some(state) = &entry.state {
+            list.add_child(Label::new(state.clone()).with_text_size(appearance::SMALL_TEXT_SIZE).padding((18.0, 0.0, 0.0, 0.0)));
+        }
+        if let Some(server) = &entry.server {
+            list.add_child(Label::new(format!("Through {}", server)).with_text_size(appearance::SMALL_TEXT_SIZE).padding((18.0, 0.0, 0.0, 0.0)));
+        }
+        if let Some(note) = &entry.note {
+            list.add_child(Label::new(format!("Note: {}", note)).with_text_size(appearance::SMALL_TEXT_SIZE).with_line_break_mode(LineBreaking::WordWrap).padding((18.0, 0.0, 0.0, 0.0)));
+        }
+        if let Some(transcript) = entry.transcript.clone() {
+            let details_button = Button::new("Details…")
+                .on_click(move |ctx, _data: &mut AppState, _env| {
+                    ctx.new_window(transcript_window(&transcript));
+                });
+            list.add_child(details_button.padding((18.0, 0.0, 0.0, 0.0)));
+        }
+        if !entry.succeeded {
+            list.add_child(Label::new(entry.result).with_text_size(appearance::SMALL_TEXT_SIZE).with_line_break_mode(LineBreaking::WordWrap).padding((18.0, 0.0, 0.0, 0.0)));
+        }
+        list.add_spacer(6.0);
+    }
+    
+    list
+}
+
+// Asks when to be reminded to try an unanswered call again
+fn follow_up_window(entry: history::HistoryEntry, callee: &str) -> WindowDesc<AppState> {
+    let mut layout = Flex::column()
+        .with_child(Label::new(format!("No answer from {}?", callee)))
+        .with_spacer(5.0)
+        .with_child(Label::new("Remind me to call again:"))
+        .with_spacer(10.0);
+    
+    let mut choices = Flex::row();
+    for follow_up in reminders::FollowUp::ALL {
+        let entry = entry.clone();
+        choices.add_child(Button::new(follow_up.label()).on_click(move |ctx, data: &mut AppState, _env| {
+            data.status_message = match reminders::schedule(&entry, follow_up) {
+                Ok(due) => format!("Reminder set for {}", due.format("%a %H:%M")),
+                Err(e) => format!("Reminder not set: {}", e),
+            };
+            ctx.window().close();
+        }));
+        choices.add_spacer(6.0);
+    }
+    layout.add_child(choices);
+    layout.add_spacer(10.0);
+    layout.add_child(Button::new("Cancel").on_click(|ctx, _data: &mut AppState, _env| {
+        ctx.window().close();
+    }));
+    
+    WindowDesc::new(layout.padding(15.0))
+        .title("Follow Up")
+        .window_size((380.0, 170.0))
+        .resizable(false)
+}
+
+// Where this copy keeps its files, and why those places were picked
+// e.g. "com.click-to-call.app, files under click-to-call (from the build)"
+fn identity_line() -> String {
+    let identity = identity();
+    let scheme = if identity.url_scheme.is_empty() { String::new() } else { format!(", {}: links", identity.url_scheme) };
+    format!("{}, files under {}{} (from the {})", identity.bundle_id, identity.slug, scheme, identity.source)
+}
+
+fn diagnostics_window() -> WindowDesc<AppState> {
+    let config = paths::config_location();
+    let runtime = paths::runtime_location();
+    
+    let entry = |title: &str, path: &std::path::Path, source: &str| {
+        Flex::column()
+            .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+            .with_child(Label::new(format!("{}:", title)))
+            .with_child(Label::new(path.display().to_string()).with_line_break_mode(druid::widget::LineBreaking::WordWrap))
+            .with_child(Label::new(format!("({})", source)).with_text_size(11.0))
+    };
+    
+    let mut layout = Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(Label::new(format!("{} {}", identity().name, env!("CARGO_PKG_VERSION"))))
+        .with_child(Label::new(identity_line()).with_text_size(11.0).with_line_break_mode(druid::widget::LineBreaking::WordWrap))
+        .with_spacer(15.0)
+        .with_child(entry("Preferences", &paths::preferences_file(), config.source))
+        .with_spacer(10.0)
+        .with_child(entry("Call stats", &paths::stats_file(), config.source))
+        .with_spacer(10.0)
+        .with_child(entry("Call history", &paths::history_database(), config.source))
+        .with_spacer(10.0)
+        .with_child(entry("Instance socket", &paths::socket_file(), runtime.source))
+        .with_spacer(20.0)
+        .with_child(Label::new("Effective configuration:"))
+        .with_spacer(5.0);
+    
+    // The settings actually in use, and which layer each one came from
+    for setting in effective_config::effective_config() {
+        layout.add_child(Label::new(format!("{} = {}", setting.name, setting.value))
+            .with_line_break_mode(druid::widget::LineBreaking::WordWrap));
+        layout.add_child(Label::new(format!("from {}", setting.source)).with_text_size(11.0));
+        layout.add_spacer(4.0);
+    }
+    
+    let scroll = druid::widget::Scroll::new(layout.padding(20.0)).vertical();
+    
+    WindowDesc::new(scroll.expand())
+        .title("Diagnostics")
+        .window_size((460.0, 520.0))
+}
+
+// Borderless, transparent window that outlines an area of the screen
+fn highlight_window(bounds: Rect) -> WindowDesc<AppState> {
+    let outline = Painter::new(|ctx, _data: &AppState, _env| {
+        let rect = ctx.size().to_rect().inset(-2.0);
+        ctx.stroke(rect, &Color::rgb8(0xFF, 0xB0, 0x00), 4.0);
+    });
+    
+    // Leave a little room around the text for the outline
+    let frame = bounds.inflate(6.0, 6.0);
+    
+    WindowDesc::new(outline)
+        .show_titlebar(false)
+        .transparent(true)
+        .resizable(false)
+        .set_always_on_top(true)
+        .set_position(frame.origin())
+        .window_size(frame.size())
+}
@@ -0,0 +1,168 @@
+// First-launch setup: a few steps from the PBX to a working tel: link, shown
+// instead of the full settings form when there are no settings yet
+
+use super::lenses::*;
+use super::{make_default_tel_handler, save_settings, test_connection};
+use crate::backend::BackendKind;
+use crate::config::AppState;
+use crate::health::Health;
+use crate::identity::identity;
+use druid::widget::{Button, Either, Flex, Label, LineBreaking, RadioGroup, SizedBox, TextBox, ViewSwitcher};
+use druid::{Data, Env, Widget, WidgetExt, WindowDesc};
+
+// Where the setup is; the form's fields are the settings themselves
+#[derive(Clone, Copy, Data, Default, PartialEq)]
+pub enum SetupStep {
+    // Which PBX, and its domain
+    #[default]
+    Pbx,
+    // Whether the PBX can be reached
+    Connection,
+    // The extension and key calls are placed with
+    Account,
+    // Opening tel: links with the app
+    TelLinks,
+    Done,
+}
+
+impl SetupStep {
+    fn next(self) -> SetupStep {
+        match self {
+            SetupStep::Pbx => SetupStep::Connection,
+            SetupStep::Connection => SetupStep::Account,
+            SetupStep::Account => SetupStep::TelLinks,
+            SetupStep::TelLinks | SetupStep::Done => SetupStep::Done,
+        }
+    }
+
+    fn previous(self) -> SetupStep {
+        match self {
+            SetupStep::Pbx | SetupStep::Connection => SetupStep::Pbx,
+            SetupStep::Account => SetupStep::Connection,
+            SetupStep::TelLinks => SetupStep::Account,
+            SetupStep::Done => SetupStep::TelLinks,
+        }
+    }
+
+    // Why the step can't be left yet, if it can't
+    fn missing(self, data: &AppState) -> Option<&'static str> {
+        match self {
+            SetupStep::Pbx if data.domain.trim().is_empty() => Some("Enter the PBX's domain or address first"),
+            SetupStep::Account if data.extension.trim().is_empty() => Some("Enter your extension first"),
+            _ => None,
+        }
+    }
+}
+
+fn text(text: impl Into<String>) -> impl Widget<AppState> {
+    Label::new(text.into()).with_line_break_mode(LineBreaking::WordWrap)
+}
+
+fn step(step: SetupStep) -> Box<dyn Widget<AppState>> {
+    let layout = Flex::column().cross_axis_alignment(druid::widget::CrossAxisAlignment::Start);
+    let layout = match step {
+        SetupStep::Pbx => layout
+            .with_child(text(format!("Welcome to {}. Which PBX places your calls?", identity().name)))
+            .with_spacer(10.0)
+            .with_child(RadioGroup::column(BackendKind::ALL.iter().map(|kind| (kind.label(), *kind))).lens(BackendLens))
+            .with_spacer(10.0)
+            .with_child(Flex::row().with_child(Label::new("Domain:")).with_flex_child(
+                TextBox::new().with_placeholder("e.g. pbx.example.com").lens(DomainLens).expand_width(),
+                1.0,
+            )),
+        SetupStep::Connection => layout
+            .with_child(text("Check that this Mac can reach the PBX. The login is checked once you've entered your account."))
+            .with_spacer(10.0)
+            .with_child(Button::new("Test Connection").on_click(|ctx, data: &mut AppState, _env| test_connection(ctx, data, reachability))),
+        SetupStep::Account => layout
+            .with_child(text("The extension your calls ring first, and the key or password for the PBX."))
+            .with_spacer(10.0)
+            .with_child(Flex::row().with_child(Label::new("Extension:")).with_flex_child(
+                TextBox::new().with_placeholder("e.g. 201").lens(ExtensionLens).expand_width(),
+                1.0,
+            ))
+            .with_spacer(10.0)
+            .with_child(Either::new(
+                |data: &AppState, _env| data.backend == BackendKind::FusionPbx,
+                SizedBox::empty(),
+                Flex::row()
+                    .with_child(Label::new("Username:"))
+                    .with_flex_child(TextBox::new().with_placeholder("AMI/ARI user or 3CX client id").lens(BackendUsernameLens).expand_width(), 1.0)
+                    .padding((0.0, 0.0, 0.0, 10.0)),
+            ))
+            .with_child(Flex::row().with_child(Label::new("Key:")).with_flex_child(
+                TextBox::new().with_placeholder("API key, or the backend's password/secret").lens(KeyLens).expand_width(),
+                1.0,
+            ))
+            .with_spacer(10.0)
+            .with_child(Button::new("Test Login").on_click(|ctx, data: &mut AppState, _env| test_connection(ctx, data, |health, domain| health.summary(domain)))),
+        SetupStep::TelLinks => layout
+            .with_child(text(format!("Phone numbers on web pages and in other apps are tel: links. Open them with {} to call them from your extension.", identity().name)))
+            .with_spacer(10.0)
+            .with_child(Button::new("Make Default for tel: Links").on_click(|_ctx, data: &mut AppState, _env| make_default_tel_handler(data))),
+        SetupStep::Done => layout
+            .with_child(text("That's all. Finish saves the settings; everything else is in the settings window."))
+            .with_spacer(10.0)
+            .with_child(Label::dynamic(|data: &AppState, _env: &Env| format!("Calls start at extension {} on {}.", data.extension.trim(), data.domain.trim()))),
+    };
+    Box::new(layout)
+}
+
+// Reachability only; the login fails until the account is filled in
+fn reachability(health: Health, domain: &str) -> String {
+    match health {
+        Health::Unreachable(_) | Health::BadCertificate(_) | Health::Healthy { .. } => health.summary(domain),
+        Health::Rejected(_) | Health::Failed(_) => format!("{}: reachable", domain),
+    }
+}
+
+pub fn onboarding_window() -> WindowDesc<AppState> {
+    let page = ViewSwitcher::new(|data: &AppState, _env| data.setup_step, |step_shown, _data, _env| step(*step_shown));
+
+    let back_button = Button::new("Back").on_click(|_ctx, data: &mut AppState, _env| {
+        data.setup_step = data.setup_step.previous();
+        data.status_message.clear();
+    });
+    let next_button = Button::new(|data: &AppState, _env: &Env| {
+        if data.setup_step == SetupStep::Done { "Finish" } else { "Next" }.to_string()
+    })
+    .on_click(|ctx, data: &mut AppState, _env| {
+        if let Some(missing) = data.setup_step.missing(data) {
+            data.status_message = missing.to_string();
+            return;
+        }
+        if data.setup_step != SetupStep::Done {
+            data.setup_step = data.setup_step.next();
+            data.status_message.clear();
+            return;
+        }
+        // The setup asked already
+        data.tel_handler_prompted = true;
+        match save_settings(data, ctx.get_external_handle()) {
+            Ok(warning) => {
+                data.status_message = match warning {
+                    Some(warning) => format!("Settings saved, but {}", warning),
+                    None => "You're all set; type a number to call it".to_string(),
+                };
+                ctx.window().close();
+            }
+            Err(message) => data.status_message = format!("Settings not saved: {}", message),
+        }
+    });
+
+    let layout = Flex::column()
+        .with_flex_child(page.expand(), 1.0)
+        .with_child(Label::dynamic(|data: &AppState, _env: &Env| data.status_message.clone()).with_line_break_mode(LineBreaking::WordWrap).expand_width())
+        .with_spacer(10.0)
+        .with_child(Flex::row().with_child(Either::new(
+            |data: &AppState, _env| data.setup_step == SetupStep::Pbx,
+            SizedBox::empty(),
+            back_button,
+        )).with_flex_spacer(1.0).with_child(next_button))
+        .padding(20.0);
+
+    WindowDesc::new(layout)
+        .title(format!("Set Up {}", identity().name))
+        .window_size((460.0, 420.0))
+        .resizable(false)
+}
@@ -0,0 +1,66 @@
+// Making this app the one macOS opens tel: links with, so nobody has to find
+// the setting in FaceTime's preferences, and finding out whether it is
+
+use crate::identity::identity;
+
+#[cfg(target_os = "macos")]
+mod ffi {
+    use objc::runtime::Object;
+
+    #[link(name = "CoreServices", kind = "framework")]
+    extern "C" {
+        // CFStringRef is toll-free bridged with NSString
+        pub fn LSSetDefaultHandlerForURLScheme(scheme: *mut Object, bundle_id: *mut Object) -> i32;
+        pub fn LSCopyDefaultHandlerForURLScheme(scheme: *mut Object) -> *mut Object;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFRelease(object: *mut Object);
+    }
+}
+
+// Bundle identifier of the app that opens links with `scheme`, if any
+#[cfg(target_os = "macos")]
+pub fn default_handler(scheme: &str) -> Option<String> {
+    use crate::macos::{ns_string, rust_string};
+
+    unsafe {
+        let handler = ffi::LSCopyDefaultHandlerForURLScheme(ns_string(scheme));
+        if handler.is_null() {
+            return None;
+        }
+        let bundle_id = rust_string(handler);
+        ffi::CFRelease(handler);
+        bundle_id
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn default_handler(_scheme: &str) -> Option<String> {
+    None
+}
+
+// Whether links with `scheme` open in this app
+pub fn is_default(scheme: &str) -> bool {
+    default_handler(scheme).is_some_and(|handler| handler.eq_ignore_ascii_case(&identity().bundle_id))
+}
+
+// Ask Launch Services to open links with `scheme` in this app. macOS may ask
+// the user to confirm the change.
+#[cfg(target_os = "macos")]
+pub fn register(scheme: &str) -> Result<(), String> {
+    use crate::macos::ns_string;
+
+    let status = unsafe { ffi::LSSetDefaultHandlerForURLScheme(ns_string(scheme), ns_string(&identity().bundle_id)) };
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(format!("macOS didn't make {} the app for {}: links (error {})", identity().name, scheme, status))
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn register(scheme: &str) -> Result<(), String> {
+    Err(format!("only macOS can make {} the app for {}: links", identity().name, scheme))
+}
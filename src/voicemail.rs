@@ -0,0 +1,145 @@
+// Voicemail: how many new messages the extension's voicemail box has, asked
+// every minute while the profile has it on. The count is shown on the
+// Voicemail button, which calls the voicemail feature code, and on the menu
+// bar icon. FreeSWITCH and FusionPBX are asked over the event socket, with
+// the login presence uses, and Asterisk over AMI.
+
+use crate::backend::{self, BackendKind, CallError};
+use crate::config::AppState;
+use crate::presence::Target;
+use crate::{menu_bar, notify, runtime};
+use druid::ExtEventSink;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+// How often the PBX is asked
+const INTERVAL: Duration = Duration::from_secs(60);
+
+// Longest an answer may take
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Feature code for your own voicemail on FusionPBX, FreeSWITCH and FreePBX
+pub const DEFAULT_NUMBER: &str = "*97";
+
+// Extension whose box is counted, None while that's switched off
+static TARGET: Mutex<Option<Target>> = Mutex::new(None);
+static STARTED: AtomicBool = AtomicBool::new(false);
+// The last answer, or why there wasn't one; None before the first
+static LAST: Mutex<Option<Result<u32, String>>> = Mutex::new(None);
+
+// Lets the UI show the count on the Voicemail button
+static EVENT_SINK: OnceLock<ExtEventSink> = OnceLock::new();
+
+pub fn set_event_sink(sink: ExtEventSink) {
+    EVENT_SINK.set(sink).ok();
+}
+
+// Whether the settings for counting voicemail are complete, before they're saved
+pub fn check(state: &AppState) -> Result<(), String> {
+    if !state.show_voicemail {
+        return Ok(());
+    }
+    if state.voicemail_number.trim().contains(|c: char| !(c.is_ascii_digit() || c == '*' || c == '#')) {
+        return Err(format!("The voicemail number \"{}\" should be digits, * and # only", state.voicemail_number.trim()));
+    }
+    match state.backend {
+        BackendKind::FreeSwitchEsl | BackendKind::AsteriskAmi => Ok(()),
+        BackendKind::FusionPbx if state.event_socket_password.is_empty() => Err("Counting voicemail needs the event socket's password".to_string()),
+        BackendKind::FusionPbx => Ok(()),
+        backend => Err(format!("Voicemail can only be counted on FusionPBX, FreeSWITCH and Asterisk AMI, not {}", backend.label())),
+    }
+}
+
+// What the Voicemail button calls
+pub fn number(state: &AppState) -> String {
+    match state.voicemail_number.trim() {
+        "" => DEFAULT_NUMBER.to_string(),
+        number => number.to_string(),
+    }
+}
+
+// Ask `target`'s PBX how many new messages its extension has
+async fn lookup(target: &Target) -> Result<u32, CallError> {
+    let (account, extension) = target.resolve().await;
+    let backend = account.backend();
+    backend::within(LOOKUP_TIMEOUT, backend.new_voicemail(&extension)).await
+}
+
+// Count this extension's messages in the background, or stop with None. A
+// new target is counted straight away.
+pub fn set_target(target: Option<Target>) {
+    let mut current = TARGET.lock().unwrap();
+    let changed = match (current.as_ref(), target.as_ref()) {
+        (Some(current), Some(target)) => !current.same(target),
+        (None, None) => false,
+        _ => true,
+    };
+    *current = target.clone();
+    drop(current);
+    if changed {
+        *LAST.lock().unwrap() = None;
+        show(0);
+    }
+
+    if let Some(target) = target {
+        if !STARTED.swap(true, Ordering::SeqCst) {
+            runtime().spawn(lookup_loop());
+        }
+        runtime().spawn(async move { report(&target, lookup(&target).await) });
+    }
+}
+
+async fn lookup_loop() {
+    loop {
+        tokio::time::sleep(INTERVAL).await;
+
+        let target = TARGET.lock().unwrap().clone();
+        if let Some(target) = target {
+            let count = lookup(&target).await;
+            report(&target, count);
+        }
+    }
+}
+
+fn report(target: &Target, count: Result<u32, CallError>) {
+    // The settings may have changed while the PBX was asked
+    if !TARGET.lock().unwrap().as_ref().is_some_and(|current| current.same(target)) {
+        return;
+    }
+    let count = count.map_err(|e| e.to_string());
+    let previous = LAST.lock().unwrap().replace(count.clone());
+    if previous.as_ref() == Some(&count) {
+        return;
+    }
+
+    let count = match count {
+        Ok(count) => count,
+        // The count stays as it was until the PBX answers again
+        Err(e) => {
+            warn!("Couldn't count extension {}'s voicemail: {}", target.extension(), e);
+            return;
+        }
+    };
+    info!("Extension {} has {} new voicemail messages", target.extension(), count);
+    // Only messages that came in while the app was watching; at launch the button says it
+    if let Some(Ok(before)) = previous {
+        if count > before {
+            let new = count - before;
+            let message = match new {
+                1 => "You have a new voicemail message".to_string(),
+                _ => format!("You have {} new voicemail messages", new),
+            };
+            notify::show_notification("New Voicemail", &message);
+        }
+    }
+    show(count);
+}
+
+fn show(count: u32) {
+    menu_bar::set_voicemail(count);
+    if let Some(sink) = EVENT_SINK.get() {
+        sink.add_idle_callback(move |data: &mut AppState| data.new_voicemail = count);
+    }
+}
@@ -0,0 +1,77 @@
+// Noticing when the Mac wakes from sleep, so scheduled calls can wait a moment
+// instead of firing the instant the lid opens
+
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::info;
+
+// How often the watcher compares the wall clock with the monotonic clock
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// The wall clock getting this far ahead means the Mac was asleep
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(60);
+
+// Grace period used when the setting is left empty
+const DEFAULT_GRACE_MINUTES: i64 = 5;
+
+static GRACE_PERIOD: Mutex<ChronoDuration> = Mutex::new(ChronoDuration::zero());
+static LAST_WAKE: Mutex<Option<DateTime<Local>>> = Mutex::new(None);
+// Wall clock and monotonic clock at the last check
+static LAST_CHECK: Mutex<Option<(SystemTime, Instant)>> = Mutex::new(None);
+static WATCHER_STARTED: AtomicBool = AtomicBool::new(false);
+
+// Minutes to hold scheduled calls after waking, e.g. "10". Empty means the
+// default; "0" turns the grace period off.
+pub fn parse_grace_period(value: &str) -> Option<ChronoDuration> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Some(ChronoDuration::minutes(DEFAULT_GRACE_MINUTES));
+    }
+    value.parse::<u32>().ok().map(|minutes| ChronoDuration::minutes(minutes.into()))
+}
+
+pub fn set_grace_period(period: ChronoDuration) {
+    *GRACE_PERIOD.lock().unwrap() = period;
+}
+
+// Start watching for the Mac waking from sleep. Safe to call more than once.
+pub fn start() {
+    if !WATCHER_STARTED.swap(true, Ordering::SeqCst) {
+        thread::spawn(|| loop {
+            check();
+            thread::sleep(CHECK_INTERVAL);
+        });
+    }
+}
+
+// Notice a wake that happened since the last check. The monotonic clock stops
+// while the Mac sleeps and the wall clock doesn't, so a sleep shows up as the
+// wall clock getting ahead. Schedulers call this before deciding anything, so
+// they can't run before the watcher has noticed.
+pub fn check() {
+    let now = (SystemTime::now(), Instant::now());
+    let mut last_check = LAST_CHECK.lock().unwrap();
+    if let Some((wall, monotonic)) = *last_check {
+        let wall_elapsed = now.0.duration_since(wall).unwrap_or_default();
+        let asleep = wall_elapsed.saturating_sub(now.1.duration_since(monotonic));
+        if asleep > SLEEP_THRESHOLD {
+            info!("Woke from sleep after about {} minutes", asleep.as_secs() / 60);
+            *LAST_WAKE.lock().unwrap() = Some(Local::now());
+        }
+    }
+    *last_check = Some(now);
+}
+
+// When the grace period after the last wake ends, if we woke up at all
+pub fn grace_period_end() -> Option<DateTime<Local>> {
+    let woke = (*LAST_WAKE.lock().unwrap())?;
+    Some(woke + *GRACE_PERIOD.lock().unwrap())
+}
+
+// Whether scheduled calls should still wait because the Mac just woke up
+pub fn in_grace_period() -> bool {
+    grace_period_end().is_some_and(|end| Local::now() < end)
+}
@@ -0,0 +1,165 @@
+// The FreeSWITCH event socket against a stand-in on localhost that answers
+// commands the way mod_event_socket does and then plays a call's events.
+
+use click_to_call::backend::{self, BackendKind, CallState, Connection, HttpAuth, Presence, Tls};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+const CALL_UUID: &str = "3f2504e0-4f89-11d3-9a0c-0305e82c3301";
+
+fn event(name: &str, extra: &str) -> String {
+    let body = format!("Event-Name: {}\nUnique-ID: {}\n{}\n", name, CALL_UUID, extra);
+    format!("Content-Length: {}\nContent-Type: text/event-plain\n\n{}", body.len(), body)
+}
+
+// Accept one connection, check the commands sent, then send `events`.
+// Returns the commands it got.
+async fn event_socket(listener: TcpListener, events: Vec<String>) -> Vec<String> {
+    let (stream, _) = listener.accept().await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"Content-Type: auth/request\n\n").await.unwrap();
+
+    let mut commands = Vec::new();
+    while commands.len() < 3 {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+        commands.push(line.trim().to_string());
+        writer.write_all(b"Content-Type: command/reply\nReply-Text: +OK\n\n").await.unwrap();
+    }
+    for event in events {
+        writer.write_all(event.as_bytes()).await.unwrap();
+    }
+    commands
+}
+
+async fn follow(events: Vec<String>) -> (Vec<CallState>, Vec<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(event_socket(listener, events));
+
+    let tls = Tls::default();
+    let auth = HttpAuth::default();
+    let backend = backend::backend_for(BackendKind::FreeSwitchEsl, Connection { host: &host, username: "", secret: "ClueCon", context: "", tls: &tls, http_request: "", auth: &auth });
+    let (states, mut updates) = tokio::sync::mpsc::unbounded_channel();
+    backend::within(Duration::from_secs(2), backend.follow(CALL_UUID, states)).await.unwrap_or_else(|e| panic!("following failed: {}", e));
+    let mut seen = Vec::new();
+    while let Ok(state) = updates.try_recv() {
+        seen.push(state);
+    }
+    (seen, server.await.unwrap())
+}
+
+#[tokio::test]
+async fn esl_follows_a_call_that_connects() {
+    let events = vec![
+        event("CHANNEL_PROGRESS", ""),
+        event("CHANNEL_ANSWER", ""),
+        event("CHANNEL_BRIDGE", ""),
+        event("CHANNEL_HANGUP_COMPLETE", "Hangup-Cause: NORMAL_CLEARING"),
+    ];
+    let (states, commands) = follow(events).await;
+    assert_eq!(commands, ["auth ClueCon", &format!("filter Unique-ID {}", CALL_UUID), "event plain CHANNEL_PROGRESS CHANNEL_PROGRESS_MEDIA CHANNEL_ANSWER CHANNEL_BRIDGE CHANNEL_HANGUP_COMPLETE"]);
+    assert_eq!(
+        states,
+        [CallState::Ringing, CallState::Answered, CallState::Connected, CallState::HungUp { connected: true, cause: "NORMAL_CLEARING".to_string() }]
+    );
+}
+
+#[tokio::test]
+async fn esl_reports_why_a_call_didnt_connect() {
+    let events = vec![event("CHANNEL_ANSWER", ""), event("CHANNEL_HANGUP_COMPLETE", "Hangup-Cause: USER_BUSY")];
+    let (states, _) = follow(events).await;
+    assert_eq!(states, [CallState::Answered, CallState::HungUp { connected: false, cause: "USER_BUSY".to_string() }]);
+}
+
+// Accept one connection and answer each api command with the reply `answer`
+// gives for it. Returns the commands it got.
+async fn api_socket(listener: TcpListener, answer: fn(&str) -> String) -> Vec<String> {
+    let (stream, _) = listener.accept().await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"Content-Type: auth/request\n\n").await.unwrap();
+
+    let mut commands = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.unwrap() == 0 {
+            return commands;
+        }
+        let command = line.trim().to_string();
+        if command.is_empty() {
+            continue;
+        }
+        commands.push(command.clone());
+        match command.strip_prefix("api ") {
+            Some(api) => {
+                let body = answer(api);
+                let reply = format!("Content-Type: api/response\nContent-Length: {}\n\n{}", body.len(), body);
+                writer.write_all(reply.as_bytes()).await.unwrap();
+            }
+            None if command == "exit" => return commands,
+            None => writer.write_all(b"Content-Type: command/reply\nReply-Text: +OK\n\n").await.unwrap(),
+        }
+    }
+}
+
+async fn presence(extension: &str, answer: fn(&str) -> String) -> (Presence, Vec<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(api_socket(listener, answer));
+
+    let tls = Tls::default();
+    let auth = HttpAuth::default();
+    let backend = backend::backend_for(BackendKind::FreeSwitchEsl, Connection { host: &host, username: "", secret: "ClueCon", context: "", tls: &tls, http_request: "", auth: &auth });
+    let presence = backend::within(Duration::from_secs(2), backend.presence(extension)).await.unwrap_or_else(|e| panic!("lookup failed: {}", e));
+    (presence, server.await.unwrap())
+}
+
+#[tokio::test]
+async fn esl_reports_an_extension_on_a_call() {
+    let (found, commands) = presence("201", |api| match api {
+        "global_getvar domain" => "pbx.example.com".to_string(),
+        "sofia_contact 201@pbx.example.com" => "sofia/internal/sip:201@192.0.2.10:5060".to_string(),
+        // 1201's channel is listed too, as `like` matches anywhere
+        _ => r#"{"row_count":2,"rows":[{"presence_id":"1201@pbx.example.com"},{"presence_id":"201@pbx.example.com"}]}"#.to_string(),
+    })
+    .await;
+    assert_eq!(found, Presence::OnCall);
+    assert_eq!(commands[1..4], ["api global_getvar domain", "api sofia_contact 201@pbx.example.com", "api show channels like 201@pbx.example.com as json"]);
+}
+
+#[tokio::test]
+async fn esl_tells_a_free_phone_from_one_that_isnt_registered() {
+    let (found, _) = presence("201@tenant.example.com", |api| match api {
+        "sofia_contact 201@tenant.example.com" => "sofia/internal/sip:201@192.0.2.10:5060".to_string(),
+        _ => r#"{"row_count":1,"rows":[{"presence_id":"1201@tenant.example.com"}]}"#.to_string(),
+    })
+    .await;
+    assert_eq!(found, Presence::Idle);
+
+    let (found, commands) = presence("202@tenant.example.com", |_| "error/user_not_registered".to_string()).await;
+    assert_eq!(found, Presence::Unregistered);
+    assert_eq!(commands, ["auth ClueCon", "api sofia_contact 202@tenant.example.com", "exit"]);
+}
+
+#[tokio::test]
+async fn esl_counts_new_voicemail() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(api_socket(listener, |api| match api {
+        "vm_boxcount 201@tenant.example.com" => "3".to_string(),
+        _ => "-ERR no reply".to_string(),
+    }));
+
+    let tls = Tls::default();
+    let auth = HttpAuth::default();
+    let backend = backend::backend_for(BackendKind::FreeSwitchEsl, Connection { host: &host, username: "", secret: "ClueCon", context: "", tls: &tls, http_request: "", auth: &auth });
+    let count = backend::within(Duration::from_secs(2), backend.new_voicemail("201@tenant.example.com")).await;
+    assert_eq!(count.unwrap_or_else(|e| panic!("counting failed: {}", e)), 3);
+    assert_eq!(server.await.unwrap(), ["auth ClueCon", "api vm_boxcount 201@tenant.example.com", "exit"]);
+}